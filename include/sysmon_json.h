@@ -0,0 +1,128 @@
+#ifndef SYSMON_JSON_H
+#define SYSMON_JSON_H
+
+/* Generated with cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * XML input or output format
+ */
+#define SYSMON_JSON_FORMAT_XML 0
+
+/**
+ * JSON input or output format
+ */
+#define SYSMON_JSON_FORMAT_JSON 1
+
+/**
+ * The call succeeded
+ */
+#define SYSMON_JSON_OK 0
+
+/**
+ * A pointer was null, a string was not UTF-8, or a format was unknown
+ */
+#define SYSMON_JSON_ERR_INVALID_ARGUMENT -1
+
+/**
+ * Filesystem or stream IO failed
+ */
+#define SYSMON_JSON_ERR_IO -2
+
+/**
+ * The XML input could not be parsed
+ */
+#define SYSMON_JSON_ERR_XML_PARSE -3
+
+/**
+ * The JSON input could not be parsed
+ */
+#define SYSMON_JSON_ERR_JSON_PARSE -4
+
+/**
+ * The configuration failed Sysmon schema validation
+ */
+#define SYSMON_JSON_ERR_VALIDATION -5
+
+/**
+ * The input is not a usable configuration or the conversion is unsupported
+ */
+#define SYSMON_JSON_ERR_INVALID_FILE -6
+
+/**
+ * Preprocessing the input failed
+ */
+#define SYSMON_JSON_ERR_PREPROCESS -7
+
+/**
+ * Any other failure
+ */
+#define SYSMON_JSON_ERR_OTHER -99
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Converts a configuration between formats.
+ *
+ * On success `*out_buf` receives a NUL-terminated string that must be
+ * released with `sysmon_json_free`, and `*out_len` (if not null) its length
+ * in bytes excluding the terminator. On failure a negative error code is
+ * returned, `*out_buf` is left untouched, and `sysmon_json_last_error`
+ * describes the problem.
+ *
+ * # Safety
+ * `input` must be a NUL-terminated string and `out_buf` a valid pointer.
+ * `out_len` may be null.
+ */
+int sysmon_json_convert_str(const char *input,
+                            int in_fmt,
+                            int out_fmt,
+                            char **out_buf,
+                            size_t *out_len);
+
+/**
+ * Merges `count` configurations into one.
+ *
+ * `inputs` and `formats` are parallel arrays of `count` entries. The merged
+ * configuration is returned through `out_buf`/`out_len` with the same
+ * ownership rules as `sysmon_json_convert_str`.
+ *
+ * # Safety
+ * `inputs` and `formats` must point to `count` valid entries, each input a
+ * NUL-terminated string. `out_buf` must be a valid pointer; `out_len` may
+ * be null.
+ */
+int sysmon_json_merge_str(const char *const *inputs,
+                          const int *formats,
+                          size_t count,
+                          int out_fmt,
+                          char **out_buf,
+                          size_t *out_len);
+
+/**
+ * Returns a description of the last error on this thread, or null if the
+ * last call succeeded. The string is owned by the library.
+ */
+const char *sysmon_json_last_error(void);
+
+/**
+ * Releases a string returned by this library. Passing null is a no-op.
+ *
+ * # Safety
+ * `ptr` must be null or a pointer previously returned through `out_buf` that
+ * has not been freed yet.
+ */
+void sysmon_json_free(char *ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SYSMON_JSON_H */
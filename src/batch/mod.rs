@@ -1,5 +1,11 @@
+mod cancel;
 mod processor;
 mod progress;
+mod retry;
 
-pub use processor::{BatchProcessor, BatchProcessingStats};
+pub use cancel::CancellationToken;
+pub use processor::{
+    BatchProcessor, BatchProcessingStats, FileEvent, FileResult, FileStatus, VerificationFailure,
+};
 pub use progress::ProgressReporter;
+pub use retry::RetryPolicy;
@@ -1,16 +1,34 @@
 use rayon::prelude::*;
-use walkdir::WalkDir;
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
 use crate::config::ProcessingOptions;
-use crate::error::ConversionError;
+use crate::converter::FileFormat;
+use crate::error::{BatchConversionError, ConversionError};
+use crate::stats::{compute_rule_stats_from_file, RuleStats};
+use super::cancel::CancellationToken;
 use super::progress::ProgressReporter;
+use super::retry::RetryPolicy;
+#[cfg(feature = "validation")]
+use sysmon_validator::{parse_sysmon_config_from_str, validate_sysmon_config};
+
+/// Called with each input before it is converted
+type PreHook = Box<dyn Fn(&Path) -> Result<(), ConversionError> + Send + Sync>;
+/// Called with each input and its output after a successful conversion
+type PostHook = Box<dyn Fn(&Path, &Path) -> Result<(), ConversionError> + Send + Sync>;
 
 /// Handles batch processing of multiple Sysmon configuration files
 pub struct BatchProcessor {
-    processed_count: Arc<AtomicUsize>,
-    error_count: Arc<AtomicUsize>,
+    retry_policy: Option<RetryPolicy>,
+    pre_hook: Option<PreHook>,
+    post_hook: Option<PostHook>,
+    cancellation: Option<CancellationToken>,
+    audit_log: Option<Arc<Mutex<BufWriter<File>>>>,
 }
 
 /// Statistics from batch processing operation
@@ -20,17 +38,170 @@ pub struct BatchProcessingStats {
     pub processed: usize,
     /// Number of files that failed to process
     pub errors: usize,
+    /// Number of files skipped by ignore patterns or the overwrite policy
+    pub skipped: usize,
+    /// Number of failed files that hit `ProcessingOptions::per_file_timeout`
+    pub timed_out_count: usize,
+    /// Files that failed to process, with the reason
+    pub failures: BatchConversionError,
+    /// Result for every file that was visited
+    pub results: Vec<FileResult>,
+    /// Rule counts across the converted files, when
+    /// `ProcessingOptions::show_stats` is set
+    pub rule_stats: RuleStats,
+    /// Whether the `CancellationToken` given to `with_cancellation` was
+    /// cancelled during the batch
+    pub cancelled: bool,
+    /// Size of the converted inputs
+    pub total_input_bytes: u64,
+    /// Size of their outputs
+    pub total_output_bytes: u64,
+    /// `total_input_bytes - total_output_bytes`; negative when the outputs
+    /// are larger, as JSON to XML conversions usually are
+    pub size_reduction_bytes: i64,
+    /// `size_reduction_bytes` as a percentage of `total_input_bytes`
+    pub size_reduction_percent: f64,
+}
+
+impl BatchProcessingStats {
+    /// Gets the results of the files that were converted
+    pub fn successful_files(&self) -> impl Iterator<Item = &FileResult> {
+        self.results.iter().filter(|result| result.status == FileStatus::Converted)
+    }
+}
+
+/// An output file that `BatchProcessor::verify_outputs` could not read back
+#[derive(Debug)]
+pub struct VerificationFailure {
+    /// Output file
+    pub path: PathBuf,
+    /// Why it failed verification
+    pub error: ConversionError,
+}
+
+/// Outcome of processing a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file was converted
+    Converted,
+    /// The file was left untouched
+    Skipped,
+    /// The file could not be converted; the error is in `failures`
+    Failed,
+}
+
+/// Sent by `BatchProcessor::process_directory_with_events` as files are
+/// processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileEvent {
+    /// Processing of the file has begun
+    Started(PathBuf),
+    /// The file was converted
+    Completed {
+        input: PathBuf,
+        output: PathBuf,
+        duration: Duration,
+    },
+    /// The file could not be converted
+    Failed { input: PathBuf, error: String },
+}
+
+/// Per-file result of a batch operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResult {
+    /// Input file
+    pub input: PathBuf,
+    /// Output file, once it has been determined
+    pub output: Option<PathBuf>,
+    /// What happened to the file
+    pub status: FileStatus,
+    /// Number of times the conversion was retried after a recoverable error
+    pub retry_count: u32,
+    /// Size of the input file
+    pub input_bytes: u64,
+    /// Size of the output file, once it has been written
+    pub output_bytes: u64,
+}
+
+impl FileResult {
+    fn new(input: &Path) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            output: None,
+            status: FileStatus::Failed,
+            retry_count: 0,
+            input_bytes: 0,
+            output_bytes: 0,
+        }
+    }
 }
 
 impl BatchProcessor {
     /// Creates a new BatchProcessor instance
     pub fn new() -> Self {
         Self {
-            processed_count: Arc::new(AtomicUsize::new(0)),
-            error_count: Arc::new(AtomicUsize::new(0)),
+            retry_policy: None,
+            pre_hook: None,
+            post_hook: None,
+            cancellation: None,
+            audit_log: None,
         }
     }
 
+    /// Retries files that fail with recoverable errors according to `policy`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Stops the batch once `token` is cancelled: files not yet started are
+    /// skipped, and `BatchProcessingStats::cancelled` is set
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Appends one JSON line per processed file to the file at `path`,
+    /// creating it if needed:
+    ///
+    /// ```text
+    /// {"error_msg":"","input":"a.xml","ms":12,"output":"out/a.json","status":"ok","ts":"2024-01-01T12:00:00.000Z"}
+    /// ```
+    ///
+    /// The fields are those of `process_directory_ndjson`, with the error
+    /// in `error_msg`. Each line is flushed as it is written; a line that
+    /// cannot be written is logged and does not fail the batch.
+    pub fn with_audit_log(mut self, path: PathBuf) -> Result<Self, ConversionError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| ConversionError::io_error(&path, e))?;
+        self.audit_log = Some(Arc::new(Mutex::new(BufWriter::new(file))));
+        Ok(self)
+    }
+
+    /// Calls `hook` with each input before converting it. A file whose hook
+    /// fails is skipped, not counted as a failure.
+    pub fn with_pre_hook(
+        mut self,
+        hook: impl Fn(&Path) -> Result<(), ConversionError> + Send + Sync + 'static,
+    ) -> Self {
+        self.pre_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` with each input and its output once the input has been
+    /// converted, for example to upload the output. A file whose hook fails
+    /// counts as a failure.
+    pub fn with_post_hook(
+        mut self,
+        hook: impl Fn(&Path, &Path) -> Result<(), ConversionError> + Send + Sync + 'static,
+    ) -> Self {
+        self.post_hook = Some(Box::new(hook));
+        self
+    }
+
     /// Process a directory of files with progress tracking
     pub fn process_directory_with_progress(
         &self,
@@ -43,40 +214,13 @@ impl BatchProcessor {
         // Create output directory if it doesn't exist
         std::fs::create_dir_all(output_dir)?;
 
-        let walker = if recursive {
-            WalkDir::new(input_dir)
-        } else {
-            WalkDir::new(input_dir).max_depth(1)
-        };
-
         // Collect files first to enable parallel processing
-        let files: Vec<_> = walker
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| e.file_type().is_file())
-            .collect();
+        let files = crate::walk_files(input_dir, recursive, options);
 
-        // Process files in parallel using rayon
-        files.par_iter().for_each(|entry| {
-            let result = self.process_single_file(entry.path(), output_dir, options);
-            
-            match result {
-                Ok(_) => {
-                    self.processed_count.fetch_add(1, Ordering::SeqCst);
-                    progress.increment();
-                }
-                Err(e) => {
-                    eprintln!("Error processing {}: {}", entry.path().display(), e);
-                    self.error_count.fetch_add(1, Ordering::SeqCst);
-                    progress.increment();
-                }
-            }
-        });
-
-        Ok(BatchProcessingStats {
-            processed: self.processed_count.load(Ordering::SeqCst),
-            errors: self.error_count.load(Ordering::SeqCst),
-        })
+        progress.begin_stage("convert", files.len());
+        let result = self.process_files(&files, output_dir, options, Some(progress), None, None);
+        progress.end_stage();
+        result
     }
 
     /// Process a directory of files
@@ -90,48 +234,293 @@ impl BatchProcessor {
         // Create output directory if it doesn't exist
         std::fs::create_dir_all(output_dir)?;
 
-        let walker = if recursive {
-            WalkDir::new(input_dir)
-        } else {
-            WalkDir::new(input_dir).max_depth(1)
+        // Collect files first to enable parallel processing
+        let files = crate::walk_files(input_dir, recursive, options);
+
+        self.process_files(&files, output_dir, options, None, None, None)
+    }
+
+    /// Process a directory of files, writing one JSON object per file to
+    /// `writer` as soon as the file is done:
+    ///
+    /// ```text
+    /// {"input":"a.xml","ms":12,"msg":"","output":"out/a.json","status":"ok","ts":"2024-01-01T12:00:00.000Z"}
+    /// ```
+    ///
+    /// `status` is `ok`, `skipped`, or `error`, with the error in `msg`;
+    /// `output` is `null` when it was never determined. Each line is
+    /// flushed. Failing to write a line fails the batch once every file has
+    /// been processed.
+    pub fn process_directory_ndjson(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        recursive: bool,
+        options: &ProcessingOptions,
+        writer: &mut (impl Write + Send),
+    ) -> Result<BatchProcessingStats, ConversionError> {
+        std::fs::create_dir_all(output_dir)?;
+        let files = crate::walk_files(input_dir, recursive, options);
+
+        let writer = Mutex::new(writer);
+        let write_error = Mutex::new(None);
+        let record = |result: &FileResult, error: Option<&ConversionError>, elapsed: Duration| {
+            let line = file_entry(result, error, elapsed, "msg");
+            let mut writer = writer.lock().unwrap();
+            let written = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+            if let Err(e) = written {
+                write_error.lock().unwrap().get_or_insert(e);
+            }
         };
 
-        // Collect files first to enable parallel processing
-        let files: Vec<_> = walker
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| e.file_type().is_file())
-            .collect();
+        let stats = self.process_files(&files, output_dir, options, None, Some(&record), None)?;
+        match write_error.into_inner().unwrap() {
+            Some(e) => Err(ConversionError::io_error("<ndjson>", e)),
+            None => Ok(stats),
+        }
+    }
+
+    /// Processes a directory on a background thread, as `process_directory`
+    /// does, sending a `FileEvent` for each file as it starts and as it is
+    /// converted or fails. Files skipped by the options only get `Started`.
+    /// The channel closes once the batch is done; the thread then returns
+    /// the statistics.
+    pub fn process_directory_with_events(
+        self,
+        input_dir: &Path,
+        output_dir: &Path,
+        recursive: bool,
+        options: ProcessingOptions,
+    ) -> (
+        JoinHandle<Result<BatchProcessingStats, ConversionError>>,
+        mpsc::Receiver<FileEvent>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        let (input_dir, output_dir) = (input_dir.to_path_buf(), output_dir.to_path_buf());
+
+        let handle = std::thread::spawn(move || {
+            std::fs::create_dir_all(&output_dir)?;
+            let files = crate::walk_files(&input_dir, recursive, &options);
+
+            // The receiver may be dropped by a caller that stops listening
+            let sender = Mutex::new(sender);
+            let send = |event: FileEvent| {
+                let _ = sender.lock().unwrap().send(event);
+            };
+            let started = |input: &Path| send(FileEvent::Started(input.to_path_buf()));
+            let record = |result: &FileResult, error: Option<&ConversionError>, elapsed: Duration| {
+                match (error, result.status, &result.output) {
+                    (Some(error), ..) => send(FileEvent::Failed {
+                        input: result.input.clone(),
+                        error: error.to_string(),
+                    }),
+                    (None, FileStatus::Converted, Some(output)) => send(FileEvent::Completed {
+                        input: result.input.clone(),
+                        output: output.clone(),
+                        duration: elapsed,
+                    }),
+                    _ => {}
+                }
+            };
+
+            self.process_files(&files, &output_dir, &options, None, Some(&record), Some(&started))
+        });
+        (handle, receiver)
+    }
+
+    /// Reads back the output of every converted file in `stats`: XML outputs
+    /// must be valid Sysmon configs and JSON outputs must parse. This catches
+    /// outputs corrupted or changed after they were written. Failing outputs
+    /// are returned rather than raised, leaving the caller to decide how many
+    /// to tolerate; only an output that is neither XML nor JSON is an error.
+    pub fn verify_outputs(
+        stats: &BatchProcessingStats,
+    ) -> Result<Vec<VerificationFailure>, ConversionError> {
+        Self::verify_outputs_observed(stats, None)
+    }
+
+    /// Verifies outputs as `verify_outputs` does, as a `verify` stage of
+    /// `progress`. With `process_directory_with_progress`, which runs a
+    /// `convert` stage, this makes a two-pass run; create `progress` with
+    /// room for both passes.
+    pub fn verify_outputs_with_progress(
+        stats: &BatchProcessingStats,
+        progress: &ProgressReporter,
+    ) -> Result<Vec<VerificationFailure>, ConversionError> {
+        let result = Self::verify_outputs_observed(stats, Some(progress));
+        progress.end_stage();
+        result
+    }
+
+    fn verify_outputs_observed(
+        stats: &BatchProcessingStats,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<Vec<VerificationFailure>, ConversionError> {
+        let outputs: Vec<&Path> = stats.successful_files().filter_map(|result| result.output.as_deref()).collect();
+        if let Some(progress) = progress {
+            progress.begin_stage("verify", outputs.len());
+        }
+        let mut failures = Vec::new();
+        for output in outputs {
+            let format = match FileFormat::from_path(output) {
+                Some(format @ (FileFormat::Xml | FileFormat::Json)) => format,
+                _ => {
+                    return Err(ConversionError::InvalidFile(format!(
+                        "Cannot verify output {}",
+                        output.display()
+                    )))
+                }
+            };
+            if let Err(error) = verify_output(output, format) {
+                failures.push(VerificationFailure { path: output.to_path_buf(), error });
+            }
+            if let Some(progress) = progress {
+                progress.increment();
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Converts `files` in parallel. `started` is called as each file is
+    /// picked up, and `record` as each file is done, with its error if it
+    /// failed and the time it took. With `options.sort_files`, files are
+    /// taken in canonical path order and `results` keeps that order.
+    fn process_files(
+        &self,
+        files: &[walkdir::DirEntry],
+        output_dir: &Path,
+        options: &ProcessingOptions,
+        progress: Option<&ProgressReporter>,
+        record: Option<&(dyn Fn(&FileResult, Option<&ConversionError>, Duration) + Sync)>,
+        started: Option<&(dyn Fn(&Path) + Sync)>,
+    ) -> Result<BatchProcessingStats, ConversionError> {
+        let mut files: Vec<&walkdir::DirEntry> = files.iter().collect();
+        if options.sort_files {
+            files.sort_by_cached_key(|entry| {
+                std::fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path().to_path_buf())
+            });
+        }
 
         // Process files in parallel using rayon
-        files.par_iter().for_each(|entry| {
-            let result = self.process_single_file(entry.path(), output_dir, options);
+        let failures = Mutex::new(BatchConversionError::new());
+        let results = Mutex::new(Vec::with_capacity(files.len()));
+        let rule_stats = Mutex::new(RuleStats::default());
+        let timed_out_count = AtomicUsize::new(0);
+        let is_cancelled = || self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled);
+        let process = || files.par_iter().enumerate().for_each(|(position, entry)| {
+            let mut file_result = FileResult::new(entry.path());
+            let start = Instant::now();
+            let result = if is_cancelled() {
+                Ok(FileStatus::Skipped)
+            } else {
+                if let Some(started) = started {
+                    started(entry.path());
+                }
+                self.process_single_file(entry.path(), output_dir, options, &mut file_result, &timed_out_count)
+            };
             
             match result {
-                Ok(_) => {
-                    self.processed_count.fetch_add(1, Ordering::SeqCst);
+                Ok(status) => {
+                    file_result.status = status;
+                    if options.show_stats && status == FileStatus::Converted {
+                        if let Some(stats) = converted_rule_stats(&file_result) {
+                            rule_stats.lock().unwrap().add(&stats);
+                        }
+                    }
+                    if let Some(record) = record {
+                        record(&file_result, None, start.elapsed());
+                    }
+                    self.audit(&file_result, None, start.elapsed());
                 }
                 Err(e) => {
-                    eprintln!("Error processing {}: {}", entry.path().display(), e);
-                    self.error_count.fetch_add(1, Ordering::SeqCst);
+                    if !options.silent {
+                        eprintln!("Error processing {}: {}", entry.path().display(), e);
+                    }
+                    if let Some(record) = record {
+                        record(&file_result, Some(&e), start.elapsed());
+                    }
+                    self.audit(&file_result, Some(&e), start.elapsed());
+                    failures.lock().unwrap().push(entry.path(), e);
                 }
             }
+
+            results.lock().unwrap().push((position, file_result));
+            if let Some(progress) = progress {
+                progress.increment();
+            }
         });
 
+        // Honor an explicit worker count with a dedicated pool
+        match options.workers {
+            Some(workers) => rayon::ThreadPoolBuilder::new()
+                .num_threads(workers)
+                .build()
+                .map_err(|e| ConversionError::BatchError(format!("Failed to create worker pool: {}", e)))?
+                .install(process),
+            None => process(),
+        }
+
+        let mut results = results.into_inner().unwrap();
+        if options.sort_files {
+            results.sort_by_key(|&(position, _)| position);
+        }
+        let results: Vec<FileResult> = results.into_iter().map(|(_, result)| result).collect();
+        let count = |status: FileStatus| results.iter().filter(|result| result.status == status).count();
+
+        let converted = results.iter().filter(|result| result.status == FileStatus::Converted);
+        let total_input_bytes: u64 = converted.clone().map(|result| result.input_bytes).sum();
+        let total_output_bytes: u64 = converted.map(|result| result.output_bytes).sum();
+        let size_reduction_bytes = total_input_bytes as i64 - total_output_bytes as i64;
+        let size_reduction_percent = match total_input_bytes {
+            0 => 0.0,
+            total => size_reduction_bytes as f64 / total as f64 * 100.0,
+        };
+        if options.show_stats {
+            log::info!(
+                "Converted {} bytes into {} bytes ({:.1}% reduction)",
+                total_input_bytes, total_output_bytes, size_reduction_percent
+            );
+        }
+
         Ok(BatchProcessingStats {
-            processed: self.processed_count.load(Ordering::SeqCst),
-            errors: self.error_count.load(Ordering::SeqCst),
+            processed: count(FileStatus::Converted),
+            errors: count(FileStatus::Failed),
+            skipped: count(FileStatus::Skipped),
+            timed_out_count: timed_out_count.into_inner(),
+            failures: failures.into_inner().unwrap(),
+            results,
+            rule_stats: rule_stats.into_inner().unwrap(),
+            cancelled: is_cancelled(),
+            total_input_bytes,
+            total_output_bytes,
+            size_reduction_bytes,
+            size_reduction_percent,
         })
     }
 
+    /// Appends the entry for a processed file to the audit log, if any
+    fn audit(&self, result: &FileResult, error: Option<&ConversionError>, elapsed: Duration) {
+        let Some(audit_log) = &self.audit_log else {
+            return;
+        };
+        let line = file_entry(result, error, elapsed, "error_msg");
+        let mut writer = audit_log.lock().unwrap();
+        if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+            log::warn!("Failed to write audit log entry: {}", e);
+        }
+    }
+
     fn process_single_file(
         &self,
         input: &Path,
         output_dir: &Path,
         options: &ProcessingOptions,
-    ) -> Result<(), ConversionError> {
+        file_result: &mut FileResult,
+        timed_out_count: &AtomicUsize,
+    ) -> Result<FileStatus, ConversionError> {
         // Skip files larger than max_size
         if let Ok(metadata) = input.metadata() {
+            file_result.input_bytes = metadata.len();
             if metadata.len() > options.max_file_size {
                 return Err(ConversionError::InvalidFile(
                     format!("File too large: {}", input.display())
@@ -143,11 +532,17 @@ impl BatchProcessor {
         if let Some(patterns) = &options.ignore_patterns {
             if let Some(file_name) = input.file_name().and_then(|n| n.to_str()) {
                 if patterns.iter().any(|p| file_name.contains(p)) {
-                    return Ok(());
+                    return Ok(FileStatus::Skipped);
                 }
             }
         }
 
+        if let Some(pre_hook) = &self.pre_hook {
+            if pre_hook(input).is_err() {
+                return Ok(FileStatus::Skipped);
+            }
+        }
+
         // Determine output path
         let file_name = input.file_name().ok_or_else(|| {
             ConversionError::InvalidFile("Invalid input filename".into())
@@ -160,6 +555,11 @@ impl BatchProcessor {
         };
 
         let output_path = output_dir.join(file_name).with_extension(new_extension);
+        file_result.output = Some(output_path.clone());
+
+        if !crate::prepare_output(&output_path, options.overwrite_policy)? {
+            return Ok(FileStatus::Skipped);
+        }
 
         // Create backup if enabled
         if options.create_backup && output_path.exists() {
@@ -167,8 +567,211 @@ impl BatchProcessor {
             std::fs::copy(&output_path, &backup_path)?;
         }
 
-        // Convert the file
-        crate::convert_file(input, &output_path)
+        // Convert the file, retrying transient failures when a policy is set
+        let retry_policy = self.retry_policy;
+        let (result, retries) = match options.per_file_timeout {
+            // A conversion that times out keeps running in the background,
+            // so it only writes a temporary file, which is persisted here
+            // once it arrives in time and deleted otherwise
+            Some(timeout) => {
+                let (input_path, converted_path) = (input.to_path_buf(), output_path.clone());
+                let convert = move || retrying(retry_policy, || convert_to_temp(&input_path, &converted_path));
+                match run_with_timeout(convert, timeout) {
+                    Some((result, retries)) => (result.and_then(|temp| persist(temp, &output_path)), retries),
+                    None => {
+                        timed_out_count.fetch_add(1, Ordering::SeqCst);
+                        return Err(ConversionError::BatchError(format!(
+                            "Timeout after {}ms",
+                            timeout.as_millis()
+                        )));
+                    }
+                }
+            }
+            None => retrying(retry_policy, || convert_to(input, &output_path, options.atomic_write)),
+        };
+        file_result.retry_count = retries;
+        result?;
+        file_result.output_bytes = std::fs::metadata(&output_path)
+            .map_err(|e| ConversionError::io_error(&output_path, e))?
+            .len();
+
+        if let Some(post_hook) = &self.post_hook {
+            post_hook(input, &output_path)?;
+        }
+        Ok(FileStatus::Converted)
+    }
+}
+
+/// Checks that `path` holds a valid config in `format`. Without the
+/// `validation` feature, XML outputs only need to be well formed.
+fn verify_output(path: &Path, format: FileFormat) -> Result<(), ConversionError> {
+    let content = std::fs::read_to_string(path).map_err(|e| ConversionError::io_error(path, e))?;
+    if format == FileFormat::Json {
+        serde_json::from_str::<serde_json::Value>(&content)?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "validation")]
+    {
+        let config = parse_sysmon_config_from_str(&content)?;
+        validate_sysmon_config(&config)?;
+    }
+    #[cfg(not(feature = "validation"))]
+    crate::converter::xml::xml_to_value(&content)?;
+    Ok(())
+}
+
+/// Converts `input` into `output`. With `atomic`, the conversion is written
+/// to a temporary file in the output's directory and renamed over `output`,
+/// so `output` is either complete or untouched.
+fn convert_to(input: &Path, output: &Path, atomic: bool) -> Result<(), ConversionError> {
+    if !atomic {
+        return crate::convert_file(input, output);
+    }
+    persist(convert_to_temp(input, output)?, output)
+}
+
+/// Converts `input` into a temporary file in the directory of `output`,
+/// which is deleted when dropped unless it is passed to `persist`
+fn convert_to_temp(input: &Path, output: &Path) -> Result<NamedTempFile, ConversionError> {
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // Keep the extension, which picks the output format; the leading dot
+    // keeps directory walks from picking the file up
+    let extension = output
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let temp = tempfile::Builder::new()
+        .prefix(".sysmon-json-")
+        .suffix(&extension)
+        .tempfile_in(dir)
+        .map_err(|e| ConversionError::io_error(dir, e))?;
+
+    // A failed conversion drops, and so deletes, the temporary file
+    crate::convert_file(input, temp.path())?;
+    Ok(temp)
+}
+
+/// Renames a converted temporary file over `output`
+fn persist(temp: NamedTempFile, output: &Path) -> Result<(), ConversionError> {
+    temp.persist(output).map_err(|e| {
+        let _ = e.file.close();
+        ConversionError::io_error(output, e.error)
+    })?;
+    Ok(())
+}
+
+/// Runs `operation` under `policy`, or once without one, returning its
+/// result and the number of retries
+fn retrying<T>(
+    policy: Option<RetryPolicy>,
+    mut operation: impl FnMut() -> Result<T, ConversionError>,
+) -> (Result<T, ConversionError>, u32) {
+    match policy {
+        Some(policy) => policy.run(operation),
+        None => (operation(), 0),
+    }
+}
+
+/// Runs `task` on its own thread, giving up after `timeout`. A task that
+/// times out cannot be stopped; it is left to finish in the background and
+/// its result is dropped, so it must not write anything but temporary
+/// files.
+fn run_with_timeout<T, F>(task: F, timeout: Duration) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // The receiver is gone once the batch has given up on the task
+        let _ = sender.send(task());
+    });
+    receiver.recv_timeout(timeout).ok()
+}
+
+/// Counts the rules of a converted file, reading whichever side of the
+/// conversion is JSON so the XML is not validated a second time
+fn converted_rule_stats(result: &FileResult) -> Option<RuleStats> {
+    let is_json = |path: &Path| FileFormat::from_path(path) == Some(FileFormat::Json);
+    let path = match &result.output {
+        Some(output) if is_json(output) => output,
+        _ => &result.input,
+    };
+    match compute_rule_stats_from_file(path) {
+        Ok(stats) => Some(stats),
+        Err(e) => {
+            log::debug!("No rule statistics for {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Describes a processed file as a JSON object, with the error message
+/// under `message_key`. `status` is `ok`, `skipped`, or `error`.
+fn file_entry(
+    result: &FileResult,
+    error: Option<&ConversionError>,
+    elapsed: Duration,
+    message_key: &str,
+) -> serde_json::Value {
+    let status = match (result.status, error) {
+        (_, Some(_)) | (FileStatus::Failed, _) => "error",
+        (FileStatus::Skipped, None) => "skipped",
+        (FileStatus::Converted, None) => "ok",
+    };
+    let mut entry = serde_json::json!({
+        "ts": utc_timestamp(SystemTime::now()),
+        "input": result.input.display().to_string(),
+        "output": result.output.as_ref().map(|p| p.display().to_string()),
+        "status": status,
+        "ms": elapsed.as_millis() as u64,
+    });
+    entry[message_key] = error.map(ToString::to_string).unwrap_or_default().into();
+    entry
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with milliseconds
+fn utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+impl Drop for BatchProcessor {
+    fn drop(&mut self) {
+        // Entries are flushed as they are written; this only catches a
+        // write interrupted by a panic
+        if let Some(audit_log) = &self.audit_log {
+            if let Ok(mut writer) = audit_log.lock() {
+                let _ = writer.flush();
+            }
+        }
     }
 }
 
@@ -176,4 +779,517 @@ impl Default for BatchProcessor {
     fn default() -> Self {
         Self::new()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{OverwritePolicy, ProcessingOptionsBuilder};
+    use std::fs;
+    use tempfile::tempdir;
+
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_skip_policy_counts_skipped() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::create_dir_all(&output_dir).unwrap();
+
+        fs::write(input_dir.join("test.xml"), VALID_XML).unwrap();
+        fs::write(output_dir.join("test.json"), "manual edits").unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .overwrite_policy(OverwritePolicy::Skip)
+            .create_backup(true)
+            .build();
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.processed, 0);
+        assert_eq!(fs::read_to_string(output_dir.join("test.json")).unwrap(), "manual edits");
+        // A skipped output is left alone, so there is nothing to back up
+        assert!(!output_dir.join("test.bak").exists());
+    }
+
+    #[test]
+    fn test_counts_per_run() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::create_dir_all(&output_dir).unwrap();
+
+        fs::write(input_dir.join("test.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("broken.xml"), "<Sysmon>").unwrap();
+
+        let processor = BatchProcessor::new();
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        for _ in 0..2 {
+            let stats = processor
+                .process_directory(&input_dir, &output_dir, false, &options)
+                .unwrap();
+            assert_eq!(stats.processed, 1);
+            assert_eq!(stats.errors, 1);
+            assert_eq!(stats.skipped, 0);
+        }
+    }
+
+    #[test]
+    fn test_rule_stats() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        let xml = r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="test" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="is">C:\Windows\System32\test.exe</Image>
+                            <CommandLine condition="contains">test</CommandLine>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+        fs::write(input_dir.join("a.xml"), xml).unwrap();
+        fs::write(input_dir.join("b.xml"), xml).unwrap();
+
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &ProcessingOptions::default())
+            .unwrap();
+        assert_eq!(stats.rule_stats.total, 4);
+        assert_eq!(stats.rule_stats.by_event_type.get("ProcessCreate"), Some(&4));
+        assert_eq!(stats.rule_stats.by_condition.get("contains"), Some(&2));
+
+        let options = ProcessingOptionsBuilder::new().show_stats(false).build();
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+        assert_eq!(stats.rule_stats, RuleStats::default());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hidden_and_symlinked_directories() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let shared_dir = temp_dir.path().join("shared");
+        let xml = r#"<Sysmon schemaversion="4.30"><EventFiltering/></Sysmon>"#;
+        for dir in [".git", "node_modules"] {
+            fs::create_dir_all(input_dir.join(dir)).unwrap();
+            fs::write(input_dir.join(dir).join(format!("{}.xml", dir.trim_start_matches('.'))), xml).unwrap();
+        }
+        fs::create_dir_all(&shared_dir).unwrap();
+        fs::write(input_dir.join("a.xml"), xml).unwrap();
+        fs::write(shared_dir.join("shared.xml"), xml).unwrap();
+        std::os::unix::fs::symlink(&shared_dir, input_dir.join("linked")).unwrap();
+        std::os::unix::fs::symlink(&input_dir, input_dir.join("loop")).unwrap();
+
+        let visited = |options: &ProcessingOptions| {
+            let output_dir = tempdir().unwrap();
+            let stats = BatchProcessor::new()
+                .process_directory(&input_dir, output_dir.path(), true, options)
+                .unwrap();
+            let mut names: Vec<_> = stats
+                .results
+                .iter()
+                .map(|r| r.input.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(visited(&ProcessingOptions::default()), vec!["a.xml"]);
+
+        let options = ProcessingOptionsBuilder::new()
+            .follow_symlinks(true)
+            .include_hidden(true)
+            .build();
+        assert_eq!(
+            visited(&options),
+            vec!["a.xml", "git.xml", "node_modules.xml", "shared.xml"]
+        );
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(utc_timestamp(time), "2024-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn test_process_directory_ndjson() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("good.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("bad.xml"), "<Sysmon><Broken></Sysmon>").unwrap();
+
+        let mut output = Vec::new();
+        let stats = BatchProcessor::new()
+            .process_directory_ndjson(&input_dir, &output_dir, false, &ProcessingOptions::default(), &mut output)
+            .unwrap();
+        assert_eq!((stats.processed, stats.errors), (1, 1));
+
+        let output = String::from_utf8(output).unwrap();
+        let mut lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        lines.sort_by_key(|line| line["input"].as_str().unwrap().to_string());
+        assert_eq!(lines.len(), 2);
+
+        let (bad, good) = (&lines[0], &lines[1]);
+        assert!(bad["input"].as_str().unwrap().ends_with("bad.xml"));
+        assert_eq!(bad["status"], "error");
+        assert!(!bad["msg"].as_str().unwrap().is_empty());
+        assert_eq!(good["status"], "ok");
+        assert_eq!(good["msg"], "");
+        assert!(good["output"].as_str().unwrap().ends_with("good.json"));
+        assert!(good["ms"].is_u64());
+        assert!(good["ts"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn test_failures_are_recorded_per_file() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .max_file_size(4)
+            .build();
+        fs::write(input_dir.join("large.xml"), "<Sysmon></Sysmon>").unwrap();
+
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        assert_eq!(stats.errors, 1);
+        let error = stats.failures.error_for(&input_dir.join("large.xml")).unwrap();
+        assert!(matches!(error, ConversionError::InvalidFile(_)));
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        assert_eq!(run_with_timeout(|| 42, Duration::from_secs(5)), Some(42));
+
+        let slow = || std::thread::sleep(Duration::from_secs(2));
+        assert_eq!(run_with_timeout(slow, Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn test_timeout_is_recorded_as_failure() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("test.xml"), r#"<Sysmon schemaversion="4.30"></Sysmon>"#).unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .per_file_timeout(Some(Duration::ZERO))
+            .silent(true)
+            .build();
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        assert_eq!(stats.timed_out_count, 1);
+        assert_eq!(stats.errors, 1);
+        let error = stats.failures.error_for(&input_dir.join("test.xml")).unwrap();
+        assert!(error.to_string().contains("Timeout after 0ms"), "{}", error);
+
+        // The abandoned conversion finishes without leaving anything behind
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_sort_files_orders_results() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        fs::create_dir_all(&input_dir).unwrap();
+        let names = ["delta.xml", "alpha.xml", "charlie.xml", "bravo.xml"];
+        for name in names {
+            fs::write(input_dir.join(name), r#"<Sysmon schemaversion="4.30"></Sysmon>"#).unwrap();
+        }
+
+        let options = ProcessingOptionsBuilder::new()
+            .sort_files(true)
+            .workers(Some(4))
+            .silent(true)
+            .build();
+        let run = |output: &str| {
+            BatchProcessor::new()
+                .process_directory(&input_dir, &temp_dir.path().join(output), false, &options)
+                .unwrap()
+                .results
+                .into_iter()
+                .map(|result| result.input.file_name().unwrap().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let first = run("first");
+        assert_eq!(first, ["alpha.xml", "bravo.xml", "charlie.xml", "delta.xml"]);
+        assert_eq!(run("second"), first);
+    }
+
+    #[test]
+    fn test_pre_and_post_hooks() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        for name in ["keep.xml", "hold.xml", "reject.xml"] {
+            fs::write(input_dir.join(name), VALID_XML).unwrap();
+        }
+
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let uploads = Arc::clone(&uploaded);
+        let processor = BatchProcessor::new()
+            .with_pre_hook(|input| match input.file_name().unwrap().to_str() {
+                Some("hold.xml") => Err(ConversionError::BatchError("on hold".into())),
+                _ => Ok(()),
+            })
+            .with_post_hook(move |input, output| {
+                if input.ends_with("reject.xml") {
+                    return Err(ConversionError::BatchError("upload failed".into()));
+                }
+                uploads.lock().unwrap().push(output.to_path_buf());
+                Ok(())
+            });
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        let stats = processor
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        assert_eq!(stats.processed, 1);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.errors, 1);
+        assert!(!output_dir.join("hold.json").exists());
+        assert!(stats.failures.error_for(&input_dir.join("reject.xml")).is_some());
+        assert_eq!(*uploaded.lock().unwrap(), [output_dir.join("keep.json")]);
+    }
+
+    #[test]
+    fn test_process_directory_with_events() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("good.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("bad.json"), "{ not json").unwrap();
+
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        let (handle, events) = BatchProcessor::new()
+            .process_directory_with_events(&input_dir, &output_dir, false, options);
+        let events: Vec<FileEvent> = events.iter().collect();
+        let stats = handle.join().unwrap().unwrap();
+
+        assert_eq!(stats.processed, 1);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(events.len(), 4);
+        let started = |path: &Path| {
+            events.iter().position(|e| *e == FileEvent::Started(path.to_path_buf())).unwrap()
+        };
+        let completed = events.iter().position(|e| matches!(
+            e,
+            FileEvent::Completed { input, output, .. }
+                if *input == input_dir.join("good.xml") && *output == output_dir.join("good.json")
+        )).unwrap();
+        let failed = events.iter().position(|e| matches!(
+            e,
+            FileEvent::Failed { input, error } if *input == input_dir.join("bad.json") && !error.is_empty()
+        )).unwrap();
+        assert!(started(&input_dir.join("good.xml")) < completed);
+        assert!(started(&input_dir.join("bad.json")) < failed);
+    }
+
+    #[test]
+    fn test_cancellation_skips_remaining_files() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        for i in 0..4 {
+            fs::write(input_dir.join(format!("{}.xml", i)), "<Sysmon></Sysmon>").unwrap();
+        }
+
+        // Cancelled by the first file to start
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let options = ProcessingOptionsBuilder::new().workers(Some(1)).silent(true).build();
+        let stats = BatchProcessor::new()
+            .with_cancellation(token)
+            .with_pre_hook(move |_| {
+                canceller.cancel();
+                Ok(())
+            })
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        assert!(stats.cancelled);
+        assert_eq!(stats.processed + stats.errors, 1);
+        assert_eq!(stats.skipped, 3);
+        assert_eq!(stats.results.len(), 4);
+
+        let stats = BatchProcessor::new()
+            .with_cancellation(CancellationToken::new())
+            .process_directory(&input_dir, &temp_dir.path().join("again"), false, &options)
+            .unwrap();
+        assert!(!stats.cancelled);
+        assert_eq!(stats.skipped, 0);
+    }
+
+    #[test]
+    fn test_atomic_write() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("good.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("bad.json"), "{ not json").unwrap();
+
+        let convert = |output: &str, atomic: bool| {
+            let output_dir = temp_dir.path().join(output);
+            let options = ProcessingOptionsBuilder::new().atomic_write(atomic).silent(true).build();
+            let stats = BatchProcessor::new()
+                .process_directory(&input_dir, &output_dir, false, &options)
+                .unwrap();
+            assert_eq!((stats.processed, stats.errors), (1, 1));
+            output_dir
+        };
+        let direct = convert("direct", false);
+        let atomic = convert("atomic", true);
+
+        // The output is complete, the failed one absent, and no temporary
+        // file is left behind
+        assert_eq!(
+            fs::read_to_string(atomic.join("good.json")).unwrap(),
+            fs::read_to_string(direct.join("good.json")).unwrap()
+        );
+        let names: Vec<_> = fs::read_dir(&atomic)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["good.json"]);
+    }
+
+    #[test]
+    fn test_size_statistics() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("test.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("bad.json"), "{ not json").unwrap();
+
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+
+        // Only the converted file counts
+        let input_bytes = fs::metadata(input_dir.join("test.xml")).unwrap().len();
+        let output_bytes = fs::metadata(output_dir.join("test.json")).unwrap().len();
+        assert_eq!(stats.total_input_bytes, input_bytes);
+        assert_eq!(stats.total_output_bytes, output_bytes);
+        assert_eq!(stats.size_reduction_bytes, input_bytes as i64 - output_bytes as i64);
+        let percent = stats.size_reduction_bytes as f64 / input_bytes as f64 * 100.0;
+        assert!((stats.size_reduction_percent - percent).abs() < 1e-9);
+
+        let converted = stats.results.iter().find(|r| r.status == FileStatus::Converted).unwrap();
+        assert_eq!((converted.input_bytes, converted.output_bytes), (input_bytes, output_bytes));
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("good.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("bad.json"), "{ not json").unwrap();
+
+        // A second run appends to the log of the first
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        for _ in 0..2 {
+            BatchProcessor::new()
+                .with_audit_log(audit_path.clone())
+                .unwrap()
+                .process_directory(&input_dir, &output_dir, false, &options)
+                .unwrap();
+        }
+
+        let content = fs::read_to_string(&audit_path).unwrap();
+        let entries: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 4);
+        for entry in &entries {
+            assert!(entry["ts"].as_str().unwrap().ends_with('Z'));
+            assert!(entry["ms"].is_u64());
+            if entry["input"].as_str().unwrap().ends_with("good.xml") {
+                assert_eq!(entry["status"], "ok");
+                assert_eq!(entry["error_msg"], "");
+                assert!(entry["output"].as_str().unwrap().ends_with("good.json"));
+            } else {
+                assert_eq!(entry["status"], "error");
+                assert!(!entry["error_msg"].as_str().unwrap().is_empty());
+            }
+        }
+
+        let unwritable = temp_dir.path().join("missing").join("audit.jsonl");
+        assert!(BatchProcessor::new().with_audit_log(unwritable).is_err());
+    }
+
+    #[test]
+    fn test_verify_outputs() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("a.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("b.xml"), VALID_XML).unwrap();
+
+        let options = ProcessingOptionsBuilder::new().silent(true).build();
+        let stats = BatchProcessor::new()
+            .process_directory(&input_dir, &output_dir, false, &options)
+            .unwrap();
+        assert_eq!(stats.successful_files().count(), 2);
+        assert!(BatchProcessor::verify_outputs(&stats).unwrap().is_empty());
+
+        // An output truncated after the batch is caught
+        fs::write(output_dir.join("b.json"), "{\"Sysmon\": {").unwrap();
+        let failures = BatchProcessor::verify_outputs(&stats).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].path, output_dir.join("b.json"));
+        assert!(matches!(failures[0].error, ConversionError::JsonParse(_)), "{:?}", failures[0].error);
+
+        // Converting again and verifying, as two stages of one reporter
+        let progress = ProgressReporter::new(4);
+        let stats = BatchProcessor::new()
+            .process_directory_with_progress(&input_dir, &output_dir, false, &options, &progress)
+            .unwrap();
+        assert_eq!(progress.to_string(), "2/4");
+        assert!(BatchProcessor::verify_outputs_with_progress(&stats, &progress).unwrap().is_empty());
+        assert_eq!(progress.processed(), 4);
+        assert_eq!(progress.current_stage(), None);
+    }
 }
\ No newline at end of file
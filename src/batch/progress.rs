@@ -1,5 +1,6 @@
+use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 /// Progress reporter for batch operations
@@ -8,6 +9,16 @@ pub struct ProgressReporter {
     total: u64,
     processed: Arc<AtomicUsize>,
     start_time: Instant,
+    /// The stage begun by `begin_stage`, until `end_stage`
+    stage: Mutex<Option<Stage>>,
+    stage_processed: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Stage {
+    name: String,
+    total: usize,
+    start_time: Instant,
 }
 
 impl ProgressReporter {
@@ -17,12 +28,15 @@ impl ProgressReporter {
             total: total_files as u64,
             processed: Arc::new(AtomicUsize::new(0)),
             start_time: Instant::now(),
+            stage: Mutex::new(None),
+            stage_processed: AtomicUsize::new(0),
         }
     }
 
-    /// Increments the progress counter
+    /// Increments the progress counter and the counter of the current stage
     pub fn increment(&self) {
         self.processed.fetch_add(1, Ordering::SeqCst);
+        self.stage_processed.fetch_add(1, Ordering::SeqCst);
     }
 
     /// Gets the total number of files
@@ -40,8 +54,93 @@ impl ProgressReporter {
         self.start_time.elapsed()
     }
 
-    /// Gets a cloned counter for parallel processing
+    /// Gets a cloned counter for parallel processing. Increments made
+    /// through it are not counted in the current stage.
     pub fn get_counter(&self) -> Arc<AtomicUsize> {
         Arc::clone(&self.processed)
     }
-}
\ No newline at end of file
+
+    /// Starts a stage of `stage_total` items, such as the validation pass
+    /// after a merge. The stage counter restarts from zero while the overall
+    /// counter carries on; a stage still running is ended first.
+    pub fn begin_stage(&self, name: &str, stage_total: usize) {
+        let mut stage = self.stage.lock().unwrap();
+        if let Some(previous) = stage.take() {
+            log_stage_end(&previous);
+        }
+        self.stage_processed.store(0, Ordering::SeqCst);
+        *stage = Some(Stage {
+            name: name.to_string(),
+            total: stage_total,
+            start_time: Instant::now(),
+        });
+    }
+
+    /// Ends the current stage, logging the time it took
+    pub fn end_stage(&self) {
+        if let Some(stage) = self.stage.lock().unwrap().take() {
+            log_stage_end(&stage);
+        }
+    }
+
+    /// Gets the name of the current stage. The name is copied out, since
+    /// another thread may begin a new stage at any time.
+    pub fn current_stage(&self) -> Option<String> {
+        self.stage.lock().unwrap().as_ref().map(|stage| stage.name.clone())
+    }
+
+    /// Gets the number of items processed in the current stage
+    pub fn stage_processed(&self) -> usize {
+        self.stage_processed.load(Ordering::SeqCst)
+    }
+}
+
+fn log_stage_end(stage: &Stage) {
+    log::info!("Stage {} finished in {:.2?}", stage.name, stage.start_time.elapsed());
+}
+
+impl fmt::Display for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(stage) = self.stage.lock().unwrap().as_ref() {
+            return write!(
+                f,
+                "[{}] {}/{} ({}/{})",
+                stage.name,
+                self.stage_processed(),
+                stage.total,
+                self.processed(),
+                self.total
+            );
+        }
+        write!(f, "{}/{}", self.processed(), self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages() {
+        let progress = ProgressReporter::new(4);
+        assert_eq!(progress.current_stage(), None);
+        assert_eq!(progress.to_string(), "0/4");
+
+        progress.begin_stage("convert", 2);
+        progress.increment();
+        progress.increment();
+        assert_eq!(progress.current_stage().as_deref(), Some("convert"));
+        assert_eq!(progress.to_string(), "[convert] 2/2 (2/4)");
+
+        // The stage counter restarts, the overall counter does not
+        progress.begin_stage("verify", 2);
+        progress.increment();
+        assert_eq!(progress.stage_processed(), 1);
+        assert_eq!(progress.processed(), 3);
+        assert_eq!(progress.to_string(), "[verify] 1/2 (3/4)");
+
+        progress.end_stage();
+        assert_eq!(progress.current_stage(), None);
+        assert_eq!(progress.to_string(), "3/4");
+    }
+}
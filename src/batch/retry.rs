@@ -0,0 +1,99 @@
+use std::time::Duration;
+use crate::error::ConversionError;
+
+/// Policy for retrying files that fail with recoverable errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay between attempts
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a new RetryPolicy
+    pub fn new(max_attempts: u32, delay: Duration) -> Self {
+        Self { max_attempts, delay }
+    }
+
+    /// Runs an operation, retrying while it fails with a recoverable error.
+    /// Returns the final result and the number of retries performed.
+    pub fn run<T, F>(&self, mut operation: F) -> (Result<T, ConversionError>, u32)
+    where
+        F: FnMut() -> Result<T, ConversionError>,
+    {
+        let mut retries = 0;
+        loop {
+            match operation() {
+                Err(e) if e.is_recoverable() && retries + 1 < self.max_attempts => {
+                    retries += 1;
+                    std::thread::sleep(self.delay);
+                }
+                result => return (result, retries),
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_retries_recoverable_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let mut calls = 0;
+
+        let (result, retries) = policy.run(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(ConversionError::io_error("flaky.xml", io::Error::from(io::ErrorKind::WouldBlock)))
+            } else {
+                Ok(())
+            }
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(retries, 2);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_does_not_retry_structural_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let mut calls = 0;
+
+        let (result, retries) = policy.run(|| -> Result<(), ConversionError> {
+            calls += 1;
+            Err(ConversionError::ValidationError("bad rule".into()))
+        });
+
+        assert!(matches!(result, Err(ConversionError::ValidationError(_))));
+        assert_eq!(retries, 0);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let mut calls = 0;
+
+        let (result, _) = policy.run(|| -> Result<(), ConversionError> {
+            calls += 1;
+            Err(ConversionError::io_error("flaky.xml", io::Error::from(io::ErrorKind::Interrupted)))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,211 @@
+//! Command line interface for converting, merging, and batch processing
+//! Sysmon configurations.
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use log::LevelFilter;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use sysmon_json::batch::BatchProcessor;
+use sysmon_json::error::ConversionError;
+use sysmon_json::{convert_file, convert_stdio, merge_configs, FileFormat, ProcessingOptionsBuilder};
+
+/// Some inputs failed, for reasons that do not share a single category
+const EXIT_FAILURE: u8 = 1;
+/// The input failed parsing or Sysmon schema validation
+const EXIT_VALIDATION: u8 = 3;
+/// Reading or writing a file failed
+const EXIT_IO: u8 = 4;
+
+fn cli() -> Command {
+    Command::new("sysmon-json")
+        .about("Convert, merge, and batch process Sysmon configurations")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Only report errors")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Enable debug logging")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Convert a single file between XML and JSON")
+                .arg(path_arg("input", "Input configuration file"))
+                .arg(path_arg("output", "Output file; the extension selects the format")),
+        )
+        .subcommand(
+            Command::new("stdio")
+                .about("Convert a configuration read from stdin, writing it to stdout")
+                .arg(format_arg("from", "Format of the input"))
+                .arg(format_arg("to", "Format of the output")),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge every configuration in a directory into one file")
+                .arg(path_arg("dir", "Directory containing configurations"))
+                .arg(path_arg("output", "Merged output file"))
+                .arg(recursive_arg()),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Convert every configuration in a directory")
+                .arg(path_arg("input_dir", "Directory containing configurations"))
+                .arg(path_arg("output_dir", "Directory for converted files"))
+                .arg(recursive_arg())
+                .arg(
+                    Arg::new("workers")
+                        .long("workers")
+                        .value_name("N")
+                        .help("Number of worker threads")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("ignore")
+                        .long("ignore")
+                        .value_name("PATTERN")
+                        .help("Skip files whose name contains PATTERN")
+                        .num_args(1..)
+                        .action(ArgAction::Append),
+                ),
+        )
+}
+
+fn path_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .help(help)
+        .required(true)
+        .value_parser(value_parser!(PathBuf))
+}
+
+fn format_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .help(help)
+        .required(true)
+        .value_parser(["xml", "json"])
+}
+
+fn recursive_arg() -> Arg {
+    Arg::new("recursive")
+        .short('r')
+        .long("recursive")
+        .help("Descend into subdirectories")
+        .action(ArgAction::SetTrue)
+}
+
+/// Maps an error onto the process exit code
+fn exit_code(error: &ConversionError) -> u8 {
+    match error {
+        ConversionError::BatchConversionError(batch) => {
+            let mut codes = batch.iter().map(|(_, e)| exit_code(e));
+            match codes.next() {
+                Some(first) if codes.all(|code| code == first) => first,
+                _ => EXIT_FAILURE,
+            }
+        }
+        e if e.is_recoverable() => EXIT_IO,
+        ConversionError::XmlParse(_)
+        | ConversionError::JsonParse(_)
+        | ConversionError::ValidationError(_)
+        | ConversionError::ParserError(_)
+        | ConversionError::PreprocessError(_) => EXIT_VALIDATION,
+        _ => EXIT_FAILURE,
+    }
+}
+
+fn run(matches: &ArgMatches, silent: bool) -> Result<(), ConversionError> {
+    let path = |m: &ArgMatches, name: &str| m.get_one::<PathBuf>(name).unwrap().clone();
+
+    match matches.subcommand() {
+        Some(("convert", m)) => convert_file(&path(m, "input"), &path(m, "output")),
+        Some(("stdio", m)) => {
+            let format = |name: &str| {
+                let ext = m.get_one::<String>(name).unwrap();
+                FileFormat::from_extension(ext).unwrap()
+            };
+            convert_stdio(format("from"), format("to"))
+        }
+        Some(("merge", m)) => {
+            merge_configs(&path(m, "dir"), &path(m, "output"), m.get_flag("recursive")).map(|_| ())
+        }
+        Some(("batch", m)) => {
+            let ignore_patterns = m
+                .get_many::<String>("ignore")
+                .map(|patterns| patterns.cloned().collect());
+            let options = ProcessingOptionsBuilder::new()
+                .workers(m.get_one::<usize>("workers").copied())
+                .ignore_patterns(ignore_patterns)
+                .silent(silent)
+                .build();
+
+            let stats = BatchProcessor::new().process_directory(
+                &path(m, "input_dir"),
+                &path(m, "output_dir"),
+                m.get_flag("recursive"),
+                &options,
+            )?;
+
+            if !silent {
+                println!(
+                    "Processed: {}, Skipped: {}, Errors: {}",
+                    stats.processed, stats.skipped, stats.errors
+                );
+                if stats.rule_stats.total > 0 {
+                    print!("\n{}", stats.rule_stats.display_table());
+                }
+            }
+            if stats.failures.is_empty() {
+                Ok(())
+            } else {
+                Err(ConversionError::BatchConversionError(stats.failures))
+            }
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let silent = matches.get_flag("quiet");
+
+    let level = if silent {
+        LevelFilter::Error
+    } else if matches.get_flag("verbose") {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Warn
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+
+    match run(&matches, silent) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        cli().debug_assert();
+    }
+}
@@ -1,3 +1,4 @@
 mod options;
+pub use options::OverwritePolicy;
 pub use options::ProcessingOptions;
 pub use options::ProcessingOptionsBuilder;
\ No newline at end of file
@@ -1,7 +1,50 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
-/// Options for controlling Sysmon configuration processing
+/// Callback receiving `(bytes_read, total_bytes)` while an input file is read
+#[derive(Clone)]
+pub struct ProgressHook(Arc<dyn Fn(u64, u64) + Send + Sync>);
+
+impl ProgressHook {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn call(&self, bytes_read: u64, total_bytes: u64) {
+        (self.0)(bytes_read, total_bytes)
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Policy applied when a conversion output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file alone and skip the input
+    Skip,
+    /// Rename the existing file to `.bak` before writing
+    Backup,
+    /// Fail the conversion of that file
+    Error,
+}
+
+/// Options for controlling Sysmon configuration processing. Fields missing
+/// from serialized options take their default values.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProcessingOptions {
     /// Maximum allowed file size in bytes
     pub max_file_size: u64,
@@ -26,6 +69,46 @@ pub struct ProcessingOptions {
     
     /// Whether to show processing statistics
     pub show_stats: bool,
+
+    /// What to do when an output file already exists
+    pub overwrite_policy: OverwritePolicy,
+
+    /// Called while `convert_file_with_options` reads its input
+    #[serde(skip)]
+    pub progress: Option<ProgressHook>,
+
+    /// Minimum number of bytes read between two `progress` calls
+    pub progress_granularity: u64,
+
+    /// Values for `{{NAME}}` placeholders in element text and attribute
+    /// values, substituted before the input is validated and converted
+    pub variables: Option<HashMap<String, String>>,
+
+    /// Whether a placeholder without a value is an error rather than left
+    /// as written
+    pub strict_variables: bool,
+
+    /// Whether directory walks follow symbolic links to directories. Links
+    /// to files are read either way; circular links are detected and skipped.
+    pub follow_symlinks: bool,
+
+    /// Whether directory walks visit hidden entries (names starting with
+    /// `.`) and dependency directories such as `node_modules`
+    pub include_hidden: bool,
+
+    /// Longest a batch waits for one file to convert before recording it
+    /// as failed and moving on. Outputs are then written as with
+    /// `atomic_write`, whatever it is set to.
+    pub per_file_timeout: Option<Duration>,
+
+    /// Whether a batch processes files in canonical path order, so that
+    /// `BatchProcessingStats::results` lists them in the same order on
+    /// every run
+    pub sort_files: bool,
+
+    /// Whether a batch writes each output to a temporary file next to it
+    /// and renames it into place, so readers never see a partial output
+    pub atomic_write: bool,
 }
 
 impl Default for ProcessingOptions {
@@ -39,6 +122,16 @@ impl Default for ProcessingOptions {
             verify_output: true,
             silent: false,
             show_stats: true,
+            overwrite_policy: OverwritePolicy::Overwrite,
+            progress: None,
+            progress_granularity: 1024 * 1024, // 1MB
+            variables: None,
+            strict_variables: false,
+            follow_symlinks: false,
+            include_hidden: false,
+            per_file_timeout: None,
+            sort_files: false,
+            atomic_write: false,
         }
     }
 }
@@ -104,6 +197,69 @@ impl ProcessingOptionsBuilder {
         self
     }
 
+    /// Sets the policy for existing output files
+    pub fn overwrite_policy(mut self, policy: OverwritePolicy) -> Self {
+        self.options.overwrite_policy = policy;
+        self
+    }
+
+    /// Sets the callback notified of bytes read from the input file
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        self.options.progress = Some(ProgressHook::new(callback));
+        self
+    }
+
+    /// Sets the minimum number of bytes read between progress calls
+    pub fn progress_granularity(mut self, bytes: u64) -> Self {
+        self.options.progress_granularity = bytes;
+        self
+    }
+
+    /// Sets the values for template placeholders
+    pub fn variables(mut self, variables: Option<HashMap<String, String>>) -> Self {
+        self.options.variables = variables;
+        self
+    }
+
+    /// Sets whether unresolved placeholders are an error
+    pub fn strict_variables(mut self, strict: bool) -> Self {
+        self.options.strict_variables = strict;
+        self
+    }
+
+    /// Sets whether directory walks follow symbolic links
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.options.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets whether directory walks visit hidden entries
+    pub fn include_hidden(mut self, include: bool) -> Self {
+        self.options.include_hidden = include;
+        self
+    }
+
+    /// Sets how long a batch waits for one file to convert
+    pub fn per_file_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.per_file_timeout = timeout;
+        self
+    }
+
+    /// Sets whether a batch processes files in canonical path order
+    pub fn sort_files(mut self, sort: bool) -> Self {
+        self.options.sort_files = sort;
+        self
+    }
+
+    /// Sets whether a batch renames complete outputs into place
+    pub fn atomic_write(mut self, atomic: bool) -> Self {
+        self.options.atomic_write = atomic;
+        self
+    }
+
     /// Builds the ProcessingOptions
     pub fn build(self) -> ProcessingOptions {
         self.options
@@ -131,6 +287,14 @@ mod tests {
         assert!(options.verify_output);
         assert!(!options.silent);
         assert!(options.show_stats);
+        assert_eq!(options.overwrite_policy, OverwritePolicy::Overwrite);
+        assert!(options.variables.is_none());
+        assert!(!options.strict_variables);
+        assert!(!options.follow_symlinks);
+        assert!(!options.include_hidden);
+        assert!(options.per_file_timeout.is_none());
+        assert!(!options.sort_files);
+        assert!(!options.atomic_write);
     }
 
     #[test]
@@ -145,6 +309,10 @@ mod tests {
             .silent(true)
             .verify_output(false)
             .show_stats(false)
+            .overwrite_policy(OverwritePolicy::Skip)
+            .per_file_timeout(Some(Duration::from_secs(5)))
+            .sort_files(true)
+            .atomic_write(true)
             .build();
 
         assert_eq!(options.max_file_size, 5 * 1024 * 1024);
@@ -155,6 +323,10 @@ mod tests {
         assert!(options.silent);
         assert!(!options.verify_output);
         assert!(!options.show_stats);
+        assert_eq!(options.overwrite_policy, OverwritePolicy::Skip);
+        assert_eq!(options.per_file_timeout, Some(Duration::from_secs(5)));
+        assert!(options.sort_files);
+        assert!(options.atomic_write);
     }
 
     #[test]
@@ -174,4 +346,37 @@ mod tests {
         assert!(!options.silent);
         assert!(options.show_stats);
     }
+
+    #[test]
+    fn test_deserialize_older_options() {
+        // Options saved before the later fields were added
+        let options: ProcessingOptions = serde_json::from_str(
+            r#"{
+                "max_file_size": 1048576,
+                "max_depth": 3,
+                "workers": 2,
+                "ignore_patterns": ["draft"],
+                "create_backup": true,
+                "verify_output": false,
+                "silent": true,
+                "show_stats": false
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(options.max_file_size, 1024 * 1024);
+        assert_eq!(options.max_depth, 3);
+        assert_eq!(options.workers, Some(2));
+        assert!(options.create_backup);
+        assert!(!options.verify_output);
+        assert_eq!(options.overwrite_policy, OverwritePolicy::Overwrite);
+        assert_eq!(options.progress_granularity, 1024 * 1024);
+        assert!(options.variables.is_none());
+        assert!(!options.follow_symlinks);
+        assert!(options.per_file_timeout.is_none());
+        assert!(!options.atomic_write);
+
+        let options: ProcessingOptions = serde_json::from_str("{}").unwrap();
+        assert!(options.verify_output);
+    }
 }
\ No newline at end of file
@@ -1,24 +1,50 @@
-use std::fs;
-use std::path::Path;
 use quick_xml::{Writer, events::{Event, BytesStart, BytesEnd, BytesText}};
 use std::io::Cursor;
 use serde_json::Value;
 use crate::error::ConversionError;
-use crate::converter::{JsonToXml, Converter};
+use crate::converter::{check_text_key, JsonToXml, JsonToXmlOptions, Converter, DEFAULT_SPECIAL_PREFIX};
+use crate::model::SchemaVersion;
 
 impl Converter for JsonToXml {
-    fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError> {
-        let json_content = fs::read_to_string(input)?;
-        let value: Value = serde_json::from_str(&json_content)?;
-        let xml_string = value_to_xml(&value)?;
-        fs::write(output, xml_string)?;
-        Ok(())
+    fn convert_str(&self, json_content: &str) -> Result<String, ConversionError> {
+        let mut value: Value = serde_json::from_str(json_content)?;
+        if let Some(version) = &self.options().default_schema_version {
+            insert_schema_version(&mut value, version)?;
+        }
+        value_to_xml_with_options(&value, self.options())
     }
 }
 
+/// Gives the `Sysmon` root element `version` when it declares no
+/// `@schemaversion`, checking that `version` reads as one first
+fn insert_schema_version(value: &mut Value, version: &str) -> Result<(), ConversionError> {
+    let Some(Value::Object(root)) = value.get_mut("Sysmon") else {
+        return Ok(());
+    };
+    if root.contains_key("@schemaversion") {
+        return Ok(());
+    }
+    if SchemaVersion::parse(version).is_none() {
+        return Err(ConversionError::InvalidFile(format!(
+            "Invalid default schema version {:?}",
+            version
+        )));
+    }
+    root.insert("@schemaversion".to_string(), Value::String(version.to_string()));
+    Ok(())
+}
+
 pub fn value_to_xml(value: &Value) -> Result<String, ConversionError> {
+    value_to_xml_with_options(value, &JsonToXmlOptions::default())
+}
+
+/// Writes a JSON value as XML, reading element text from
+/// `options.text_key`. Keys that are not valid XML element names, such as
+/// a text key other than `options.text_key`, are an error.
+pub fn value_to_xml_with_options(value: &Value, options: &JsonToXmlOptions) -> Result<String, ConversionError> {
+    check_text_key(&options.text_key, &options.special_prefix)?;
     let mut writer = Writer::new(Cursor::new(Vec::new()));
-    write_value(&mut writer, None, value)?;
+    write_value(&mut writer, None, value, options)?;
     let result = String::from_utf8(writer.into_inner().into_inner())
         .map_err(|e| ConversionError::InvalidFile(e.to_string()))?;
     Ok(result)
@@ -28,7 +54,11 @@ fn write_value<W: std::io::Write>(
     writer: &mut Writer<W>,
     name: Option<&str>,
     value: &Value,
+    options: &JsonToXmlOptions,
 ) -> Result<(), ConversionError> {
+    if let Some(name) = name {
+        check_element_name(name)?;
+    }
     match value {
         Value::Object(map) => {
             let tag_name = name.unwrap_or("root"); // Use provided name or default to "root"
@@ -36,9 +66,18 @@ fn write_value<W: std::io::Write>(
 
             // Write attributes first
             for (key, value) in map.iter() {
-                if key.starts_with('@') {
-                    if let Value::String(attr_value) = value {
-                        elem.push_attribute((&key[1..], attr_value.as_str()));
+                if let Some(attr_name) = key.strip_prefix('@') {
+                    match value {
+                        Value::String(attr_value) => {
+                            elem.push_attribute((attr_name, attr_value.as_str()));
+                        },
+                        Value::Number(n) => {
+                            elem.push_attribute((attr_name, n.to_string().as_str()));
+                        },
+                        Value::Bool(b) => {
+                            elem.push_attribute((attr_name, b.to_string().as_str()));
+                        },
+                        _ => {},
                     }
                 }
             }
@@ -46,21 +85,22 @@ fn write_value<W: std::io::Write>(
             writer.write_event(Event::Start(elem))?;
 
             // Write text content if it exists
-            if let Some(Value::String(text)) = map.get("$text") {
-                writer.write_event(Event::Text(BytesText::new(text)))?;
+            if let Some(text) = map.get(&options.text_key).and_then(scalar_text) {
+                writer.write_event(Event::Text(BytesText::new(&text)))?;
             }
 
             // Write child elements
-            if let Some(Value::Array(children)) = map.get("$children") {
+            let children_key = format!("{}children", options.special_prefix);
+            if let Some(Value::Array(children)) = map.get(&children_key) {
                 for child in children {
-                    write_value(writer, None, child)?;
+                    write_value(writer, None, child, options)?;
                 }
             }
 
             // Write regular elements (non-attributes, non-special)
             for (key, value) in map.iter() {
-                if !key.starts_with('$') && !key.starts_with('@') {
-                    write_value(writer, Some(key), value)?;
+                if !key.starts_with(options.special_prefix.as_str()) && !key.starts_with('@') {
+                    write_value(writer, Some(key), value, options)?;
                 }
             }
 
@@ -68,7 +108,7 @@ fn write_value<W: std::io::Write>(
         },
         Value::Array(arr) => {
             for value in arr {
-                write_value(writer, name, value)?;
+                write_value(writer, name, value, options)?;
             }
         },
         Value::String(s) => {
@@ -107,6 +147,61 @@ fn write_value<W: std::io::Write>(
     Ok(())
 }
 
+/// Checks that a JSON key can be written as an XML element, so that a key
+/// from another convention, such as `#text`, is not written as `<#text>`
+pub(crate) fn check_element_name(name: &str) -> Result<(), ConversionError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+    if !valid {
+        return Err(ConversionError::InvalidFile(format!(
+            "JSON key {:?} is not a valid XML element name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Checks every key of a JSON document that is neither an attribute nor a
+/// `$` key with `check_element_name`, for readers that only understand the
+/// default `$text` key. JSON written with another `XmlToJsonOptions::text_key`,
+/// such as `#text`, would otherwise read as elements without text.
+pub(crate) fn check_default_keys(value: &Value) -> Result<(), ConversionError> {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if !key.starts_with('@') && !key.starts_with(DEFAULT_SPECIAL_PREFIX) {
+                    check_element_name(key).map_err(|_| {
+                        ConversionError::InvalidFile(format!(
+                            "JSON key {:?} is not a valid XML element name; only JSON using the default `$text` key can be read here",
+                            key
+                        ))
+                    })?;
+                }
+                check_default_keys(value)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                check_default_keys(item)?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Renders a string, number, or boolean as element text. Numbers and
+/// booleans come from `XmlToJsonOptions::infer_types`.
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +218,31 @@ mod tests {
         assert!(xml.contains("version=\"1.0\""));
         assert!(xml.contains(">content<"));
     }
+
+    #[test]
+    fn test_default_schema_version() {
+        let options = |version: &str| JsonToXmlOptions {
+            default_schema_version: Some(version.to_string()),
+            ..Default::default()
+        };
+        let without = r#"{"Sysmon": {"HashAlgorithms": "sha256"}}"#;
+        let with = r#"{"Sysmon": {"@schemaversion": "4.30", "HashAlgorithms": "sha256"}}"#;
+
+        let xml = JsonToXml::new().convert_str(without).unwrap();
+        assert!(!xml.contains("schemaversion"), "{}", xml);
+
+        let converter = JsonToXml::with_options(options("4.50"));
+        let xml = converter.convert_str(without).unwrap();
+        assert!(xml.contains(r#"<Sysmon schemaversion="4.50">"#), "{}", xml);
+        let xml = converter.convert_str(with).unwrap();
+        assert!(xml.contains(r#"<Sysmon schemaversion="4.30">"#), "{}", xml);
+
+        let converter = JsonToXml::with_options(options("latest"));
+        assert!(matches!(
+            converter.convert_str(without),
+            Err(ConversionError::InvalidFile(_))
+        ));
+        // A declared version leaves the default unused
+        assert!(converter.convert_str(with).is_ok());
+    }
 }
\ No newline at end of file
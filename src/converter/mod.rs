@@ -1,25 +1,283 @@
 use crate::error::ConversionError;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::{Mutex, OnceLock};
 
 pub mod xml;
 pub mod json;
+pub mod warnings;
 
+pub use warnings::{ValidationWarning, WarningSeverity};
+
+/// Converts configuration content between two formats.
+///
+/// Implement at least one of `convert_str` and `convert`, as each defaults
+/// to the other. A converter that only implements `convert` cannot convert
+/// content in memory without the `fs` feature.
 pub trait Converter {
-    fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError>;
+    /// Converts configuration content held in memory. By default, the
+    /// content goes through `convert` and a temporary directory.
+    fn convert_str(&self, input: &str) -> Result<String, ConversionError> {
+        #[cfg(feature = "fs")]
+        {
+            let dir = tempfile::tempdir().map_err(|e| ConversionError::io_error(std::env::temp_dir(), e))?;
+            let (input_path, output_path) = (dir.path().join("input"), dir.path().join("output"));
+            std::fs::write(&input_path, input).map_err(|e| ConversionError::io_error(&input_path, e))?;
+            self.convert(&input_path, &output_path)?;
+            std::fs::read_to_string(&output_path).map_err(|e| ConversionError::io_error(&output_path, e))
+        }
+        #[cfg(not(feature = "fs"))]
+        {
+            let _ = input;
+            Err(ConversionError::InvalidFile(
+                "This converter only converts files, which needs the fs feature".into(),
+            ))
+        }
+    }
+
+    /// Converts a configuration file, writing the result to `output`
+    #[cfg(feature = "fs")]
+    fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError> {
+        let content = std::fs::read_to_string(input)
+            .map_err(|e| ConversionError::io_error(input, e))?;
+        let converted = self.convert_str(&content)?;
+        std::fs::write(output, converted)
+            .map_err(|e| ConversionError::io_error(output, e))
+    }
+}
+
+/// Configuration file formats known to the converters
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Xml,
+    Json,
+    Yaml,
+}
+
+impl FileFormat {
+    /// Gets the canonical file extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Xml => "xml",
+            FileFormat::Json => "json",
+            FileFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Maps a file extension (case-insensitive) to a format
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "xml" => Some(FileFormat::Xml),
+            "json" => Some(FileFormat::Json),
+            "yaml" | "yml" => Some(FileFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Infers the format from a path's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_extension)
+    }
+}
+
+/// Options controlling how XML documents are mapped onto JSON values
+#[derive(Debug, Clone)]
+pub struct XmlToJsonOptions {
+    /// Element names that are always emitted as JSON arrays, even when
+    /// they occur only once under their parent
+    pub always_array_elements: HashSet<String>,
+
+    /// Store numeric and boolean text nodes (and the `@schemaversion`
+    /// attribute) as JSON numbers and booleans instead of strings. Text a
+    /// number would not write back as, such as `0445` or `4.30`, stays a
+    /// string.
+    pub infer_types: bool,
+
+    /// Replace arrays holding a single value with that value, for JSON
+    /// schemas expecting scalars. Elements in `always_array_elements` are
+    /// left as arrays.
+    pub flatten_single_element_arrays: bool,
+
+    /// Key holding the text of an element that also has attributes, `$text`
+    /// by default. Other JSON tools may expect another key, such as `#text`
+    /// for the BadgerFish convention. It must start with `special_prefix`.
+    /// `JsonToXml` reads such JSON back with the same `JsonToXmlOptions`;
+    /// `ConfigMerger` and `SysmonDocument` only understand `$text`, and
+    /// reject JSON using another key.
+    pub text_key: String,
+
+    /// Prefix of the keys that are neither attributes nor child elements,
+    /// such as the text key, `$` by default
+    pub special_prefix: String,
+}
+
+impl Default for XmlToJsonOptions {
+    fn default() -> Self {
+        Self {
+            always_array_elements: HashSet::new(),
+            infer_types: false,
+            flatten_single_element_arrays: false,
+            text_key: DEFAULT_TEXT_KEY.to_string(),
+            special_prefix: DEFAULT_SPECIAL_PREFIX.to_string(),
+        }
+    }
+}
+
+/// Options controlling how JSON values are written as XML documents
+#[derive(Debug, Clone)]
+pub struct JsonToXmlOptions {
+    /// Schema version given to a `Sysmon` root element without
+    /// `@schemaversion`, such as `4.50`. `None` leaves the root as written.
+    pub default_schema_version: Option<String>,
+
+    /// Key holding the text of an element, as in `XmlToJsonOptions`
+    pub text_key: String,
+
+    /// Prefix of the keys not written as child elements, as in
+    /// `XmlToJsonOptions`
+    pub special_prefix: String,
+}
+
+impl Default for JsonToXmlOptions {
+    fn default() -> Self {
+        Self {
+            default_schema_version: None,
+            text_key: DEFAULT_TEXT_KEY.to_string(),
+            special_prefix: DEFAULT_SPECIAL_PREFIX.to_string(),
+        }
+    }
 }
 
-pub struct XmlToJson;
-pub struct JsonToXml;
+const DEFAULT_TEXT_KEY: &str = "$text";
+pub(crate) const DEFAULT_SPECIAL_PREFIX: &str = "$";
+
+/// Checks that the text key starts with the special prefix, so that it is
+/// never read back as a child element
+fn check_text_key(text_key: &str, special_prefix: &str) -> Result<(), ConversionError> {
+    if special_prefix.is_empty() || special_prefix.starts_with('@') || !text_key.starts_with(special_prefix) {
+        return Err(ConversionError::InvalidFile(format!(
+            "Text key {:?} must start with the special prefix {:?}, which may not be empty or start with '@'",
+            text_key, special_prefix
+        )));
+    }
+    Ok(())
+}
+
+/// Converts XML Sysmon configurations to JSON.
+///
+/// Sibling elements sharing a name (for example two `Image` filters under
+/// one `ProcessCreate`) are collected into a JSON array in document order.
+/// Earlier releases kept only the last occurrence.
+pub struct XmlToJson {
+    options: XmlToJsonOptions,
+}
+
+pub struct JsonToXml {
+    options: JsonToXmlOptions,
+}
 
 impl XmlToJson {
     pub fn new() -> Self {
-        Self
+        Self {
+            options: XmlToJsonOptions::default(),
+        }
+    }
+
+    /// Creates a converter using the given options
+    pub fn with_options(options: XmlToJsonOptions) -> Self {
+        Self { options }
+    }
+
+    /// Gets the options used by this converter
+    pub fn options(&self) -> &XmlToJsonOptions {
+        &self.options
+    }
+}
+
+impl Default for XmlToJson {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl JsonToXml {
     pub fn new() -> Self {
-        Self
+        Self {
+            options: JsonToXmlOptions::default(),
+        }
+    }
+
+    /// Creates a converter using the given options
+    pub fn with_options(options: JsonToXmlOptions) -> Self {
+        Self { options }
+    }
+
+    /// Gets the options used by this converter
+    pub fn options(&self) -> &JsonToXmlOptions {
+        &self.options
+    }
+}
+
+impl Default for JsonToXml {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Factory producing a fresh converter instance
+pub type ConverterFactory = Box<dyn Fn() -> Box<dyn Converter> + Send + Sync>;
+
+/// Registry mapping `(from, to)` extension pairs to converter factories.
+///
+/// The global registry is pre-populated with the built-in XML and JSON
+/// converters. Downstream crates can register additional pairs to plug in
+/// their own `Converter` implementations.
+pub struct ConverterRegistry {
+    factories: HashMap<(String, String), ConverterFactory>,
+}
+
+impl ConverterRegistry {
+    /// Creates a registry containing the built-in converters
+    pub fn new() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        registry.register("xml", "json", || Box::new(XmlToJson::new()));
+        registry.register("json", "xml", || Box::new(JsonToXml::new()));
+        registry
+    }
+
+    /// Gets the process-wide registry used by `get_converter`
+    pub fn global() -> &'static Mutex<ConverterRegistry> {
+        static REGISTRY: OnceLock<Mutex<ConverterRegistry>> = OnceLock::new();
+        REGISTRY.get_or_init(|| Mutex::new(ConverterRegistry::new()))
+    }
+
+    /// Registers a converter factory, replacing any existing entry for the pair
+    pub fn register<F>(&mut self, from: &str, to: &str, factory: F)
+    where
+        F: Fn() -> Box<dyn Converter> + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            (from.to_lowercase(), to.to_lowercase()),
+            Box::new(factory),
+        );
+    }
+
+    /// Creates a converter for the given pair, if one is registered
+    pub fn get(&self, from: &str, to: &str) -> Option<Box<dyn Converter>> {
+        self.factories
+            .get(&(from.to_lowercase(), to.to_lowercase()))
+            .map(|factory| factory())
+    }
+}
+
+impl Default for ConverterRegistry {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -34,11 +292,92 @@ pub fn get_converter(input: &Path, output: &Path) -> Result<Box<dyn Converter>,
         .ok_or_else(|| ConversionError::InvalidFile("Output file has no extension".into()))?
         .to_lowercase();
 
-    match (input_ext.as_str(), output_ext.as_str()) {
-        ("xml", "json") => Ok(Box::new(XmlToJson::new())),
-        ("json", "xml") => Ok(Box::new(JsonToXml::new())),
-        _ => Err(ConversionError::InvalidFile(
-            format!("Unsupported conversion: {} to {}", input_ext, output_ext)
-        )),
+    match (FileFormat::from_extension(&input_ext), FileFormat::from_extension(&output_ext)) {
+        (Some(from), Some(to)) => get_converter_for_formats(from, to),
+        // Extensions outside `FileFormat` may still have a registered converter
+        _ => lookup_converter(&input_ext, &output_ext),
     }
-}
\ No newline at end of file
+}
+
+/// Gets a converter for a pair of formats without consulting the filesystem
+pub fn get_converter_for_formats(
+    from: FileFormat,
+    to: FileFormat,
+) -> Result<Box<dyn Converter>, ConversionError> {
+    lookup_converter(from.extension(), to.extension())
+}
+
+fn lookup_converter(from: &str, to: &str) -> Result<Box<dyn Converter>, ConversionError> {
+    let registry = ConverterRegistry::global()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    registry.get(from, to).ok_or_else(|| ConversionError::InvalidFile(
+        format!("Unsupported conversion: {} to {}", from, to)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopConverter;
+
+    impl Converter for NoopConverter {
+        fn convert_str(&self, input: &str) -> Result<String, ConversionError> {
+            Ok(input.to_string())
+        }
+    }
+
+    /// Implements only `convert`, as converters did before `convert_str`
+    #[cfg(feature = "fs")]
+    struct UppercaseFileConverter;
+
+    #[cfg(feature = "fs")]
+    impl Converter for UppercaseFileConverter {
+        fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError> {
+            let content = std::fs::read_to_string(input).map_err(|e| ConversionError::io_error(input, e))?;
+            std::fs::write(output, content.to_uppercase()).map_err(|e| ConversionError::io_error(output, e))
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_file_only_converter_converts_str() {
+        assert_eq!(UppercaseFileConverter.convert_str("<sysmon/>").unwrap(), "<SYSMON/>");
+    }
+
+    #[test]
+    fn test_builtin_converters_registered() {
+        let registry = ConverterRegistry::new();
+        assert!(registry.get("xml", "json").is_some());
+        assert!(registry.get("JSON", "XML").is_some());
+        assert!(registry.get("xml", "csv").is_none());
+    }
+
+    #[test]
+    fn test_register_custom_converter() {
+        ConverterRegistry::global()
+            .lock()
+            .unwrap()
+            .register("xml", "csv", || Box::new(NoopConverter));
+
+        let converter = get_converter(Path::new("in.xml"), Path::new("out.csv"));
+        assert!(converter.is_ok());
+        assert!(get_converter(Path::new("in.xml"), Path::new("out.yaml")).is_err());
+    }
+
+    #[test]
+    fn test_get_converter_for_formats() {
+        assert!(get_converter_for_formats(FileFormat::Xml, FileFormat::Json).is_ok());
+        assert!(get_converter_for_formats(FileFormat::Json, FileFormat::Xml).is_ok());
+        assert!(get_converter_for_formats(FileFormat::Yaml, FileFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_file_format_from_path() {
+        assert_eq!(FileFormat::from_path(Path::new("config.XML")), Some(FileFormat::Xml));
+        assert_eq!(FileFormat::from_path(Path::new("config.yml")), Some(FileFormat::Yaml));
+        assert_eq!(FileFormat::from_path(Path::new("config")), None);
+    }
+}
@@ -0,0 +1,266 @@
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Schema versions older than this predate `RuleGroup` and are deprecated
+const MIN_SUPPORTED_SCHEMA: f64 = 4.22;
+
+/// `contains` filters matching this many characters or fewer are flagged
+const BROAD_CONTAINS_LEN: usize = 3;
+
+/// How much attention a warning deserves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WarningSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+/// A non-fatal issue found in a configuration that otherwise converts cleanly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationWarning {
+    /// Location of the issue, as `/`-separated element names with array
+    /// indices (for example `Sysmon/EventFiltering/RuleGroup[1]`)
+    pub path: Option<String>,
+    /// Description of the issue
+    pub message: String,
+    /// How serious the issue is
+    pub severity: WarningSeverity,
+}
+
+impl ValidationWarning {
+    fn new(path: &str, severity: WarningSeverity, message: String) -> Self {
+        Self {
+            path: Some(path.to_string()),
+            message,
+            severity,
+        }
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "[{:?}] {}: {}", self.severity, path, self.message),
+            None => write!(f, "[{:?}] {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Lints a converted configuration (in the `xml_to_value` layout).
+///
+/// Reports deprecated schema versions, event filters outside a `RuleGroup`,
+/// unnamed `RuleGroup`s, `contains` filters short enough to match almost
+/// anything, and rules repeated within one event filter.
+pub fn collect_warnings(value: &Value) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+    let Some(sysmon) = value.get("Sysmon").and_then(Value::as_object) else {
+        return warnings;
+    };
+
+    check_schema_version(sysmon, &mut warnings);
+
+    let Some(filtering) = sysmon.get("EventFiltering").and_then(Value::as_object) else {
+        return warnings;
+    };
+    let base = "Sysmon/EventFiltering";
+
+    for (key, value) in child_elements(filtering) {
+        if key == "RuleGroup" {
+            for (i, group) in items(value).into_iter().enumerate() {
+                let path = indexed_path(base, key, value, i);
+                check_rule_group(&path, group, &mut warnings);
+            }
+        } else {
+            warnings.push(ValidationWarning::new(
+                &format!("{}/{}", base, key),
+                WarningSeverity::Low,
+                format!("{} is not inside a RuleGroup (deprecated layout)", key),
+            ));
+            check_event(base, key, value, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+fn check_schema_version(sysmon: &Map<String, Value>, warnings: &mut Vec<ValidationWarning>) {
+    let version = match sysmon.get("@schemaversion") {
+        Some(Value::String(s)) => s.parse::<f64>().ok(),
+        Some(Value::Number(n)) => n.as_f64(),
+        _ => None,
+    };
+
+    if let Some(version) = version.filter(|v| *v < MIN_SUPPORTED_SCHEMA) {
+        warnings.push(ValidationWarning::new(
+            "Sysmon/@schemaversion",
+            WarningSeverity::Medium,
+            format!("Schema version {} is deprecated", version),
+        ));
+    }
+}
+
+fn check_rule_group(path: &str, group: &Value, warnings: &mut Vec<ValidationWarning>) {
+    let Some(group) = group.as_object() else {
+        return;
+    };
+
+    let named = group
+        .get("@name")
+        .and_then(Value::as_str)
+        .is_some_and(|name| !name.trim().is_empty());
+    if !named {
+        warnings.push(ValidationWarning::new(
+            path,
+            WarningSeverity::Low,
+            "RuleGroup has no name".to_string(),
+        ));
+    }
+
+    for (event, value) in child_elements(group) {
+        check_event(path, event, value, warnings);
+    }
+}
+
+/// Checks every occurrence of an event filter element such as `ProcessCreate`
+fn check_event(parent: &str, name: &str, value: &Value, warnings: &mut Vec<ValidationWarning>) {
+    for (i, event) in items(value).into_iter().enumerate() {
+        if let Some(event) = event.as_object() {
+            let path = indexed_path(parent, name, value, i);
+            let mut seen = HashSet::new();
+            check_rules(&path, event, &mut seen, warnings);
+        }
+    }
+}
+
+/// Checks the field filters of an event (or of a nested `Rule`)
+fn check_rules<'a>(
+    path: &str,
+    event: &'a Map<String, Value>,
+    seen: &mut HashSet<(&'a str, &'a str, &'a str)>,
+    warnings: &mut Vec<ValidationWarning>,
+) {
+    for (field, value) in child_elements(event) {
+        for (i, rule) in items(value).into_iter().enumerate() {
+            let rule_path = indexed_path(path, field, value, i);
+            let (condition, text) = match rule {
+                Value::String(text) => ("is", text.as_str()),
+                Value::Object(obj) => match obj.get("$text").and_then(Value::as_str) {
+                    Some(text) => (
+                        obj.get("@condition").and_then(Value::as_str).unwrap_or("is"),
+                        text,
+                    ),
+                    // A `Rule` element groups further filters
+                    None => {
+                        check_rules(&rule_path, obj, seen, warnings);
+                        continue;
+                    }
+                },
+                _ => continue,
+            };
+
+            if condition.eq_ignore_ascii_case("contains")
+                && text.trim().chars().count() <= BROAD_CONTAINS_LEN
+            {
+                warnings.push(ValidationWarning::new(
+                    &rule_path,
+                    WarningSeverity::High,
+                    format!("'contains' filter on \"{}\" is very broad", text),
+                ));
+            }
+
+            if !seen.insert((field.as_str(), condition, text)) {
+                warnings.push(ValidationWarning::new(
+                    &rule_path,
+                    WarningSeverity::Medium,
+                    format!("Duplicate rule: {} {} \"{}\"", field, condition, text),
+                ));
+            }
+        }
+    }
+}
+
+/// Iterates over child elements, skipping attributes and text
+fn child_elements(obj: &Map<String, Value>) -> impl Iterator<Item = (&String, &Value)> {
+    obj.iter()
+        .filter(|(key, _)| !key.starts_with('@') && !key.starts_with('$'))
+}
+
+/// Gets the occurrences of an element, which is an array when repeated
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        other => vec![other],
+    }
+}
+
+fn indexed_path(parent: &str, name: &str, value: &Value, index: usize) -> String {
+    if value.is_array() {
+        format!("{}/{}[{}]", parent, name, index)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::xml::xml_to_value;
+
+    fn warnings_for(xml: &str) -> Vec<ValidationWarning> {
+        collect_warnings(&xml_to_value(xml).unwrap())
+    }
+
+    #[test]
+    fn test_clean_config_has_no_warnings() {
+        let warnings = warnings_for(r#"<Sysmon schemaversion="4.30">
+            <EventFiltering>
+                <RuleGroup name="test" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">C:\Windows\System32\test.exe</Image>
+                        <CommandLine condition="contains">mimikatz</CommandLine>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_lint_findings() {
+        let warnings = warnings_for(r#"<Sysmon schemaversion="4.10">
+            <EventFiltering>
+                <RuleGroup groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">C:\Windows\System32\test.exe</Image>
+                        <Image condition="is">C:\Windows\System32\test.exe</Image>
+                        <CommandLine condition="contains">-e</CommandLine>
+                    </ProcessCreate>
+                </RuleGroup>
+                <NetworkConnect onmatch="include">
+                    <DestinationPort condition="is">4444</DestinationPort>
+                </NetworkConnect>
+            </EventFiltering>
+        </Sysmon>"#);
+
+        let find = |path: &str| {
+            warnings
+                .iter()
+                .find(|w| w.path.as_deref() == Some(path))
+                .unwrap_or_else(|| panic!("no warning at {}: {:?}", path, warnings))
+        };
+
+        assert_eq!(find("Sysmon/@schemaversion").severity, WarningSeverity::Medium);
+        assert_eq!(find("Sysmon/EventFiltering/RuleGroup").severity, WarningSeverity::Low);
+        assert_eq!(
+            find("Sysmon/EventFiltering/RuleGroup/ProcessCreate/Image[1]").severity,
+            WarningSeverity::Medium
+        );
+        assert_eq!(
+            find("Sysmon/EventFiltering/RuleGroup/ProcessCreate/CommandLine").severity,
+            WarningSeverity::High
+        );
+        assert_eq!(find("Sysmon/EventFiltering/NetworkConnect").severity, WarningSeverity::Low);
+        assert_eq!(warnings.len(), 5);
+    }
+}
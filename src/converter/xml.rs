@@ -1,123 +1,286 @@
-use std::fs;
-use std::path::Path;
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
 use serde_json::{Value, Map};
 use crate::error::ConversionError;
-use crate::converter::{XmlToJson, Converter};
+use crate::converter::{check_text_key, XmlToJson, XmlToJsonOptions, Converter};
+use crate::converter::warnings::{collect_warnings, ValidationWarning};
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(feature = "validation")]
 use sysmon_validator::{parse_sysmon_config_from_str, validate_sysmon_config};
+#[cfg(feature = "validation")]
 use log::info;
+#[cfg(feature = "validation")]
+use crate::schema::annotate_validation_error;
 
 impl Converter for XmlToJson {
-    fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError> {
-        // Read the XML content
-        let xml_content = fs::read_to_string(input)?;
-        
-        // Validate the Sysmon configuration before converting
-        info!("Validating Sysmon configuration before conversion");
-        let config = parse_sysmon_config_from_str(&xml_content)?;
-        validate_sysmon_config(&config)?;
-        
-        // If validation passes, proceed with conversion
-        info!("Validation successful, proceeding with conversion");
-        let value = xml_to_value(&xml_content)?;
+    fn convert_str(&self, xml_content: &str) -> Result<String, ConversionError> {
+        let value = self.validated_value(xml_content)?;
         let json_string = serde_json::to_string_pretty(&value)?;
-        fs::write(output, json_string)?;
         
-        Ok(())
+        Ok(json_string)
     }
 }
 
-fn xml_to_value(xml: &str) -> Result<Value, ConversionError> {
-    let mut reader = Reader::from_str(xml);
-    
-    match read_next_value(&mut reader) {
-        Ok(Some(value)) => Ok(value),
-        Ok(None) => Ok(Value::Null),
-        Err(e) => Err(e),
+impl XmlToJson {
+    /// Converts a file like `convert`, and also lints the configuration.
+    ///
+    /// The warnings never fail the conversion; see `collect_warnings` for
+    /// what is reported.
+    #[cfg(feature = "fs")]
+    pub fn convert_with_warnings(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> Result<Vec<ValidationWarning>, ConversionError> {
+        let content = std::fs::read_to_string(input)
+            .map_err(|e| ConversionError::io_error(input, e))?;
+        let (json, warnings) = self.convert_str_with_warnings(&content)?;
+        std::fs::write(output, json)
+            .map_err(|e| ConversionError::io_error(output, e))?;
+        Ok(warnings)
     }
-}
 
-fn read_next_value(reader: &mut Reader<&[u8]>) -> Result<Option<Value>, ConversionError> {
-    let mut buf = Vec::new();
+    /// Converts content like `convert_str`, and also lints the configuration
+    pub fn convert_str_with_warnings(
+        &self,
+        xml_content: &str,
+    ) -> Result<(String, Vec<ValidationWarning>), ConversionError> {
+        let value = self.validated_value(xml_content)?;
+        // The lints read the default layout, whatever the options
+        let warnings = collect_warnings(&xml_to_value(xml_content)?);
+        Ok((serde_json::to_string_pretty(&value)?, warnings))
+    }
 
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                let mut obj = Map::new();
-
-                // Handle attributes
-                for attr in e.attributes().flatten() {
-                    let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
-                    let value = String::from_utf8_lossy(&attr.value).into_owned();
-                    obj.insert(key, Value::String(value));
-                }
+    fn validated_value(&self, xml_content: &str) -> Result<Value, ConversionError> {
+        // Validate the Sysmon configuration before converting
+        #[cfg(feature = "validation")]
+        {
+            info!("Validating Sysmon configuration before conversion");
+            let config = parse_sysmon_config_from_str(xml_content)
+                .map_err(|e| annotate_validation_error(e.into(), xml_content))?;
+            validate_sysmon_config(&config)
+                .map_err(|e| annotate_validation_error(e.into(), xml_content))?;
+            info!("Validation successful, proceeding with conversion");
+        }
 
-                // Handle child elements
-                let mut text_content = String::new();
-                let mut children = Map::new(); // Changed from Vec<Value> to Map<String, Value>
-
-                loop {
-                    match reader.read_event_into(&mut buf) {
-                        Ok(Event::Start(ref child)) => {
-                            let child_name = String::from_utf8_lossy(child.name().as_ref()).into_owned();
-                            if let Some(child_value) = read_next_value(reader)? {
-                                children.insert(child_name, child_value);
-                            }
-                        },
-                        Ok(Event::Text(e)) => {
-                            text_content.push_str(&e.unescape().map_err(|e| 
-                                ConversionError::XmlParse(quick_xml::DeError::InvalidXml(e)))?);
-                        },
-                        Ok(Event::End(ref e)) if e.name().as_ref() == e.name().as_ref() => {
-                            break;
-                        },
-                        Ok(Event::Eof) => break,
-                        Err(e) => return Err(ConversionError::XmlParse(
-                            quick_xml::DeError::InvalidXml(e))),
-                        _ => {},
-                    }
-                    buf.clear();
-                }
+        xml_to_value_with_options(xml_content, &self.options)
+    }
+}
 
-                // Handle the content
-                if !text_content.trim().is_empty() {
-                    obj.insert("$text".to_string(), Value::String(text_content.trim().to_string()));
-                }
+/// Parses an XML document into a JSON value using the default options.
+pub fn xml_to_value(xml: &str) -> Result<Value, ConversionError> {
+    xml_to_value_with_options(xml, &XmlToJsonOptions::default())
+}
 
-                // Merge children into obj
-                if !children.is_empty() {
-                    obj.extend(children);
-                }
+/// Parses an XML document into a JSON value.
+///
+/// Attributes become `@`-prefixed keys and element text is stored under
+/// `XmlToJsonOptions::text_key`, `$text` by default. Repeated sibling elements are collected into an array.
+pub fn xml_to_value_with_options(xml: &str, options: &XmlToJsonOptions) -> Result<Value, ConversionError> {
+    check_text_key(&options.text_key, &options.special_prefix)?;
+    let mut value = parse_value(xml, options)?;
+    if options.flatten_single_element_arrays {
+        flatten_single_element_arrays(&mut value, options);
+    }
+    Ok(value)
+}
 
-                // Return the object with the element name
-                let mut element = Map::new();
-                element.insert(name, Value::Object(obj));
-                return Ok(Some(Value::Object(element)));
+fn parse_value(xml: &str, options: &XmlToJsonOptions) -> Result<Value, ConversionError> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let start = e.into_owned();
+                let value = read_element(&mut reader, &start, options)?;
+                let mut root = Map::new();
+                root.insert(element_name(&start), value);
+                return Ok(Value::Object(root));
+            },
+            Ok(Event::Empty(e)) => {
+                let mut root = Map::new();
+                root.insert(element_name(&e), empty_element_value(&e, options));
+                return Ok(Value::Object(root));
             },
             Ok(Event::Text(e)) => {
-                let text = e.unescape().map_err(|e| 
-                    ConversionError::XmlParse(quick_xml::DeError::InvalidXml(e)))?.into_owned();
+                let text = e.unescape().map_err(xml_error)?.into_owned();
                 if !text.trim().is_empty() {
-                    return Ok(Some(Value::String(text.trim().to_string())));
+                    return Ok(text_value(text.trim(), options));
                 }
             },
-            Ok(Event::Eof) => return Ok(None),
-            Err(e) => return Err(ConversionError::XmlParse(
-                quick_xml::DeError::InvalidXml(e))),
+            Ok(Event::Eof) => return Ok(Value::Null),
+            Err(e) => return Err(xml_error(e)),
             _ => {}
         }
         buf.clear();
     }
 }
 
+/// Reads the content of an element whose start tag has already been consumed
+fn read_element(
+    reader: &mut Reader<&[u8]>,
+    start: &BytesStart,
+    options: &XmlToJsonOptions,
+) -> Result<Value, ConversionError> {
+    let mut obj = element_attributes(start, options);
+    let mut text_content = String::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let child = e.into_owned();
+                let child_value = read_element(reader, &child, options)?;
+                insert_child(&mut obj, element_name(&child), child_value, options);
+            },
+            Ok(Event::Empty(e)) => {
+                let child_value = empty_element_value(&e, options);
+                insert_child(&mut obj, element_name(&e), child_value, options);
+            },
+            Ok(Event::Text(e)) => {
+                text_content.push_str(&e.unescape().map_err(xml_error)?);
+            },
+            Ok(Event::End(_)) | Ok(Event::Eof) => break,
+            Err(e) => return Err(xml_error(e)),
+            _ => {},
+        }
+        buf.clear();
+    }
+
+    let text = text_content.trim();
+    if obj.is_empty() {
+        // Plain text elements collapse to a string
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        return Ok(text_value(text, options));
+    }
+
+    if !text.is_empty() {
+        obj.insert(options.text_key.clone(), text_value(text, options));
+    }
+
+    Ok(Value::Object(obj))
+}
+
+/// Replaces the one-value arrays under `value` with their value, except for
+/// elements in `always_array_elements`
+fn flatten_single_element_arrays(value: &mut Value, options: &XmlToJsonOptions) {
+    match value {
+        Value::Object(obj) => {
+            for (name, child) in obj.iter_mut() {
+                flatten_single_element_arrays(child, options);
+                if options.always_array_elements.contains(name) {
+                    continue;
+                }
+                if let Value::Array(items) = child {
+                    if items.len() == 1 {
+                        *child = items.pop().unwrap();
+                    }
+                }
+            }
+        },
+        Value::Array(items) => {
+            for item in items {
+                flatten_single_element_arrays(item, options);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Adds a child element to its parent, turning repeated names into arrays
+fn insert_child(
+    obj: &mut Map<String, Value>,
+    name: String,
+    value: Value,
+    options: &XmlToJsonOptions,
+) {
+    match obj.get_mut(&name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        },
+        None => {
+            if options.always_array_elements.contains(&name) {
+                obj.insert(name, Value::Array(vec![value]));
+            } else {
+                obj.insert(name, value);
+            }
+        },
+    }
+}
+
+fn element_name(start: &BytesStart) -> String {
+    String::from_utf8_lossy(start.name().as_ref()).into_owned()
+}
+
+fn element_attributes(start: &BytesStart, options: &XmlToJsonOptions) -> Map<String, Value> {
+    let mut obj = Map::new();
+    for attr in start.attributes().flatten() {
+        let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+        let value = String::from_utf8_lossy(&attr.value).into_owned();
+        if key == "@schemaversion" {
+            obj.insert(key, text_value(&value, options));
+        } else {
+            obj.insert(key, Value::String(value));
+        }
+    }
+    obj
+}
+
+fn empty_element_value(start: &BytesStart, options: &XmlToJsonOptions) -> Value {
+    let obj = element_attributes(start, options);
+    if obj.is_empty() {
+        Value::Null
+    } else {
+        Value::Object(obj)
+    }
+}
+
+/// Builds the JSON value for a text node, inferring its type when enabled
+fn text_value(text: &str, options: &XmlToJsonOptions) -> Value {
+    if options.infer_types {
+        if let Some(value) = infer_type(text) {
+            return value;
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// Reads text as a boolean or number only when that writes back as the
+/// same text, so `0445` and `4.30` stay strings
+fn infer_type(text: &str) -> Option<Value> {
+    match text {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+
+    let number = match text.parse::<i64>() {
+        Ok(n) => serde_json::Number::from(n),
+        Err(_) => serde_json::Number::from_f64(text.parse::<f64>().ok()?)?,
+    };
+    (number.to_string() == text).then_some(Value::Number(number))
+}
+
+fn xml_error(e: quick_xml::Error) -> ConversionError {
+    ConversionError::XmlParse(quick_xml::DeError::InvalidXml(e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "fs")]
+    use std::fs;
+    #[cfg(feature = "fs")]
     use tempfile::tempdir;
 
     #[test]
+    #[cfg(feature = "fs")]
     fn test_valid_sysmon_conversion() {
         let temp_dir = tempdir().unwrap();
         let input_path = temp_dir.path().join("valid.xml");
@@ -142,6 +305,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "fs", feature = "validation"))]
     fn test_invalid_sysmon_conversion() {
         let temp_dir = tempdir().unwrap();
         let input_path = temp_dir.path().join("invalid.xml");
@@ -164,4 +328,196 @@ mod tests {
         let result = converter.convert(&input_path, &output_path);
         assert!(matches!(result, Err(ConversionError::ValidationError(_))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_repeated_elements_become_array() {
+        let xml = r#"
+            <ProcessCreate onmatch="include">
+                <Image condition="is">C:\Windows\System32\first.exe</Image>
+                <Image condition="is">C:\Windows\System32\second.exe</Image>
+            </ProcessCreate>"#;
+
+        let value = xml_to_value(xml).unwrap();
+        let images = value["ProcessCreate"]["Image"].as_array().unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0]["$text"], r"C:\Windows\System32\first.exe");
+        assert_eq!(images[1]["$text"], r"C:\Windows\System32\second.exe");
+        assert_eq!(images[1]["@condition"], "is");
+    }
+
+    #[test]
+    fn test_always_array_elements() {
+        let xml = r#"
+            <ProcessCreate onmatch="include">
+                <Image condition="is">test.exe</Image>
+                <CommandLine condition="contains">test</CommandLine>
+            </ProcessCreate>"#;
+
+        let mut options = XmlToJsonOptions::default();
+        options.always_array_elements.insert("Image".to_string());
+
+        let value = xml_to_value_with_options(xml, &options).unwrap();
+        let images = value["ProcessCreate"]["Image"].as_array().unwrap();
+        assert_eq!(images.len(), 1);
+        assert!(value["ProcessCreate"]["CommandLine"].is_object());
+    }
+
+    #[test]
+    fn test_flatten_single_element_arrays() {
+        let xml = r#"
+            <EventFiltering>
+                <RuleGroup name="processes" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">first.exe</Image>
+                        <Image condition="is">second.exe</Image>
+                        <CommandLine condition="contains">test</CommandLine>
+                        <ParentImage condition="is">parent.exe</ParentImage>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>"#;
+
+        let mut options = XmlToJsonOptions {
+            flatten_single_element_arrays: true,
+            ..Default::default()
+        };
+        options.always_array_elements.extend(["RuleGroup".to_string(), "ParentImage".to_string()]);
+
+        let value = xml_to_value_with_options(xml, &options).unwrap();
+        let groups = value["EventFiltering"]["RuleGroup"].as_array().unwrap();
+        assert_eq!(groups.len(), 1);
+        let process_create = &groups[0]["ProcessCreate"];
+        assert_eq!(process_create["Image"].as_array().unwrap().len(), 2);
+        assert!(process_create["CommandLine"].is_object());
+        assert!(process_create["ParentImage"].is_array());
+    }
+
+    #[test]
+    fn test_text_key() {
+        use crate::converter::{JsonToXml, JsonToXmlOptions};
+
+        let xml = r#"<ProcessCreate onmatch="include">
+                <Image condition="is">a.exe</Image>
+                <CommandLine>plain</CommandLine>
+            </ProcessCreate>"#;
+
+        let options = XmlToJsonOptions {
+            text_key: "#text".to_string(),
+            special_prefix: "#".to_string(),
+            ..Default::default()
+        };
+        let value = xml_to_value_with_options(xml, &options).unwrap();
+        let image = &value["ProcessCreate"]["Image"];
+        assert_eq!(image["#text"], "a.exe");
+        assert!(image.get("$text").is_none());
+        // Elements without attributes still collapse to their text
+        assert_eq!(value["ProcessCreate"]["CommandLine"], "plain");
+
+        // Read back with the same keys
+        let json = value.to_string();
+        let back = JsonToXml::with_options(JsonToXmlOptions {
+            text_key: "#text".to_string(),
+            special_prefix: "#".to_string(),
+            ..Default::default()
+        })
+        .convert_str(&json)
+        .unwrap();
+        assert!(back.contains(r#"<Image condition="is">a.exe</Image>"#), "{}", back);
+        // The default keys would write `#text` as an element
+        assert!(JsonToXml::new().convert_str(&json).is_err());
+
+        let options = XmlToJsonOptions {
+            text_key: "#text".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            xml_to_value_with_options(xml, &options),
+            Err(ConversionError::InvalidFile(_))
+        ));
+
+        let value = xml_to_value(xml).unwrap();
+        assert_eq!(value["ProcessCreate"]["Image"]["$text"], "a.exe");
+    }
+
+    const TYPED_XML: &str = r#"
+        <Sysmon schemaversion="4.30">
+            <DnsLookup>true</DnsLookup>
+            <ArchiveDirectory>42</ArchiveDirectory>
+            <HashAlgorithms>md5,sha256</HashAlgorithms>
+            <EventFiltering>
+                <RuleGroup name="ports" groupRelation="or">
+                    <NetworkConnect onmatch="include">
+                        <DestinationPort condition="is">4444</DestinationPort>
+                        <DestinationPort condition="is">0445</DestinationPort>
+                    </NetworkConnect>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#;
+
+    #[test]
+    fn test_infer_types_enabled() {
+        let options = XmlToJsonOptions {
+            infer_types: true,
+            ..Default::default()
+        };
+
+        let value = xml_to_value_with_options(TYPED_XML, &options).unwrap();
+        let sysmon = &value["Sysmon"];
+        assert_eq!(sysmon["DnsLookup"], Value::Bool(true));
+        assert_eq!(sysmon["ArchiveDirectory"], serde_json::json!(42));
+        assert_eq!(sysmon["HashAlgorithms"], "md5,sha256");
+        // Text that would not write back the same is left alone
+        assert_eq!(sysmon["@schemaversion"], "4.30");
+        let ports = &sysmon["EventFiltering"]["RuleGroup"]["NetworkConnect"]["DestinationPort"];
+        assert_eq!(ports[0]["$text"], serde_json::json!(4444));
+        assert_eq!(ports[1]["$text"], "0445");
+    }
+
+    #[test]
+    fn test_infer_types_round_trip() {
+        let options = XmlToJsonOptions {
+            infer_types: true,
+            ..Default::default()
+        };
+
+        let value = xml_to_value_with_options(TYPED_XML, &options).unwrap();
+        let xml = crate::converter::json::value_to_xml(&value).unwrap();
+        // `value_to_xml` names the unnamed outer object `root`
+        assert_eq!(xml_to_value(&xml).unwrap()["root"], xml_to_value(TYPED_XML).unwrap());
+    }
+
+    #[test]
+    fn test_infer_types_disabled() {
+        let value = xml_to_value(TYPED_XML).unwrap();
+        let sysmon = &value["Sysmon"];
+        assert_eq!(sysmon["@schemaversion"], "4.30");
+        assert_eq!(sysmon["DnsLookup"], "true");
+        assert_eq!(sysmon["ArchiveDirectory"], "42");
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_convert_with_warnings() {
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("broad.xml");
+        let output_path = temp_dir.path().join("broad.json");
+
+        fs::write(&input_path, r#"
+            <Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="test" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <CommandLine condition="contains">-e</CommandLine>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#).unwrap();
+
+        let warnings = XmlToJson::new()
+            .convert_with_warnings(&input_path, &output_path)
+            .unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, crate::converter::WarningSeverity::High);
+        assert!(output_path.exists());
+    }
+}
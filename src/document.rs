@@ -0,0 +1,383 @@
+use crate::converter::json::check_default_keys;
+use crate::converter::xml::xml_to_value;
+use crate::converter::FileFormat;
+use crate::error::ConversionError;
+use crate::merger::{merged_config_to_xml, with_schema_version, ConfigMerger};
+use crate::model::Value;
+use crate::preprocessor::preprocess_str;
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(feature = "validation")]
+use sysmon_validator::{parse_sysmon_config_from_str, validate_sysmon_config};
+
+/// A Sysmon configuration loaded into memory.
+///
+/// Wraps the content of the `Sysmon` root element (attributes as `@` keys,
+/// text as `$text`, as produced by the converters) and the schema version
+/// found on it.
+#[derive(Debug, Clone)]
+pub struct SysmonDocument {
+    root: Value,
+    schema_version: Option<String>,
+}
+
+/// A single filter rule in a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentRule {
+    /// Name of the enclosing `RuleGroup`, if it has one
+    pub rule_group: Option<String>,
+    /// Event filter element, such as `ProcessCreate`
+    pub event_type: String,
+    /// `include` or `exclude`, if set on the event filter
+    pub onmatch: Option<String>,
+    /// Field the rule matches, such as `Image`
+    pub field: String,
+    /// Match condition; `is` when the rule has none
+    pub condition: String,
+    /// Value the field is compared against
+    pub value: String,
+    /// `name` of the filter, or of the enclosing `Rule` element
+    pub rule_name: Option<String>,
+    /// `groupRelation` of the enclosing `Rule` element, or else of the
+    /// `RuleGroup`
+    pub group_relation: Option<String>,
+}
+
+impl SysmonDocument {
+    /// Loads a document from a file, using the extension to pick the format
+    #[cfg(feature = "fs")]
+    pub fn load(path: &Path) -> Result<Self, ConversionError> {
+        let format = FileFormat::from_path(path).ok_or_else(|| {
+            ConversionError::InvalidFile(format!("Unknown format: {}", path.display()))
+        })?;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ConversionError::io_error(path, e))?;
+        Self::load_str(&content, format)
+    }
+
+    /// Loads a document from a string.
+    ///
+    /// XML input is preprocessed (and validated, with the `validation`
+    /// feature). JSON input may either be
+    /// wrapped in a `Sysmon` key (as written by `XmlToJson`) or hold the
+    /// root element's content directly, and must keep element text under the
+    /// default `$text` key.
+    pub fn load_str(content: &str, format: FileFormat) -> Result<Self, ConversionError> {
+        let json = match format {
+            FileFormat::Xml => xml_to_value(&preprocess_str(content)?)?,
+            FileFormat::Json => {
+                let json = serde_json::from_str(content)?;
+                check_default_keys(&json)?;
+                json
+            }
+            other => {
+                return Err(ConversionError::InvalidFile(format!(
+                    "Unsupported document format: {}",
+                    other.extension()
+                )))
+            }
+        };
+
+        let root = match json {
+            serde_json::Value::Object(mut obj) if obj.len() == 1 && obj.contains_key("Sysmon") => {
+                obj.remove("Sysmon").unwrap_or_default()
+            }
+            other => other,
+        };
+        if !root.is_object() {
+            return Err(ConversionError::InvalidFile("Document root is not an object".into()));
+        }
+
+        Ok(Self::from_value(serde_json::from_value(root)?))
+    }
+
+    /// Wraps the content of a `Sysmon` root element
+    pub fn from_value(root: Value) -> Self {
+        let schema_version = match &root {
+            Value::Object(obj) => match obj.get("@schemaversion") {
+                Some(Value::String(version)) => Some(version.clone()),
+                Some(Value::Number(version)) => Some(version.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+        Self { root, schema_version }
+    }
+
+    /// Gets the schema version declared by the document
+    pub fn schema_version(&self) -> Option<&str> {
+        self.schema_version.as_deref()
+    }
+
+    /// Gets the content of the `Sysmon` root element
+    pub fn value(&self) -> &Value {
+        &self.root
+    }
+
+    /// Validates the document against the Sysmon schema
+    #[cfg(feature = "validation")]
+    pub fn validate(&self) -> Result<(), ConversionError> {
+        let xml = self.to_xml_string()?;
+        let config = parse_sysmon_config_from_str(&xml)?;
+        validate_sysmon_config(&config)?;
+        Ok(())
+    }
+
+    /// Renders the document as XML
+    pub fn to_xml_string(&self) -> Result<String, ConversionError> {
+        merged_config_to_xml(&self.root)
+    }
+
+    /// Renders the document as JSON in the layout written by `XmlToJson`.
+    /// Like `to_xml_string`, a document without a schema version gets
+    /// `DEFAULT_SCHEMA_VERSION`.
+    pub fn to_json_string(&self) -> Result<String, ConversionError> {
+        let root = with_schema_version(&self.root);
+        let mut wrapped = HashMap::new();
+        wrapped.insert("Sysmon", root.as_ref());
+        Ok(serde_json::to_string_pretty(&wrapped)?)
+    }
+
+    /// Saves the document, using the extension to pick the format
+    #[cfg(feature = "fs")]
+    pub fn save(&self, path: &Path) -> Result<(), ConversionError> {
+        let content = match FileFormat::from_path(path) {
+            Some(FileFormat::Xml) => self.to_xml_string()?,
+            Some(FileFormat::Json) => self.to_json_string()?,
+            _ => {
+                return Err(ConversionError::InvalidFile(format!(
+                    "Unsupported output format: {}",
+                    path.display()
+                )))
+            }
+        };
+        std::fs::write(path, content).map_err(|e| ConversionError::io_error(path, e))
+    }
+
+    /// Merges the rules of `other` into this document, using the same logic
+    /// as `ConfigMerger`
+    pub fn merge(&mut self, other: &SysmonDocument) -> Result<(), ConversionError> {
+        let mut merger = ConfigMerger::new();
+        merger.add_json_value(serde_json::to_value(&self.root)?, "document")?;
+        merger.add_json_value(serde_json::to_value(&other.root)?, "merged document")?;
+        *self = Self::from_value(merger.finish()?);
+        Ok(())
+    }
+
+    /// Lists every filter rule in the document, in `RuleGroup`s or directly
+    /// under `EventFiltering`
+    pub fn rules(&self) -> Vec<DocumentRule> {
+        collect_rules(&self.root)
+    }
+}
+
+/// Lists every filter rule of the content of a `Sysmon` root element, as
+/// `SysmonDocument::rules` does
+pub(crate) fn collect_rules(root: &Value) -> Vec<DocumentRule> {
+    let mut rules = Vec::new();
+    let Some(filtering) = child(root, "EventFiltering") else {
+        return rules;
+    };
+    let Value::Object(filtering) = filtering else {
+        return rules;
+    };
+
+    for (name, value) in elements(filtering) {
+        if name == "RuleGroup" {
+            for group in items(value) {
+                collect_group_rules(group, &mut rules);
+            }
+        } else {
+            collect_event_rules(&None, &None, name, value, &mut rules);
+        }
+    }
+
+    rules
+}
+
+/// Collects the filters of one `RuleGroup`
+pub(crate) fn collect_group_rules(group: &Value, rules: &mut Vec<DocumentRule>) {
+    let group_name = attribute(group, "@name");
+    let group_relation = attribute(group, "@groupRelation");
+    if let Value::Object(group) = group {
+        for (event, events) in elements(group) {
+            collect_event_rules(&group_name, &group_relation, event, events, rules);
+        }
+    }
+}
+
+/// Collects the filters of an event type, in the `RuleGroup` with the given
+/// name and `groupRelation`
+fn collect_event_rules(
+    group: &Option<String>,
+    group_relation: &Option<String>,
+    event_type: &str,
+    value: &Value,
+    rules: &mut Vec<DocumentRule>,
+) {
+    for event in items(value) {
+        let onmatch = attribute(event, "@onmatch");
+        if let Value::Object(fields) = event {
+            let template = DocumentRule {
+                rule_group: group.clone(),
+                event_type: event_type.to_string(),
+                onmatch,
+                field: String::new(),
+                condition: String::new(),
+                value: String::new(),
+                rule_name: None,
+                group_relation: group_relation.clone(),
+            };
+            collect_field_rules(&template, fields, rules);
+        }
+    }
+}
+
+/// Collects field filters, descending into `Rule` elements that group them
+fn collect_field_rules(template: &DocumentRule, fields: &HashMap<String, Value>, rules: &mut Vec<DocumentRule>) {
+    for (field, value) in elements(fields) {
+        for rule in items(value) {
+            let (condition, text) = match rule {
+                Value::String(text) => ("is".to_string(), text.clone()),
+                Value::Object(obj) => match attribute(rule, "$text") {
+                    Some(text) => (
+                        attribute(rule, "@condition").unwrap_or_else(|| "is".to_string()),
+                        text,
+                    ),
+                    _ => {
+                        let template = DocumentRule {
+                            rule_name: attribute(rule, "@name").or_else(|| template.rule_name.clone()),
+                            group_relation: attribute(rule, "@groupRelation")
+                                .or_else(|| template.group_relation.clone()),
+                            ..template.clone()
+                        };
+                        collect_field_rules(&template, obj, rules);
+                        continue;
+                    }
+                },
+                _ => continue,
+            };
+
+            rules.push(DocumentRule {
+                field: field.clone(),
+                condition,
+                value: text,
+                rule_name: attribute(rule, "@name").or_else(|| template.rule_name.clone()),
+                ..template.clone()
+            });
+        }
+    }
+}
+
+fn child<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(obj) => obj.get(name),
+        _ => None,
+    }
+}
+
+fn attribute(value: &Value, name: &str) -> Option<String> {
+    match child(value, name)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Iterates over child elements, skipping attributes and text
+fn elements(obj: &HashMap<String, Value>) -> impl Iterator<Item = (&String, &Value)> {
+    obj.iter()
+        .filter(|(key, _)| !key.starts_with('@') && !key.starts_with('$'))
+}
+
+/// Gets the occurrences of an element, which is an array when repeated
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        other => vec![other],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_image(image: &str) -> String {
+        format!(r#"<Sysmon schemaversion="4.30">
+            <EventFiltering>
+                <RuleGroup name="{image}" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">C:\Windows\System32\{image}</Image>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#)
+    }
+
+    #[test]
+    fn test_load_str_and_rules() {
+        let doc = SysmonDocument::load_str(&config_with_image("a.exe"), FileFormat::Xml).unwrap();
+        assert_eq!(doc.schema_version(), Some("4.30"));
+
+        let rules = doc.rules();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].rule_group.as_deref(), Some("a.exe"));
+        assert_eq!(rules[0].event_type, "ProcessCreate");
+        assert_eq!(rules[0].onmatch.as_deref(), Some("include"));
+        assert_eq!(rules[0].field, "Image");
+        assert_eq!(rules[0].condition, "is");
+        assert!(rules[0].value.ends_with("a.exe"));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let doc = SysmonDocument::load_str(&config_with_image("a.exe"), FileFormat::Xml).unwrap();
+        let json = doc.to_json_string().unwrap();
+        let reloaded = SysmonDocument::load_str(&json, FileFormat::Json).unwrap();
+
+        assert_eq!(reloaded.schema_version(), Some("4.30"));
+        assert_eq!(reloaded.rules(), doc.rules());
+        #[cfg(feature = "validation")]
+        assert!(reloaded.validate().is_ok());
+    }
+
+    #[test]
+    fn test_other_text_key() {
+        let json = r##"{"Sysmon": {"@schemaversion": "4.30", "EventFiltering": {"RuleGroup": {
+            "ProcessCreate": {"@onmatch": "include",
+                "Image": {"@condition": "is", "#text": "a.exe"}}}}}}"##;
+        let error = SysmonDocument::load_str(json, FileFormat::Json).unwrap_err();
+        assert!(error.to_string().contains("#text"), "{}", error);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let result = SysmonDocument::load_str("Sysmon: {}", FileFormat::Yaml);
+        assert!(matches!(result, Err(ConversionError::InvalidFile(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_merge_and_save() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first_path = temp_dir.path().join("first.xml");
+        let second_path = temp_dir.path().join("second.xml");
+        let merged_path = temp_dir.path().join("merged.xml");
+        std::fs::write(&first_path, config_with_image("first.exe")).unwrap();
+        std::fs::write(&second_path, config_with_image("second.exe")).unwrap();
+
+        let mut doc = SysmonDocument::load(&first_path).unwrap();
+        let other = SysmonDocument::load(&second_path).unwrap();
+        doc.merge(&other).unwrap();
+        doc.save(&merged_path).unwrap();
+
+        let merged = SysmonDocument::load(&merged_path).unwrap();
+        #[cfg(feature = "validation")]
+        assert!(merged.validate().is_ok());
+        let values: Vec<_> = merged.rules().into_iter().map(|r| r.value).collect();
+        assert!(values.iter().any(|v| v.ends_with("first.exe")));
+        assert!(values.iter().any(|v| v.ends_with("second.exe")));
+    }
+}
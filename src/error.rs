@@ -1,8 +1,14 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::str::Utf8Error;
 use thiserror::Error;
+#[cfg(feature = "validation")]
 use sysmon_validator::{ValidationError, errors::ParserError};
 
+/// Error from preprocessing a configuration. The `ValidationError` and
+/// `ParserError` variants only exist with the `validation` feature.
+#[non_exhaustive]
 #[derive(Error, Debug)]
 pub enum PreprocessError {
     #[error("IO error: {0}")]
@@ -11,14 +17,76 @@ pub enum PreprocessError {
     #[error("XML parsing error: {0}")]
     XmlError(#[from] quick_xml::Error),
 
+    #[cfg(feature = "validation")]
     #[error("Validation error: {0}")]
     ValidationError(#[from] ValidationError),
 
     #[error("Path error: {0}")]
     PathError(String),
 
+    #[error("Unresolved template variable: {0}")]
+    UnresolvedVariable(String),
+
+    #[cfg(feature = "validation")]
     #[error("Parser error: {0}")]
     ParserError(#[from] ParserError),
+
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<PreprocessError>,
+    },
+}
+
+/// Category of a `PreprocessError`, one per variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreprocessErrorKind {
+    Io,
+    XmlParse,
+    ValidationError,
+    PathError,
+    ParserError,
+    UnresolvedVariable,
+}
+
+impl fmt::Display for PreprocessErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl PreprocessError {
+    /// Attaches the path of the file being preprocessed
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        PreprocessError::File {
+            path: path.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Gets the path of the file being preprocessed, when it is known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            PreprocessError::File { path, .. } => Some(path.as_path()),
+            _ => None,
+        }
+    }
+
+    /// Gets the category of the error, for matching without destructuring.
+    /// Errors attached to a file report the kind of the underlying error.
+    pub fn kind(&self) -> PreprocessErrorKind {
+        match self {
+            PreprocessError::IoError(_) => PreprocessErrorKind::Io,
+            PreprocessError::XmlError(_) => PreprocessErrorKind::XmlParse,
+            #[cfg(feature = "validation")]
+            PreprocessError::ValidationError(_) => PreprocessErrorKind::ValidationError,
+            PreprocessError::PathError(_) => PreprocessErrorKind::PathError,
+            PreprocessError::UnresolvedVariable(_) => PreprocessErrorKind::UnresolvedVariable,
+            #[cfg(feature = "validation")]
+            PreprocessError::ParserError(_) => PreprocessErrorKind::ParserError,
+            PreprocessError::File { source, .. } => source.kind(),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -46,7 +114,7 @@ pub enum ConversionError {
     ParserError(String),
 
     #[error("Batch conversion errors:\n{0}")]
-    BatchConversionError(String),
+    BatchConversionError(BatchConversionError),
 
     #[error("Batch processing error: {0}")]
     BatchError(String),
@@ -63,6 +131,11 @@ pub enum ConversionError {
         depth: u32,
     },
 
+    #[error("Output file already exists: {path}")]
+    OutputExists {
+        path: PathBuf,
+    },
+
     #[error("Verification failed: {0}")]
     VerificationError(String),
 
@@ -70,12 +143,124 @@ pub enum ConversionError {
     PreprocessError(PreprocessError),
 }
 
+/// Category of a `ConversionError`, one per variant
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConversionErrorKind {
+    Io,
+    XmlParse,
+    JsonParse,
+    InvalidFile,
+    ValidationError,
+    ParserError,
+    BatchConversionError,
+    BatchError,
+    FileSizeLimitExceeded,
+    MaxDepthExceeded,
+    OutputExists,
+    VerificationError,
+    PreprocessError,
+}
+
+impl fmt::Display for ConversionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Per-file failures collected during a batch operation
+#[derive(Debug, Default)]
+pub struct BatchConversionError {
+    /// Each failed input path with the error it produced
+    pub errors: Vec<(PathBuf, ConversionError)>,
+}
+
+impl BatchConversionError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a failure for a path
+    pub fn push(&mut self, path: impl Into<PathBuf>, error: ConversionError) {
+        self.errors.push((path.into(), error));
+    }
+
+    /// Returns true when no failures were recorded
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Gets the number of recorded failures
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Iterates over the failed paths and their errors
+    pub fn iter(&self) -> impl Iterator<Item = &(PathBuf, ConversionError)> {
+        self.errors.iter()
+    }
+
+    /// Gets every failed path, in the order the failures were recorded
+    pub fn failed_paths(&self) -> Vec<&Path> {
+        self.errors.iter().map(|(path, _)| path.as_path()).collect()
+    }
+
+    /// Groups the failed paths by error kind
+    pub fn by_kind(&self) -> HashMap<String, Vec<&Path>> {
+        let mut groups: HashMap<String, Vec<&Path>> = HashMap::new();
+        for (path, error) in &self.errors {
+            groups
+                .entry(error.kind().to_string())
+                .or_default()
+                .push(path.as_path());
+        }
+        groups
+    }
+
+    /// Gets the error recorded for a path, if any
+    pub fn error_for(&self, path: &Path) -> Option<&ConversionError> {
+        self.errors
+            .iter()
+            .find(|(failed, _)| failed == path)
+            .map(|(_, error)| error)
+    }
+}
+
+impl<'a> IntoIterator for &'a BatchConversionError {
+    type Item = &'a (PathBuf, ConversionError);
+    type IntoIter = std::slice::Iter<'a, (PathBuf, ConversionError)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+impl From<Vec<(PathBuf, ConversionError)>> for BatchConversionError {
+    fn from(errors: Vec<(PathBuf, ConversionError)>) -> Self {
+        Self { errors }
+    }
+}
+
+impl fmt::Display for BatchConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (path, error)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "validation")]
 impl From<ValidationError> for ConversionError {
     fn from(err: ValidationError) -> Self {
         ConversionError::ValidationError(err.to_string())
     }
 }
 
+#[cfg(feature = "validation")]
 impl From<ParserError> for ConversionError {
     fn from(err: ParserError) -> Self {
         ConversionError::ParserError(err.to_string())
@@ -101,6 +286,47 @@ impl ConversionError {
             source,
         }
     }
+
+    /// Returns true for transient failures (filesystem IO) that may succeed
+    /// on retry, and false for structural errors in the input itself
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            ConversionError::Io { .. } => true,
+            ConversionError::PreprocessError(e) => e.kind() == PreprocessErrorKind::Io,
+            _ => false,
+        }
+    }
+
+    /// Gets the category of the error, for matching without destructuring
+    pub fn kind(&self) -> ConversionErrorKind {
+        match self {
+            ConversionError::Io { .. } => ConversionErrorKind::Io,
+            ConversionError::XmlParse(_) => ConversionErrorKind::XmlParse,
+            ConversionError::JsonParse(_) => ConversionErrorKind::JsonParse,
+            ConversionError::InvalidFile(_) => ConversionErrorKind::InvalidFile,
+            ConversionError::ValidationError(_) => ConversionErrorKind::ValidationError,
+            ConversionError::ParserError(_) => ConversionErrorKind::ParserError,
+            ConversionError::BatchConversionError(_) => ConversionErrorKind::BatchConversionError,
+            ConversionError::BatchError(_) => ConversionErrorKind::BatchError,
+            ConversionError::FileSizeLimitExceeded { .. } => ConversionErrorKind::FileSizeLimitExceeded,
+            ConversionError::MaxDepthExceeded { .. } => ConversionErrorKind::MaxDepthExceeded,
+            ConversionError::OutputExists { .. } => ConversionErrorKind::OutputExists,
+            ConversionError::VerificationError(_) => ConversionErrorKind::VerificationError,
+            ConversionError::PreprocessError(_) => ConversionErrorKind::PreprocessError,
+        }
+    }
+
+    /// Gets the file path carried by the error, if the variant has one
+    pub fn source_path(&self) -> Option<&Path> {
+        match self {
+            ConversionError::Io { path, .. } => Some(path.as_path()),
+            ConversionError::OutputExists { path } => Some(path.as_path()),
+            ConversionError::FileSizeLimitExceeded { path, .. } => Some(Path::new(path)),
+            ConversionError::MaxDepthExceeded { path, .. } => Some(Path::new(path)),
+            ConversionError::PreprocessError(e) => e.path(),
+            _ => None,
+        }
+    }
 }
 
 impl From<quick_xml::events::attributes::AttrError> for PreprocessError {
@@ -116,4 +342,76 @@ impl From<std::io::Error> for ConversionError {
             source,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_path() {
+        let io = ConversionError::io_error("configs/a.xml", std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(io.source_path(), Some(Path::new("configs/a.xml")));
+
+        let size = ConversionError::FileSizeLimitExceeded {
+            path: "configs/big.xml".to_string(),
+            size: 1024,
+        };
+        assert_eq!(size.source_path(), Some(Path::new("configs/big.xml")));
+
+        let depth = ConversionError::MaxDepthExceeded {
+            path: "configs/deep".to_string(),
+            depth: 10,
+        };
+        assert_eq!(depth.source_path(), Some(Path::new("configs/deep")));
+
+        assert_eq!(ConversionError::InvalidFile("bad".into()).source_path(), None);
+    }
+
+    #[test]
+    fn test_kind() {
+        let io = ConversionError::io_error("a.xml", std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(io.kind(), ConversionErrorKind::Io);
+        assert_eq!(
+            ConversionError::ValidationError("bad rule".into()).kind(),
+            ConversionErrorKind::ValidationError
+        );
+        assert_eq!(ConversionErrorKind::FileSizeLimitExceeded.to_string(), "FileSizeLimitExceeded");
+    }
+
+    #[test]
+    fn test_preprocess_kind() {
+        let io = PreprocessError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(io.kind(), PreprocessErrorKind::Io);
+        assert_eq!(PreprocessError::PathError("bad".into()).kind(), PreprocessErrorKind::PathError);
+
+        let in_file = PreprocessError::PathError("bad".into()).in_file("configs/a.xml");
+        assert_eq!(in_file.kind(), PreprocessErrorKind::PathError);
+        assert_eq!(in_file.path(), Some(Path::new("configs/a.xml")));
+        assert_eq!(in_file.to_string(), "configs/a.xml: Path error: bad");
+        assert!(ConversionError::from(io.in_file("a.xml")).is_recoverable());
+    }
+
+    #[test]
+    fn test_batch_error_accessors() {
+        let mut batch = BatchConversionError::new();
+        batch.push("a.xml", ConversionError::ValidationError("bad rule".into()));
+        batch.push("b.xml", ConversionError::io_error("b.xml", std::io::Error::from(std::io::ErrorKind::NotFound)));
+        batch.push("c.xml", ConversionError::ValidationError("missing schema".into()));
+
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.iter().count(), 3);
+        assert_eq!(
+            batch.failed_paths(),
+            vec![Path::new("a.xml"), Path::new("b.xml"), Path::new("c.xml")]
+        );
+
+        let groups = batch.by_kind();
+        assert_eq!(groups["ValidationError"], vec![Path::new("a.xml"), Path::new("c.xml")]);
+        assert_eq!(groups["Io"], vec![Path::new("b.xml")]);
+
+        let rendered = ConversionError::BatchConversionError(batch).to_string();
+        assert!(rendered.contains("a.xml: Validation error: bad rule"));
+        assert!(rendered.contains("c.xml: Validation error: missing schema"));
+    }
+}
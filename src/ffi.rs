@@ -0,0 +1,392 @@
+//! C ABI bindings for embedding the converter and merger.
+//!
+//! Ownership rules:
+//! - Input strings are borrowed for the duration of the call only.
+//! - Strings returned through `out_buf` are allocated by this library and
+//!   must be released with `sysmon_json_free`, never with `free()`.
+//! - The pointer returned by `sysmon_json_last_error` is owned by the library
+//!   and stays valid until the next call into the library on the same thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+
+use crate::converter::FileFormat;
+use crate::error::ConversionError;
+use crate::merger::{merged_config_to_xml, ConfigMerger};
+
+/// XML input or output format
+pub const SYSMON_JSON_FORMAT_XML: c_int = 0;
+/// JSON input or output format
+pub const SYSMON_JSON_FORMAT_JSON: c_int = 1;
+
+/// The call succeeded
+pub const SYSMON_JSON_OK: c_int = 0;
+/// A pointer was null, a string was not UTF-8, or a format was unknown
+pub const SYSMON_JSON_ERR_INVALID_ARGUMENT: c_int = -1;
+/// Filesystem or stream IO failed
+pub const SYSMON_JSON_ERR_IO: c_int = -2;
+/// The XML input could not be parsed
+pub const SYSMON_JSON_ERR_XML_PARSE: c_int = -3;
+/// The JSON input could not be parsed
+pub const SYSMON_JSON_ERR_JSON_PARSE: c_int = -4;
+/// The configuration failed Sysmon schema validation
+pub const SYSMON_JSON_ERR_VALIDATION: c_int = -5;
+/// The input is not a usable configuration or the conversion is unsupported
+pub const SYSMON_JSON_ERR_INVALID_FILE: c_int = -6;
+/// Preprocessing the input failed
+pub const SYSMON_JSON_ERR_PREPROCESS: c_int = -7;
+/// Any other failure
+pub const SYSMON_JSON_ERR_OTHER: c_int = -99;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = None);
+}
+
+/// Records `error` as the last error and returns its error code
+fn fail(error: &ConversionError) -> c_int {
+    set_last_error(&error.to_string());
+    error_code(error)
+}
+
+fn invalid_argument(message: &str) -> c_int {
+    set_last_error(message);
+    SYSMON_JSON_ERR_INVALID_ARGUMENT
+}
+
+fn error_code(error: &ConversionError) -> c_int {
+    match error {
+        ConversionError::Io { .. } => SYSMON_JSON_ERR_IO,
+        ConversionError::XmlParse(_) => SYSMON_JSON_ERR_XML_PARSE,
+        ConversionError::JsonParse(_) => SYSMON_JSON_ERR_JSON_PARSE,
+        ConversionError::ValidationError(_) | ConversionError::ParserError(_) => SYSMON_JSON_ERR_VALIDATION,
+        ConversionError::InvalidFile(_) => SYSMON_JSON_ERR_INVALID_FILE,
+        ConversionError::PreprocessError(_) => SYSMON_JSON_ERR_PREPROCESS,
+        _ => SYSMON_JSON_ERR_OTHER,
+    }
+}
+
+fn format_from_c(format: c_int) -> Option<FileFormat> {
+    match format {
+        SYSMON_JSON_FORMAT_XML => Some(FileFormat::Xml),
+        SYSMON_JSON_FORMAT_JSON => Some(FileFormat::Json),
+        _ => None,
+    }
+}
+
+/// Borrows a C string as UTF-8
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn borrow_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, c_int> {
+    if ptr.is_null() {
+        return Err(invalid_argument(&format!("{} is null", name)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| invalid_argument(&format!("{} is not valid UTF-8", name)))
+}
+
+/// Hands an owned string to the caller through `out_buf`/`out_len`
+///
+/// # Safety
+/// `out_buf` must be a valid, non-null pointer; `out_len` may be null.
+unsafe fn write_output(output: String, out_buf: *mut *mut c_char, out_len: *mut usize) -> c_int {
+    let len = output.len();
+    match CString::new(output) {
+        Ok(output) => {
+            *out_buf = output.into_raw();
+            if !out_len.is_null() {
+                *out_len = len;
+            }
+            SYSMON_JSON_OK
+        }
+        Err(_) => {
+            set_last_error("Output contains an interior NUL byte");
+            SYSMON_JSON_ERR_OTHER
+        }
+    }
+}
+
+/// Converts a configuration between formats.
+///
+/// On success `*out_buf` receives a NUL-terminated string that must be
+/// released with `sysmon_json_free`, and `*out_len` (if not null) its length
+/// in bytes excluding the terminator. On failure a negative error code is
+/// returned, `*out_buf` is left untouched, and `sysmon_json_last_error`
+/// describes the problem.
+///
+/// # Safety
+/// `input` must be a NUL-terminated string and `out_buf` a valid pointer.
+/// `out_len` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn sysmon_json_convert_str(
+    input: *const c_char,
+    in_fmt: c_int,
+    out_fmt: c_int,
+    out_buf: *mut *mut c_char,
+    out_len: *mut usize,
+) -> c_int {
+    clear_last_error();
+
+    if out_buf.is_null() {
+        return invalid_argument("out_buf is null");
+    }
+    let input = match borrow_str(input, "input") {
+        Ok(input) => input,
+        Err(code) => return code,
+    };
+    let (from, to) = match (format_from_c(in_fmt), format_from_c(out_fmt)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return invalid_argument("Unknown format"),
+    };
+
+    match crate::convert_str(input, from, to) {
+        Ok(output) => write_output(output, out_buf, out_len),
+        Err(e) => fail(&e),
+    }
+}
+
+/// Merges `count` configurations into one.
+///
+/// `inputs` and `formats` are parallel arrays of `count` entries. The merged
+/// configuration is returned through `out_buf`/`out_len` with the same
+/// ownership rules as `sysmon_json_convert_str`.
+///
+/// # Safety
+/// `inputs` and `formats` must point to `count` valid entries, each input a
+/// NUL-terminated string. `out_buf` must be a valid pointer; `out_len` may
+/// be null.
+#[no_mangle]
+pub unsafe extern "C" fn sysmon_json_merge_str(
+    inputs: *const *const c_char,
+    formats: *const c_int,
+    count: usize,
+    out_fmt: c_int,
+    out_buf: *mut *mut c_char,
+    out_len: *mut usize,
+) -> c_int {
+    clear_last_error();
+
+    if out_buf.is_null() {
+        return invalid_argument("out_buf is null");
+    }
+    if count > 0 && (inputs.is_null() || formats.is_null()) {
+        return invalid_argument("inputs or formats is null");
+    }
+    let out_format = match format_from_c(out_fmt) {
+        Some(format) => format,
+        None => return invalid_argument("Unknown output format"),
+    };
+
+    let mut merger = ConfigMerger::new();
+    for i in 0..count {
+        let label = format!("input[{}]", i);
+        let content = match borrow_str(*inputs.add(i), &label) {
+            Ok(content) => content,
+            Err(code) => return code,
+        };
+
+        let result = match format_from_c(*formats.add(i)) {
+            Some(FileFormat::Xml) => merger.add_xml_str(content, &label),
+            Some(FileFormat::Json) => serde_json::from_str(content)
+                .map_err(ConversionError::from)
+                .and_then(|value| merger.add_json_value(value, &label)),
+            _ => return invalid_argument(&format!("Unknown format for {}", label)),
+        };
+        if let Err(e) = result {
+            return fail(&e);
+        }
+    }
+
+    let rendered = merger.finish().and_then(|merged| match out_format {
+        FileFormat::Json => serde_json::to_string_pretty(&merged).map_err(ConversionError::from),
+        _ => merged_config_to_xml(&merged),
+    });
+
+    match rendered {
+        Ok(output) => write_output(output, out_buf, out_len),
+        Err(e) => fail(&e),
+    }
+}
+
+/// Returns a description of the last error on this thread, or null if the
+/// last call succeeded. The string is owned by the library.
+#[no_mangle]
+pub extern "C" fn sysmon_json_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Releases a string returned by this library. Passing null is a no-op.
+///
+/// # Safety
+/// `ptr` must be null or a pointer previously returned through `out_buf` that
+/// has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn sysmon_json_free(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[cfg(feature = "validation")]
+    const INVALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="sometimes">
+                    <Image condition="is">test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    fn take_output(ptr: *mut c_char) -> String {
+        let output = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_string();
+        unsafe { sysmon_json_free(ptr) };
+        output
+    }
+
+    fn last_error() -> Option<String> {
+        let ptr = sysmon_json_last_error();
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+        }
+    }
+
+    #[test]
+    fn test_convert_str_success() {
+        let input = CString::new(VALID_XML).unwrap();
+        let mut out: *mut c_char = ptr::null_mut();
+        let mut len = 0usize;
+
+        let rc = unsafe {
+            sysmon_json_convert_str(
+                input.as_ptr(),
+                SYSMON_JSON_FORMAT_XML,
+                SYSMON_JSON_FORMAT_JSON,
+                &mut out,
+                &mut len,
+            )
+        };
+
+        assert_eq!(rc, SYSMON_JSON_OK);
+        assert!(last_error().is_none());
+        let output = take_output(out);
+        assert_eq!(output.len(), len);
+        assert!(output.contains("test.exe"));
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_convert_str_validation_error() {
+        let input = CString::new(INVALID_XML).unwrap();
+        let mut out: *mut c_char = ptr::null_mut();
+
+        let rc = unsafe {
+            sysmon_json_convert_str(
+                input.as_ptr(),
+                SYSMON_JSON_FORMAT_XML,
+                SYSMON_JSON_FORMAT_JSON,
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+
+        assert!(rc < 0);
+        assert!(out.is_null());
+        assert!(last_error().is_some());
+    }
+
+    #[test]
+    fn test_convert_str_invalid_arguments() {
+        let mut out: *mut c_char = ptr::null_mut();
+        let rc = unsafe {
+            sysmon_json_convert_str(
+                ptr::null(),
+                SYSMON_JSON_FORMAT_XML,
+                SYSMON_JSON_FORMAT_JSON,
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(rc, SYSMON_JSON_ERR_INVALID_ARGUMENT);
+
+        let input = CString::new(VALID_XML).unwrap();
+        let rc = unsafe {
+            sysmon_json_convert_str(input.as_ptr(), 42, SYSMON_JSON_FORMAT_JSON, &mut out, ptr::null_mut())
+        };
+        assert_eq!(rc, SYSMON_JSON_ERR_INVALID_ARGUMENT);
+        assert!(last_error().unwrap().contains("format"));
+    }
+
+    #[test]
+    fn test_merge_str() {
+        let xml = CString::new(VALID_XML).unwrap();
+        let json = CString::new(r#"{
+            "@schemaversion": "4.30",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "test2",
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": {"@condition": "is", "$text": "C:\\Windows\\System32\\other.exe"}
+                    }
+                }
+            }
+        }"#).unwrap();
+        let inputs = [xml.as_ptr(), json.as_ptr()];
+        let formats = [SYSMON_JSON_FORMAT_XML, SYSMON_JSON_FORMAT_JSON];
+        let mut out: *mut c_char = ptr::null_mut();
+
+        let rc = unsafe {
+            sysmon_json_merge_str(
+                inputs.as_ptr(),
+                formats.as_ptr(),
+                inputs.len(),
+                SYSMON_JSON_FORMAT_XML,
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+
+        assert_eq!(rc, SYSMON_JSON_OK, "{:?}", last_error());
+        let output = take_output(out);
+        assert!(output.contains("test.exe"));
+        assert!(output.contains("other.exe"));
+    }
+
+    #[test]
+    fn test_free_null_is_noop() {
+        unsafe { sysmon_json_free(ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,216 @@
+//! Building include-only configs from flat lists of indicators
+
+use crate::error::ConversionError;
+use crate::model::{SchemaVersion, Value};
+use crate::schema::event_type;
+#[cfg(feature = "fs")]
+use crate::document::SysmonDocument;
+#[cfg(feature = "validation")]
+use crate::merger::merged_config_to_xml;
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(feature = "validation")]
+use sysmon_validator::{parse_sysmon_config_from_str, validate_sysmon_config};
+
+/// A single value to include, such as an image path or a hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indicator {
+    /// Event filter element, such as `ProcessCreate`
+    pub event_type: String,
+    /// Field to match, such as `Image` or `Hashes`
+    pub field: String,
+    /// Match condition, such as `is` or `contains`
+    pub condition: String,
+    pub value: String,
+}
+
+impl Indicator {
+    pub fn new(event_type: &str, field: &str, condition: &str, value: &str) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            field: field.to_string(),
+            condition: condition.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Builds a config that includes every indicator.
+///
+/// The result is the content of the `Sysmon` root element, in the layout
+/// produced by `ConfigMerger`, with one `RuleGroup` per event type (in order
+/// of first appearance) and `onmatch="include"`. Event types and fields are
+/// checked against the `schema` tables, and with the `validation` feature the
+/// result is also validated against the Sysmon schema.
+pub fn config_from_indicators(
+    indicators: &[Indicator],
+    schema_version: &str,
+) -> Result<Value, ConversionError> {
+    let version = SchemaVersion::parse(schema_version).ok_or_else(|| {
+        ConversionError::InvalidFile(format!("Invalid schema version: {}", schema_version))
+    })?;
+
+    // Field filters per event type, keeping first-appearance order of both
+    let mut event_types: Vec<(&str, Vec<(&str, Vec<Value>)>)> = Vec::new();
+    for indicator in indicators {
+        check_indicator(indicator, version)?;
+
+        let event_pos = match event_types.iter().position(|(name, _)| *name == indicator.event_type) {
+            Some(pos) => pos,
+            None => {
+                event_types.push((&indicator.event_type, Vec::new()));
+                event_types.len() - 1
+            }
+        };
+        let fields = &mut event_types[event_pos].1;
+        let field_pos = match fields.iter().position(|(name, _)| *name == indicator.field) {
+            Some(pos) => pos,
+            None => {
+                fields.push((&indicator.field, Vec::new()));
+                fields.len() - 1
+            }
+        };
+
+        let mut rule = HashMap::new();
+        rule.insert("@condition".to_string(), Value::String(indicator.condition.clone()));
+        rule.insert("$text".to_string(), Value::String(indicator.value.clone()));
+        fields[field_pos].1.push(Value::Object(rule));
+    }
+
+    let groups: Vec<Value> = event_types
+        .into_iter()
+        .map(|(name, fields)| {
+            let mut event = HashMap::new();
+            event.insert("@onmatch".to_string(), Value::String("include".to_string()));
+            for (field, mut rules) in fields {
+                let rules = if rules.len() == 1 {
+                    rules.remove(0)
+                } else {
+                    Value::Array(rules)
+                };
+                event.insert(field.to_string(), rules);
+            }
+
+            let mut group = HashMap::new();
+            group.insert("@name".to_string(), Value::String(name.to_string()));
+            group.insert("@groupRelation".to_string(), Value::String("or".to_string()));
+            group.insert(name.to_string(), Value::Object(event));
+            Value::Object(group)
+        })
+        .collect();
+
+    let mut root = HashMap::new();
+    root.insert("@schemaversion".to_string(), Value::String(schema_version.to_string()));
+    if !groups.is_empty() {
+        let mut filtering = HashMap::new();
+        filtering.insert("RuleGroup".to_string(), Value::Array(groups));
+        root.insert("EventFiltering".to_string(), Value::Object(filtering));
+    }
+    let config = Value::Object(root);
+
+    #[cfg(feature = "validation")]
+    {
+        let xml = merged_config_to_xml(&config)?;
+        let parsed = parse_sysmon_config_from_str(&xml)?;
+        validate_sysmon_config(&parsed)?;
+    }
+
+    Ok(config)
+}
+
+/// Builds a config with `config_from_indicators` and saves it, using the
+/// extension of `output` to pick XML or JSON
+#[cfg(feature = "fs")]
+pub fn write_config_from_indicators(
+    indicators: &[Indicator],
+    schema_version: &str,
+    output: &Path,
+) -> Result<(), ConversionError> {
+    let config = config_from_indicators(indicators, schema_version)?;
+    SysmonDocument::from_value(config).save(output)
+}
+
+fn check_indicator(indicator: &Indicator, version: SchemaVersion) -> Result<(), ConversionError> {
+    let info = event_type(&indicator.event_type).ok_or_else(|| {
+        ConversionError::InvalidFile(format!("Unknown event type: {}", indicator.event_type))
+    })?;
+    if info.min_schema > version {
+        return Err(ConversionError::InvalidFile(format!(
+            "{} requires schema version {} or later",
+            info.name, info.min_schema
+        )));
+    }
+    if !info.fields.contains(&indicator.field.as_str()) {
+        return Err(ConversionError::InvalidFile(format!(
+            "{} has no field {}",
+            info.name, indicator.field
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::SysmonDocument;
+
+    fn indicators() -> Vec<Indicator> {
+        let mut indicators: Vec<_> = ["a.exe", "b.exe", "c.exe", "d.exe", "e.exe"]
+            .iter()
+            .map(|image| {
+                Indicator::new("ProcessCreate", "Image", "end with", &format!(r"\{}", image))
+            })
+            .collect();
+        indicators.push(Indicator::new("ProcessCreate", "Hashes", "contains", "SHA256=0A1B2C"));
+        indicators.push(Indicator::new("ProcessCreate", "Hashes", "contains", "SHA256=3D4E5F"));
+        indicators
+    }
+
+    #[test]
+    fn test_config_from_indicators() {
+        let config = config_from_indicators(&indicators(), "4.50").unwrap();
+
+        let doc = SysmonDocument::from_value(config);
+        assert_eq!(doc.schema_version(), Some("4.50"));
+        let rules = doc.rules();
+        assert_eq!(rules.len(), 7);
+        assert!(rules.iter().all(|r| r.onmatch.as_deref() == Some("include")));
+        assert!(rules.iter().all(|r| r.rule_group.as_deref() == Some("ProcessCreate")));
+        assert_eq!(rules.iter().filter(|r| r.field == "Hashes").count(), 2);
+
+        #[cfg(feature = "validation")]
+        {
+            let xml = doc.to_xml_string().unwrap();
+            let parsed = parse_sysmon_config_from_str(&xml).unwrap();
+            assert!(validate_sysmon_config(&parsed).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_event_types_and_fields() {
+        let unknown = [Indicator::new("ProcesCreate", "Image", "is", "a.exe")];
+        assert!(config_from_indicators(&unknown, "4.50").is_err());
+
+        let bad_field = [Indicator::new("DnsQuery", "Image2", "is", "a.exe")];
+        assert!(config_from_indicators(&bad_field, "4.50").is_err());
+
+        let too_new = [Indicator::new("ClipboardChange", "Image", "is", "a.exe")];
+        assert!(config_from_indicators(&too_new, "4.30").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_write_config_from_indicators() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let xml_path = temp_dir.path().join("iocs.xml");
+        let json_path = temp_dir.path().join("iocs.json");
+
+        write_config_from_indicators(&indicators(), "4.50", &xml_path).unwrap();
+        write_config_from_indicators(&indicators(), "4.50", &json_path).unwrap();
+
+        assert!(std::fs::read_to_string(&xml_path).unwrap().contains("SHA256=0A1B2C"));
+        let reloaded = SysmonDocument::load(&json_path).unwrap();
+        assert_eq!(reloaded.rules().len(), 7);
+    }
+}
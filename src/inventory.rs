@@ -0,0 +1,196 @@
+//! Rule inventory across a directory of configs, for review before merging
+
+use crate::config::ProcessingOptions;
+use crate::document::{DocumentRule, SysmonDocument};
+use crate::error::ConversionError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Rule counts for one event type
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EventTypeCounts {
+    pub total: usize,
+    pub include: usize,
+    pub exclude: usize,
+}
+
+/// Rule counts for a file or a whole directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RuleCounts {
+    pub total: usize,
+    pub by_event_type: BTreeMap<String, EventTypeCounts>,
+}
+
+impl RuleCounts {
+    fn add(&mut self, rule: &DocumentRule) {
+        self.total += 1;
+        let counts = self.by_event_type.entry(rule.event_type.clone()).or_default();
+        counts.total += 1;
+        match rule.onmatch.as_deref() {
+            Some("include") => counts.include += 1,
+            Some("exclude") => counts.exclude += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Inventory of a single config file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInventory {
+    pub path: PathBuf,
+    pub schema_version: Option<String>,
+    pub counts: RuleCounts,
+    /// Why the file could not be loaded; its counts are empty when set
+    pub error: Option<String>,
+}
+
+/// A rule found in more than one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateRule {
+    pub event_type: String,
+    pub onmatch: Option<String>,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+    /// Files containing the rule, in scan order
+    pub files: Vec<PathBuf>,
+}
+
+/// Outcome of `scan`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InventoryReport {
+    pub files: Vec<FileInventory>,
+    /// Counts over every file that loaded
+    pub totals: RuleCounts,
+    pub duplicates: Vec<DuplicateRule>,
+}
+
+type RuleIdentity = (String, Option<String>, String, String, String);
+
+/// Builds an inventory of the XML and JSON configs in a directory.
+///
+/// Rules are extracted the same way as `SysmonDocument::rules`. Files that
+/// fail to load are listed with their error; `Err` is only returned when the
+/// directory itself cannot be read. The directory is walked as with the
+/// default `ProcessingOptions`.
+pub fn scan(dir: &Path, recursive: bool) -> Result<InventoryReport, ConversionError> {
+    std::fs::read_dir(dir).map_err(|e| ConversionError::io_error(dir, e))?;
+
+    let mut report = InventoryReport::default();
+    let mut seen: BTreeMap<RuleIdentity, Vec<PathBuf>> = BTreeMap::new();
+
+    for entry in crate::walk_files(dir, recursive, &ProcessingOptions::default()) {
+        let path = entry.path();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase());
+        if !matches!(ext.as_deref(), Some("xml") | Some("json")) {
+            continue;
+        }
+
+        let mut file = FileInventory {
+            path: path.to_path_buf(),
+            schema_version: None,
+            counts: RuleCounts::default(),
+            error: None,
+        };
+
+        match SysmonDocument::load(path) {
+            Ok(doc) => {
+                file.schema_version = doc.schema_version().map(str::to_string);
+                for rule in doc.rules() {
+                    file.counts.add(&rule);
+                    report.totals.add(&rule);
+
+                    let identity = (rule.event_type, rule.onmatch, rule.field, rule.condition, rule.value);
+                    let files = seen.entry(identity).or_default();
+                    if !files.iter().any(|f| f == path) {
+                        files.push(path.to_path_buf());
+                    }
+                }
+            }
+            Err(e) => file.error = Some(e.to_string()),
+        }
+
+        report.files.push(file);
+    }
+
+    report.duplicates = seen
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((event_type, onmatch, field, condition, value), files)| DuplicateRule {
+            event_type,
+            onmatch,
+            field,
+            condition,
+            value,
+            files,
+        })
+        .collect();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn config(rules: &str) -> String {
+        format!(r#"<Sysmon schemaversion="4.30">
+            <EventFiltering>
+                <RuleGroup name="test" groupRelation="or">
+                    {rules}
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#)
+    }
+
+    #[test]
+    fn test_scan_counts_and_duplicates() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("a.xml"),
+            config(r#"<ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\shared.exe</Image>
+                    <Image condition="is">C:\Windows\System32\only_a.exe</Image>
+                </ProcessCreate>"#),
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("b.xml"),
+            config(r#"<ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\shared.exe</Image>
+                </ProcessCreate>
+                <NetworkConnect onmatch="exclude">
+                    <DestinationPort condition="is">443</DestinationPort>
+                </NetworkConnect>"#),
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("c.json"), "{ not json").unwrap();
+
+        let report = scan(temp_dir.path(), false).unwrap();
+
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.files[0].counts.total, 2);
+        assert_eq!(report.files[0].schema_version.as_deref(), Some("4.30"));
+        assert_eq!(report.files[1].counts.by_event_type["NetworkConnect"].exclude, 1);
+        assert!(report.files[2].error.is_some());
+
+        assert_eq!(report.totals.total, 4);
+        assert_eq!(report.totals.by_event_type["ProcessCreate"].include, 3);
+
+        assert_eq!(report.duplicates.len(), 1);
+        assert!(report.duplicates[0].value.ends_with("shared.exe"));
+        assert_eq!(
+            report.duplicates[0].files,
+            vec![temp_dir.path().join("a.xml"), temp_dir.path().join("b.xml")]
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["totals"]["total"], 4);
+    }
+}
@@ -1,107 +1,1084 @@
 // Re-export the primary types and functions
+#[cfg(feature = "fs")]
 pub mod batch;
 pub mod config;
 pub mod error;
 pub mod converter;
+pub mod document;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod generate;
+#[cfg(feature = "fs")]
+pub mod inventory;
 pub mod model;
 pub mod merger;
 pub mod preprocessor; 
+pub mod report;
+pub mod schema;
+#[cfg(feature = "fs")]
+pub mod search;
+pub mod stats;
+#[cfg(feature = "validation")]
+pub mod validation;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 use std::path::Path;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+#[cfg(feature = "fs")]
 use std::fs;
+#[cfg(feature = "fs")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "fs")]
+use walkdir::{DirEntry, WalkDir};
+#[cfg(feature = "fs")]
+use std::collections::HashSet;
+#[cfg(feature = "fs")]
+use std::fmt;
 use error::ConversionError;
+use model::SchemaVersion;
+use std::collections::HashMap;
 
+pub use error::{BatchConversionError, ConversionErrorKind, PreprocessErrorKind};
+
+#[cfg(feature = "fs")]
 pub use batch::BatchProcessor;
+pub use converter::FileFormat;
+pub use document::SysmonDocument;
+pub use config::OverwritePolicy;
 pub use config::ProcessingOptions;
 pub use config::ProcessingOptionsBuilder;
-pub use merger::{merge_configs, merge_configs_with_progress};
-pub use model::Value;
+#[cfg(feature = "fs")]
+pub use merger::{
+    merge_config_files, merge_configs, merge_configs_report, merge_configs_with_options,
+    merge_configs_with_progress, merge_configs_with_value, merge_to_value,
+};
+pub use model::{Value, DEFAULT_SCHEMA_VERSION};
+#[cfg(feature = "fs")]
 pub use preprocessor::preprocess_config; 
+pub use preprocessor::preprocess_str;
+#[cfg(all(feature = "fs", feature = "validation"))]
+pub use validation::validate_file;
+#[cfg(feature = "validation")]
+pub use validation::{validate_str, ValidationReport};
+#[cfg(feature = "watch")]
+pub use watch::{watch_directory, WatchOptions};
+
+/// Converts Sysmon configuration content held in memory between formats.
+///
+/// XML input is validated and path-normalized by the preprocessor first,
+/// matching what `convert_file` does for files.
+pub fn convert_str(content: &str, from: FileFormat, to: FileFormat) -> Result<String, ConversionError> {
+    let converter = converter::get_converter_for_formats(from, to)?;
+    if from == FileFormat::Xml {
+        let preprocessed = preprocess_str(content)?;
+        converter.convert_str(&preprocessed)
+    } else {
+        converter.convert_str(content)
+    }
+}
+
+/// Converts a configuration read from stdin, writing the result to stdout,
+/// so the library can sit in a shell pipeline. A leading UTF-8 byte order
+/// mark is dropped.
+pub fn convert_stdio(input_format: FileFormat, output_format: FileFormat) -> Result<(), ConversionError> {
+    convert_stdio_with_options(input_format, output_format, &ProcessingOptions::default())
+}
+
+/// Converts stdin to stdout as `convert_stdio` does. Input larger than
+/// `options.max_file_size` is rejected, and `options.variables` are
+/// substituted as `convert_file_with_options` does.
+pub fn convert_stdio_with_options(
+    input_format: FileFormat,
+    output_format: FileFormat,
+    options: &ProcessingOptions,
+) -> Result<(), ConversionError> {
+    convert_stream(
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+        input_format,
+        output_format,
+        options,
+    )
+}
+
+fn convert_stream(
+    reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+    input_format: FileFormat,
+    output_format: FileFormat,
+    options: &ProcessingOptions,
+) -> Result<(), ConversionError> {
+    use std::io::Read;
+
+    let stdin = Path::new("<stdin>");
+    let mut content = String::new();
+    reader
+        .take(options.max_file_size.saturating_add(1))
+        .read_to_string(&mut content)
+        .map_err(|e| ConversionError::io_error(stdin, e))?;
+    if content.len() as u64 > options.max_file_size {
+        return Err(ConversionError::FileSizeLimitExceeded {
+            path: stdin.display().to_string(),
+            size: content.len() as u64,
+        });
+    }
+
+    let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+    let content = match &options.variables {
+        Some(variables) => {
+            substitute_variables(stdin, Some(input_format), content, variables, options.strict_variables)?
+        }
+        None => content.to_string(),
+    };
+    let converted = convert_str(&content, input_format, output_format)?;
+
+    let stdout = Path::new("<stdout>");
+    writer
+        .write_all(converted.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(|e| ConversionError::io_error(stdout, e))
+}
+
+/// Generates a minimal Sysmon config, with an empty `EventFiltering`, as XML
+/// or JSON.
+///
+/// With the `validation` feature the skeleton is validated before it is
+/// returned.
+pub fn generate_skeleton(schema_version: &str, output_format: FileFormat) -> Result<String, ConversionError> {
+    generate_skeleton_with_event_types(schema_version, &[], output_format)
+}
+
+/// Generates a skeleton as `generate_skeleton` does, with one `RuleGroup` per
+/// event type. Each group holds an empty `onmatch="include"` filter, which
+/// logs nothing until rules are added.
+pub fn generate_skeleton_with_event_types(
+    schema_version: &str,
+    event_types: &[&str],
+    format: FileFormat,
+) -> Result<String, ConversionError> {
+    let version = SchemaVersion::parse(schema_version).ok_or_else(|| {
+        ConversionError::InvalidFile(format!("Invalid schema version: {}", schema_version))
+    })?;
+
+    let mut groups = Vec::new();
+    for &name in event_types {
+        let info = schema::event_type(name)
+            .ok_or_else(|| ConversionError::InvalidFile(format!("Unknown event type: {}", name)))?;
+        if info.min_schema > version {
+            return Err(ConversionError::InvalidFile(format!(
+                "{} requires schema version {} or later",
+                info.name, info.min_schema
+            )));
+        }
+
+        let mut event = HashMap::new();
+        event.insert("@onmatch".to_string(), Value::String("include".to_string()));
+        let mut group = HashMap::new();
+        group.insert("@name".to_string(), Value::String(name.to_string()));
+        group.insert("@groupRelation".to_string(), Value::String("or".to_string()));
+        group.insert(name.to_string(), Value::Object(event));
+        groups.push(Value::Object(group));
+    }
+
+    // An empty element reads back as null, so `<EventFiltering/>` round-trips
+    let filtering = if groups.is_empty() {
+        Value::Null
+    } else {
+        Value::Object(HashMap::from([("RuleGroup".to_string(), Value::Array(groups))]))
+    };
+    let mut root = HashMap::new();
+    root.insert("@schemaversion".to_string(), Value::String(schema_version.to_string()));
+    root.insert("EventFiltering".to_string(), filtering);
+
+    let doc = SysmonDocument::from_value(Value::Object(root));
+    let content = match format {
+        FileFormat::Xml => doc.to_xml_string()?,
+        FileFormat::Json => doc.to_json_string()?,
+        other => {
+            return Err(ConversionError::InvalidFile(format!(
+                "Unsupported skeleton format: {}",
+                other.extension()
+            )))
+        }
+    };
+
+    #[cfg(feature = "validation")]
+    {
+        let report = validate_str(&content, format)?;
+        if !report.is_valid() {
+            let messages: Vec<&str> = report.errors.iter().map(|issue| issue.message.as_str()).collect();
+            return Err(ConversionError::ValidationError(messages.join("; ")));
+        }
+    }
+
+    Ok(content)
+}
+
+/// Reads a Sysmon configuration file, XML or JSON, and returns only its
+/// `EventFiltering` section.
+///
+/// The outer `Sysmon` element and its settings (`HashAlgorithms`,
+/// `DnsLookup`, and so on) are dropped, so rule sets can be combined on
+/// their own. An empty `<EventFiltering/>` gives `Value::Null`.
+#[cfg(feature = "fs")]
+pub fn extract_event_filtering(input: &Path) -> Result<Value, ConversionError> {
+    let doc = SysmonDocument::load(input)?;
+    event_filtering(&doc, &input.display().to_string())
+}
+
+/// Returns the `EventFiltering` section of configuration content held in
+/// memory, as `extract_event_filtering` does for files
+pub fn extract_event_filtering_str(content: &str, format: FileFormat) -> Result<Value, ConversionError> {
+    let doc = SysmonDocument::load_str(content, format)?;
+    event_filtering(&doc, "config")
+}
+
+fn event_filtering(doc: &SysmonDocument, label: &str) -> Result<Value, ConversionError> {
+    match doc.value() {
+        Value::Object(root) => root.get("EventFiltering").cloned(),
+        _ => None,
+    }
+    .ok_or_else(|| ConversionError::InvalidFile(format!("No EventFiltering section in {}", label)))
+}
 
 /// Converts a Sysmon configuration file between XML and JSON formats.
+#[cfg(feature = "fs")]
 pub fn convert_file(input: &Path, output: &Path) -> Result<(), ConversionError> {
+    // Only XML inputs go through the preprocessor; other formats (including
+    // ones handled by registered custom converters) are converted as-is
+    if FileFormat::from_path(input) != Some(FileFormat::Xml) {
+        let converter = converter::get_converter(input, output)?;
+        return converter.convert(input, output);
+    }
+
     // Preprocess the input file
-    let preprocessed = preprocess_config(input)
-        .map_err(|e| ConversionError::InvalidFile(format!("Preprocessing failed: {:?}", e)))?;
-    
-    // Write preprocessed content back to a temporary file
-    let temp_dir = tempfile::tempdir()?;
-    let temp_path = temp_dir.path().join(input.file_name().unwrap());
-    std::fs::write(&temp_path, preprocessed)?;
-    
-    // Get converter and process the preprocessed file
-    let converter = converter::get_converter(&temp_path, output)?;
-    converter.convert(&temp_path, output)?;
-    
-    Ok(())
+    let preprocessed = preprocess_config(input)?;
+
+    // Convert the preprocessed content in memory
+    let converter = converter::get_converter(input, output)?;
+    let converted = converter.convert_str(&preprocessed)?;
+    fs::write(output, converted).map_err(|e| ConversionError::io_error(output, e))
+}
+
+/// Converts a Sysmon configuration file, honoring the overwrite policy in `options`.
+///
+/// Inputs larger than `options.max_file_size` are rejected. When
+/// `options.create_backup` is set, an existing output is copied to a `.bak`
+/// file before it is replaced.
+///
+/// When `options.progress` is set, it is called with `(bytes_read, total_bytes)`
+/// every `options.progress_granularity` bytes while the input is read, and
+/// once more when the whole file has been read.
+///
+/// When `options.variables` is set, `{{NAME}}` placeholders in element text
+/// and attribute values (string values for JSON input) are substituted before
+/// the input is validated and converted. Placeholders without a value are
+/// left as written, or rejected when `options.strict_variables` is set.
+#[cfg(feature = "fs")]
+pub fn convert_file_with_options(
+    input: &Path,
+    output: &Path,
+    options: &ProcessingOptions,
+) -> Result<(), ConversionError> {
+    convert_file_checked(input, output, options).map(|_| ())
+}
+
+/// Does the work of `convert_file_with_options`, returning `false` when the
+/// overwrite policy skipped the input
+#[cfg(feature = "fs")]
+fn convert_file_checked(
+    input: &Path,
+    output: &Path,
+    options: &ProcessingOptions,
+) -> Result<bool, ConversionError> {
+    let size = fs::metadata(input)
+        .map_err(|e| ConversionError::io_error(input, e))?
+        .len();
+    if size > options.max_file_size {
+        return Err(ConversionError::FileSizeLimitExceeded {
+            path: input.display().to_string(),
+            size,
+        });
+    }
+
+    if !prepare_output(output, options.overwrite_policy)? {
+        return Ok(false);
+    }
+    if options.create_backup && output.exists() {
+        fs::copy(output, output.with_extension("bak"))
+            .map_err(|e| ConversionError::io_error(output, e))?;
+    }
+    convert_prepared(input, output, options)?;
+    Ok(true)
+}
+
+/// Converts a file whose output has been cleared by the overwrite policy
+#[cfg(feature = "fs")]
+fn convert_prepared(
+    input: &Path,
+    output: &Path,
+    options: &ProcessingOptions,
+) -> Result<(), ConversionError> {
+    if options.progress.is_none() && options.variables.is_none() {
+        return convert_file(input, output);
+    }
+
+    let content = match &options.progress {
+        Some(progress) => read_with_progress(input, progress, options.progress_granularity)?,
+        None => fs::read_to_string(input).map_err(|e| ConversionError::io_error(input, e))?,
+    };
+    let content = match &options.variables {
+        Some(variables) => substitute_variables(
+            input,
+            FileFormat::from_path(input),
+            &content,
+            variables,
+            options.strict_variables,
+        )?,
+        None => content,
+    };
+    let content = if FileFormat::from_path(input) == Some(FileFormat::Xml) {
+        preprocess_str(&content)?
+    } else {
+        content
+    };
+    let converted = converter::get_converter(input, output)?.convert_str(&content)?;
+    fs::write(output, converted).map_err(|e| ConversionError::io_error(output, e))
+}
+
+/// Substitutes template placeholders in XML or JSON content. `input` names
+/// the content in errors.
+fn substitute_variables(
+    input: &Path,
+    format: Option<FileFormat>,
+    content: &str,
+    variables: &std::collections::HashMap<String, String>,
+    strict: bool,
+) -> Result<String, ConversionError> {
+    use preprocessor::{PreprocessStep, TemplateSubstitutionStep};
+
+    match format {
+        Some(FileFormat::Xml) => TemplateSubstitutionStep::new(variables.clone(), strict)
+            .process(content)
+            .map_err(|e| e.in_file(input).into()),
+        Some(FileFormat::Json) => {
+            let mut value: serde_json::Value = serde_json::from_str(content)?;
+            preprocessor::substitute_json(&mut value, variables, strict)
+                .map_err(|e| e.in_file(input))?;
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        _ => Err(ConversionError::InvalidFile(format!(
+            "Template variables are only supported for XML and JSON input: {}",
+            input.display()
+        ))),
+    }
+}
+
+/// Reads a file as UTF-8 in chunks, reporting the bytes read so far
+#[cfg(feature = "fs")]
+fn read_with_progress(
+    path: &Path,
+    progress: &config::ProgressHook,
+    granularity: u64,
+) -> Result<String, ConversionError> {
+    use std::io::Read;
+
+    let mut file = fs::File::open(path).map_err(|e| ConversionError::io_error(path, e))?;
+    let total = file
+        .metadata()
+        .map_err(|e| ConversionError::io_error(path, e))?
+        .len();
+
+    let mut content = Vec::with_capacity(total as usize);
+    let mut buf = [0u8; 64 * 1024];
+    let mut last_reported = 0;
+    loop {
+        let read = file.read(&mut buf).map_err(|e| ConversionError::io_error(path, e))?;
+        if read == 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..read]);
+        let bytes_read = content.len() as u64;
+        if bytes_read - last_reported >= granularity.max(1) {
+            progress.call(bytes_read, total.max(bytes_read));
+            last_reported = bytes_read;
+        }
+    }
+
+    let bytes_read = content.len() as u64;
+    if last_reported != bytes_read || bytes_read == 0 {
+        progress.call(bytes_read, bytes_read);
+    }
+
+    String::from_utf8(content).map_err(|e| {
+        let error = std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        ConversionError::io_error(path, error)
+    })
+}
+
+/// Applies the overwrite policy to an output path.
+///
+/// Returns `false` when the existing output should be left alone and the
+/// input skipped.
+#[cfg(feature = "fs")]
+pub(crate) fn prepare_output(output: &Path, policy: OverwritePolicy) -> Result<bool, ConversionError> {
+    match plan_output(output, policy) {
+        PlannedAction::Convert | PlannedAction::Overwrite => Ok(true),
+        PlannedAction::Skip => Ok(false),
+        PlannedAction::Backup => {
+            let backup_path = output.with_extension("bak");
+            fs::rename(output, &backup_path)
+                .map_err(|e| ConversionError::io_error(output, e))?;
+            Ok(true)
+        }
+        PlannedAction::Error => Err(ConversionError::OutputExists {
+            path: output.to_path_buf(),
+        }),
+    }
+}
+
+/// Decides what the overwrite policy does with an output path, without
+/// touching the disk
+#[cfg(feature = "fs")]
+fn plan_output(output: &Path, policy: OverwritePolicy) -> PlannedAction {
+    if !output.exists() {
+        return PlannedAction::Convert;
+    }
+
+    match policy {
+        OverwritePolicy::Overwrite => PlannedAction::Overwrite,
+        OverwritePolicy::Skip => PlannedAction::Skip,
+        OverwritePolicy::Backup => PlannedAction::Backup,
+        OverwritePolicy::Error => PlannedAction::Error,
+    }
+}
+
+/// What `convert_folder_with_options` would do with one input file
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// Convert to an output that does not exist yet
+    Convert,
+    /// Convert, replacing the existing output
+    Overwrite,
+    /// Rename the existing output to `.bak`, then convert
+    Backup,
+    /// Leave the input alone (unsupported extension or overwrite policy)
+    Skip,
+    /// Fail, because the output exists and the policy is `Error`
+    Error,
+}
+
+/// A single file in a `ConversionPlan`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedConversion {
+    pub input: PathBuf,
+    /// Output that would be written; `None` for unsupported inputs
+    pub output: Option<PathBuf>,
+    pub action: PlannedAction,
+}
+
+/// Outcome of `convert_folder_plan`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct ConversionPlan {
+    pub entries: Vec<PlannedConversion>,
+}
+
+#[cfg(feature = "fs")]
+impl ConversionPlan {
+    /// Entries with the given action
+    pub fn with_action(&self, action: PlannedAction) -> impl Iterator<Item = &PlannedConversion> {
+        self.entries.iter().filter(move |entry| entry.action == action)
+    }
+}
+
+/// Summary of a `convert_folder` run
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct FolderConversionReport {
+    /// Number of files converted
+    pub converted: usize,
+    /// Number of files skipped (unsupported extension or overwrite policy)
+    pub skipped: usize,
+    /// Files that failed to convert, with the reason
+    pub failed: Vec<(PathBuf, ConversionError)>,
+    /// Time taken by the whole run
+    pub duration: Duration,
+}
+
+#[cfg(feature = "fs")]
+impl FolderConversionReport {
+    /// Returns true when no file failed to convert
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Turns per-file failures into a `ConversionError::BatchConversionError`,
+    /// returning the report unchanged when every file succeeded
+    pub fn into_result(mut self) -> Result<Self, ConversionError> {
+        if self.failed.is_empty() {
+            return Ok(self);
+        }
+        let failed = std::mem::take(&mut self.failed);
+        Err(ConversionError::BatchConversionError(failed.into()))
+    }
 }
 
 /// Converts all Sysmon configuration files in a directory between XML and JSON formats.
-pub fn convert_folder(input_dir: &Path, output_dir: &Path) -> Result<(), ConversionError> {
+///
+/// Per-file failures are recorded in the returned report; an `Err` is only
+/// returned when the directory itself cannot be processed.
+#[cfg(feature = "fs")]
+pub fn convert_folder(input_dir: &Path, output_dir: &Path) -> Result<FolderConversionReport, ConversionError> {
+    convert_folder_with_options(input_dir, output_dir, &ProcessingOptions::default())
+}
+
+/// Converts all Sysmon configuration files in a directory, converting each
+/// one as `convert_file_with_options` does. Files whose name contains one of
+/// `options.ignore_patterns` are skipped.
+///
+/// Use `convert_folder_plan` to see what a run would do first.
+#[cfg(feature = "fs")]
+pub fn convert_folder_with_options(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &ProcessingOptions,
+) -> Result<FolderConversionReport, ConversionError> {
+    let start_time = Instant::now();
+    let inputs = folder_inputs(input_dir, options)?;
+
+    // Create output directory if it doesn't exist
+    fs::create_dir_all(output_dir)
+        .map_err(|e| ConversionError::io_error(output_dir, e))?;
+
+    let mut report = FolderConversionReport::default();
+
+    for path in inputs {
+        let Some(output_path) = folder_output_path(&path, output_dir, options) else {
+            report.skipped += 1;
+            continue;
+        };
+
+        match convert_file_checked(&path, &output_path, options) {
+            Ok(true) => report.converted += 1,
+            Ok(false) => report.skipped += 1,
+            Err(e) => report.failed.push((path, e)),
+        }
+    }
+
+    report.duration = start_time.elapsed();
+    Ok(report)
+}
+
+/// Lists what `convert_folder_with_options` would do with each file in
+/// `input_dir`, without reading the inputs or writing anything.
+///
+/// Files are selected and outputs named with the same code as the real run,
+/// and the overwrite policy is applied to the outputs that exist now.
+#[cfg(feature = "fs")]
+pub fn convert_folder_plan(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &ProcessingOptions,
+) -> Result<ConversionPlan, ConversionError> {
+    let entries = folder_inputs(input_dir, options)?
+        .into_iter()
+        .map(|input| {
+            let output = folder_output_path(&input, output_dir, options);
+            let action = match &output {
+                Some(output) => plan_output(output, options.overwrite_policy),
+                None => PlannedAction::Skip,
+            };
+            PlannedConversion { input, output, action }
+        })
+        .collect();
+    Ok(ConversionPlan { entries })
+}
+
+/// Lists the files directly inside a folder to convert
+#[cfg(feature = "fs")]
+fn folder_inputs(input_dir: &Path, options: &ProcessingOptions) -> Result<Vec<PathBuf>, ConversionError> {
     if !input_dir.is_dir() {
         return Err(ConversionError::InvalidFile(
             format!("Input path is not a directory: {}", input_dir.display())
         ));
     }
+    fs::read_dir(input_dir).map_err(|e| ConversionError::io_error(input_dir, e))?;
 
-    // Create output directory if it doesn't exist
-    fs::create_dir_all(output_dir)?;
+    Ok(walk_files(input_dir, false, options)
+        .into_iter()
+        .map(DirEntry::into_path)
+        .collect())
+}
 
-    let entries = fs::read_dir(input_dir)?;
-    let mut conversion_errors = Vec::new();
+/// Lists the files under `dir` in file name order, descending into linked
+/// directories and visiting hidden entries only when `options` asks for it.
+/// Links to files are listed either way. Entries that cannot be read,
+/// including circular links, are skipped with a warning.
+#[cfg(feature = "fs")]
+pub(crate) fn walk_files(dir: &Path, recursive: bool, options: &ProcessingOptions) -> Vec<DirEntry> {
+    walk_dir(dir, recursive, options.follow_symlinks, options.include_hidden)
+}
 
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
+/// `walk_files` for callers that keep `follow_symlinks` and
+/// `include_hidden` in their own options
+#[cfg(feature = "fs")]
+pub(crate) fn walk_dir(dir: &Path, recursive: bool, follow_symlinks: bool, include_hidden: bool) -> Vec<DirEntry> {
+    let mut walker = WalkDir::new(dir).follow_links(follow_symlinks).sort_by_file_name();
+    if !recursive {
+        walker = walker.max_depth(1);
+    }
 
-        // Skip if not a file
-        if !path.is_file() {
-            continue;
-        }
+    walker
+        .into_iter()
+        .filter_entry(move |e| include_hidden || e.depth() == 0 || !is_hidden(e))
+        .filter_map(|entry| entry.map_err(|e| log::warn!("Skipping unreadable entry: {}", e)).ok())
+        .filter(|e| e.file_type().is_file() || (e.path_is_symlink() && e.path().is_file()))
+        .collect()
+}
 
-        // Get file extension
-        let ext = match path.extension().and_then(|e| e.to_str()) {
-            Some(ext) => ext.to_lowercase(),
-            None => continue, // Skip files without extension
-        };
+/// Hidden entries (`.git`, `.vscode`, ...) and dependency directories that
+/// never hold configs of their own
+#[cfg(feature = "fs")]
+fn is_hidden(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    name.starts_with('.') || (entry.file_type().is_dir() && name == "node_modules")
+}
 
-        // Only process xml or json files
-        if ext != "xml" && ext != "json" {
-            continue;
+/// Gets the output path for a folder input: the same name in `output_dir`,
+/// with the opposite extension. `None` for anything but XML and JSON, and
+/// for files matching `options.ignore_patterns`.
+#[cfg(feature = "fs")]
+fn folder_output_path(input: &Path, output_dir: &Path, options: &ProcessingOptions) -> Option<PathBuf> {
+    let name = input.file_name()?.to_str()?;
+    if let Some(patterns) = &options.ignore_patterns {
+        if patterns.iter().any(|p| name.contains(p.as_str())) {
+            return None;
         }
+    }
 
-        // Create output path with opposite extension
-        let new_ext = if ext == "xml" { "json" } else { "xml" };
-        let file_name = path.file_name().unwrap();
-        let new_name = Path::new(file_name)
-            .with_extension(new_ext);
-        let output_path = output_dir.join(new_name);
+    let ext = input.extension()?.to_str()?.to_lowercase();
+    let new_ext = match ext.as_str() {
+        "xml" => "json",
+        "json" => "xml",
+        _ => return None,
+    };
+    let file_name = input.file_name()?;
+    Some(output_dir.join(Path::new(file_name).with_extension(new_ext)))
+}
 
-        // Convert the file
-        if let Err(e) = convert_file(&path, &output_path) {
-            conversion_errors.push((path.clone(), e));
+/// A filter rule listed in a `ComparisonReport`, as written in its config
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSummary {
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+    /// `include` or `exclude`, if set on the event filter
+    pub onmatch: Option<String>,
+}
+
+#[cfg(feature = "fs")]
+impl fmt::Display for RuleSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} {} {}",
+            self.event_type,
+            self.onmatch.as_deref().unwrap_or("-"),
+            self.field,
+            self.condition,
+            self.value
+        )
+    }
+}
+
+/// Outcome of `compare_configs`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComparisonReport {
+    pub schema_version_a: String,
+    pub schema_version_b: String,
+    pub rules_only_in_a: Vec<RuleSummary>,
+    pub rules_only_in_b: Vec<RuleSummary>,
+    /// Number of distinct rules found in both configs
+    pub rules_in_both: usize,
+    /// Event types with filters in the first config, sorted
+    pub coverage_a: Vec<String>,
+    /// Event types with filters in the second config, sorted
+    pub coverage_b: Vec<String>,
+}
+
+#[cfg(feature = "fs")]
+impl ComparisonReport {
+    /// Returns true when both configs have the same rules
+    pub fn is_identical(&self) -> bool {
+        self.rules_only_in_a.is_empty() && self.rules_only_in_b.is_empty()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Schema version: {} -> {}", self.schema_version_a, self.schema_version_b)?;
+        writeln!(f, "Rules in both: {}", self.rules_in_both)?;
+        for (label, rules) in [("Removed", &self.rules_only_in_a), ("Added", &self.rules_only_in_b)] {
+            writeln!(f, "{} rules: {}", label, rules.len())?;
+            for rule in rules {
+                writeln!(f, "  {}", rule)?;
+            }
         }
+        write!(
+            f,
+            "Event types: {} -> {}",
+            self.coverage_a.join(", "),
+            self.coverage_b.join(", ")
+        )
     }
+}
+
+/// Compares the filter rules of two Sysmon configs, XML or JSON.
+///
+/// Rules are matched the way `ConfigMerger` dedupes them: by event type,
+/// `onmatch`, field, condition, and value, with conditions and values
+/// compared ignoring case and values after path normalization. Rules
+/// repeated within one config are listed once.
+#[cfg(feature = "fs")]
+pub fn compare_configs(a: &Path, b: &Path) -> Result<ComparisonReport, ConversionError> {
+    let a = SysmonDocument::load(a)?;
+    let b = SysmonDocument::load(b)?;
+    let (keys_a, rules_a) = comparison_rules(&a);
+    let (keys_b, rules_b) = comparison_rules(&b);
 
-    // If there were any errors, return them as part of the error
-    if !conversion_errors.is_empty() {
-        let error_msg = conversion_errors
+    let only_in = |rules: Vec<(merger::FilterKey, RuleSummary)>, other: &HashSet<merger::FilterKey>| {
+        rules
             .into_iter()
-            .map(|(path, err)| format!("{}: {}", path.display(), err))
+            .filter(|(key, _)| !other.contains(key))
+            .map(|(_, rule)| rule)
             .collect::<Vec<_>>()
-            .join("\n");
-        
-        return Err(ConversionError::BatchConversionError(error_msg));
+    };
+    let schema_version =
+        |doc: &SysmonDocument| doc.schema_version().unwrap_or(DEFAULT_SCHEMA_VERSION).to_string();
+
+    Ok(ComparisonReport {
+        schema_version_a: schema_version(&a),
+        schema_version_b: schema_version(&b),
+        rules_in_both: keys_a.intersection(&keys_b).count(),
+        coverage_a: comparison_coverage(&rules_a),
+        coverage_b: comparison_coverage(&rules_b),
+        rules_only_in_a: only_in(rules_a, &keys_b),
+        rules_only_in_b: only_in(rules_b, &keys_a),
+    })
+}
+
+/// Gets the distinct rules of a document, keyed as `ConfigMerger` dedupes them
+#[cfg(feature = "fs")]
+fn comparison_rules(
+    doc: &SysmonDocument,
+) -> (HashSet<merger::FilterKey>, Vec<(merger::FilterKey, RuleSummary)>) {
+    let mut keys = HashSet::new();
+    let mut rules = Vec::new();
+    for rule in doc.rules() {
+        let onmatch = rule.onmatch.as_deref().unwrap_or_default();
+        let key = merger::FilterKey::new(&rule.event_type, onmatch, &rule.field, &rule.condition, &rule.value);
+        if keys.insert(key.clone()) {
+            rules.push((
+                key,
+                RuleSummary {
+                    event_type: rule.event_type,
+                    field: rule.field,
+                    condition: rule.condition,
+                    value: rule.value,
+                    onmatch: rule.onmatch,
+                },
+            ));
+        }
     }
+    (keys, rules)
+}
 
-    Ok(())
+#[cfg(feature = "fs")]
+fn comparison_coverage(rules: &[(merger::FilterKey, RuleSummary)]) -> Vec<String> {
+    let mut event_types: Vec<String> = rules.iter().map(|(_, rule)| rule.event_type.clone()).collect();
+    event_types.sort();
+    event_types.dedup();
+    event_types
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "fs"))]
 mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    fn convert_over_existing(policy: OverwritePolicy) -> (tempfile::TempDir, Result<(), ConversionError>) {
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("test.xml");
+        let output_path = temp_dir.path().join("test.json");
+
+        fs::write(&input_path, VALID_XML).unwrap();
+        fs::write(&output_path, "manual edits").unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .overwrite_policy(policy)
+            .build();
+        let result = convert_file_with_options(&input_path, &output_path, &options);
+        (temp_dir, result)
+    }
+
+    #[test]
+    fn test_overwrite_policy_overwrite() {
+        let (temp_dir, result) = convert_over_existing(OverwritePolicy::Overwrite);
+        assert!(result.is_ok());
+        let output = fs::read_to_string(temp_dir.path().join("test.json")).unwrap();
+        assert!(output.contains("test.exe"));
+    }
+
+    #[test]
+    fn test_overwrite_policy_skip() {
+        let (temp_dir, result) = convert_over_existing(OverwritePolicy::Skip);
+        assert!(result.is_ok());
+        let output = fs::read_to_string(temp_dir.path().join("test.json")).unwrap();
+        assert_eq!(output, "manual edits");
+    }
+
+    #[test]
+    fn test_overwrite_policy_backup() {
+        let (temp_dir, result) = convert_over_existing(OverwritePolicy::Backup);
+        assert!(result.is_ok());
+        let backup = fs::read_to_string(temp_dir.path().join("test.bak")).unwrap();
+        assert_eq!(backup, "manual edits");
+        let output = fs::read_to_string(temp_dir.path().join("test.json")).unwrap();
+        assert!(output.contains("test.exe"));
+    }
+
+    #[test]
+    fn test_overwrite_policy_error() {
+        let (temp_dir, result) = convert_over_existing(OverwritePolicy::Error);
+        assert!(matches!(result, Err(ConversionError::OutputExists { .. })));
+        let output = fs::read_to_string(temp_dir.path().join("test.json")).unwrap();
+        assert_eq!(output, "manual edits");
+    }
+
+    #[test]
+    fn test_convert_file_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("test.xml");
+        let output_path = temp_dir.path().join("test.json");
+        let padding = " ".repeat(320_000);
+        let xml = VALID_XML.replace("<EventFiltering>", &format!("{}<EventFiltering>", padding));
+        fs::write(&input_path, &xml).unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&calls);
+        let options = ProcessingOptionsBuilder::new()
+            .progress(move |read, total| recorded.lock().unwrap().push((read, total)))
+            .progress_granularity(64 * 1024)
+            .build();
+        convert_file_with_options(&input_path, &output_path, &options).unwrap();
+
+        let calls = calls.lock().unwrap();
+        let total = xml.len() as u64;
+        assert!(calls.len() > 1, "{:?}", calls);
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(calls.iter().all(|&(_, t)| t == total));
+        assert_eq!(calls.last(), Some(&(total, total)));
+        assert!(fs::read_to_string(&output_path).unwrap().contains("test.exe"));
+    }
+
+    #[test]
+    fn test_convert_file_size_limit_and_backup() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("test.xml");
+        let output = temp_dir.path().join("test.json");
+        fs::write(&input, VALID_XML).unwrap();
+        fs::write(&output, "previous").unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .max_file_size(16)
+            .build();
+        let result = convert_file_with_options(&input, &output, &options);
+        assert!(matches!(result, Err(ConversionError::FileSizeLimitExceeded { .. })));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "previous");
+
+        // An output the policy skips is not backed up
+        let options = ProcessingOptionsBuilder::new()
+            .overwrite_policy(OverwritePolicy::Skip)
+            .create_backup(true)
+            .build();
+        convert_file_with_options(&input, &output, &options).unwrap();
+        assert!(!output.with_extension("bak").exists());
+
+        let options = ProcessingOptionsBuilder::new()
+            .create_backup(true)
+            .build();
+        convert_file_with_options(&input, &output, &options).unwrap();
+        assert_eq!(fs::read_to_string(output.with_extension("bak")).unwrap(), "previous");
+        assert!(fs::read_to_string(&output).unwrap().contains("ProcessCreate"));
+    }
+
+    #[test]
+    fn test_convert_folder_ignore_patterns() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&input_dir).unwrap();
+        fs::write(input_dir.join("kept.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("draft-ignored.xml"), VALID_XML).unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .ignore_patterns(Some(vec!["draft".to_string()]))
+            .build();
+        let plan = convert_folder_plan(&input_dir, &output_dir, &options).unwrap();
+        assert_eq!(plan.with_action(PlannedAction::Skip).count(), 1);
+
+        let report = convert_folder_with_options(&input_dir, &output_dir, &options).unwrap();
+        assert_eq!((report.converted, report.skipped), (1, 1));
+        assert!(output_dir.join("kept.json").exists());
+        assert!(!output_dir.join("draft-ignored.json").exists());
+    }
+
+    #[test]
+    fn test_extract_event_filtering() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("config.xml");
+        fs::write(&input, VALID_XML).unwrap();
+
+        let filtering = serde_json::to_value(extract_event_filtering(&input).unwrap()).unwrap();
+        assert_eq!(filtering["RuleGroup"]["@name"], "test");
+
+        let missing = temp_dir.path().join("missing.xml");
+        assert!(extract_event_filtering(&missing).is_err());
+    }
+
+    #[test]
+    fn test_compare_configs() {
+        let temp_dir = tempdir().unwrap();
+        let a = temp_dir.path().join("a.xml");
+        let b = temp_dir.path().join("b.json");
+        fs::write(&a, VALID_XML).unwrap();
+        let json = serde_json::json!({
+            "Sysmon": {
+                "@schemaversion": "4.50",
+                "EventFiltering": {
+                    "RuleGroup": {
+                        "@name": "test",
+                        "@groupRelation": "or",
+                        "ProcessCreate": {
+                            "@onmatch": "include",
+                            "Image": { "@condition": "IS", "$text": "c:/windows/system32/TEST.EXE" }
+                        },
+                        "NetworkConnect": {
+                            "@onmatch": "include",
+                            "DestinationPort": { "@condition": "is", "$text": "4444" }
+                        }
+                    }
+                }
+            }
+        });
+        fs::write(&b, json.to_string()).unwrap();
+
+        let report = compare_configs(&a, &b).unwrap();
+        assert_eq!(report.schema_version_a, "4.30");
+        assert_eq!(report.schema_version_b, "4.50");
+        assert_eq!(report.rules_in_both, 1);
+        assert!(report.rules_only_in_a.is_empty());
+        assert_eq!(
+            report.rules_only_in_b,
+            vec![RuleSummary {
+                event_type: "NetworkConnect".to_string(),
+                field: "DestinationPort".to_string(),
+                condition: "is".to_string(),
+                value: "4444".to_string(),
+                onmatch: Some("include".to_string()),
+            }]
+        );
+        assert_eq!(report.coverage_a, vec!["ProcessCreate"]);
+        assert_eq!(report.coverage_b, vec!["NetworkConnect", "ProcessCreate"]);
+        assert!(!report.is_identical());
+
+        let text = report.to_string();
+        assert!(text.contains("Schema version: 4.30 -> 4.50"), "{}", text);
+        assert!(text.contains("NetworkConnect (include): DestinationPort is 4444"), "{}", text);
+
+        assert!(compare_configs(&a, &a).unwrap().is_identical());
+    }
+
+    #[test]
+    fn test_convert_file_template_variables() {
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("template.xml");
+        let output_path = temp_dir.path().join("template.json");
+        let xml = VALID_XML.replace(r"C:\Windows\System32\test.exe", r"{{CORP_TOOLS_DIR}}\{{AGENT}}");
+        assert!(xml.contains("{{AGENT}}"));
+        fs::write(&input_path, &xml).unwrap();
+
+        let mut variables = std::collections::HashMap::new();
+        variables.insert("CORP_TOOLS_DIR".to_string(), r"C:\Tools".to_string());
+        variables.insert("AGENT".to_string(), "agent.exe".to_string());
+        let options = ProcessingOptionsBuilder::new()
+            .variables(Some(variables.clone()))
+            .strict_variables(true)
+            .build();
+        convert_file_with_options(&input_path, &output_path, &options).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output_path).unwrap()).unwrap();
+        let output = json.to_string();
+        assert!(output.contains(r"C:\\tools\\agent.exe"), "{}", output);
+        assert!(!output.contains("{{"));
+
+        // A missing value is an error in strict mode
+        fs::remove_file(&output_path).unwrap();
+        variables.remove("AGENT");
+        let options = ProcessingOptionsBuilder::new()
+            .variables(Some(variables))
+            .strict_variables(true)
+            .build();
+        let err = convert_file_with_options(&input_path, &output_path, &options).unwrap_err();
+        assert!(err.to_string().contains("AGENT"), "{}", err);
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_convert_file_preprocess_error_message() {
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("invalid_rules.xml");
+        let output_path = temp_dir.path().join("invalid_rules.json");
+        let xml = VALID_XML.replace(r#"onmatch="include""#, r#"onmatch="invalid_match_type""#);
+        fs::write(&input_path, &xml).unwrap();
+
+        let expected = match sysmon_validator::parse_sysmon_config_from_str(&xml) {
+            Ok(config) => sysmon_validator::validate_sysmon_config(&config).unwrap_err().to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        let err = convert_file(&input_path, &output_path).unwrap_err();
+        assert_eq!(err.source_path(), Some(input_path.as_path()));
+        let message = err.to_string();
+        assert!(message.contains(&expected), "{}", message);
+        assert!(message.contains("invalid_rules.xml"), "{}", message);
+        assert!(!message.contains("ValidationError("), "{}", message);
+        assert!(!message.contains("ParserError("), "{}", message);
+    }
+
     #[test]
     fn test_convert_file() {
         let temp_dir = tempdir().unwrap();
@@ -134,9 +1111,272 @@ mod tests {
             "<root><test>value2</test></root>"
         ).unwrap();
 
-        let result = convert_folder(&input_dir, &output_dir);
-        assert!(result.is_ok());
+        let report = convert_folder(&input_dir, &output_dir).unwrap();
+        assert_eq!(report.converted, 2);
+        assert!(report.is_success());
         assert!(output_dir.join("test1.json").exists());
         assert!(output_dir.join("test2.json").exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_convert_folder_report_counts() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+
+        fs::create_dir(&input_dir).unwrap();
+        fs::write(input_dir.join("valid.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("invalid.xml"), "<Sysmon><Broken>").unwrap();
+        fs::write(input_dir.join("notes.txt"), "not a config").unwrap();
+        fs::write(input_dir.join("README"), "no extension").unwrap();
+
+        let report = convert_folder(&input_dir, &output_dir).unwrap();
+        assert_eq!(report.converted, 1);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].0.ends_with("invalid.xml"));
+        assert!(output_dir.join("valid.json").exists());
+    }
+
+    #[test]
+    fn test_convert_folder_structured_errors() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+
+        fs::create_dir(&input_dir).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(input_dir.join("kept.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("fresh.xml"), VALID_XML).unwrap();
+        fs::write(output_dir.join("kept.json"), "manual edits").unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .overwrite_policy(OverwritePolicy::Error)
+            .build();
+        let report = convert_folder_with_options(&input_dir, &output_dir, &options).unwrap();
+        assert_eq!(report.converted, 1);
+
+        match report.into_result() {
+            Err(ConversionError::BatchConversionError(batch)) => {
+                let error = batch.error_for(&input_dir.join("kept.xml")).unwrap();
+                assert!(matches!(error, ConversionError::OutputExists { .. }));
+                assert!(batch.error_for(&input_dir.join("fresh.xml")).is_none());
+            }
+            other => panic!("Expected BatchConversionError, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_folder_plan_matches_run() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+
+        fs::create_dir(&input_dir).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(input_dir.join("kept.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("fresh.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join("notes.txt"), "not a config").unwrap();
+        fs::write(output_dir.join("kept.json"), "manual edits").unwrap();
+
+        let options = ProcessingOptionsBuilder::new()
+            .overwrite_policy(OverwritePolicy::Skip)
+            .build();
+        let mut plan = convert_folder_plan(&input_dir, &output_dir, &options).unwrap();
+        plan.entries.sort_by(|a, b| a.input.cmp(&b.input));
+
+        // Planning leaves the disk alone
+        assert!(!output_dir.join("fresh.json").exists());
+
+        let actions: Vec<_> = plan
+            .entries
+            .iter()
+            .map(|e| (e.input.file_name().unwrap().to_str().unwrap(), e.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("fresh.xml", PlannedAction::Convert),
+                ("kept.xml", PlannedAction::Skip),
+                ("notes.txt", PlannedAction::Skip),
+            ]
+        );
+        assert_eq!(plan.entries[2].output, None);
+
+        let report = convert_folder_with_options(&input_dir, &output_dir, &options).unwrap();
+        assert_eq!(report.converted, plan.with_action(PlannedAction::Convert).count());
+        assert_eq!(report.skipped, plan.with_action(PlannedAction::Skip).count());
+
+        let mut produced: Vec<_> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|path| fs::read_to_string(path).unwrap() != "manual edits")
+            .collect();
+        produced.sort();
+        let planned: Vec<_> = plan
+            .with_action(PlannedAction::Convert)
+            .filter_map(|e| e.output.clone())
+            .collect();
+        assert_eq!(produced, planned);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_convert_folder_hidden_and_symlinked_files() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&input_dir).unwrap();
+        fs::write(input_dir.join("a.xml"), VALID_XML).unwrap();
+        fs::write(input_dir.join(".hidden.xml"), VALID_XML).unwrap();
+        fs::write(temp_dir.path().join("shared.xml"), VALID_XML).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path().join("shared.xml"), input_dir.join("linked.xml")).unwrap();
+
+        let planned = |options: &ProcessingOptions| {
+            let plan = convert_folder_plan(&input_dir, &output_dir, options).unwrap();
+            let mut names: Vec<_> = plan
+                .entries
+                .iter()
+                .map(|e| e.input.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // A link to a file is converted without following links
+        let defaults = ProcessingOptions::default();
+        assert_eq!(planned(&defaults), vec!["a.xml", "linked.xml"]);
+        let report = convert_folder_with_options(&input_dir, &output_dir, &defaults).unwrap();
+        assert_eq!(report.converted, 2);
+        assert!(output_dir.join("linked.json").exists());
+
+        let options = ProcessingOptionsBuilder::new()
+            .follow_symlinks(true)
+            .include_hidden(true)
+            .build();
+        assert_eq!(planned(&options), vec![".hidden.xml", "a.xml", "linked.xml"]);
+        let report = convert_folder_with_options(&input_dir, &output_dir, &options).unwrap();
+        assert_eq!(report.converted, 3);
+    }
+
+    #[test]
+    fn test_convert_folder_missing_input() {
+        let temp_dir = tempdir().unwrap();
+        let result = convert_folder(&temp_dir.path().join("missing"), temp_dir.path());
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod str_tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_skeleton() {
+        let xml = generate_skeleton("4.90", FileFormat::Xml).unwrap();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?><Sysmon schemaversion="4.90"><EventFiltering/></Sysmon>"#
+        );
+
+        let json = generate_skeleton("4.90", FileFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "Sysmon": { "@schemaversion": "4.90", "EventFiltering": null } })
+        );
+        let doc = SysmonDocument::load_str(&json, FileFormat::Json).unwrap();
+        assert_eq!(doc.to_xml_string().unwrap(), xml);
+
+        let xml = generate_skeleton_with_event_types("4.90", &["ProcessCreate", "DnsQuery"], FileFormat::Xml)
+            .unwrap();
+        let doc = SysmonDocument::load_str(&xml, FileFormat::Xml).unwrap();
+        assert!(doc.rules().is_empty());
+        assert!(xml.contains(r#"<RuleGroup groupRelation="or" name="ProcessCreate"><ProcessCreate onmatch="include">"#));
+        assert!(xml.contains(r#"name="DnsQuery""#));
+
+        assert!(generate_skeleton("four", FileFormat::Xml).is_err());
+        assert!(generate_skeleton_with_event_types("4.90", &["NotAnEvent"], FileFormat::Xml).is_err());
+        // DnsQuery needs schema 4.21
+        assert!(generate_skeleton_with_event_types("4.00", &["DnsQuery"], FileFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_extract_event_filtering_str() {
+        let xml = r#"<Sysmon schemaversion="4.50">
+            <HashAlgorithms>md5,sha256</HashAlgorithms>
+            <EventFiltering>
+                <RuleGroup name="test" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">cmd.exe</Image>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#;
+        let filtering = extract_event_filtering_str(xml, FileFormat::Xml).unwrap();
+        let json = serde_json::to_value(&filtering).unwrap();
+        assert_eq!(json["RuleGroup"]["ProcessCreate"]["Image"]["$text"], "cmd.exe");
+        assert!(json.get("HashAlgorithms").is_none());
+        assert!(json.get("@schemaversion").is_none());
+
+        let wrapped = serde_json::json!({ "Sysmon": { "@schemaversion": "4.50", "EventFiltering": json } });
+        let from_json = extract_event_filtering_str(&wrapped.to_string(), FileFormat::Json).unwrap();
+        assert_eq!(serde_json::to_value(&from_json).unwrap(), json);
+
+        let skeleton = generate_skeleton("4.90", FileFormat::Xml).unwrap();
+        assert!(matches!(extract_event_filtering_str(&skeleton, FileFormat::Xml).unwrap(), Value::Null));
+
+        let error = extract_event_filtering_str(r#"{"@schemaversion": "4.50"}"#, FileFormat::Json).unwrap_err();
+        assert!(error.to_string().contains("No EventFiltering"), "{}", error);
+    }
+
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">c:/windows/system32/test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_convert_str_round_trip() {
+        let json = convert_str(VALID_XML, FileFormat::Xml, FileFormat::Json).unwrap();
+        assert!(json.contains(r#"C:\\windows\\system32\\test.exe"#));
+
+        let xml = convert_str(&json, FileFormat::Json, FileFormat::Xml).unwrap();
+        assert!(xml.contains(r"C:\windows\system32\test.exe"));
+    }
+
+    #[test]
+    fn test_string_pipeline_without_files() {
+        let preprocessed = preprocess_str(VALID_XML).unwrap();
+        let value = converter::xml::xml_to_value(&preprocessed).unwrap();
+        let xml = converter::json::value_to_xml(&value).unwrap();
+        assert!(xml.contains("ProcessCreate"));
+    }
+
+    #[test]
+    fn test_convert_stream() {
+        let input = format!("\u{feff}{}", VALID_XML);
+        let mut output = Vec::new();
+        let options = ProcessingOptions::default();
+        convert_stream(input.as_bytes(), &mut output, FileFormat::Xml, FileFormat::Json, &options).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert!(json.get("Sysmon").is_some());
+
+        let options = ProcessingOptionsBuilder::new().max_file_size(16).build();
+        let result = convert_stream(VALID_XML.as_bytes(), Vec::new(), FileFormat::Xml, FileFormat::Json, &options);
+        assert!(matches!(result, Err(ConversionError::FileSizeLimitExceeded { .. })));
+
+        let result = convert_stream(&b"\xff\xfe"[..], Vec::new(), FileFormat::Xml, FileFormat::Json, &ProcessingOptions::default());
+        assert!(matches!(result, Err(ConversionError::Io { ref path, .. }) if path == Path::new("<stdin>")));
+    }
+
+    #[test]
+    fn test_convert_str_unsupported_format() {
+        let result = convert_str(VALID_XML, FileFormat::Xml, FileFormat::Yaml);
+        assert!(matches!(result, Err(ConversionError::InvalidFile(_))));
+    }
+}
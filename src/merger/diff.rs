@@ -0,0 +1,311 @@
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use crate::model::{SchemaVersion, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use super::{setting_text, setting_value, FilterKey};
+
+/// A filter rule listed in a `ConfigDiff`, as written in its config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffRule {
+    /// `include` or `exclude`, if set on the event filter
+    pub onmatch: Option<String>,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+}
+
+impl fmt::Display for DiffRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.onmatch.as_deref().unwrap_or("-"),
+            self.field,
+            self.condition,
+            self.value
+        )
+    }
+}
+
+/// A rule matching the same field and value in both configs, but with
+/// another condition or `onmatch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedRule {
+    pub old: DiffRule,
+    pub new: DiffRule,
+}
+
+/// The rule changes of one event type
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EventDiff {
+    pub added: Vec<DiffRule>,
+    pub removed: Vec<DiffRule>,
+    pub changed: Vec<ChangedRule>,
+}
+
+/// A top-level setting, or the schema version, that differs between the
+/// configs. `None` means the config does not set it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Outcome of `diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigDiff {
+    /// Rule changes by event type, such as `ProcessCreate`; event types
+    /// without changes are left out
+    pub events: BTreeMap<String, EventDiff>,
+    /// Settings that differ, sorted by name
+    pub settings: Vec<SettingChange>,
+}
+
+impl ConfigDiff {
+    /// Returns true when the configs have the same rules and settings
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.settings.is_empty()
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No differences");
+        }
+
+        let mut lines = Vec::new();
+        let unset = |value: &Option<String>| value.clone().unwrap_or_else(|| "(unset)".to_string());
+        for change in &self.settings {
+            lines.push(format!("{}: {} -> {}", change.name, unset(&change.old), unset(&change.new)));
+        }
+        for (event_type, diff) in &self.events {
+            lines.push(format!("{}:", event_type));
+            lines.extend(diff.added.iter().map(|rule| format!("  + {}", rule)));
+            lines.extend(diff.removed.iter().map(|rule| format!("  - {}", rule)));
+            lines.extend(diff.changed.iter().map(|c| format!("  ~ {} => {}", c.old, c.new)));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Compares two Sysmon configs, XML or JSON, rule by rule.
+///
+/// Rules are matched the way `ConfigMerger` dedupes them, so changes in case
+/// or path separators do not show up. A rule removed and a rule added with
+/// the same event type, field, and value are reported as one changed rule.
+/// Top-level settings and the schema version are compared as well.
+pub fn diff(old: &Path, new: &Path) -> Result<ConfigDiff, ConversionError> {
+    let old = SysmonDocument::load(old)?;
+    let new = SysmonDocument::load(new)?;
+    let old_rules = distinct_rules(&old);
+    let new_rules = distinct_rules(&new);
+
+    let only_in = |rules: &[(FilterKey, String, DiffRule)], other: &[(FilterKey, String, DiffRule)]| {
+        let other: HashSet<&FilterKey> = other.iter().map(|(key, ..)| key).collect();
+        rules
+            .iter()
+            .filter(|(key, ..)| !other.contains(key))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let mut removed = only_in(&old_rules, &new_rules);
+    let added = only_in(&new_rules, &old_rules);
+
+    let mut events: BTreeMap<String, EventDiff> = BTreeMap::new();
+    for (key, event_type, rule) in added {
+        let same_filter = removed.iter().position(|(old_key, ..)| {
+            (old_key.event_type.as_str(), old_key.field.as_str(), old_key.value.as_str())
+                == (key.event_type.as_str(), key.field.as_str(), key.value.as_str())
+        });
+        let event = events.entry(event_type).or_default();
+        match same_filter {
+            Some(i) => {
+                let (_, _, old) = removed.remove(i);
+                event.changed.push(ChangedRule { old, new: rule });
+            }
+            None => event.added.push(rule),
+        }
+    }
+    for (_, event_type, rule) in removed {
+        events.entry(event_type).or_default().removed.push(rule);
+    }
+
+    Ok(ConfigDiff {
+        events,
+        settings: setting_changes(&old, &new),
+    })
+}
+
+/// Gets the distinct rules of a document, keyed as `ConfigMerger` dedupes
+/// them, with their event type
+fn distinct_rules(doc: &SysmonDocument) -> Vec<(FilterKey, String, DiffRule)> {
+    let mut seen = HashSet::new();
+    let mut rules = Vec::new();
+    for rule in doc.rules() {
+        let onmatch = rule.onmatch.as_deref().unwrap_or_default();
+        let key = FilterKey::new(&rule.event_type, onmatch, &rule.field, &rule.condition, &rule.value);
+        if seen.insert(key.clone()) {
+            let diff_rule = DiffRule {
+                onmatch: rule.onmatch,
+                field: rule.field,
+                condition: rule.condition,
+                value: rule.value,
+            };
+            rules.push((key, rule.event_type, diff_rule));
+        }
+    }
+    rules
+}
+
+/// Compares everything outside `EventFiltering`. Schema versions are
+/// compared as versions, so `4.5` and `4.50` are the same.
+fn setting_changes(old: &SysmonDocument, new: &SysmonDocument) -> Vec<SettingChange> {
+    let settings = |doc: &SysmonDocument| -> BTreeMap<String, String> {
+        let Value::Object(root) = doc.value() else {
+            return BTreeMap::new();
+        };
+        root.iter()
+            .filter(|(name, _)| *name != "EventFiltering" && !name.starts_with('$'))
+            .map(|(name, value)| {
+                (name.trim_start_matches('@').to_string(), setting_text(&setting_value(value)))
+            })
+            .collect()
+    };
+    let old = settings(old);
+    let new = settings(new);
+
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (old, new) = (old.get(name), new.get(name));
+            let same = match (old, new) {
+                (Some(a), Some(b)) if name == "schemaversion" => match (SchemaVersion::parse(a), SchemaVersion::parse(b)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => a == b,
+                },
+                _ => old == new,
+            };
+            (!same).then(|| SettingChange {
+                name: name.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_diff_mixed_formats() {
+        let temp_dir = tempdir().unwrap();
+        let old = temp_dir.path().join("old.xml");
+        fs::write(
+            &old,
+            r#"<Sysmon schemaversion="4.50">
+                <HashAlgorithms>md5</HashAlgorithms>
+                <EventFiltering>
+                    <RuleGroup name="processes" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">C:\Windows\System32\cmd.exe</Image>
+                            <Image condition="is">C:\Tools\old.exe</Image>
+                            <CommandLine condition="contains">-enc</CommandLine>
+                        </ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="network" groupRelation="or">
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">4444</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+        let new = temp_dir.path().join("new.json");
+        fs::write(
+            &new,
+            r#"{"Sysmon": {
+                "@schemaversion": "4.5",
+                "HashAlgorithms": "sha256",
+                "DnsLookup": "false",
+                "EventFiltering": {"RuleGroup": [
+                    {"@name": "processes", "@groupRelation": "or",
+                     "ProcessCreate": {"@onmatch": "include",
+                        "Image": [
+                            {"@condition": "end with", "$text": "c:/windows/system32/CMD.EXE"},
+                            {"@condition": "is", "$text": "C:\\Tools\\new.exe"}
+                        ],
+                        "CommandLine": {"@condition": "begin with", "$text": "-enc"}}},
+                    {"@name": "network", "@groupRelation": "or",
+                     "NetworkConnect": {"@onmatch": "exclude",
+                        "DestinationPort": {"@condition": "is", "$text": "4444"}}}
+                ]}
+            }}"#,
+        )
+        .unwrap();
+
+        let diff = diff(&old, &new).unwrap();
+        assert!(!diff.is_empty());
+
+        // cmd.exe is unchanged apart from case and separators
+        let process_create = &diff.events["ProcessCreate"];
+        let rule = |onmatch: &str, field: &str, condition: &str, value: &str| DiffRule {
+            onmatch: Some(onmatch.to_string()),
+            field: field.to_string(),
+            condition: condition.to_string(),
+            value: value.to_string(),
+        };
+        assert_eq!(process_create.added, vec![rule("include", "Image", "is", r"C:\Tools\new.exe")]);
+        assert_eq!(process_create.removed, vec![rule("include", "Image", "is", r"C:\Tools\old.exe")]);
+        assert_eq!(
+            process_create.changed,
+            vec![ChangedRule {
+                old: rule("include", "CommandLine", "contains", "-enc"),
+                new: rule("include", "CommandLine", "begin with", "-enc"),
+            }]
+        );
+        assert_eq!(
+            diff.events["NetworkConnect"].changed,
+            vec![ChangedRule {
+                old: rule("include", "DestinationPort", "is", "4444"),
+                new: rule("exclude", "DestinationPort", "is", "4444"),
+            }]
+        );
+        assert_eq!(diff.events.len(), 2);
+
+        // 4.5 and 4.50 are the same schema version
+        let setting = |name: &str, old: Option<&str>, new: Option<&str>| SettingChange {
+            name: name.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        };
+        assert_eq!(
+            diff.settings,
+            vec![
+                setting("DnsLookup", None, Some("false")),
+                setting("HashAlgorithms", Some("md5"), Some("sha256")),
+            ]
+        );
+
+        let text = diff.to_string();
+        assert!(text.contains("HashAlgorithms: md5 -> sha256"), "{}", text);
+        assert!(text.contains(r"  + include Image is C:\Tools\new.exe"), "{}", text);
+        assert!(text.contains("  ~ include CommandLine contains -enc => include CommandLine begin with -enc"), "{}", text);
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["events"]["ProcessCreate"]["removed"][0]["value"], r"C:\Tools\old.exe");
+
+        assert!(super::diff(&old, &old).unwrap().is_empty());
+        assert_eq!(super::diff(&new, &new).unwrap().to_string(), "No differences");
+    }
+}
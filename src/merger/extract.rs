@@ -0,0 +1,140 @@
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use crate::model::Value;
+use crate::schema::event_type;
+use std::path::Path;
+
+use super::{rule_groups, set_rule_groups, write_derived_config};
+
+/// Writes the settings of `input` and its rules for `event_types`, include
+/// and exclude alike, as XML or JSON by the extension of `output`.
+///
+/// `RuleGroup`s keep their attributes and lose the other event types;
+/// groups left without any are dropped. Event type names are checked
+/// against the `schema` tables, and the result is validated against the
+/// Sysmon schema with the `validation` feature.
+pub fn extract(input: &Path, event_types: &[&str], output: &Path) -> Result<(), ConversionError> {
+    for name in event_types {
+        if event_type(name).is_none() {
+            return Err(ConversionError::InvalidFile(format!("Unknown event type: {}", name)));
+        }
+    }
+
+    let mut root = SysmonDocument::load(input)?.value().clone();
+    let kept = event_type_groups(&root, event_types);
+    set_rule_groups(&mut root, kept);
+
+    write_derived_config(&root, output, true)
+}
+
+/// Copies the `RuleGroup`s of the content of a `Sysmon` root element with
+/// only the events of `event_types`, leaving out groups without any
+pub(super) fn event_type_groups(root: &Value, event_types: &[&str]) -> Vec<Value> {
+    rule_groups(root)
+        .into_iter()
+        .filter_map(|group| {
+            let Value::Object(group) = group else {
+                return None;
+            };
+            let mut has_events = false;
+            let kept = group
+                .iter()
+                .filter(|(name, _)| {
+                    let wanted = event_types.contains(&name.as_str());
+                    has_events |= wanted;
+                    wanted || name.starts_with('@') || name.starts_with('$')
+                })
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            has_events.then_some(Value::Object(kept))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_extract() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("full.xml");
+        fs::write(
+            &input,
+            r#"<Sysmon schemaversion="4.50">
+                <HashAlgorithms>sha256</HashAlgorithms>
+                <EventFiltering>
+                    <RuleGroup name="processes" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">powershell.exe</Image>
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                        <ProcessCreate onmatch="exclude">
+                            <Image condition="is">C:\Windows\System32\svchost.exe</Image>
+                        </ProcessCreate>
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">4444</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                    <RuleGroup name="dns" groupRelation="or">
+                        <DnsQuery onmatch="exclude">
+                            <QueryName condition="end with">.microsoft.com</QueryName>
+                        </DnsQuery>
+                    </RuleGroup>
+                    <RuleGroup name="files" groupRelation="or">
+                        <FileCreate onmatch="include">
+                            <TargetFilename condition="contains">\Startup\</TargetFilename>
+                        </FileCreate>
+                        <ImageLoad onmatch="include">
+                            <ImageLoaded condition="end with">amsi.dll</ImageLoaded>
+                        </ImageLoad>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+
+        for output in ["laptop.xml", "laptop.json"] {
+            let output = temp_dir.path().join(output);
+            extract(&input, &["ProcessCreate", "DnsQuery"], &output).unwrap();
+
+            let laptop = SysmonDocument::load(&output).unwrap();
+            assert_eq!(laptop.schema_version(), Some("4.50"));
+            let Value::Object(root) = laptop.value() else {
+                panic!("root is not an object");
+            };
+            assert!(matches!(root.get("HashAlgorithms"), Some(Value::String(v)) if v == "sha256"));
+
+            let mut counts = BTreeMap::new();
+            let mut groups = Vec::new();
+            for rule in laptop.rules() {
+                *counts.entry((rule.event_type, rule.onmatch.unwrap_or_default())).or_insert(0) += 1;
+                groups.push(rule.rule_group.unwrap_or_default());
+            }
+            let expected = BTreeMap::from([
+                (("DnsQuery".to_string(), "exclude".to_string()), 1),
+                (("ProcessCreate".to_string(), "exclude".to_string()), 1),
+                (("ProcessCreate".to_string(), "include".to_string()), 2),
+            ]);
+            assert_eq!(counts, expected);
+            groups.dedup();
+            assert!(!groups.contains(&"files".to_string()), "{:?}", groups);
+        }
+    }
+
+    #[test]
+    fn test_extract_unknown_event_type() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("full.xml");
+        fs::write(&input, r#"<Sysmon schemaversion="4.50"><HashAlgorithms>sha256</HashAlgorithms></Sysmon>"#)
+            .unwrap();
+        let output = temp_dir.path().join("laptop.xml");
+
+        let error = extract(&input, &["ProcessCreate", "ProcessCreat"], &output).unwrap_err();
+        assert!(error.to_string().contains("Unknown event type: ProcessCreat"), "{}", error);
+        assert!(!output.exists());
+    }
+}
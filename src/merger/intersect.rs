@@ -0,0 +1,184 @@
+use crate::error::ConversionError;
+use log::{info, warn};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::{
+    is_and_group, retain_rule, rule_identities, write_merged_config, ConfigMerger, FilterKey, MergeOptions,
+    MergeReport, RuleIdentity,
+};
+
+/// Writes the rules found in every one of `paths` to `output`, as XML or
+/// JSON by its extension.
+///
+/// Rules are matched as `MergeOptions::dedupe` matches them, and rules with
+/// `groupRelation="and"` only when all their filters match. Settings and the
+/// schema version are merged from every input as `options` asks. When no
+/// rule is common to all inputs, the output holds the settings only and the
+/// report carries a warning.
+pub fn intersect(
+    paths: &[PathBuf],
+    output: &Path,
+    options: &MergeOptions,
+) -> Result<MergeReport, ConversionError> {
+    let mut merger = ConfigMerger::with_options(options.clone());
+    for path in paths {
+        info!("Processing file: {}", path.display());
+        merger.add_file(path)?;
+    }
+    merger.retain_common_rules();
+
+    let merged_config = merger.combine()?;
+    write_merged_config(&merged_config, output, options, &())?;
+
+    let mut report = merger.report();
+    if merger.current_rules.is_empty() {
+        let warning = format!("No rule is common to all {} inputs", merger.processed_files_count);
+        warn!("{}", warning);
+        report.warnings.push(warning);
+    }
+    Ok(report)
+}
+
+impl ConfigMerger {
+    /// Keeps only the filters found in every input read, matched as for
+    /// deduplication. Rules with `groupRelation="and"` are compared whole.
+    fn retain_common_rules(&mut self) {
+        let inputs = self.processed_files_count;
+        let mut sources: HashMap<RuleIdentity, HashSet<&str>> = HashMap::new();
+        for (rule, origin) in self.current_rules.iter().zip(&self.rule_origins) {
+            for identity in rule_identities(rule) {
+                sources.entry(identity).or_default().insert(&origin.source);
+            }
+        }
+        let mut common_filters: HashSet<FilterKey> = HashSet::new();
+        let mut common_and_rules: HashSet<Vec<FilterKey>> = HashSet::new();
+        for (identity, sources) in sources {
+            if sources.len() != inputs {
+                continue;
+            }
+            match identity {
+                RuleIdentity::Filter(key) => common_filters.insert(key),
+                RuleIdentity::And(keys) => common_and_rules.insert(keys),
+            };
+        }
+
+        let (rules, origins) = std::mem::take(&mut self.current_rules)
+            .into_iter()
+            .zip(std::mem::take(&mut self.rule_origins))
+            .filter_map(|(rule, origin)| {
+                if is_and_group(&rule) {
+                    let Some(RuleIdentity::And(keys)) = rule_identities(&rule).pop() else {
+                        return None;
+                    };
+                    return common_and_rules.contains(&keys).then_some((rule, origin));
+                }
+                let mut keep = |key: &FilterKey, _: &str, _: &str| common_filters.contains(key);
+                retain_rule(Cow::Owned(rule), &mut keep).map(|rule| (rule.into_owned(), origin))
+            })
+            .unzip();
+        self.current_rules = rules;
+        self.rule_origins = origins;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::SysmonDocument;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_intersect() {
+        let xml = |images: &str, extra: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="processes" groupRelation="or">
+                        <ProcessCreate onmatch="include">{images}</ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="dns" groupRelation="or">
+                        <DnsQuery onmatch="include">
+                            <QueryName condition="is">example.com</QueryName>
+                        </DnsQuery>
+                    </RuleGroup>
+                    {extra}
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let and_group = r#"<RuleGroup name="and" groupRelation="and">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">x.exe</Image>
+                    <CommandLine condition="contains">y</CommandLine>
+                </ProcessCreate>
+            </RuleGroup>"#;
+        let temp_dir = tempdir().unwrap();
+        let inputs = vec![
+            temp_dir.path().join("a.xml"),
+            temp_dir.path().join("b.json"),
+            temp_dir.path().join("c.xml"),
+        ];
+        fs::write(
+            &inputs[0],
+            xml(
+                r#"<Image condition="end with">C:\Windows\System32\cmd.exe</Image>
+                   <Image condition="is">a.exe</Image>"#,
+                and_group,
+            ),
+        )
+        .unwrap();
+        fs::write(
+            &inputs[1],
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": [
+                {"@name": "b", "@groupRelation": "or",
+                 "ProcessCreate": {"@onmatch": "include", "Image": [
+                    {"@condition": "end with", "$text": "c:/windows/system32/CMD.EXE"},
+                    {"@condition": "is", "$text": "b.exe"}]},
+                 "DnsQuery": {"@onmatch": "include",
+                    "QueryName": {"@condition": "is", "$text": "example.com"}}},
+                {"@name": "and", "@groupRelation": "and",
+                 "ProcessCreate": {"@onmatch": "include",
+                    "CommandLine": {"@condition": "contains", "$text": "y"},
+                    "Image": {"@condition": "is", "$text": "x.exe"}}}
+            ]}}"#,
+        )
+        .unwrap();
+        fs::write(
+            &inputs[2],
+            xml(
+                r#"<Image condition="end with">C:\Windows\System32\cmd.exe</Image>
+                   <Image condition="contains">cmd.exe</Image>"#,
+                "",
+            ),
+        )
+        .unwrap();
+
+        let output = temp_dir.path().join("common.xml");
+        let report = intersect(&inputs, &output, &MergeOptions::default()).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let common = SysmonDocument::load(&output).unwrap();
+        assert_eq!(common.schema_version(), Some("4.50"));
+        let mut rules: Vec<_> = common
+            .rules()
+            .into_iter()
+            .map(|rule| (rule.field, rule.condition, rule.value))
+            .collect();
+        rules.sort();
+        assert_eq!(
+            rules,
+            vec![
+                ("Image".to_string(), "end with".to_string(), r"C:\Windows\System32\cmd.exe".to_string()),
+                ("QueryName".to_string(), "is".to_string(), "example.com".to_string()),
+            ]
+        );
+
+        let disjoint = temp_dir.path().join("disjoint.json");
+        fs::write(&disjoint, r#"{"@schemaversion": "4.50", "HashAlgorithms": "sha256"}"#).unwrap();
+        let output = temp_dir.path().join("empty.xml");
+        let report = intersect(&[inputs[0].clone(), disjoint], &output, &MergeOptions::default()).unwrap();
+        assert!(report.warnings.iter().any(|w| w.contains("No rule is common")), "{:?}", report.warnings);
+        let empty = SysmonDocument::load(&output).unwrap();
+        assert!(empty.rules().is_empty());
+    }
+}
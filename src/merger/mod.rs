@@ -1,100 +1,946 @@
-use crate::{batch::ProgressReporter, error::ConversionError};
-use crate::model::Value;
-use log::{error, info};
+#[cfg(feature = "fs")]
+use crate::batch::ProgressReporter;
+use crate::converter::json::{check_default_keys, check_element_name};
+use crate::converter::FileFormat;
+use crate::error::ConversionError;
+use crate::model::{EventType, SchemaVersion, Value, DEFAULT_SCHEMA_VERSION};
+use crate::stats::{compute_group_stats, RuleStats};
+use crate::preprocessor::{normalize_path, substitute_json, PreprocessStep, TemplateSubstitutionStep};
+#[cfg(feature = "fs")]
+use log::{debug, error, info};
+use serde::Serialize;
 use serde_json;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+#[cfg(feature = "fs")]
+use globset::{Glob, GlobSet, GlobSetBuilder};
+#[cfg(feature = "validation")]
 use sysmon_validator::parse_sysmon_config_from_str;
 use quick_xml::{
     Reader,
     Writer,
     events::{Event, BytesStart, BytesText, BytesDecl},
 };
+#[cfg(feature = "validation")]
 use sysmon_validator::validate_sysmon_config;
+#[cfg(feature = "validation")]
+use crate::schema::annotate_validation_error;
+
+#[cfg(feature = "fs")]
+mod diff;
+#[cfg(feature = "fs")]
+mod extract;
+#[cfg(feature = "fs")]
+mod intersect;
+mod observer;
+#[cfg(feature = "fs")]
+mod split;
+#[cfg(feature = "fs")]
+mod subtract;
+#[cfg(feature = "fs")]
+pub use diff::{diff, ChangedRule, ConfigDiff, DiffRule, EventDiff, SettingChange};
+#[cfg(feature = "fs")]
+pub use extract::extract;
+#[cfg(feature = "fs")]
+pub use intersect::intersect;
+pub use observer::{MergeEvent, MergeObserver, MergePhase, MergeWarning};
+#[cfg(feature = "fs")]
+pub use split::{split, SplitFile, SplitReport};
+#[cfg(feature = "fs")]
+pub use subtract::{subtract, EmptySubtractionPolicy, SubtractOptions};
+
+/// How `ConfigMerger` arranges the collected rules into `RuleGroup`s
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// One group per event type and `onmatch`, named `MergedRules <event> <onmatch>`
+    #[default]
+    Flatten,
+    /// Every input `RuleGroup` as written, with its name and `groupRelation`
+    PreserveGroups,
+    /// One group per input file, named after the file. Filters of the same
+    /// event type and `onmatch` are combined within the group.
+    GroupPerFile,
+}
+
+/// What `ConfigMerger` does with a filter both included and excluded for
+/// the same event type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep both filters and log a warning
+    #[default]
+    Warn,
+    /// Fail the merge
+    Error,
+    /// Drop the exclude filter
+    IncludeWins,
+    /// Drop the include filter
+    ExcludeWins,
+}
+
+/// Which `schemaversion` the merged config gets when the inputs declare
+/// different ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SchemaVersionPolicy {
+    /// The highest version declared by an input
+    #[default]
+    Highest,
+    /// The version of the first input that declared one
+    First,
+    /// This version, whatever the inputs declare
+    Explicit(String),
+    /// Fail the merge unless every input declares the same version
+    ErrorOnMismatch,
+}
+
+/// Which value the merged config gets for a top-level setting, such as
+/// `HashAlgorithms`, set by more than one input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SettingsPolicy {
+    /// The value of the first input that sets it
+    #[default]
+    FirstWins,
+    /// The value of the last input that sets it
+    LastWins,
+    /// Fail the merge when inputs set different values
+    ErrorOnConflict,
+}
+
+/// What `ConfigMerger` does when the merged config would filter the same
+/// event type and `onmatch` in more than one `RuleGroup`. Sysmon only
+/// honors the first of them, so the others would be silently ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateFilterPolicy {
+    /// Move the repeated filters out of their groups into one
+    /// `MergedRules <event> <onmatch>` group, as `MergeStrategy::Flatten`
+    /// builds them
+    #[default]
+    Consolidate,
+    /// Fail the merge, naming the event types
+    Error,
+}
+
+/// What `ConfigMerger` does with an input file that cannot be read, parsed,
+/// or validated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidFilePolicy {
+    /// Fail the merge
+    #[default]
+    Abort,
+    /// Leave the file out and record it in `MergeReport::skipped_files`
+    Skip,
+}
+
+/// Top-level `Sysmon` elements carried over by `ConfigMerger`, in the order
+/// they are written
+pub const MERGED_SETTINGS: &[&str] = &[
+    "HashAlgorithms",
+    "DnsLookup",
+    "CheckRevocation",
+    "ArchiveDirectory",
+    "DriverName",
+];
+
+/// Options controlling how `ConfigMerger` builds the merged config
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// How rules are arranged into groups in the merged config
+    pub strategy: MergeStrategy,
+    /// Drop filters repeating an earlier filter with the same event type,
+    /// `onmatch`, field, condition, and value. Conditions and values are
+    /// compared ignoring case, and values after path normalization. Filters
+    /// combined with `and` are kept.
+    pub dedupe: bool,
+    /// What to do with filters included by one input and excluded by another
+    pub conflict_policy: ConflictPolicy,
+    /// Which `schemaversion` the merged config gets
+    pub schema_version_policy: SchemaVersionPolicy,
+    /// The `schemaversion` used when no input declares one. `None` uses
+    /// `DEFAULT_SCHEMA_VERSION`.
+    pub default_schema_version: Option<String>,
+    /// Which value wins for settings in `MERGED_SETTINGS` set by several inputs
+    pub settings_policy: SettingsPolicy,
+    /// Sort the collected rules (see `rule_sort_key`) before building the
+    /// merged config, so the output does not depend on file discovery order
+    pub sort_output: bool,
+    /// Values for `{{NAME}}` placeholders, substituted in each input before
+    /// it is validated and merged
+    pub variables: Option<HashMap<String, String>>,
+    /// Reject inputs with placeholders that have no value in `variables`
+    /// instead of leaving them as written
+    pub strict_variables: bool,
+    /// Validate each XML input and the merged config against the Sysmon
+    /// schema. Has no effect without the `validation` feature.
+    pub validate: bool,
+    /// Mark each filter with the input it came from, as a `$source` key,
+    /// which XML output renders as a `<!-- source: ... -->` comment
+    pub annotate_sources: bool,
+    /// Keep the `$source` keys of `annotate_sources` in JSON output instead
+    /// of dropping them
+    pub sources_in_json: bool,
+    /// What to do with input files that fail to parse or validate
+    pub on_invalid: InvalidFilePolicy,
+    /// What to do when `strategy` would filter an event type and `onmatch`
+    /// in several groups
+    pub duplicate_filter_policy: DuplicateFilterPolicy,
+    /// Glob patterns, such as `windows/**`, for the files a directory merge
+    /// reads, matched against the path relative to the directory. Empty
+    /// reads every XML and JSON file.
+    pub include_globs: Vec<String>,
+    /// Glob patterns, such as `**/*.draft.xml`, for files a directory merge
+    /// leaves out, even when `include_globs` matches them
+    pub exclude_globs: Vec<String>,
+    /// Also sync the output's directory after the merged config is renamed
+    /// into place, so the rename itself survives a crash. Unix only.
+    pub sync_directory: bool,
+    /// Whether a directory merge follows symbolic links to directories, as
+    /// `ProcessingOptions::follow_symlinks` does for conversions
+    pub follow_symlinks: bool,
+    /// Whether a directory merge visits hidden entries, as
+    /// `ProcessingOptions::include_hidden` does for conversions
+    pub include_hidden: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            strategy: MergeStrategy::default(),
+            dedupe: true,
+            conflict_policy: ConflictPolicy::default(),
+            schema_version_policy: SchemaVersionPolicy::default(),
+            default_schema_version: None,
+            settings_policy: SettingsPolicy::default(),
+            sort_output: false,
+            variables: None,
+            strict_variables: false,
+            validate: true,
+            annotate_sources: false,
+            sources_in_json: false,
+            on_invalid: InvalidFilePolicy::default(),
+            duplicate_filter_policy: DuplicateFilterPolicy::default(),
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            sync_directory: false,
+            follow_symlinks: false,
+            include_hidden: false,
+        }
+    }
+}
+
+/// Builder pattern for MergeOptions
+pub struct MergeOptionsBuilder {
+    options: MergeOptions,
+}
+
+impl MergeOptionsBuilder {
+    /// Creates a new MergeOptionsBuilder with default values
+    pub fn new() -> Self {
+        Self {
+            options: MergeOptions::default(),
+        }
+    }
+
+    /// Sets how rules are arranged into groups
+    pub fn strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.options.strategy = strategy;
+        self
+    }
+
+    /// Sets whether repeated filters are dropped
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.options.dedupe = dedupe;
+        self
+    }
+
+    /// Sets the policy for filters both included and excluded
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.options.conflict_policy = policy;
+        self
+    }
+
+    /// Sets the policy for picking the schema version
+    pub fn schema_version_policy(mut self, policy: SchemaVersionPolicy) -> Self {
+        self.options.schema_version_policy = policy;
+        self
+    }
+
+    /// Sets the schema version used when no input declares one
+    pub fn default_schema_version(mut self, version: Option<String>) -> Self {
+        self.options.default_schema_version = version;
+        self
+    }
+
+    /// Sets the policy for top-level settings set by several inputs
+    pub fn settings_policy(mut self, policy: SettingsPolicy) -> Self {
+        self.options.settings_policy = policy;
+        self
+    }
+
+    /// Sets whether the collected rules are sorted
+    pub fn sort_output(mut self, sort: bool) -> Self {
+        self.options.sort_output = sort;
+        self
+    }
+
+    /// Sets the values for template placeholders
+    pub fn variables(mut self, variables: Option<HashMap<String, String>>) -> Self {
+        self.options.variables = variables;
+        self
+    }
+
+    /// Sets whether unresolved placeholders are an error
+    pub fn strict_variables(mut self, strict: bool) -> Self {
+        self.options.strict_variables = strict;
+        self
+    }
+
+    /// Sets whether inputs and the merged config are validated
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.options.validate = validate;
+        self
+    }
+
+    /// Sets whether filters are marked with the input they came from
+    pub fn annotate_sources(mut self, annotate: bool) -> Self {
+        self.options.annotate_sources = annotate;
+        self
+    }
+
+    /// Sets whether source marks are kept in JSON output
+    pub fn sources_in_json(mut self, keep: bool) -> Self {
+        self.options.sources_in_json = keep;
+        self
+    }
+
+    /// Sets what happens to input files that fail to parse or validate
+    pub fn on_invalid(mut self, policy: InvalidFilePolicy) -> Self {
+        self.options.on_invalid = policy;
+        self
+    }
+
+    /// Sets what happens to an event type and `onmatch` filtered in several groups
+    pub fn duplicate_filter_policy(mut self, policy: DuplicateFilterPolicy) -> Self {
+        self.options.duplicate_filter_policy = policy;
+        self
+    }
+
+    /// Sets the glob patterns for the files a directory merge reads
+    pub fn include_globs(mut self, patterns: Vec<String>) -> Self {
+        self.options.include_globs = patterns;
+        self
+    }
+
+    /// Sets the glob patterns for the files a directory merge leaves out
+    pub fn exclude_globs(mut self, patterns: Vec<String>) -> Self {
+        self.options.exclude_globs = patterns;
+        self
+    }
+
+    /// Sets whether the output's directory is synced after the rename
+    pub fn sync_directory(mut self, enabled: bool) -> Self {
+        self.options.sync_directory = enabled;
+        self
+    }
+
+    /// Sets whether a directory merge follows symbolic links
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.options.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets whether a directory merge visits hidden entries
+    pub fn include_hidden(mut self, include: bool) -> Self {
+        self.options.include_hidden = include;
+        self
+    }
+
+    /// Builds the MergeOptions
+    pub fn build(self) -> MergeOptions {
+        self.options
+    }
+}
+
+impl Default for MergeOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts describing a merge
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MergeStats {
+    pub files: usize,
+    /// XML and JSON files a directory merge left out because of
+    /// `MergeOptions::include_globs` or `exclude_globs`
+    pub excluded_files: usize,
+    /// Filters collected from the inputs, before deduplication, counted as
+    /// `rule_stats` counts them
+    pub rules: usize,
+    /// Collected filters for each event type, such as `ProcessCreate`
+    pub rules_by_event_type: BTreeMap<String, usize>,
+    /// The distinct `schemaversion`s declared by the inputs, in the order seen
+    pub schema_versions: Vec<String>,
+    /// The last file read, if any
+    pub last_processed_file: Option<PathBuf>,
+    /// Filters dropped by `MergeOptions::dedupe`
+    pub duplicates_removed: usize,
+    /// Known event types with filters, in event ID order
+    pub event_types: Vec<EventType>,
+    /// Counts of the rules the merged config gets, after deduplication
+    pub rule_stats: RuleStats,
+}
+
+/// A filter included by one input and excluded by another. Filters match
+/// when their event type, field, condition, and value are equal, comparing
+/// as `MergeOptions::dedupe` does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeConflict {
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+    /// Input holding the first include filter
+    pub include_source: String,
+    /// Input holding the first exclude filter
+    pub exclude_source: String,
+}
+
+impl std::fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {:?} is included by {} and excluded by {}",
+            self.event_type, self.field, self.condition, self.value,
+            self.include_source, self.exclude_source
+        )
+    }
+}
+
+/// What `ConfigMerger::report` found in the inputs
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeReport {
+    pub stats: MergeStats,
+    pub conflicts: Vec<MergeConflict>,
+    /// The `schemaversion` declared by each input, as `(label, version)`
+    pub schema_versions: Vec<(String, String)>,
+    /// Problems that do not stop the merge, such as falling back to the
+    /// default schema version
+    pub warnings: Vec<String>,
+    /// Files left out under `InvalidFilePolicy::Skip`, as `(path, error)`
+    pub skipped_files: Vec<(String, String)>,
+}
+
+impl MergeStats {
+    /// Percentage of the known event types with filters in the merged config
+    pub fn coverage_percentage(&self) -> f64 {
+        self.event_types.len() as f64 / EventType::all().len() as f64 * 100.0
+    }
+}
 
 /// Merges Sysmon configs from multiple XML/JSON files.
 pub struct ConfigMerger {
-    schema_version: Option<String>,
+    options: MergeOptions,
+    /// `(label, version)` for every input declaring a `schemaversion`
+    schema_versions: Vec<(String, String)>,
+    /// `(label, name, value)` for every setting in `MERGED_SETTINGS` read
+    settings: Vec<(String, String, Value)>,
     current_rules: Vec<Value>,
+    /// Where each of `current_rules` came from
+    rule_origins: Vec<RuleOrigin>,
+    group_count: usize,
     last_processed_file: Option<PathBuf>,
     processed_files_count: usize,
+    excluded_files_count: usize,
+    skip_invalid_rules: bool,
+    skip_warnings: Vec<String>,
+    skipped_rule_count: usize,
+    /// `(path, error)` for every file left out under `InvalidFilePolicy::Skip`
+    skipped_files: Vec<(String, String)>,
+    /// Receives a `MergeEvent` for each input file, see `with_log_sink`
+    log_sink: Option<Box<dyn Fn(MergeEvent) + Send + Sync>>,
 }
 
 impl ConfigMerger {
     pub fn new() -> Self {
+        Self::with_options(MergeOptions::default())
+    }
+
+    pub fn with_options(options: MergeOptions) -> Self {
         Self {
-            schema_version: None,
+            options,
+            schema_versions: Vec::new(),
+            settings: Vec::new(),
             current_rules: Vec::new(),
+            rule_origins: Vec::new(),
+            group_count: 0,
             last_processed_file: None,
             processed_files_count: 0,
+            excluded_files_count: 0,
+            skip_invalid_rules: false,
+            skip_warnings: Vec::new(),
+            skipped_rule_count: 0,
+            skipped_files: Vec::new(),
+            log_sink: None,
         }
     }
 
-    pub fn current_rules_count(&self) -> usize {
-        self.current_rules.len()
+    /// Creates a merger holding the rules of configs built in memory, each
+    /// the content of a `Sysmon` root element as `add_config` takes it.
+    /// Fails on the first config that does not validate; to skip those
+    /// instead, use `with_skip_invalid_rules` and `add_config`.
+    pub fn from_configs(configs: &[Value]) -> Result<Self, ConversionError> {
+        let mut merger = Self::new();
+        for (i, config) in configs.iter().enumerate() {
+            merger.add_config(config, &format!("config {}", i + 1))?;
+        }
+        Ok(merger)
     }
 
-    pub fn last_processed_file(&self) -> Option<&Path> {
-        self.last_processed_file.as_ref().map(|p| p.as_path())
+    /// Calls `sink` as each input file is started, completed, skipped, or
+    /// fails, independently of the `log` output. Unlike a `MergeObserver`,
+    /// the sink stays with the merger for every merge it runs.
+    pub fn with_log_sink(mut self, sink: impl Fn(MergeEvent) + Send + Sync + 'static) -> Self {
+        self.log_sink = Some(Box::new(sink));
+        self
     }
 
-    pub fn processed_files_count(&self) -> usize {
-        self.processed_files_count
+    /// When enabled, XML configs that fail validation are skipped with a
+    /// warning instead of aborting the merge
+    pub fn with_skip_invalid_rules(mut self, enabled: bool) -> Self {
+        self.skip_invalid_rules = enabled;
+        self
     }
 
-    /// Process a directory of config files, validating each one
-    pub fn merge_directory(&mut self, dir: &Path, recursive: bool) -> Result<Value, ConversionError> {
-        let walker = if recursive {
-            WalkDir::new(dir)
-        } else {
-            WalkDir::new(dir).max_depth(1)
-        };
+    /// Gets the warnings recorded for configs skipped by `with_skip_invalid_rules`
+    pub fn skip_warnings(&self) -> &[String] {
+        &self.skip_warnings
+    }
 
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if !path.is_file() {
-                continue;
+    /// Gets the number of configs whose rules were skipped because they
+    /// failed validation
+    pub fn skipped_rule_count(&self) -> usize {
+        self.skipped_rule_count
+    }
+
+    /// Clears every input added so far, keeping the options, so that one
+    /// merger can run several unrelated merges
+    pub fn reset(&mut self) {
+        let options = std::mem::take(&mut self.options);
+        let log_sink = self.log_sink.take();
+        *self = Self::with_options(options).with_skip_invalid_rules(self.skip_invalid_rules);
+        self.log_sink = log_sink;
+    }
+
+    /// Gets the counts for the inputs added so far, as `finish` would merge them
+    pub fn stats(&self) -> MergeStats {
+        self.stats_of(&self.ordered_rules())
+    }
+
+    fn stats_of(&self, ordered: &OrderedRules) -> MergeStats {
+        let mut event_types: Vec<EventType> = self
+            .unique_event_types()
+            .iter()
+            .filter_map(|name| name.parse().ok())
+            .collect();
+        event_types.sort();
+
+        let collected = compute_group_stats(&self.current_rules);
+        let mut schema_versions: Vec<String> = Vec::new();
+        for (_, version) in &self.schema_versions {
+            if !schema_versions.contains(version) {
+                schema_versions.push(version.clone());
             }
+        }
+
+        MergeStats {
+            files: self.processed_files_count,
+            excluded_files: self.excluded_files_count,
+            rules: collected.total,
+            rules_by_event_type: collected.by_event_type.into_iter().collect(),
+            schema_versions,
+            last_processed_file: self.last_processed_file.clone(),
+            duplicates_removed: ordered.duplicates_removed,
+            event_types,
+            rule_stats: compute_group_stats(ordered.rules.iter().map(|(_, rule)| rule.as_ref())),
+        }
+    }
+
+    /// Gets the statistics and conflicts for the inputs added so far
+    pub fn report(&self) -> MergeReport {
+        let ordered = self.ordered_rules();
+        MergeReport {
+            stats: self.stats_of(&ordered),
+            conflicts: ordered.conflicts,
+            schema_versions: self.schema_versions.clone(),
+            warnings: self.default_schema_version_warning().into_iter().collect(),
+            skipped_files: self.skipped_files.clone(),
+        }
+    }
+
+
+    /// Lists the distinct event types among the collected rules, sorted
+    pub fn unique_event_types(&self) -> Vec<String> {
+        let mut event_types: Vec<String> = self
+            .collected_event_types()
+            .map(str::to_string)
+            .collect();
+        event_types.sort();
+        event_types.dedup();
+        event_types
+    }
+
+    /// Gets the schema version the merged config gets under
+    /// `MergeOptions::schema_version_policy`. With `ErrorOnMismatch`, this
+    /// is the first declared version, and `finish` fails when they differ.
+    pub fn schema_version(&self) -> Option<&str> {
+        let declared = || self.schema_versions.iter().map(|(_, version)| version.as_str());
+        match &self.options.schema_version_policy {
+            SchemaVersionPolicy::Explicit(version) => Some(version.as_str()),
+            // Versions that do not parse lose to any that do
+            SchemaVersionPolicy::Highest => declared()
+                .enumerate()
+                .max_by_key(|&(i, version)| (SchemaVersion::parse(version), std::cmp::Reverse(i)))
+                .map(|(_, version)| version),
+            SchemaVersionPolicy::First | SchemaVersionPolicy::ErrorOnMismatch => declared().next(),
+        }
+    }
 
-            let ext = path
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|s| s.to_lowercase());
+    /// Gets the schema version the merged config gets, falling back to
+    /// `MergeOptions::default_schema_version` when no input declares one
+    fn merged_schema_version(&self) -> &str {
+        self.schema_version().unwrap_or_else(|| {
+            self.options
+                .default_schema_version
+                .as_deref()
+                .unwrap_or(DEFAULT_SCHEMA_VERSION)
+        })
+    }
+
+    /// Describes the fallback when no input declares a schema version
+    fn default_schema_version_warning(&self) -> Option<String> {
+        if self.schema_version().is_some() {
+            return None;
+        }
+        Some(format!(
+            "No input declares a schema version; using {}",
+            self.merged_schema_version()
+        ))
+    }
+
+    /// Checks the declared versions against `SchemaVersionPolicy::ErrorOnMismatch`
+    fn check_schema_versions(&self) -> Result<(), ConversionError> {
+        if self.options.schema_version_policy != SchemaVersionPolicy::ErrorOnMismatch {
+            return Ok(());
+        }
+
+        let parse = |version: &str| SchemaVersion::parse(version).ok_or_else(|| version.to_string());
+        let Some((_, first)) = self.schema_versions.first() else {
+            return Ok(());
+        };
+        if self.schema_versions.iter().all(|(_, version)| parse(version) == parse(first)) {
+            return Ok(());
+        }
+
+        let inputs: Vec<String> = self
+            .schema_versions
+            .iter()
+            .map(|(label, version)| format!("{} ({})", label, version))
+            .collect();
+        Err(ConversionError::ValidationError(format!(
+            "Inputs declare different schema versions: {}",
+            inputs.join(", ")
+        )))
+    }
 
-            match ext.as_deref() {
-                Some("xml") => {
-                    println!("Processing XML file: {:?}", path);
-                    let content = std::fs::read_to_string(path)?;
-                    let _config = parse_sysmon_config_from_str(&content)?;
-                    self.process_xml_file(path)?;
+    /// Picks the value of each setting in `MERGED_SETTINGS` under
+    /// `MergeOptions::settings_policy`, in `MERGED_SETTINGS` order
+    fn merged_settings(&self) -> Result<Vec<(&str, &Value)>, ConversionError> {
+        let mut merged = Vec::new();
+        for &name in MERGED_SETTINGS {
+            let values: Vec<(&str, &Value)> = self
+                .settings
+                .iter()
+                .filter(|(_, setting, _)| setting == name)
+                .map(|(label, _, value)| (label.as_str(), value))
+                .collect();
+            let value = match self.options.settings_policy {
+                SettingsPolicy::FirstWins => values.first(),
+                SettingsPolicy::LastWins => values.last(),
+                SettingsPolicy::ErrorOnConflict => {
+                    let text = |value: &Value| serde_json::to_value(value).ok();
+                    if let Some((_, first)) = values.first() {
+                        if values.iter().any(|(_, value)| text(value) != text(first)) {
+                            let inputs: Vec<String> = values
+                                .iter()
+                                .map(|(label, value)| format!("{} ({})", label, setting_text(value)))
+                                .collect();
+                            return Err(ConversionError::ValidationError(format!(
+                                "Inputs set {} differently: {}",
+                                name,
+                                inputs.join(", ")
+                            )));
+                        }
+                    }
+                    values.first()
                 }
-                Some("json") => {
-                    println!("Processing JSON file: {:?}", path);
-                    let content = std::fs::read_to_string(path)?;
-                    let json_value: serde_json::Value = serde_json::from_str(&content)?;
-                    self.process_json_value(json_value)?;
+            };
+            if let Some(&(_, value)) = value {
+                merged.push((name, value));
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Iterates over the event type keys of the collected rules, skipping
+    /// attributes and text
+    fn collected_event_types(&self) -> impl Iterator<Item = &str> {
+        self.current_rules.iter().flat_map(|rule| match rule {
+            Value::Object(obj) => obj
+                .keys()
+                .filter(|key| !key.starts_with('@') && !key.starts_with('$'))
+                .map(String::as_str)
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// Stores a rule read from the input `label`. `group` identifies the
+    /// input `RuleGroup`, from `next_group`.
+    fn push_rule(&mut self, rule: Value, label: &str, group: usize) {
+        if let Value::Object(obj) = &rule {
+            for key in obj.keys().filter(|key| !key.starts_with('@') && !key.starts_with('$')) {
+                if key.parse::<EventType>().is_err() {
+                    log::warn!("{}: unknown event type {}", label, key);
                 }
-                _ => continue,
             }
         }
+        self.current_rules.push(rule);
+        self.rule_origins.push(RuleOrigin {
+            source: label.to_string(),
+            group,
+        });
+    }
+
+    fn next_group(&mut self) -> usize {
+        self.group_count += 1;
+        self.group_count
+    }
+
+    /// Process a directory of config files, validating each one. Files are
+    /// read in sorted path order, so the same directory always merges to
+    /// the same output. Inputs added before are cleared first.
+    #[cfg(feature = "fs")]
+    pub fn merge_directory(&mut self, dir: &Path, recursive: bool) -> Result<Value, ConversionError> {
+        self.reset();
+        for path in self.directory_inputs(dir, recursive)? {
+            match FileFormat::from_path(&path) {
+                Some(FileFormat::Xml) => info!("Processing XML file: {:?}", path),
+                _ => info!("Processing JSON file: {:?}", path),
+            }
+            self.add_file(&path)?;
+        }
 
-        println!("Number of rules collected: {}", self.current_rules.len());
+        debug!("Number of rules collected: {}", self.current_rules.len());
         self.build_merged_config()
     }
 
+    /// Adds the XML and JSON files under `dir` for which `filter` returns
+    /// true, in sorted path order, returning how many were added. Unlike
+    /// `merge_directory`, earlier inputs are kept, so several directories
+    /// and filters can be combined before calling `finish`.
+    #[cfg(feature = "fs")]
+    pub fn add_directory_filtered(
+        &mut self,
+        dir: &Path,
+        recursive: bool,
+        filter: impl Fn(&Path) -> bool,
+    ) -> Result<usize, ConversionError> {
+        let mut added = 0;
+        for path in self.directory_inputs(dir, recursive)? {
+            if !filter(&path) {
+                continue;
+            }
+            info!("Processing file: {:?}", path);
+            self.add_file(&path)?;
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Lists the XML and JSON files under `dir` in sorted path order,
+    /// leaving out (and counting) those filtered by
+    /// `MergeOptions::include_globs` and `exclude_globs`
+    #[cfg(feature = "fs")]
+    fn directory_inputs(&mut self, dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, ConversionError> {
+        let include = glob_set(&self.options.include_globs)?;
+        let exclude = glob_set(&self.options.exclude_globs)?;
+        let entries = crate::walk_dir(dir, recursive, self.options.follow_symlinks, self.options.include_hidden);
+
+        let mut inputs = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            if !matches!(FileFormat::from_path(path), Some(FileFormat::Xml | FileFormat::Json)) {
+                continue;
+            }
+
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            let included = self.options.include_globs.is_empty() || include.is_match(relative);
+            if !included || exclude.is_match(relative) {
+                info!("Excluded from merge: {:?}", path);
+                self.excluded_files_count += 1;
+                continue;
+            }
+            inputs.push(path.to_path_buf());
+        }
+        Ok(inputs)
+    }
+
+    /// Merges exactly the given files, in the given order, so that the
+    /// order-dependent options (such as `SettingsPolicy::LastWins`) follow
+    /// it. Files other than XML and JSON are an error, not skipped. Inputs
+    /// added before are cleared first.
+    #[cfg(feature = "fs")]
+    pub fn merge_files(&mut self, paths: &[PathBuf]) -> Result<Value, ConversionError> {
+        self.reset();
+        let merged_config = self.merge_observed(paths, &())?;
+        self.validate_merged(&merged_config)?;
+        Ok(merged_config)
+    }
+
+    /// Merges `paths` as `merge_files` does, telling `observer` about each
+    /// phase, file, and warning along the way. The merged config is not
+    /// validated; callers validate it once, from memory or from the written
+    /// file.
+    #[cfg(feature = "fs")]
+    fn merge_observed(
+        &mut self,
+        paths: &[PathBuf],
+        observer: &dyn MergeObserver,
+    ) -> Result<Value, ConversionError> {
+        observer.on_phase(MergePhase::Parse);
+        for path in paths {
+            info!("Processing file: {:?}", path);
+            observer.on_file_start(path);
+            let kept = (self.current_rules.len(), self.skip_warnings.len(), self.skipped_files.len());
+            self.add_file(path)?;
+            for warning in &self.skip_warnings[kept.1..] {
+                observer.on_warning(MergeWarning::SkippedRules(warning.clone()));
+            }
+            for (_, error) in &self.skipped_files[kept.2..] {
+                observer.on_warning(MergeWarning::SkippedFile { path: path.clone(), error: error.clone() });
+            }
+            observer.on_file_done(path, self.current_rules.len() - kept.0);
+        }
+
+        observer.on_phase(MergePhase::Combine);
+        let merged_config = self.combine()?;
+        if let Some(warning) = self.default_schema_version_warning() {
+            observer.on_warning(MergeWarning::SchemaVersion(warning));
+        }
+        if self.options.conflict_policy == ConflictPolicy::Warn {
+            for conflict in self.ordered_rules().conflicts {
+                observer.on_warning(MergeWarning::Conflict(conflict));
+            }
+        }
+        Ok(merged_config)
+    }
+
+    /// Adds the rules of one XML or JSON config file. Under
+    /// `InvalidFilePolicy::Skip`, a file that fails is recorded instead and
+    /// nothing it added so far is kept.
+    #[cfg(feature = "fs")]
+    fn add_file(&mut self, path: &Path) -> Result<(), ConversionError> {
+        self.emit(MergeEvent::FileStarted(path.to_path_buf()));
+        let kept = (
+            self.schema_versions.len(),
+            self.settings.len(),
+            self.current_rules.len(),
+            self.skip_warnings.len(),
+        );
+        let result = match self.read_file(path) {
+            Err(e) if self.options.on_invalid == InvalidFilePolicy::Skip => {
+                log::warn!("Skipped {}: {}", path.display(), e);
+                self.schema_versions.truncate(kept.0);
+                self.settings.truncate(kept.1);
+                self.current_rules.truncate(kept.2);
+                self.rule_origins.truncate(kept.2);
+                self.skipped_files.push((path.display().to_string(), e.to_string()));
+                self.emit(MergeEvent::FileSkipped { path: path.to_path_buf(), reason: e.to_string() });
+                return Ok(());
+            }
+            result => result,
+        };
+
+        let path = path.to_path_buf();
+        self.emit(match &result {
+            Err(e) => MergeEvent::FileFailed { path, error: e.to_string() },
+            // Skipped by `with_skip_invalid_rules`
+            Ok(()) if self.skip_warnings.len() > kept.3 => MergeEvent::FileSkipped {
+                path,
+                reason: self.skip_warnings[kept.3..].join("; "),
+            },
+            Ok(()) => MergeEvent::FileCompleted { path, rules_added: self.current_rules.len() - kept.2 },
+        });
+        result
+    }
+
+    #[cfg(feature = "fs")]
+    fn emit(&self, event: MergeEvent) {
+        if let Some(sink) = &self.log_sink {
+            sink(event);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    fn read_file(&mut self, path: &Path) -> Result<(), ConversionError> {
+        match FileFormat::from_path(path) {
+            Some(FileFormat::Xml) => self.process_xml_file(path),
+            Some(FileFormat::Json) => {
+                self.last_processed_file = Some(path.to_path_buf());
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| ConversionError::io_error(path, e))?;
+                let json_value: serde_json::Value = serde_json::from_str(&content)?;
+                self.add_json_value(json_value, &path.display().to_string())
+            }
+            _ => Err(ConversionError::InvalidFile(format!(
+                "Unsupported merge input: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    #[cfg(feature = "fs")]
     fn process_xml_file(&mut self, path: &Path) -> Result<(), ConversionError> {
         self.last_processed_file = Some(path.to_path_buf());
-        let content = std::fs::read_to_string(path)?;
-    
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ConversionError::io_error(path, e))?;
+        self.add_xml_str(&content, &path.display().to_string())
+    }
+
+    /// Adds the rules of an XML config held in memory. `label` stands in for
+    /// the file path in error messages.
+    pub fn add_xml_str(&mut self, content: &str, label: &str) -> Result<(), ConversionError> {
+        let substituted;
+        let content = match &self.options.variables {
+            Some(variables) => {
+                let step = TemplateSubstitutionStep::new(variables.clone(), self.options.strict_variables);
+                substituted = step.process(content).map_err(|e| e.in_file(label))?;
+                substituted.as_str()
+            }
+            None => content,
+        };
+
         // Validate the XML content before processing
-        let config = parse_sysmon_config_from_str(&content)
-            .map_err(|e| ConversionError::ValidationError(format!("Parse error in {}: {}", path.display(), e)))?;
-        
-        validate_sysmon_config(&config)
-            .map_err(|e| ConversionError::ValidationError(format!("Validation error in {}: {}", path.display(), e)))?;
+        #[cfg(feature = "validation")]
+        if self.options.validate {
+            if let Err(e) = validate_xml(content, label) {
+                return self.skip_invalid(label, e);
+            }
+        }
     
-        let mut reader = Reader::from_str(&content);
+        let mut reader = Reader::from_str(content);
         let mut buf = Vec::new();
         let mut stack = Vec::new();
         let mut in_event_filtering = false;
@@ -103,26 +949,11 @@ impl ConfigMerger {
         loop {
             match reader.read_event_into(&mut buf) {
                 Ok(Event::Start(e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    let mut attributes = HashMap::new();
-                    
-                    // Process attributes
-                    for attr in e.attributes() {
-                        let attr = attr.map_err(|e| ConversionError::XmlParse(e.into()))?;
-                        let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
-                        let value = String::from_utf8_lossy(&attr.value).into_owned();
-                        attributes.insert(key, Value::String(value.clone()));
-                        
-                        if name == "Sysmon" && attr.key.as_ref() == b"schemaversion" && self.schema_version.is_none() {
-                            self.schema_version = Some(value);
-                        }
-                    }
-                    
-                    stack.push((name.clone(), attributes, HashMap::new()));
-                    
-                    if name == "EventFiltering" {
+                    let element = self.open_element(&e, label)?;
+                    if element.0 == "EventFiltering" {
                         in_event_filtering = true;
                     }
+                    stack.push(element);
                 },
                 Ok(Event::Text(e)) => {
                     if let Some((_, _, ref mut content)) = stack.last_mut() {
@@ -132,43 +963,19 @@ impl ConfigMerger {
                         }
                     }
                 },
-                Ok(Event::End(e)) => {
-                    let end_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    
-                    if let Some((element_name, attributes, content)) = stack.pop() {
-                        let mut obj = attributes;
-                        
-                        for (k, v) in content {
-                            obj.insert(k, v);
-                        }
-                        
-                        let value = Value::Object(obj);
-                        
-                        if element_name == "RuleGroup" && in_event_filtering {
-                            if let Value::Object(ref rule_obj) = value {
-                                for (key, val) in rule_obj {
-                                    if !key.starts_with('@') && key != "RuleGroup" {
-                                        let mut new_rule = HashMap::new();
-                                        for (attr_key, attr_val) in rule_obj.iter() {
-                                            if attr_key.starts_with('@') {
-                                                new_rule.insert(attr_key.clone(), attr_val.clone());
-                                            }
-                                        }
-                                        new_rule.insert(key.clone(), val.clone());
-                                        self.current_rules.push(Value::Object(new_rule));
-                                    }
-                                }
-                            }
-                        }
-                        
-                        if let Some((_, _, ref mut parent_content)) = stack.last_mut() {
-                            parent_content.insert(element_name, value);
+                Ok(Event::End(_)) => {
+                    if let Some(element) = stack.pop() {
+                        if element.0 == "EventFiltering" {
+                            in_event_filtering = false;
                         }
+                        self.close_element(element, &mut stack, in_event_filtering, label);
                     }
-                    
-                    if end_name == "EventFiltering" {
-                        in_event_filtering = false;
-                    }
+                },
+                // A self-closing element, such as an event without filters,
+                // opens and closes at once
+                Ok(Event::Empty(e)) => {
+                    let element = self.open_element(&e, label)?;
+                    self.close_element(element, &mut stack, in_event_filtering, label);
                 },
                 Ok(Event::Eof) => break,
                 Err(e) => return Err(ConversionError::XmlParse(e.into())),
@@ -180,15 +987,93 @@ impl ConfigMerger {
         Ok(())
     }
 
-    fn process_json_value(&mut self, json_value: serde_json::Value) -> Result<(), ConversionError> {
+    /// Reads the name and attributes of an element, noting the schema
+    /// version of the `Sysmon` root element
+    fn open_element(&mut self, start: &BytesStart, label: &str) -> Result<OpenElement, ConversionError> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        let mut attributes = HashMap::new();
+        for attr in start.attributes() {
+            let attr = attr.map_err(|e| ConversionError::XmlParse(e.into()))?;
+            let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+            let value = String::from_utf8_lossy(&attr.value).into_owned();
+            attributes.insert(key, Value::String(value.clone()));
+
+            if name == "Sysmon" && attr.key.as_ref() == b"schemaversion" {
+                self.schema_versions.push((label.to_string(), value));
+            }
+        }
+        Ok((name, attributes, HashMap::new()))
+    }
+
+    /// Finishes an element, collecting it as a setting or as the rules of a
+    /// `RuleGroup`, and adds it to its parent on `stack`
+    fn close_element(
+        &mut self,
+        (element_name, attributes, content): OpenElement,
+        stack: &mut [OpenElement],
+        in_event_filtering: bool,
+        label: &str,
+    ) {
+        let mut obj = attributes;
+        for (k, v) in content {
+            obj.insert(k, v);
+        }
+        let value = Value::Object(obj);
+
+        if stack.len() == 1 && MERGED_SETTINGS.contains(&element_name.as_str()) {
+            self.settings.push((label.to_string(), element_name.clone(), setting_value(&value)));
+        }
+
+        if element_name == "RuleGroup" && in_event_filtering {
+            if let Value::Object(ref rule_obj) = value {
+                let group = self.next_group();
+                for (key, val) in rule_obj {
+                    if !key.starts_with('@') && key != "RuleGroup" {
+                        let mut new_rule = HashMap::new();
+                        for (attr_key, attr_val) in rule_obj.iter() {
+                            if attr_key.starts_with('@') {
+                                new_rule.insert(attr_key.clone(), attr_val.clone());
+                            }
+                        }
+                        new_rule.insert(key.clone(), val.clone());
+                        self.push_rule(Value::Object(new_rule), label, group);
+                    }
+                }
+            }
+        }
+
+        if let Some((_, _, ref mut parent_content)) = stack.last_mut() {
+            insert_child(parent_content, element_name, value);
+        }
+    }
+
+    /// Adds the rules of a JSON config held in memory. `label` stands in for
+    /// the file path in error messages. Like `add_xml_str`, the config is
+    /// validated first when `MergeOptions::validate` is set.
+    pub fn add_json_value(&mut self, mut json_value: serde_json::Value, label: &str) -> Result<(), ConversionError> {
+        check_default_keys(&json_value)?;
+        if let Some(variables) = &self.options.variables {
+            substitute_json(&mut json_value, variables, self.options.strict_variables)
+                .map_err(|e| e.in_file(label))?;
+        }
+
+        #[cfg(feature = "validation")]
+        if self.options.validate && json_value.is_object() {
+            let config = convert_json_value(&json_value);
+            if let Err(e) = merged_config_to_xml(&config).and_then(|xml| validate_xml(&xml, label)) {
+                return self.skip_invalid(label, e);
+            }
+        }
+
         match json_value {
             serde_json::Value::Object(obj) => {
-                // If missing, store the first discovered schema version
-                if self.schema_version.is_none() {
-                    if let Some(ver) = obj.get("@schemaversion") {
-                        if let Some(ver_str) = ver.as_str() {
-                            self.schema_version = Some(ver_str.to_string());
-                        }
+                if let Some(version) = obj.get("@schemaversion").and_then(|v| v.as_str()) {
+                    self.schema_versions.push((label.to_string(), version.to_string()));
+                }
+                for &name in MERGED_SETTINGS {
+                    if let Some(value) = obj.get(name) {
+                        let value = setting_value(&convert_json_value(value));
+                        self.settings.push((label.to_string(), name.to_string(), value));
                     }
                 }
 
@@ -199,109 +1084,997 @@ impl ConfigMerger {
                             serde_json::Value::Array(arr) => {
                                 for rg in arr {
                                     let value = convert_json_value(rg);
-                                    self.current_rules.push(value);
+                                    let group = self.next_group();
+                                    self.push_rule(value, label, group);
                                 }
                             }
                             _ => {
                                 // Single RuleGroup
                                 let value = convert_json_value(rule_groups);
-                                self.current_rules.push(value);
+                                let group = self.next_group();
+                                self.push_rule(value, label, group);
                             }
                         }
                     }
                 }
             }
-            _ => return Err(ConversionError::InvalidFile("Not a JSON object".into())),
+            _ => return Err(ConversionError::InvalidFile(format!("Not a JSON object: {}", label))),
         }
         self.processed_files_count += 1;
         Ok(())
     }
 
+    /// Adds the rules of a config held in memory, given as the content of a
+    /// `Sysmon` root element (the layout of `finish` and
+    /// `generate::config_from_indicators`). As with `add_json_value`, the
+    /// config is validated first when `MergeOptions::validate` is set.
+    pub fn add_config(&mut self, config: &Value, label: &str) -> Result<(), ConversionError> {
+        self.add_json_value(serde_json::to_value(config)?, label)
+    }
+
+    /// Records a config that failed validation as skipped, or returns the
+    /// error unless `with_skip_invalid_rules` is enabled
+    #[cfg(feature = "validation")]
+    fn skip_invalid(&mut self, label: &str, e: ConversionError) -> Result<(), ConversionError> {
+        if !self.skip_invalid_rules {
+            return Err(e);
+        }
+        let warning = format!("Skipped {}: {}", label, e);
+        log::warn!("{}", warning);
+        self.skip_warnings.push(warning);
+        self.skipped_rule_count += 1;
+        Ok(())
+    }
+
+    /// Builds the merged configuration from every input added so far
+    pub fn finish(&self) -> Result<Value, ConversionError> {
+        self.build_merged_config()
+    }
+
     fn build_merged_config(&self) -> Result<Value, ConversionError> {
-        let version = self.schema_version.clone().unwrap_or_else(|| "4.30".into());
+        let merged_value = self.combine()?;
+        self.validate_merged(&merged_value)?;
+        Ok(merged_value)
+    }
+
+    /// Builds the merged configuration without validating it
+    fn combine(&self) -> Result<Value, ConversionError> {
+        self.check_schema_versions()?;
+        if let Some(warning) = self.default_schema_version_warning() {
+            log::warn!("{}", warning);
+        }
+        let version = self.merged_schema_version().to_string();
     
         // Root object
         let mut root = HashMap::new();
         root.insert("@schemaversion".to_string(), Value::String(version));
+        for (name, value) in self.merged_settings()? {
+            root.insert(name.to_string(), value.clone());
+        }
     
-        // Add EventFiltering with a single RuleGroup
+        // Add EventFiltering with the groups `options.strategy` asks for
         if !self.current_rules.is_empty() {
             let mut ef_map = HashMap::new();
-            
-            // Create a single RuleGroup that combines all rules
-            let mut combined_rule_group = HashMap::new();
-            combined_rule_group.insert("@name".to_string(), Value::String("MergedRules".to_string()));
-            combined_rule_group.insert("@groupRelation".to_string(), Value::String("or".to_string()));
-    
-            // Collect all ProcessCreate rules
-            let mut process_creates = Vec::new();
-            for rule in &self.current_rules {
-                if let Value::Object(rule_obj) = rule {
-                    if let Some(process_create) = rule_obj.get("ProcessCreate") {
-                        process_creates.push(process_create.clone());
-                    }
+
+            let OrderedRules { mut rules, conflicts, .. } = self.ordered_rules();
+            if self.options.annotate_sources {
+                for (i, rule) in &mut rules {
+                    tag_sources(rule.to_mut(), &self.rule_origins[*i].source);
                 }
             }
-    
-            // Combine all ProcessCreate rules into a single rule
-            if !process_creates.is_empty() {
-                let mut combined_process_create = HashMap::new();
-                combined_process_create.insert("@onmatch".to_string(), Value::String("include".to_string()));
-                
-                // Combine all Image conditions
-                let mut images = Vec::new();
-                for pc in process_creates {
-                    if let Value::Object(pc_obj) = pc {
-                        if let Some(image) = pc_obj.get("Image") {
-                            images.push(image.clone());
+            if !conflicts.is_empty() {
+                let messages: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+                match self.options.conflict_policy {
+                    ConflictPolicy::Error => {
+                        return Err(ConversionError::ValidationError(format!(
+                            "Conflicting merge inputs: {}",
+                            messages.join("; ")
+                        )));
+                    }
+                    ConflictPolicy::Warn => {
+                        for message in &messages {
+                            log::warn!("{}", message);
                         }
                     }
+                    ConflictPolicy::IncludeWins | ConflictPolicy::ExcludeWins => {}
                 }
-                
-                combined_process_create.insert("Image".to_string(), Value::Array(images));
-                combined_rule_group.insert("ProcessCreate".to_string(), Value::Object(combined_process_create));
             }
-    
-            ef_map.insert("RuleGroup".to_string(), Value::Object(combined_rule_group));
+
+            let rule_groups = match self.options.strategy {
+                MergeStrategy::Flatten => merge_events(rules.iter().map(|(_, rule)| rule.as_ref()))
+                    .into_iter()
+                    .map(MergedEvent::into_rule_group)
+                    .collect(),
+                MergeStrategy::PreserveGroups => self.preserved_groups(&rules),
+                MergeStrategy::GroupPerFile => self.file_groups(&rules),
+            };
+            let rule_groups = single_filter_groups(rule_groups, self.options.duplicate_filter_policy)?;
+            ef_map.insert("RuleGroup".to_string(), Value::Array(rule_groups));
             root.insert("EventFiltering".to_string(), Value::Object(ef_map));
         }
     
-        let merged_value = Value::Object(root);
-    
-        // Convert to XML for validation and validate immediately
-        let mut writer = Writer::new(Vec::new());
-        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
-        write_xml_value(&mut writer, "Sysmon", &merged_value, "merged_config")?;
-        let xml_content = String::from_utf8(writer.into_inner())
-            .map_err(|e| ConversionError::InvalidFile(e.to_string()))?;
-    
-        // Validate the merged configuration
-        let config = parse_sysmon_config_from_str(&xml_content)
-            .map_err(|e| ConversionError::ValidationError(format!("Parse error: {}", e)))?;
-        
-        validate_sysmon_config(&config)
-            .map_err(|e| ConversionError::ValidationError(format!("Validation error: {}", e)))?;
-    
-        Ok(merged_value)
+        Ok(Value::Object(root))
     }
 
-}
+    /// Converts the merged configuration to XML and validates it, when
+    /// `MergeOptions::validate` asks for it
+    fn validate_merged(&self, merged_value: &Value) -> Result<(), ConversionError> {
+        #[cfg(feature = "validation")]
+        if self.options.validate {
+            let xml_content = merged_config_to_xml(merged_value)?;
 
-// Helper function to write XML
-fn write_xml_value<W: std::io::Write>(
-    writer: &mut Writer<W>,
-    name: &str,
-    value: &Value,
+            let config = parse_sysmon_config_from_str(&xml_content)
+                .map_err(|e| ConversionError::ValidationError(format!("Parse error: {}", e)))?;
+
+            validate_sysmon_config(&config)
+                .map_err(|e| ConversionError::ValidationError(format!("Validation error: {}", e)))?;
+        }
+
+        #[cfg(not(feature = "validation"))]
+        let _ = merged_value;
+        Ok(())
+    }
+
+    /// Gets the collected rules in output order, each with its index in
+    /// `current_rules`, after deduplication and conflict resolution
+    fn ordered_rules(&self) -> OrderedRules<'_> {
+        let mut order: Vec<usize> = (0..self.current_rules.len()).collect();
+        if self.options.sort_output {
+            order.sort_by_cached_key(|&i| rule_sort_key(&self.current_rules[i]));
+        }
+        let mut rules: Vec<(usize, Cow<Value>)> = order
+            .into_iter()
+            .map(|i| (i, Cow::Borrowed(&self.current_rules[i])))
+            .collect();
+
+        let mut duplicates_removed = 0;
+        if self.options.dedupe {
+            let mut seen = HashSet::new();
+            rules = rules
+                .into_iter()
+                .filter_map(|(i, rule)| {
+                    let mut keep = |key: &FilterKey, _: &str, _: &str| {
+                        let first = seen.insert(key.clone());
+                        if !first {
+                            duplicates_removed += 1;
+                        }
+                        first
+                    };
+                    retain_rule(rule, &mut keep).map(|rule| (i, rule))
+                })
+                .collect();
+        }
+
+        let conflicts = self.find_conflicts(&rules);
+        let losing = match self.options.conflict_policy {
+            ConflictPolicy::IncludeWins => Some("exclude"),
+            ConflictPolicy::ExcludeWins => Some("include"),
+            ConflictPolicy::Warn | ConflictPolicy::Error => None,
+        };
+        if let (Some(losing), false) = (losing, conflicts.is_empty()) {
+            let conflicting: HashSet<_> = conflicts.iter().map(|c| c.key.matching()).collect();
+            rules = rules
+                .into_iter()
+                .filter_map(|(i, rule)| {
+                    let mut keep = |key: &FilterKey, _: &str, _: &str| {
+                        key.onmatch != losing || !conflicting.contains(&key.matching())
+                    };
+                    retain_rule(rule, &mut keep).map(|rule| (i, rule))
+                })
+                .collect();
+        }
+
+        OrderedRules {
+            rules,
+            duplicates_removed,
+            conflicts: conflicts.into_iter().map(|c| c.conflict).collect(),
+        }
+    }
+
+    /// Finds filters included by one input and excluded by another, each
+    /// reported once against the first include and the first exclude
+    fn find_conflicts(&self, rules: &[(usize, Cow<Value>)]) -> Vec<FoundConflict> {
+        type Seen = (FilterKey, String, String, String);
+        let mut includes: Vec<Seen> = Vec::new();
+        let mut excludes: Vec<Seen> = Vec::new();
+        for (i, rule) in rules {
+            let source = &self.rule_origins[*i].source;
+            let mut record = |key: &FilterKey, condition: &str, text: &str| {
+                let seen = match key.onmatch.as_str() {
+                    "include" => &mut includes,
+                    "exclude" => &mut excludes,
+                    _ => return true,
+                };
+                if !seen.iter().any(|(k, ..)| k.matching() == key.matching()) {
+                    seen.push((key.clone(), condition.to_string(), text.to_string(), source.clone()));
+                }
+                true
+            };
+            retain_filters(rule, &mut record);
+        }
+
+        includes
+            .into_iter()
+            .filter_map(|(key, condition, value, include_source)| {
+                let (_, _, _, exclude_source) = excludes
+                    .iter()
+                    .find(|(k, ..)| k.matching() == key.matching())?;
+                let conflict = MergeConflict {
+                    event_type: key.event_type.clone(),
+                    field: key.field.clone(),
+                    condition,
+                    value,
+                    include_source,
+                    exclude_source: exclude_source.clone(),
+                };
+                Some(FoundConflict { key, conflict })
+            })
+            .collect()
+    }
+
+    /// Reassembles the input `RuleGroup`s, which XML inputs split into one
+    /// rule per event type
+    fn preserved_groups(&self, rules: &[(usize, Cow<Value>)]) -> Vec<Value> {
+        let mut groups: Vec<(usize, HashMap<String, Value>)> = Vec::new();
+        for (i, rule) in rules {
+            let Value::Object(rule) = rule.as_ref() else {
+                continue;
+            };
+            let origin = self.rule_origins[*i].group;
+            let pos = match groups.iter().position(|(group, _)| *group == origin) {
+                Some(pos) => pos,
+                None => {
+                    groups.push((origin, HashMap::new()));
+                    groups.len() - 1
+                }
+            };
+
+            let group = &mut groups[pos].1;
+            for (key, value) in rule {
+                if key.starts_with('@') {
+                    group.insert(key.clone(), value.clone());
+                } else {
+                    insert_child(group, key.clone(), value.clone());
+                }
+            }
+        }
+        groups.into_iter().map(|(_, group)| Value::Object(group)).collect()
+    }
+
+    /// Builds one `or` group per input, named after its file
+    fn file_groups(&self, rules: &[(usize, Cow<Value>)]) -> Vec<Value> {
+        let mut files: Vec<(&str, Vec<&Value>)> = Vec::new();
+        for (i, rule) in rules {
+            let source = self.rule_origins[*i].source.as_str();
+            match files.iter_mut().find(|(file, _)| *file == source) {
+                Some((_, rules)) => rules.push(rule.as_ref()),
+                None => files.push((source, vec![rule.as_ref()])),
+            }
+        }
+
+        files
+            .into_iter()
+            .map(|(source, rules)| {
+                let name = Path::new(source)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(source);
+                let mut group = HashMap::new();
+                group.insert("@name".to_string(), Value::String(name.to_string()));
+                group.insert("@groupRelation".to_string(), Value::String("or".to_string()));
+                for event in merge_events(rules) {
+                    let (event_type, value) = event.into_event();
+                    insert_child(&mut group, event_type, value);
+                }
+                Value::Object(group)
+            })
+            .collect()
+    }
+}
+
+/// Output of `ConfigMerger::ordered_rules`
+struct OrderedRules<'a> {
+    rules: Vec<(usize, Cow<'a, Value>)>,
+    /// Filters dropped by `MergeOptions::dedupe`
+    duplicates_removed: usize,
+    conflicts: Vec<MergeConflict>,
+}
+
+/// A conflict with the key of the filters involved
+struct FoundConflict {
+    key: FilterKey,
+    conflict: MergeConflict,
+}
+
+/// An element being read by `ConfigMerger::add_xml_str`: its name,
+/// attributes, and content so far
+type OpenElement = (String, HashMap<String, Value>, HashMap<String, Value>);
+
+/// Where a collected rule came from
+struct RuleOrigin {
+    /// Label of the input, usually its path
+    source: String,
+    /// Input `RuleGroup` the rule belongs to, unique across inputs
+    group: usize,
+}
+
+/// Identifies a filter: event type, `onmatch`, field, condition, and value.
+/// The condition and value are lowercased, and the value path-normalized.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct FilterKey {
+    event_type: String,
+    onmatch: String,
+    field: String,
+    condition: String,
+    value: String,
+}
+
+impl FilterKey {
+    pub(crate) fn new(event_type: &str, onmatch: &str, field: &str, condition: &str, text: &str) -> Self {
+        let value = normalize_path(text).unwrap_or_else(|_| text.to_string());
+        Self {
+            event_type: event_type.to_string(),
+            onmatch: onmatch.to_string(),
+            field: field.to_string(),
+            condition: condition.to_lowercase(),
+            value: value.to_lowercase(),
+        }
+    }
+
+    /// The key without `onmatch`, shared by an include and exclude filter
+    /// matching the same values
+    fn matching(&self) -> (&str, &str, &str, &str) {
+        (&self.event_type, &self.field, &self.condition, &self.value)
+    }
+}
+
+/// Returns true for a rule with `groupRelation="and"`, whose filters only
+/// match together
+fn is_and_group(rule: &Value) -> bool {
+    matches!(rule, Value::Object(obj) if matches!(obj.get("@groupRelation"), Some(Value::String(r)) if r == "and"))
+}
+
+/// Identifies one filter of a rule, or a whole rule with
+/// `groupRelation="and"`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RuleIdentity {
+    Filter(FilterKey),
+    /// The sorted keys of the filters of an `and` rule
+    And(Vec<FilterKey>),
+}
+
+/// Gets the identity of each filter of a rule, or a single identity for a
+/// rule with `groupRelation="and"`, made of its sorted filter keys
+#[cfg(feature = "fs")]
+fn rule_identities(rule: &Value) -> Vec<RuleIdentity> {
+    let mut keys = Vec::new();
+    let and_group = is_and_group(rule);
+    let mut record = |key: &FilterKey, _: &str, _: &str| {
+        keys.push(key.clone());
+        true
+    };
+    match rule {
+        // `retain_filters` keeps these whole without looking at the filters
+        Value::Object(obj) if and_group => {
+            let mut filters = obj.clone();
+            filters.remove("@groupRelation");
+            retain_filters(&Value::Object(filters), &mut record);
+        }
+        _ => {
+            retain_filters(rule, &mut record);
+        }
+    }
+    if and_group {
+        keys.sort();
+        return vec![RuleIdentity::And(keys)];
+    }
+    keys.into_iter().map(RuleIdentity::Filter).collect()
+}
+
+/// Gets a rule with only the filters `keep` accepts. `keep` is given the
+/// key and the filter's condition and text as written. Events left without
+/// filters are dropped, and so is the rule when none remain. Rules with
+/// `groupRelation="and"` are kept whole, since their filters only match
+/// together. Nested `Rule` elements are kept or dropped whole, with the key
+/// from `compound_key`. A rule that keeps every filter is borrowed, not copied.
+fn retain_filters<'a>(
+    rule: &'a Value,
+    keep: &mut impl FnMut(&FilterKey, &str, &str) -> bool,
+) -> Option<Cow<'a, Value>> {
+    let Value::Object(rule_obj) = rule else {
+        return Some(Cow::Borrowed(rule));
+    };
+    if is_and_group(rule) {
+        return Some(Cow::Borrowed(rule));
+    }
+
+    // `keep` is asked about every filter up front; the copy below walks the
+    // unchanged maps in the same order and replays the answers
+    let mut answers = Vec::new();
+    for (key, value) in rule_obj {
+        if key.starts_with('@') || key.starts_with('$') {
+            continue;
+        }
+        for event in items(value) {
+            let Value::Object(event_obj) = event else {
+                continue;
+            };
+            let onmatch = match event_obj.get("@onmatch") {
+                Some(Value::String(onmatch)) => onmatch.as_str(),
+                _ => "",
+            };
+            for (field, filters) in event_obj {
+                if field.starts_with('@') || field.starts_with('$') {
+                    continue;
+                }
+                for filter in items(filters) {
+                    answers.push(keep_filter(keep, key, onmatch, field, filter));
+                }
+            }
+        }
+    }
+    if answers.iter().all(|&kept| kept) {
+        let has_events = rule_obj
+            .iter()
+            .any(|(key, value)| !key.starts_with('@') && !key.starts_with('$') && !items(value).is_empty());
+        return has_events.then_some(Cow::Borrowed(rule));
+    }
+    let mut answers = answers.into_iter();
+
+    let mut retained = HashMap::new();
+    let mut has_events = false;
+    for (key, value) in rule_obj {
+        if key.starts_with('@') || key.starts_with('$') {
+            retained.insert(key.clone(), value.clone());
+            continue;
+        }
+
+        for event in items(value) {
+            let Value::Object(event_obj) = event else {
+                insert_child(&mut retained, key.clone(), event.clone());
+                has_events = true;
+                continue;
+            };
+
+            let mut kept = HashMap::new();
+            let mut filter_count = 0;
+            let mut kept_count = 0;
+            for (field, filters) in event_obj {
+                if field.starts_with('@') || field.starts_with('$') {
+                    kept.insert(field.clone(), filters.clone());
+                    continue;
+                }
+                for filter in items(filters) {
+                    filter_count += 1;
+                    if !answers.next().unwrap_or(true) {
+                        continue;
+                    }
+                    insert_child(&mut kept, field.clone(), filter.clone());
+                    kept_count += 1;
+                }
+            }
+
+            // An empty include would log nothing and an empty exclude everything
+            if kept_count > 0 || filter_count == 0 {
+                insert_child(&mut retained, key.clone(), Value::Object(kept));
+                has_events = true;
+            }
+        }
+    }
+
+    has_events.then_some(Cow::Owned(Value::Object(retained)))
+}
+
+/// Runs `retain_filters` on a rule that may be a copy already, copying
+/// only when filters are dropped from a borrowed rule
+fn retain_rule<'a>(
+    rule: Cow<'a, Value>,
+    keep: &mut impl FnMut(&FilterKey, &str, &str) -> bool,
+) -> Option<Cow<'a, Value>> {
+    match rule {
+        Cow::Borrowed(rule) => retain_filters(rule, keep),
+        Cow::Owned(rule) => {
+            let kept = match retain_filters(&rule, keep)? {
+                Cow::Borrowed(_) => None,
+                Cow::Owned(kept) => Some(kept),
+            };
+            Some(Cow::Owned(kept.unwrap_or(rule)))
+        }
+    }
+}
+
+/// Asks `keep` about one filter of an event, for `retain_filters`
+fn keep_filter(
+    keep: &mut impl FnMut(&FilterKey, &str, &str) -> bool,
+    event_type: &str,
+    onmatch: &str,
+    field: &str,
+    filter: &Value,
+) -> bool {
+    match filter_parts(filter) {
+        Some((condition, text)) => keep(&FilterKey::new(event_type, onmatch, field, condition, &text), condition, &text),
+        None => match compound_key(event_type, onmatch, field, filter) {
+            Some((filter_key, relation, filters)) => keep(&filter_key, &relation, &filters),
+            None => true,
+        },
+    }
+}
+
+/// Gets the key of a nested `Rule` element, with its `groupRelation` and its
+/// filters as written, so that compound rules are deduplicated and checked
+/// for conflicts as a unit. Rules with the same relation and filters match
+/// whatever their names and the order of their filters.
+fn compound_key(
+    event_type: &str,
+    onmatch: &str,
+    field: &str,
+    rule: &Value,
+) -> Option<(FilterKey, String, String)> {
+    let Value::Object(obj) = rule else {
+        return None;
+    };
+    if field != "Rule" {
+        return None;
+    }
+    let relation = match obj.get("@groupRelation") {
+        Some(Value::String(relation)) => relation.clone(),
+        _ => "or".to_string(),
+    };
+
+    let mut keys = Vec::new();
+    let mut written = Vec::new();
+    for (name, filters) in sorted_children(obj) {
+        for filter in items(filters) {
+            let (condition, text) = filter_parts(filter)?;
+            let key = FilterKey::new(event_type, onmatch, name, condition, &text);
+            keys.push(format!("{} {} {:?}", key.field, key.condition, key.value));
+            written.push(format!("{} {} {:?}", name, condition, text));
+        }
+    }
+    if keys.is_empty() {
+        return None;
+    }
+    keys.sort();
+
+    let key = FilterKey {
+        event_type: event_type.to_string(),
+        onmatch: onmatch.to_string(),
+        field: field.to_string(),
+        condition: relation.to_lowercase(),
+        value: keys.join("; "),
+    };
+    Some((key, relation, written.join("; ")))
+}
+
+/// Gets the condition and text of a field filter, or `None` for a nested
+/// `Rule` element
+fn filter_parts(filter: &Value) -> Option<(&str, Cow<'_, str>)> {
+    match filter {
+        Value::Object(obj) => Some((
+            match obj.get("@condition") {
+                Some(Value::String(condition)) => condition,
+                _ => "is",
+            },
+            filter_text(obj.get("$text")?)?,
+        )),
+        other => Some(("is", filter_text(other)?)),
+    }
+}
+
+/// Gets the text of a filter value. Numbers and booleans, as read with
+/// `XmlToJsonOptions::infer_types`, give the text they were parsed from.
+fn filter_text(value: &Value) -> Option<Cow<'_, str>> {
+    match value {
+        Value::String(text) => Some(Cow::Borrowed(text)),
+        Value::Number(n) => Some(Cow::Owned(n.to_string())),
+        Value::Bool(b) => Some(Cow::Owned(b.to_string())),
+        _ => None,
+    }
+}
+
+/// Combines the filters of the rules per event type and `onmatch`, in order
+/// of first appearance
+fn merge_events<'a>(rules: impl IntoIterator<Item = &'a Value>) -> Vec<MergedEvent> {
+    let mut groups: Vec<MergedEvent> = Vec::new();
+    for rule in rules {
+        let Value::Object(rule_obj) = rule else {
+            continue;
+        };
+        let and_group = matches!(rule_obj.get("@groupRelation"), Some(Value::String(r)) if r == "and");
+
+        for (event_type, event_value) in sorted_children(rule_obj) {
+            for event in items(event_value) {
+                let Value::Object(event_obj) = event else {
+                    continue;
+                };
+                let onmatch = match event_obj.get("@onmatch") {
+                    Some(Value::String(onmatch)) => Some(onmatch.clone()),
+                    _ => None,
+                };
+                let group = match groups
+                    .iter()
+                    .position(|g| g.event_type == *event_type && g.onmatch == onmatch)
+                {
+                    Some(pos) => &mut groups[pos],
+                    None => {
+                        groups.push(MergedEvent {
+                            event_type: event_type.clone(),
+                            onmatch,
+                            filters: Vec::new(),
+                        });
+                        groups.last_mut().unwrap()
+                    }
+                };
+
+                let children = sorted_children(event_obj);
+                let filter_count: usize = children.iter().map(|(_, v)| items(v).len()).sum();
+                if and_group && filter_count > 1 {
+                    // Keep the filters of an `and` group together, since
+                    // the merged groups use `or`, under the group's name
+                    let mut and_rule = HashMap::new();
+                    and_rule.insert("@groupRelation".to_string(), Value::String("and".to_string()));
+                    if let Some(name) = rule_obj.get("@name") {
+                        and_rule.insert("@name".to_string(), name.clone());
+                    }
+                    for (field, value) in children {
+                        and_rule.insert(field.clone(), value.clone());
+                    }
+                    group.push("Rule", Value::Object(and_rule));
+                } else {
+                    for (field, value) in children {
+                        for filter in items(value) {
+                            group.push(field, filter.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    groups
+}
+
+/// Applies `policy` to the event filters that `groups` repeat for the same
+/// event type and `onmatch`. Events that are not repeated stay in their
+/// groups; groups left without events are dropped.
+fn single_filter_groups(
+    groups: Vec<Value>,
+    policy: DuplicateFilterPolicy,
+) -> Result<Vec<Value>, ConversionError> {
+    // The groups filtering each event type and `onmatch`, by name
+    let mut filtered_by: Vec<((String, Option<String>), Vec<String>)> = Vec::new();
+    for group in &groups {
+        let Value::Object(group_obj) = group else {
+            continue;
+        };
+        let name = match group_obj.get("@name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => "(unnamed)".to_string(),
+        };
+        for (event_type, events) in sorted_children(group_obj) {
+            for event in items(events) {
+                let key = (event_type.clone(), event_onmatch(event));
+                match filtered_by.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, names)) => names.push(name.clone()),
+                    None => filtered_by.push((key, vec![name.clone()])),
+                }
+            }
+        }
+    }
+    filtered_by.retain(|(_, names)| names.len() > 1);
+    if filtered_by.is_empty() {
+        return Ok(groups);
+    }
+
+    if policy == DuplicateFilterPolicy::Error {
+        let messages: Vec<String> = filtered_by
+            .iter()
+            .map(|((event_type, onmatch), names)| {
+                format!(
+                    "{} onmatch={} is filtered by {}",
+                    event_type,
+                    onmatch.as_deref().unwrap_or("(none)"),
+                    names.join(", ")
+                )
+            })
+            .collect();
+        return Err(ConversionError::ValidationError(format!(
+            "Sysmon only honors the first RuleGroup per event type and onmatch: {}",
+            messages.join("; ")
+        )));
+    }
+
+    let repeated = |event_type: &str, event: &Value| {
+        let onmatch = event_onmatch(event);
+        filtered_by.iter().any(|((t, o), _)| t == event_type && *o == onmatch)
+    };
+    let mut kept = Vec::new();
+    let mut moved = Vec::new();
+    for group in groups {
+        let Value::Object(group_obj) = group else {
+            kept.push(group);
+            continue;
+        };
+        // Both halves keep the attributes, so `merge_events` still sees the
+        // name and `groupRelation` of the moved filters
+        let mut remaining = HashMap::new();
+        let mut taken = HashMap::new();
+        for (key, value) in group_obj {
+            if key.starts_with('@') || key.starts_with('$') {
+                taken.insert(key.clone(), value.clone());
+                remaining.insert(key, value);
+                continue;
+            }
+            for event in items(&value) {
+                let half = if repeated(&key, event) { &mut taken } else { &mut remaining };
+                insert_child(half, key.clone(), event.clone());
+            }
+        }
+        if !sorted_children(&taken).is_empty() {
+            moved.push(Value::Object(taken));
+        }
+        if !sorted_children(&remaining).is_empty() {
+            kept.push(Value::Object(remaining));
+        }
+    }
+
+    kept.extend(merge_events(&moved).into_iter().map(MergedEvent::into_rule_group));
+    Ok(kept)
+}
+
+/// Compiles glob patterns for matching paths relative to a merged directory
+#[cfg(feature = "fs")]
+fn glob_set(patterns: &[String]) -> Result<GlobSet, ConversionError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            ConversionError::ValidationError(format!("Invalid glob pattern {:?}: {}", pattern, e))
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| ConversionError::ValidationError(format!("Invalid glob patterns: {}", e)))
+}
+
+/// Gets the `onmatch` of an event filter element
+fn event_onmatch(event: &Value) -> Option<String> {
+    match event {
+        Value::Object(obj) => match obj.get("@onmatch") {
+            Some(Value::String(onmatch)) => Some(onmatch.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The merged filters of one event type and `onmatch`
+struct MergedEvent {
+    event_type: String,
+    onmatch: Option<String>,
+    /// Filters by element name, in order of first appearance
+    filters: Vec<(String, Vec<Value>)>,
+}
+
+impl MergedEvent {
+    fn push(&mut self, field: &str, filter: Value) {
+        match self.filters.iter_mut().find(|(name, _)| name == field) {
+            Some((_, values)) => values.push(filter),
+            None => self.filters.push((field.to_string(), vec![filter])),
+        }
+    }
+
+    /// Gets the event type and the event filter element
+    fn into_event(self) -> (String, Value) {
+        let mut event = HashMap::new();
+        if let Some(onmatch) = &self.onmatch {
+            event.insert("@onmatch".to_string(), Value::String(onmatch.clone()));
+        }
+        for (field, mut values) in self.filters {
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                Value::Array(values)
+            };
+            event.insert(field, value);
+        }
+        (self.event_type, Value::Object(event))
+    }
+
+    fn into_rule_group(self) -> Value {
+        let name = match &self.onmatch {
+            Some(onmatch) => format!("MergedRules {} {}", self.event_type, onmatch),
+            None => format!("MergedRules {}", self.event_type),
+        };
+        let (event_type, event) = self.into_event();
+        let mut group = HashMap::new();
+        group.insert("@name".to_string(), Value::String(name));
+        group.insert("@groupRelation".to_string(), Value::String("or".to_string()));
+        group.insert(event_type, event);
+        Value::Object(group)
+    }
+}
+
+/// Gets the child elements of an object, skipping attributes and text,
+/// sorted by name
+fn sorted_children(obj: &HashMap<String, Value>) -> Vec<(&String, &Value)> {
+    let mut children: Vec<_> = obj
+        .iter()
+        .filter(|(key, _)| !key.starts_with('@') && !key.starts_with('$'))
+        .collect();
+    children.sort_by(|a, b| a.0.cmp(b.0));
+    children
+}
+
+/// Marks every filter of a rule with the input it came from, as `$source`.
+/// Filters written as plain text become `$text` objects.
+fn tag_sources(rule: &mut Value, source: &str) {
+    let Value::Object(rule) = rule else {
+        return;
+    };
+    for (key, events) in rule.iter_mut() {
+        if key.starts_with('@') || key.starts_with('$') {
+            continue;
+        }
+        for event in items_mut(events) {
+            if let Value::Object(fields) = event {
+                tag_filters(fields, source);
+            }
+        }
+    }
+}
+
+fn tag_filters(fields: &mut HashMap<String, Value>, source: &str) {
+    let source_value = || Value::String(source.to_string());
+    for (field, filters) in fields.iter_mut() {
+        if field.starts_with('@') || field.starts_with('$') {
+            continue;
+        }
+        for filter in items_mut(filters) {
+            match filter {
+                Value::String(text) => {
+                    let text = Value::String(std::mem::take(text));
+                    *filter = Value::Object(HashMap::from([
+                        ("$text".to_string(), text),
+                        ("$source".to_string(), source_value()),
+                    ]));
+                }
+                Value::Object(obj) if obj.contains_key("$text") => {
+                    obj.insert("$source".to_string(), source_value());
+                }
+                // A `Rule` element grouping filters
+                Value::Object(obj) => tag_filters(obj, source),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Serializes a config with the keys of each object sorted, so that JSON
+/// output is stable, optionally leaving out the `$source` marks of
+/// `MergeOptions::annotate_sources`
+#[cfg(feature = "fs")]
+struct SortedValue<'a> {
+    value: &'a Value,
+    strip_sources: bool,
+}
+
+#[cfg(feature = "fs")]
+impl Serialize for SortedValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nested = |value| SortedValue { value, strip_sources: self.strip_sources };
+        match self.value {
+            Value::Array(values) => serializer.collect_seq(values.iter().map(nested)),
+            Value::Object(obj) => {
+                let mut entries: Vec<(&String, &Value)> = obj
+                    .iter()
+                    .filter(|(key, _)| !(self.strip_sources && key.as_str() == "$source"))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                serializer.collect_map(entries.into_iter().map(|(key, value)| (key, nested(value))))
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+fn items_mut(value: &mut Value) -> Vec<&mut Value> {
+    match value {
+        Value::Array(values) => values.iter_mut().collect(),
+        other => vec![other],
+    }
+}
+
+/// Gets the occurrences of an element, which is an array when repeated
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        other => vec![other],
+    }
+}
+
+/// Adds a child element, turning repeated elements into an array
+fn insert_child(obj: &mut HashMap<String, Value>, name: String, value: Value) {
+    match obj.get_mut(&name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = std::mem::replace(existing, Value::Null);
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            obj.insert(name, value);
+        }
+    }
+}
+
+/// Validates one input config against the Sysmon schema
+#[cfg(feature = "validation")]
+fn validate_xml(content: &str, label: &str) -> Result<(), ConversionError> {
+    let config = parse_sysmon_config_from_str(content).map_err(|e| {
+        let error = ConversionError::ValidationError(format!("Parse error in {}: {}", label, e));
+        annotate_validation_error(error, content)
+    })?;
+
+    validate_sysmon_config(&config).map_err(|e| {
+        let error = ConversionError::ValidationError(format!("Validation error in {}: {}", label, e));
+        annotate_validation_error(error, content)
+    })
+}
+
+/// Renders a merged configuration as an XML document with a `Sysmon` root.
+/// A root without `@schemaversion` gets `DEFAULT_SCHEMA_VERSION`.
+pub fn merged_config_to_xml(config: &Value) -> Result<String, ConversionError> {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    write_xml_value(&mut writer, "Sysmon", &with_schema_version(config), "merged_config")?;
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| ConversionError::InvalidFile(e.to_string()))
+}
+
+/// Adds `@schemaversion="DEFAULT_SCHEMA_VERSION"` to the content of a
+/// `Sysmon` root element that does not declare one
+pub(crate) fn with_schema_version(config: &Value) -> Cow<'_, Value> {
+    match config {
+        Value::Object(obj) if !obj.contains_key("@schemaversion") => {
+            let mut obj = obj.clone();
+            obj.insert(
+                "@schemaversion".to_string(),
+                Value::String(DEFAULT_SCHEMA_VERSION.to_string()),
+            );
+            Cow::Owned(Value::Object(obj))
+        }
+        _ => Cow::Borrowed(config),
+    }
+}
+
+// Helper function to write XML
+fn write_xml_value<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    name: &str,
+    value: &Value,
     path: impl Into<PathBuf>,
 ) -> Result<(), ConversionError> {
     let path = path.into();
-    
+    // Such as a `#text` key, which only `$text` stands for here
+    check_element_name(name)?;
+
     match value {
         Value::Object(obj) => {
+            if let Some(Value::String(source)) = obj.get("$source") {
+                // `--` may not appear in a comment
+                let comment = format!(" source: {} ", source.replace("--", "- -"));
+                writer.write_event(Event::Comment(BytesText::from_escaped(comment)))
+                    .map_err(|e| ConversionError::io_error(&path, e))?;
+            }
+
             let mut elem = BytesStart::new(name);
+            // Sort keys so the same config always renders the same bytes.
+            // Sysmon requires the top-level settings before EventFiltering.
+            let mut entries: Vec<_> = obj.iter().collect();
+            entries.sort_by_key(|&(key, _)| (key == "EventFiltering", key));
             
             // Write attributes
-            for (key, value) in obj {
+            for &(key, value) in &entries {
                 if key.starts_with('@') {
                     if let Value::String(attr_value) = value {
                         elem.push_attribute((&key[1..], attr_value.as_str()));
@@ -313,13 +2086,13 @@ fn write_xml_value<W: std::io::Write>(
                 .map_err(|e| ConversionError::io_error(&path, e))?;
 
             // Write text content
-            if let Some(Value::String(text)) = obj.get("$text") {
-                writer.write_event(Event::Text(BytesText::new(text)))
+            if let Some(text) = obj.get("$text").and_then(filter_text) {
+                writer.write_event(Event::Text(BytesText::new(&text)))
                     .map_err(|e| ConversionError::io_error(&path, e))?;
             }
 
             // Write child elements
-            for (key, value) in obj {
+            for &(key, value) in &entries {
                 if !key.starts_with('$') && !key.starts_with('@') {
                     write_xml_value(writer, key, value, &path)?;
                 }
@@ -368,50 +2141,141 @@ fn write_xml_value<W: std::io::Write>(
     Ok(())
 }
 
-fn convert_json_value(json_value: &serde_json::Value) -> Value {
-    match json_value {
-        serde_json::Value::Null => Value::Null,
-        serde_json::Value::Bool(b) => Value::Bool(*b),
-        serde_json::Value::Number(n) => Value::Number(n.clone()),
-        serde_json::Value::String(s) => Value::String(s.clone()),
-        serde_json::Value::Array(arr) => {
-            Value::Array(arr.iter().map(convert_json_value).collect())
-        }
-        serde_json::Value::Object(map) => {
-            let mut new_map = HashMap::new();
-            for (k, v) in map {
-                new_map.insert(k.clone(), convert_json_value(v));
-            }
-            Value::Object(new_map)
+/// Gets the key `sort_output` orders rules by: event type, `onmatch`, and
+/// the condition and text of the first field filter. Missing parts are empty.
+pub fn rule_sort_key(rule: &Value) -> (String, String, String, String) {
+    let mut key = (String::new(), String::new(), String::new(), String::new());
+    let Some((event_type, event)) = first_child(rule) else {
+        return key;
+    };
+    key.0 = event_type.clone();
+
+    let event = first_item(event);
+    if let Value::Object(obj) = event {
+        if let Some(Value::String(onmatch)) = obj.get("@onmatch") {
+            key.1 = onmatch.clone();
         }
     }
-}
 
-/// Public merge_configs function
+    if let Some((_, filter)) = first_child(event) {
+        match first_item(filter) {
+            Value::String(text) => {
+                key.2 = "is".to_string();
+                key.3 = text.clone();
+            }
+            Value::Object(obj) => {
+                key.2 = match obj.get("@condition") {
+                    Some(Value::String(condition)) => condition.clone(),
+                    _ => "is".to_string(),
+                };
+                if let Some(Value::String(text)) = obj.get("$text") {
+                    key.3 = text.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    key
+}
+
+/// Gets the child element with the lowest name, skipping attributes and text
+fn first_child(value: &Value) -> Option<(&String, &Value)> {
+    match value {
+        Value::Object(obj) => obj
+            .iter()
+            .filter(|(key, _)| !key.starts_with('@') && !key.starts_with('$'))
+            .min_by(|a, b| a.0.cmp(b.0)),
+        _ => None,
+    }
+}
+
+/// Gets the first occurrence of an element, which is an array when repeated
+fn first_item(value: &Value) -> &Value {
+    match value {
+        Value::Array(values) => values.first().unwrap_or(value),
+        other => other,
+    }
+}
+
+/// Reduces a text-only setting element read from XML, `{"$text": ...}`, to
+/// its text, the layout the converters use
+fn setting_value(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) if obj.is_empty() => Value::String(String::new()),
+        Value::Object(obj) if obj.len() == 1 => match obj.get("$text") {
+            Some(text) => text.clone(),
+            None => value.clone(),
+        },
+        other => other.clone(),
+    }
+}
+
+/// Renders a setting value for error messages
+fn setting_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+fn convert_json_value(json_value: &serde_json::Value) -> Value {
+    match json_value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Bool(*b),
+        serde_json::Value::Number(n) => Value::Number(n.clone()),
+        serde_json::Value::String(s) => Value::String(s.clone()),
+        serde_json::Value::Array(arr) => {
+            Value::Array(arr.iter().map(convert_json_value).collect())
+        }
+        serde_json::Value::Object(map) => {
+            let mut new_map = HashMap::new();
+            for (k, v) in map {
+                new_map.insert(k.clone(), convert_json_value(v));
+            }
+            Value::Object(new_map)
+        }
+    }
+}
+
+/// Public merge_configs function. Returns the report of the merge, as
+/// `ConfigMerger::report` gives it.
+#[cfg(feature = "fs")]
 pub fn merge_configs(
     input_dir: &Path,
     output_file: &Path,
     recursive: bool,
-) -> Result<(), ConversionError> {
-    let mut merger = ConfigMerger::new();
-   
-    // Add logging for input directory contents
+) -> Result<MergeReport, ConversionError> {
+    merge_configs_with_options(input_dir, output_file, recursive, &MergeOptions::default(), None)
+}
+
+/// Merges every config in a directory into `output_file`, as `merge_configs`
+/// does, building the merged config as `options` asks. `observer`, when
+/// given, is told about each phase, file, and warning of the merge.
+#[cfg(feature = "fs")]
+pub fn merge_configs_with_options(
+    input_dir: &Path,
+    output_file: &Path,
+    recursive: bool,
+    options: &MergeOptions,
+    observer: Option<&dyn MergeObserver>,
+) -> Result<MergeReport, ConversionError> {
+    let observer = observer.unwrap_or(&());
+    let mut merger = ConfigMerger::with_options(options.clone());
+
     info!("Scanning directory: {}", input_dir.display());
-    let files: Vec<_> = WalkDir::new(input_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .collect();
-    info!("Found {} files to process", files.len());
+    observer.on_phase(MergePhase::Scan);
+    let inputs = merger.directory_inputs(input_dir, recursive)?;
+    info!("Found {} files to process", inputs.len());
 
     // Process files and collect detailed errors
-    let merged_config = match merger.merge_directory(input_dir, recursive) {
+    let merged_config = match merger.merge_observed(&inputs, observer) {
         Ok(config) => config,
         Err(e) => {
+            let stats = merger.stats();
             error!("Merger error: {}", e);
-            error!("Rules processed: {}", merger.current_rules_count());
-            error!("Files processed: {}", merger.processed_files_count());
-            if let Some(last_file) = merger.last_processed_file() {
+            error!("Rules processed: {}", stats.rules);
+            error!("Files processed: {}", stats.files);
+            if let Some(last_file) = &stats.last_processed_file {
                 error!("Last processed file: {}", last_file.display());
             }
             return Err(e);
@@ -419,119 +2283,279 @@ pub fn merge_configs(
     };
 
     // Log merged config details before writing
-    info!(
-        "Successfully merged {} rules from {} files",
-        merger.current_rules_count(),
-        merger.processed_files_count()
-    );
-   
+    let report = merger.report();
+    info!("Successfully merged {} rules from {} files", report.stats.rules, report.stats.files);
+
     // Write and validate output
-    match output_file.extension().and_then(|ext| ext.to_str()) {
-        Some("json") => {
-            let json_str = serde_json::to_string_pretty(&merged_config)?;
-            std::fs::write(output_file, json_str)
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-        }
-        Some("xml") => {
-            let mut writer = Writer::new(Vec::new());
-            writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-            write_xml_value(&mut writer, "Sysmon", &merged_config, output_file)?;
-            let xml_content = writer.into_inner();
-           
-            // Validate before writing
-            info!("Validating merged configuration...");
-            match String::from_utf8(xml_content.clone()) {
-                Ok(xml_str) => {
-                    let config = parse_sysmon_config_from_str(&xml_str)?;
-                    if let Err(e) = validate_sysmon_config(&config) {
-                        error!("Validation failed for merged config:");
-                        error!("Rule count: {}", merger.current_rules_count());
-                        error!("Config schema version: {:?}", merger.schema_version);
-                        error!("Generated XML:\n{}", xml_str);
-                        return Err(e.into());
-                    }
-                }
-                Err(e) => return Err(ConversionError::InvalidFile(format!("Invalid UTF-8: {}", e))),
-            }
-           
-            std::fs::write(output_file, xml_content)
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-        }
-        _ => return Err(ConversionError::InvalidFile("Invalid output extension".into())),
+    observer.on_phase(MergePhase::Write);
+    if let Err(e) = write_merged_config(&merged_config, output_file, options, observer) {
+        error!("Writing merged config failed: {}", e);
+        error!("Rule count: {}", report.stats.rules);
+        error!("Config schema version: {:?}", merger.schema_version());
+        return Err(e);
     }
-    Ok(())
+    Ok(report)
+}
+
+/// Merges every config in a directory into `output_file`, as `merge_configs`
+/// does, and returns the merged config for further use
+#[cfg(feature = "fs")]
+pub fn merge_configs_with_value(
+    input_dir: &Path,
+    output_file: &Path,
+    recursive: bool,
+) -> Result<Value, ConversionError> {
+    let mut merger = ConfigMerger::new();
+    let inputs = merger.directory_inputs(input_dir, recursive)?;
+    let merged_config = merger.merge_observed(&inputs, &())?;
+    write_merged_config(&merged_config, output_file, &MergeOptions::default(), &())?;
+    Ok(merged_config)
+}
+
+/// Merges and validates every config in a directory as `merge_configs`
+/// does, returning the merged config without writing any file
+#[cfg(feature = "fs")]
+pub fn merge_to_value(input_dir: &Path, recursive: bool) -> Result<Value, ConversionError> {
+    let mut merger = ConfigMerger::new();
+    let inputs = merger.directory_inputs(input_dir, recursive)?;
+    let merged_config = merger.merge_observed(&inputs, &())?;
+    merger.validate_merged(&merged_config)?;
+    Ok(merged_config)
+}
+
+/// Merges and validates every config in a directory as
+/// `merge_configs_with_options` does, but writes nothing; only the report
+/// of the merge is returned
+#[cfg(feature = "fs")]
+pub fn merge_configs_report(
+    input_dir: &Path,
+    recursive: bool,
+    options: &MergeOptions,
+) -> Result<MergeReport, ConversionError> {
+    let mut merger = ConfigMerger::with_options(options.clone());
+    merger.merge_directory(input_dir, recursive)?;
+    Ok(merger.report())
 }
 
+#[cfg(feature = "fs")]
 pub fn merge_configs_with_progress(
     input_dir: &Path,
     output_file: &Path,
     recursive: bool,
     progress: &ProgressReporter,
 ) -> Result<(), ConversionError> {
-    let mut merger = ConfigMerger::new();
-    let walker = if recursive {
-        WalkDir::new(input_dir)
+    let options = MergeOptions::default();
+    let result = merge_configs_with_options(input_dir, output_file, recursive, &options, Some(progress));
+    progress.end_stage();
+    result.map(|_| ())
+}
+
+/// Merges exactly the given files, in the given order, into `output_file`.
+///
+/// Unlike `merge_configs`, nothing is discovered: later files are overlays
+/// on earlier ones for the order-dependent options, and a file that is not
+/// XML or JSON fails the merge.
+#[cfg(feature = "fs")]
+pub fn merge_config_files(
+    paths: &[PathBuf],
+    output_file: &Path,
+    options: &MergeOptions,
+) -> Result<(), ConversionError> {
+    let mut merger = ConfigMerger::with_options(options.clone());
+    let merged_config = merger.merge_observed(paths, &())?;
+    write_merged_config(&merged_config, output_file, options, &())
+}
+
+/// Merges files as `merge_config_files` does, counting each file in
+/// `progress`, which should be created with `ProgressReporter::new(paths.len())`
+#[cfg(feature = "fs")]
+pub fn merge_config_files_with_progress(
+    paths: &[PathBuf],
+    output_file: &Path,
+    options: &MergeOptions,
+    progress: &ProgressReporter,
+) -> Result<(), ConversionError> {
+    debug_assert_eq!(progress.total(), paths.len() as u64);
+    let mut merger = ConfigMerger::with_options(options.clone());
+    let result = merger
+        .merge_observed(paths, progress)
+        .and_then(|merged_config| write_merged_config(&merged_config, output_file, options, progress));
+    progress.end_stage();
+    result
+}
+
+/// Writes a merged config, using the extension of `output_file` to pick XML
+/// or JSON.
+///
+/// The output is streamed to a temporary file next to `output_file`, synced
+/// to disk, and renamed into place once complete, so an existing output is
+/// either replaced whole or left untouched. With `MergeOptions::validate`,
+/// the temporary file is read back and validated before the rename, which
+/// is the only validation of the merged config on the way to a file.
+#[cfg(feature = "fs")]
+fn write_merged_config(
+    merged_config: &Value,
+    output_file: &Path,
+    options: &MergeOptions,
+    observer: &dyn MergeObserver,
+) -> Result<(), ConversionError> {
+    let extension = match output_file.extension().and_then(|ext| ext.to_str()) {
+        Some(extension @ ("json" | "xml")) => extension,
+        _ => return Err(ConversionError::InvalidFile("Invalid output extension".into())),
+    };
+    let dir = match output_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // A failed write drops, and so deletes, the temporary file
+    let temp = tempfile::Builder::new()
+        .prefix(".sysmon-json-")
+        .suffix(&format!(".{}", extension))
+        .tempfile_in(dir)
+        .map_err(|e| ConversionError::io_error(dir, e))?;
+    let mut file = BufWriter::new(temp.as_file());
+
+    if extension == "json" {
+        let sorted = SortedValue {
+            value: merged_config,
+            strip_sources: options.annotate_sources && !options.sources_in_json,
+        };
+        serde_json::to_writer_pretty(&mut file, &sorted)?;
     } else {
-        WalkDir::new(input_dir).max_depth(1)
+        let mut writer = Writer::new(&mut file);
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .map_err(|e| ConversionError::io_error(output_file, e))?;
+        write_xml_value(&mut writer, "Sysmon", merged_config, output_file)?;
+    }
+    file.flush().map_err(|e| ConversionError::io_error(temp.path(), e))?;
+    drop(file);
+    temp.as_file().sync_all().map_err(|e| ConversionError::io_error(temp.path(), e))?;
+
+    #[cfg(feature = "validation")]
+    if options.validate {
+        info!("Validating merged configuration...");
+        observer.on_phase(MergePhase::Validate);
+        let content = std::fs::read_to_string(temp.path())
+            .map_err(|e| ConversionError::io_error(temp.path(), e))?;
+        let xml = match extension {
+            "json" => merged_config_to_xml(&serde_json::from_str(&content)?)?,
+            _ => content,
+        };
+        validate_xml(&xml, &output_file.display().to_string())?;
+    }
+    #[cfg(not(feature = "validation"))]
+    let _ = observer;
+
+    temp.persist(output_file).map_err(|e| {
+        let _ = e.file.close();
+        ConversionError::io_error(output_file, e.error)
+    })?;
+
+    // Directories cannot be opened as files on Windows, where the rename is
+    // already durable once it returns
+    #[cfg(unix)]
+    if options.sync_directory {
+        std::fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| ConversionError::io_error(dir, e))?;
+    }
+    Ok(())
+}
+
+/// Writes a config derived from another as `write_merged_config` does,
+/// validating it when `validate` is set and the `validation` feature is
+/// enabled. A root without `@schemaversion` gets `DEFAULT_SCHEMA_VERSION`.
+#[cfg(feature = "fs")]
+fn write_derived_config(root: &Value, output: &Path, validate: bool) -> Result<(), ConversionError> {
+    let options = MergeOptions {
+        validate,
+        ..MergeOptions::default()
     };
+    write_merged_config(&with_schema_version(root), output, &options, &())
+}
 
-    for entry in walker.into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
+/// Gets the `RuleGroup`s of the content of a `Sysmon` root element
+#[cfg(feature = "fs")]
+fn rule_groups(root: &Value) -> Vec<&Value> {
+    let Value::Object(root) = root else {
+        return Vec::new();
+    };
+    match root.get("EventFiltering") {
+        Some(Value::Object(filtering)) => filtering.get("RuleGroup").map(items).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces the `RuleGroup`s of the content of a `Sysmon` root element.
+/// Without groups, `EventFiltering` is left out unless it holds more.
+#[cfg(feature = "fs")]
+fn set_rule_groups(root: &mut Value, groups: Vec<Value>) {
+    let Value::Object(root) = root else {
+        return;
+    };
+    let Some(Value::Object(filtering)) = root.get_mut("EventFiltering") else {
+        return;
+    };
+    if !groups.is_empty() {
+        filtering.insert("RuleGroup".to_string(), Value::Array(groups));
+        return;
+    }
+    filtering.remove("RuleGroup");
+    if filtering.is_empty() {
+        root.remove("EventFiltering");
+    }
+}
+
+/// Validates every XML and JSON config in a directory without merging them.
+///
+/// Returns each file with the error that made it invalid, or `None` when it
+/// passed. `Err` is only returned when the directory itself cannot be read.
+/// The directory is walked as with the default `ProcessingOptions`.
+#[cfg(all(feature = "fs", feature = "validation"))]
+pub fn validate_only(
+    dir: &Path,
+    recursive: bool,
+) -> Result<Vec<(PathBuf, Option<ConversionError>)>, ConversionError> {
+    std::fs::read_dir(dir).map_err(|e| ConversionError::io_error(dir, e))?;
 
+    let mut results = Vec::new();
+    for entry in crate::walk_files(dir, recursive, &crate::config::ProcessingOptions::default()) {
+        let path = entry.path();
         let ext = path
             .extension()
             .and_then(|e| e.to_str())
             .map(|s| s.to_lowercase());
-
-        match ext.as_deref() {
-            Some("xml") => {
-                info!("Processing XML file: {:?}", path);
-                let content = std::fs::read_to_string(path)
-                    .map_err(|e| ConversionError::io_error(path, e))?;
-                let _config = parse_sysmon_config_from_str(&content)?;
-                merger.process_xml_file(path)?;
-                progress.increment();
-            }
-            Some("json") => {
-                info!("Processing JSON file: {:?}", path);
-                let content = std::fs::read_to_string(path)
-                    .map_err(|e| ConversionError::io_error(path, e))?;
-                let json_value: serde_json::Value = serde_json::from_str(&content)?;
-                merger.process_json_value(json_value)?;
-                progress.increment();
-            }
+        let is_json = match ext.as_deref() {
+            Some("xml") => false,
+            Some("json") => true,
             _ => continue,
-        }
+        };
+
+        info!("Validating {:?}", path);
+        let result = validate_config_file(path, is_json).err();
+        results.push((path.to_path_buf(), result));
     }
 
-    let merged_config = merger.build_merged_config()?;
+    Ok(results)
+}
 
-    // Write output based on extension
-    match output_file.extension().and_then(|ext| ext.to_str()) {
-        Some("json") => {
-            let json_str = serde_json::to_string_pretty(&merged_config)?;
-            std::fs::write(output_file, json_str)
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-        }
-        Some("xml") => {
-            let mut writer = Writer::new(Vec::new());
-            writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-            write_xml_value(&mut writer, "Sysmon", &merged_config, output_file)?;
-            std::fs::write(output_file, writer.into_inner())
-                .map_err(|e| ConversionError::io_error(output_file, e))?;
-        }
-        _ => return Err(ConversionError::InvalidFile("Invalid output extension".into())),
+#[cfg(all(feature = "fs", feature = "validation"))]
+fn validate_config_file(path: &Path, is_json: bool) -> Result<(), ConversionError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConversionError::io_error(path, e))?;
+    let label = path.display().to_string();
+    if !is_json {
+        return validate_xml(&content, &label);
     }
 
-    Ok(())
+    let json: serde_json::Value = serde_json::from_str(&content)?;
+    // Accept both the merger layout and the `Sysmon`-wrapped converter output
+    let root = json.get("Sysmon").unwrap_or(&json);
+    let xml = merged_config_to_xml(&convert_json_value(root))?;
+    validate_xml(&xml, &label)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "fs"))]
 mod tests {
     use super::*;
     use std::fs;
@@ -685,6 +2709,102 @@ mod tests {
     }
 
     #[test]
+    fn test_merge_keeps_every_event_type() {
+        let input_dir = tempdir().unwrap();
+        let output_dir = tempdir().unwrap();
+
+        let xml = r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="processes" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">\first.exe</Image>
+                            <Image condition="end with">\second.exe</Image>
+                            <CommandLine condition="contains">-encodedcommand</CommandLine>
+                        </ProcessCreate>
+                        <ProcessCreate onmatch="exclude">
+                            <ParentImage condition="is">C:\Windows\explorer.exe</ParentImage>
+                        </ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="network" groupRelation="or">
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">4444</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+
+        let json = r#"{
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": [
+                    {
+                        "@name": "files",
+                        "@groupRelation": "or",
+                        "FileCreate": {
+                            "@onmatch": "include",
+                            "TargetFilename": { "@condition": "end with", "$text": ".hta" }
+                        },
+                        "DnsQuery": {
+                            "@onmatch": "include",
+                            "QueryName": [
+                                { "@condition": "end with", "$text": ".onion" },
+                                { "@condition": "end with", "$text": ".bit" }
+                            ]
+                        }
+                    },
+                    {
+                        "@name": "more processes",
+                        "@groupRelation": "or",
+                        "ProcessCreate": {
+                            "@onmatch": "include",
+                            "Image": { "@condition": "end with", "$text": "\\third.exe" }
+                        },
+                        "ImageLoad": {
+                            "@onmatch": "include",
+                            "ImageLoaded": { "@condition": "end with", "$text": "\\dbghelp.dll" }
+                        }
+                    }
+                ]
+            }
+        }"#;
+
+        fs::write(input_dir.path().join("a.xml"), xml).unwrap();
+        fs::write(input_dir.path().join("b.json"), json).unwrap();
+
+        let values = [
+            "first.exe", "second.exe", "third.exe", "-encodedcommand", "explorer.exe",
+            "4444", ".hta", ".onion", ".bit", "dbghelp.dll",
+        ];
+        for output in ["merged.xml", "merged.json"] {
+            let output = output_dir.path().join(output);
+            merge_configs(input_dir.path(), &output, false).unwrap();
+            let merged = fs::read_to_string(&output).unwrap();
+            for value in values {
+                assert!(merged.contains(value), "{} missing from {}", value, merged);
+            }
+        }
+
+        let mut merger = ConfigMerger::new();
+        let merged = merger.merge_directory(input_dir.path(), false).unwrap();
+        let doc = crate::document::SysmonDocument::from_value(merged);
+        let rules = doc.rules();
+        assert_eq!(rules.len(), values.len());
+        let process_includes = rules
+            .iter()
+            .filter(|r| r.event_type == "ProcessCreate" && r.onmatch.as_deref() == Some("include"))
+            .count();
+        assert_eq!(process_includes, 4);
+        let mut event_types: Vec<_> = rules.iter().map(|r| r.event_type.as_str()).collect();
+        event_types.sort();
+        event_types.dedup();
+        assert_eq!(
+            event_types,
+            vec!["DnsQuery", "FileCreate", "ImageLoad", "NetworkConnect", "ProcessCreate"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
     fn test_merge_invalid_configs() {
         let temp_dir = tempdir().unwrap();
     
@@ -724,4 +2844,1921 @@ mod tests {
             Ok(_) => panic!("Expected validation to fail, but it succeeded"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_merge_options_builder() {
+        let mut variables = HashMap::new();
+        variables.insert("AGENT".to_string(), "agent.exe".to_string());
+        let options = MergeOptionsBuilder::new()
+            .strategy(MergeStrategy::GroupPerFile)
+            .dedupe(false)
+            .conflict_policy(ConflictPolicy::Error)
+            .schema_version_policy(SchemaVersionPolicy::First)
+            .default_schema_version(Some("4.50".to_string()))
+            .settings_policy(SettingsPolicy::LastWins)
+            .sort_output(true)
+            .variables(Some(variables.clone()))
+            .strict_variables(true)
+            .validate(false)
+            .annotate_sources(true)
+            .sources_in_json(true)
+            .on_invalid(InvalidFilePolicy::Skip)
+            .duplicate_filter_policy(DuplicateFilterPolicy::Error)
+            .sync_directory(true)
+            .build();
+
+        assert_eq!(options.strategy, MergeStrategy::GroupPerFile);
+        assert!(!options.dedupe);
+        assert_eq!(options.conflict_policy, ConflictPolicy::Error);
+        assert_eq!(options.schema_version_policy, SchemaVersionPolicy::First);
+        assert_eq!(options.default_schema_version.as_deref(), Some("4.50"));
+        assert_eq!(options.settings_policy, SettingsPolicy::LastWins);
+        assert!(options.sort_output);
+        assert_eq!(options.variables, Some(variables));
+        assert!(options.strict_variables);
+        assert!(!options.validate);
+        assert!(options.annotate_sources && options.sources_in_json);
+        assert_eq!(options.on_invalid, InvalidFilePolicy::Skip);
+        assert_eq!(options.duplicate_filter_policy, DuplicateFilterPolicy::Error);
+        assert!(options.sync_directory);
+
+        let defaults = MergeOptionsBuilder::new().build();
+        assert_eq!(defaults.strategy, MergeOptions::default().strategy);
+        assert!(defaults.dedupe && defaults.validate);
+        assert_eq!(defaults.on_invalid, InvalidFilePolicy::Abort);
+        assert!(!defaults.sync_directory);
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_merge_without_validation() {
+        let xml = r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="test" groupRelation="or">
+                        <ProcessCreate onmatch="invalid_match_type">
+                            <Image condition="is">test.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+
+        let mut merger = ConfigMerger::new();
+        assert!(matches!(
+            merger.add_xml_str(xml, "invalid.xml"),
+            Err(ConversionError::ValidationError(_))
+        ));
+
+        let options = MergeOptionsBuilder::new().validate(false).build();
+        let mut merger = ConfigMerger::with_options(options);
+        merger.add_xml_str(xml, "invalid.xml").unwrap();
+        assert_eq!(merger.stats().rules, 1);
+        assert!(merger.finish().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_skip_invalid_rules() {
+        let temp_dir = tempdir().unwrap();
+
+        let invalid = r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="bad" groupRelation="or">
+                        <ProcessCreate onmatch="invalid_match_type">
+                            <Image condition="is">bad.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+        let valid = r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="good" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="is">good.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+        fs::write(temp_dir.path().join("bad.xml"), invalid).unwrap();
+        fs::write(temp_dir.path().join("good.xml"), valid).unwrap();
+
+        let mut merger = ConfigMerger::new().with_skip_invalid_rules(true);
+        let merged = merger.merge_directory(temp_dir.path(), false).unwrap();
+
+        assert_eq!(merger.skipped_rule_count(), 1);
+        assert!(merger.skip_warnings()[0].contains("bad.xml"));
+        let xml = merged_config_to_xml(&merged).unwrap();
+        assert!(xml.contains("good.exe"));
+        assert!(!xml.contains("bad.exe"));
+
+        let mut strict = ConfigMerger::new();
+        assert!(strict.merge_directory(temp_dir.path(), false).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_validate_only() {
+        let temp_dir = tempdir().unwrap();
+        let valid = r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="good" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="is">good.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+        fs::write(temp_dir.path().join("a_good.xml"), valid).unwrap();
+        fs::write(temp_dir.path().join("b_bad.xml"), "<Sysmon><Broken></Sysmon>").unwrap();
+        fs::write(temp_dir.path().join("c_bad.json"), "{ not json").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let results = validate_only(temp_dir.path(), false).unwrap();
+        let names: Vec<_> = results
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a_good.xml", "b_bad.xml", "c_bad.json"]);
+        assert!(results[0].1.is_none());
+        assert!(results[1].1.is_some());
+        assert!(matches!(results[2].1, Some(ConversionError::JsonParse(_))));
+
+        assert!(validate_only(&temp_dir.path().join("missing"), false).is_err());
+    }
+
+    #[test]
+    fn test_sort_output_is_deterministic() {
+        let config = |image: &str| serde_json::json!({
+            "@schemaversion": "4.30",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": image,
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "is", "$text": image }
+                    }
+                }
+            }
+        });
+        let merge = |images: &[&str]| {
+            let options = MergeOptions {
+                sort_output: true,
+                ..Default::default()
+            };
+            let mut merger = ConfigMerger::with_options(options);
+            for image in images {
+                merger.add_json_value(config(image), image).unwrap();
+            }
+            merged_config_to_xml(&merger.finish().unwrap()).unwrap()
+        };
+
+        let forward = merge(&["a.exe", "b.exe", "c.exe"]);
+        let backward = merge(&["c.exe", "b.exe", "a.exe"]);
+        assert_eq!(forward, backward);
+        assert!(forward.find("a.exe").unwrap() < forward.find("c.exe").unwrap());
+
+        let rule = config("a.exe")["EventFiltering"]["RuleGroup"].clone();
+        assert_eq!(
+            rule_sort_key(&convert_json_value(&rule)),
+            ("ProcessCreate".into(), "include".into(), "is".into(), "a.exe".into())
+        );
+    }
+
+    #[test]
+    fn test_merger_reuse() {
+        let config = |version: &str, event: &str| format!(
+            r#"<Sysmon schemaversion="{version}">
+                <EventFiltering>
+                    <RuleGroup name="{event}" groupRelation="or">
+                        <{event} onmatch="include">
+                            <Image condition="end with">{event}.exe</Image>
+                        </{event}>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let first_dir = tempdir().unwrap();
+        fs::write(first_dir.path().join("a.xml"), config("4.50", "ProcessCreate")).unwrap();
+        fs::write(first_dir.path().join("b.xml"), config("4.50", "ProcessCreate")).unwrap();
+        let second_dir = tempdir().unwrap();
+        fs::write(second_dir.path().join("c.xml"), config("4.30", "NetworkConnect")).unwrap();
+
+        let mut merger = ConfigMerger::new();
+        merger.merge_directory(first_dir.path(), false).unwrap();
+        let stats = merger.stats();
+        assert_eq!((stats.files, stats.rules), (2, 2));
+        assert_eq!(stats.rules_by_event_type, BTreeMap::from([("ProcessCreate".to_string(), 2)]));
+        assert_eq!(stats.schema_versions, vec!["4.50"]);
+        assert_eq!(stats.last_processed_file, Some(first_dir.path().join("b.xml")));
+
+        // The second merge sees nothing of the first
+        let merged = merger.merge_directory(second_dir.path(), false).unwrap();
+        let xml = merged_config_to_xml(&merged).unwrap();
+        assert!(!xml.contains("ProcessCreate"), "{}", xml);
+        assert!(xml.contains(r#"schemaversion="4.30""#), "{}", xml);
+        let stats = merger.stats();
+        assert_eq!((stats.files, stats.rules), (1, 1));
+        assert_eq!(stats.rules_by_event_type, BTreeMap::from([("NetworkConnect".to_string(), 1)]));
+        assert_eq!(stats.schema_versions, vec!["4.30"]);
+        assert_eq!(stats.last_processed_file, Some(second_dir.path().join("c.xml")));
+
+        merger.reset();
+        assert_eq!(merger.stats(), MergeStats::default());
+    }
+
+    #[test]
+    fn test_add_directory_filtered() {
+        let config = |image: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{image}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">{image}</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let base_dir = tempdir().unwrap();
+        fs::write(base_dir.path().join("base.xml"), config("base.exe")).unwrap();
+        fs::write(base_dir.path().join("base.draft.xml"), config("draft.exe")).unwrap();
+        let team_dir = tempdir().unwrap();
+        fs::create_dir(team_dir.path().join("red")).unwrap();
+        fs::write(team_dir.path().join("red/red.xml"), config("red.exe")).unwrap();
+        fs::write(team_dir.path().join("blue.xml"), config("blue.exe")).unwrap();
+
+        let mut merger = ConfigMerger::new();
+        let not_draft = |path: &Path| !path.to_string_lossy().ends_with(".draft.xml");
+        assert_eq!(merger.add_directory_filtered(base_dir.path(), false, not_draft).unwrap(), 1);
+        let red = |path: &Path| path.parent().unwrap().ends_with("red");
+        assert_eq!(merger.add_directory_filtered(team_dir.path(), true, red).unwrap(), 1);
+        assert_eq!(merger.add_directory_filtered(team_dir.path(), false, red).unwrap(), 0);
+
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        for (image, expected) in [("base.exe", true), ("red.exe", true), ("draft.exe", false), ("blue.exe", false)] {
+            assert_eq!(xml.contains(image), expected, "{}: {}", image, xml);
+        }
+        assert_eq!(merger.stats().files, 2);
+    }
+
+    #[test]
+    fn test_from_configs() {
+        use crate::generate::{config_from_indicators, Indicator};
+
+        let configs: Vec<Value> = [
+            vec![Indicator::new("ProcessCreate", "Image", "end with", "a.exe")],
+            vec![
+                Indicator::new("ProcessCreate", "Image", "end with", "b.exe"),
+                Indicator::new("DnsQuery", "QueryName", "is", "example.com"),
+            ],
+            vec![Indicator::new("ProcessCreate", "Image", "end with", "A.EXE")],
+        ]
+        .iter()
+        .map(|indicators| config_from_indicators(indicators, "4.50").unwrap())
+        .collect();
+
+        let merger = ConfigMerger::from_configs(&configs).unwrap();
+        assert_eq!(merger.stats().files, 3);
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        assert!(xml.contains(r#"schemaversion="4.50""#), "{}", xml);
+        assert!(xml.contains("b.exe") && xml.contains("example.com"), "{}", xml);
+        // The third config only repeats a.exe, which is deduped
+        assert_eq!(xml.matches("a.exe").count() + xml.matches("A.EXE").count(), 1, "{}", xml);
+
+        assert_eq!(ConfigMerger::from_configs(&[]).unwrap().stats().files, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "validation")]
+    fn test_from_configs_invalid() {
+        let valid: Value = serde_json::from_str(
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {
+                "@name": "valid", "@groupRelation": "or",
+                "ProcessCreate": {"@onmatch": "include",
+                    "Image": {"@condition": "is", "$text": "a.exe"}}}}}"#,
+        )
+        .unwrap();
+        let invalid: Value = serde_json::from_str(
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {
+                "@name": "invalid", "@groupRelation": "or",
+                "ProcessCreate": {"@onmatch": "include",
+                    "Image": {"@condition": "invalid_operator", "$text": "b.exe"}}}}}"#,
+        )
+        .unwrap();
+
+        let error = ConfigMerger::from_configs(&[valid.clone(), invalid.clone()]).unwrap_err();
+        assert!(error.to_string().contains("config 2"), "{}", error);
+
+        let mut merger = ConfigMerger::new().with_skip_invalid_rules(true);
+        merger.add_config(&valid, "valid").unwrap();
+        merger.add_config(&invalid, "invalid").unwrap();
+        assert_eq!(merger.skipped_rule_count(), 1);
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        assert!(xml.contains("a.exe") && !xml.contains("b.exe"), "{}", xml);
+    }
+
+    #[test]
+    fn test_merge_self_closing_events() {
+        let mut merger = ConfigMerger::new();
+        merger
+            .add_xml_str(
+                r#"<Sysmon schemaversion="4.50">
+                    <EventFiltering>
+                        <RuleGroup name="quiet" groupRelation="or">
+                            <NetworkConnect onmatch="exclude"/>
+                            <ProcessCreate onmatch="include">
+                                <Image condition="is">a.exe</Image>
+                            </ProcessCreate>
+                        </RuleGroup>
+                    </EventFiltering>
+                </Sysmon>"#,
+                "quiet.xml",
+            )
+            .unwrap();
+
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        assert!(xml.contains(r#"<NetworkConnect onmatch="exclude">"#), "{}", xml);
+        assert!(xml.contains("a.exe"), "{}", xml);
+    }
+
+    #[test]
+    fn test_merge_other_text_key() {
+        // As written with `XmlToJsonOptions::text_key` set to `#text`
+        let config: Value = serde_json::from_str(
+            r##"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {
+                "@name": "processes", "@groupRelation": "or",
+                "ProcessCreate": {"@onmatch": "include",
+                    "Image": {"@condition": "is", "#text": "a.exe"}}}}}"##,
+        )
+        .unwrap();
+
+        let mut merger = ConfigMerger::with_options(MergeOptionsBuilder::new().validate(false).build());
+        let error = merger.add_config(&config, "badgerfish").unwrap_err();
+        assert!(matches!(error, ConversionError::InvalidFile(_)));
+        assert!(error.to_string().contains("#text"), "{}", error);
+        assert_eq!(merger.stats().files, 0);
+    }
+
+    #[test]
+    fn test_merge_inferred_types() {
+        // As read with `XmlToJsonOptions::infer_types`
+        let config = |port: &str| -> Value {
+            serde_json::from_str(&format!(
+                r#"{{"@schemaversion": "4.50", "EventFiltering": {{"RuleGroup": {{
+                    "@name": "ports", "@groupRelation": "or",
+                    "NetworkConnect": {{"@onmatch": "include",
+                        "DestinationPort": {{"@condition": "is", "$text": {}}}}}}}}}}}"#,
+                port
+            ))
+            .unwrap()
+        };
+
+        let merger = ConfigMerger::from_configs(&[config("4444"), config(r#""4444""#)]).unwrap();
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        assert_eq!(xml.matches(">4444</DestinationPort>").count(), 1, "{}", xml);
+    }
+
+    #[test]
+    fn test_log_sink() {
+        use std::sync::{Arc, Mutex};
+
+        let temp_dir = tempdir().unwrap();
+        let good = temp_dir.path().join("good.xml");
+        fs::write(
+            &good,
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="good" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="is">good.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+        let bad = temp_dir.path().join("bad.json");
+        fs::write(&bad, "{ not json").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let events = Arc::clone(&events);
+            move |event: MergeEvent| events.lock().unwrap().push(event)
+        };
+        let options = MergeOptionsBuilder::new().on_invalid(InvalidFilePolicy::Skip).build();
+        let mut merger = ConfigMerger::with_options(options).with_log_sink(sink);
+        merger.merge_files(&[good.clone(), bad.clone()]).unwrap();
+        {
+            let events = events.lock().unwrap();
+            assert_eq!(events.len(), 4, "{:?}", events);
+            assert_eq!(events[0], MergeEvent::FileStarted(good.clone()));
+            assert_eq!(events[1], MergeEvent::FileCompleted { path: good.clone(), rules_added: 1 });
+            assert_eq!(events[2], MergeEvent::FileStarted(bad.clone()));
+            assert!(matches!(&events[3], MergeEvent::FileSkipped { path, .. } if *path == bad));
+        }
+
+        // The sink outlives `reset`, and sees the failure without the policy
+        events.lock().unwrap().clear();
+        merger.options.on_invalid = InvalidFilePolicy::default();
+        assert!(merger.merge_files(&[bad.clone()]).is_err());
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(matches!(&events[1], MergeEvent::FileFailed { path, .. } if *path == bad));
+    }
+
+    #[test]
+    fn test_streamed_output_matches_in_memory() {
+        let input_dir = tempdir().unwrap();
+        for file in 0..4 {
+            let groups: String = (0..500)
+                .map(|group| format!(
+                    r#"<RuleGroup name="group {file}-{group}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">tool{file}_{group}.exe</Image>
+                            <CommandLine condition="contains">--flag-{group}</CommandLine>
+                        </ProcessCreate>
+                    </RuleGroup>"#
+                ))
+                .collect();
+            let config = format!(
+                r#"<Sysmon schemaversion="4.50"><EventFiltering>{groups}</EventFiltering></Sysmon>"#
+            );
+            fs::write(input_dir.path().join(format!("{file}.xml")), config).unwrap();
+        }
+
+        let output_dir = tempdir().unwrap();
+        for strategy in [MergeStrategy::Flatten, MergeStrategy::PreserveGroups] {
+            let options = MergeOptionsBuilder::new().strategy(strategy).build();
+            let output = output_dir.path().join("merged.xml");
+            merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+
+            let mut merger = ConfigMerger::with_options(options);
+            let expected = merged_config_to_xml(&merger.merge_directory(input_dir.path(), false).unwrap()).unwrap();
+            assert_eq!(fs::read_to_string(&output).unwrap(), expected);
+        }
+        // Only the output is left behind
+        let names: Vec<_> = fs::read_dir(output_dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["merged.xml"]);
+    }
+
+    #[test]
+    fn test_merge_to_value() {
+        let input_dir = tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="a" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        let merged = merge_configs_with_value(input_dir.path(), &output, false).unwrap();
+        let xml = merged_config_to_xml(&merged).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), xml);
+
+        // Nothing is written
+        fs::remove_file(&output).unwrap();
+        let merged = merge_to_value(input_dir.path(), false).unwrap();
+        assert_eq!(merged_config_to_xml(&merged).unwrap(), xml);
+        assert_eq!(fs::read_dir(output_dir.path()).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(input_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_merge_directory_logs() {
+        use log::{Level, Log, Metadata, Record};
+        use std::sync::Mutex;
+        use std::thread::{self, ThreadId};
+
+        /// Keeps every message of the merger with the thread that logged
+        /// it, since tests run in parallel
+        struct CapturingLogger(Mutex<Vec<(ThreadId, Level, String)>>);
+
+        impl Log for CapturingLogger {
+            fn enabled(&self, _metadata: &Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &Record) {
+                if !record.target().ends_with("::merger") {
+                    return;
+                }
+                let entry = (thread::current().id(), record.level(), record.args().to_string());
+                self.0.lock().unwrap().push(entry);
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let input_dir = tempdir().unwrap();
+        let xml_path = input_dir.path().join("a.xml");
+        fs::write(
+            &xml_path,
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="a" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+        ConfigMerger::new().merge_directory(input_dir.path(), false).unwrap();
+
+        let id = thread::current().id();
+        let messages: Vec<(Level, String)> = LOGGER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(thread, ..)| *thread == id)
+            .map(|(_, level, message)| (*level, message.clone()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (Level::Info, format!("Processing XML file: {:?}", xml_path)),
+                (Level::Debug, "Number of rules collected: 1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_merge_keeps_existing_output() {
+        let input_dir = tempdir().unwrap();
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        fs::write(&output, "previous merged config").unwrap();
+
+        // JSON inputs are only checked once merged
+        fs::write(
+            input_dir.path().join("bad.json"),
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {"@name": "bad",
+                "@groupRelation": "or", "ProcessCreate": {"@onmatch": "include",
+                    "Image": {"@condition": "invalid_operator", "$text": "cmd.exe"}}}}}"#,
+        )
+        .unwrap();
+        #[cfg(feature = "validation")]
+        {
+            assert!(merge_configs(input_dir.path(), &output, false).is_err());
+            assert_eq!(fs::read_to_string(&output).unwrap(), "previous merged config");
+        }
+
+        // A good merge replaces the output whole, leaving no temporary file
+        fs::write(
+            input_dir.path().join("bad.json"),
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {"@name": "good",
+                "@groupRelation": "or", "ProcessCreate": {"@onmatch": "include",
+                    "Image": {"@condition": "end with", "$text": "cmd.exe"}}}}}"#,
+        )
+        .unwrap();
+        let options = MergeOptionsBuilder::new().sync_directory(true).build();
+        merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+        assert!(fs::read_to_string(&output).unwrap().contains("cmd.exe"));
+        assert_eq!(fs::read_dir(output_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_merge_directory_is_deterministic() {
+        let config = |name: &str, image: &str| format!(
+            r#"<Sysmon schemaversion="4.30">
+                <HashAlgorithms>sha256</HashAlgorithms>
+                <EventFiltering>
+                    <RuleGroup name="{name}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">{image}</Image>
+                            <CommandLine condition="contains">{name}</CommandLine>
+                        </ProcessCreate>
+                        <NetworkConnect onmatch="exclude">
+                            <Image condition="is">{image}</Image>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let files = [
+            ("alpha.xml", config("alpha", "a.exe")),
+            ("bravo.xml", config("bravo", "b.exe")),
+            ("charlie.xml", config("charlie", "c.exe")),
+            ("delta.xml", config("delta", "d.exe")),
+        ];
+
+        let temp_dir = tempdir().unwrap();
+        let mut outputs = Vec::new();
+        for (run, order) in [[2, 0, 3, 1], [3, 2, 1, 0], [1, 3, 0, 2]].iter().enumerate() {
+            let input_dir = temp_dir.path().join(format!("input{}", run));
+            fs::create_dir(&input_dir).unwrap();
+            for &i in order {
+                let (name, content) = &files[i];
+                fs::write(input_dir.join(name), content).unwrap();
+            }
+
+            let output_file = temp_dir.path().join(format!("merged{}.xml", run));
+            merge_configs(&input_dir, &output_file, false).unwrap();
+            outputs.push(fs::read(&output_file).unwrap());
+        }
+
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[0], outputs[2]);
+        let merged = String::from_utf8(outputs.remove(0)).unwrap();
+        assert!(merged.find("a.exe").unwrap() < merged.find("d.exe").unwrap());
+    }
+
+    #[test]
+    fn test_merge_configs_report() {
+        let input_dir = tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="a" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                            <Image condition="end with">powershell.exe</Image>
+                        </ProcessCreate>
+                        <NetworkConnect onmatch="exclude">
+                            <DestinationPort condition="is">443</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        ).unwrap();
+        fs::write(
+            input_dir.path().join("b.xml"),
+            r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="b" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">CMD.EXE</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="c" groupRelation="or">
+                        <ProcessCreate onmatch="exclude">
+                            <Image condition="end with">powershell.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        ).unwrap();
+
+        let report = merge_configs_report(input_dir.path(), false, &MergeOptions::default()).unwrap();
+        assert_eq!(fs::read_dir(input_dir.path()).unwrap().count(), 2);
+
+        assert_eq!(report.stats.files, 2);
+        assert_eq!(report.stats.duplicates_removed, 1);
+        assert_eq!(report.stats.rule_stats.total, 4);
+        assert_eq!(report.stats.rule_stats.by_event_type["ProcessCreate"], 3);
+        assert_eq!(report.stats.rule_stats.by_event_type["NetworkConnect"], 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].value, "powershell.exe");
+        let versions: Vec<_> = report.schema_versions.iter().map(|(_, v)| v.as_str()).collect();
+        assert_eq!(versions, ["4.50", "4.30"]);
+        assert!(report.warnings.is_empty());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["stats"]["event_types"], serde_json::json!(["ProcessCreate", "NetworkConnect"]));
+        assert_eq!(json["conflicts"][0]["field"], "Image");
+
+        // Writing the merged config reports the same
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        let written = merge_configs(input_dir.path(), &output, false).unwrap();
+        assert!(output.exists());
+        assert_eq!(serde_json::to_value(&written).unwrap(), json);
+    }
+
+    #[test]
+    fn test_skip_invalid_files() {
+        let config = |name: &str| format!(
+            r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="{name}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">{name}.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let input_dir = tempdir().unwrap();
+        fs::write(input_dir.path().join("a.xml"), config("good1")).unwrap();
+        // The first group is read before the broken element
+        let broken = config("broken").replace("</EventFiltering>", "<RuleGroup></Broken></EventFiltering>");
+        fs::write(input_dir.path().join("b.xml"), broken).unwrap();
+        fs::write(input_dir.path().join("c.json"), "{ not json").unwrap();
+        fs::write(input_dir.path().join("d.xml"), config("good2")).unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        assert!(merge_configs(input_dir.path(), &output, false).is_err());
+
+        let options = MergeOptionsBuilder::new()
+            .on_invalid(InvalidFilePolicy::Skip)
+            .build();
+        let report = merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+        let merged = fs::read_to_string(&output).unwrap();
+        assert!(merged.contains("good1.exe"), "{}", merged);
+        assert!(merged.contains("good2.exe"), "{}", merged);
+        assert!(!merged.contains("broken.exe"), "{}", merged);
+
+        assert_eq!(report.stats.files, 2);
+        let skipped: Vec<_> = report.skipped_files.iter().map(|(path, _)| Path::new(path)).collect();
+        assert_eq!(skipped, [input_dir.path().join("b.xml"), input_dir.path().join("c.json")]);
+        assert!(report.skipped_files.iter().all(|(_, error)| !error.is_empty()));
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn test_skip_invalid_json_files() {
+        let config = |name: &str, onmatch: &str| format!(
+            r#"{{"@schemaversion": "4.50", "EventFiltering": {{"RuleGroup": {{"@name": "{name}",
+                "@groupRelation": "or", "ProcessCreate": {{"@onmatch": "{onmatch}",
+                    "Image": {{"@condition": "end with", "$text": "{name}.exe"}}}}}}}}}}"#
+        );
+        let input_dir = tempdir().unwrap();
+        fs::write(input_dir.path().join("a.json"), config("good1", "include")).unwrap();
+        fs::write(input_dir.path().join("b.json"), config("broken", "sometimes")).unwrap();
+        fs::write(input_dir.path().join("c.json"), config("good2", "include")).unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        let error = merge_configs(input_dir.path(), &output, false).unwrap_err();
+        assert!(error.to_string().contains("b.json"), "{}", error);
+
+        let options = MergeOptionsBuilder::new()
+            .on_invalid(InvalidFilePolicy::Skip)
+            .build();
+        let report = merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+        let merged = fs::read_to_string(&output).unwrap();
+        assert!(merged.contains("good1.exe") && merged.contains("good2.exe"), "{}", merged);
+        assert!(!merged.contains("broken.exe"), "{}", merged);
+
+        assert_eq!(report.stats.files, 2);
+        let skipped: Vec<_> = report.skipped_files.iter().map(|(path, _)| Path::new(path)).collect();
+        assert_eq!(skipped, [input_dir.path().join("b.json")]);
+    }
+
+    #[test]
+    fn test_merge_keeps_and_groups() {
+        let input_dir = tempdir().unwrap();
+        let output_dir = tempdir().unwrap();
+
+        // Flattened into the `or` groups, this would log every powershell.exe
+        // and every command line containing -enc
+        fs::write(
+            input_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="encoded" groupRelation="and">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">powershell.exe</Image>
+                            <CommandLine condition="contains">-enc</CommandLine>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        ).unwrap();
+        fs::write(
+            input_dir.path().join("b.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="shells" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        ).unwrap();
+
+        for strategy in [MergeStrategy::Flatten, MergeStrategy::GroupPerFile, MergeStrategy::PreserveGroups] {
+            let options = MergeOptions {
+                strategy,
+                ..Default::default()
+            };
+            let output = output_dir.path().join("merged.xml");
+            merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+
+            let xml = fs::read_to_string(&output).unwrap();
+            #[cfg(feature = "validation")]
+            validate_xml(&xml, "merged.xml").unwrap();
+            let relation = |value: &str| {
+                let rules = crate::document::SysmonDocument::load(&output).unwrap().rules();
+                rules.into_iter().find(|r| r.value == value).unwrap().group_relation
+            };
+            assert_eq!(relation("powershell.exe").as_deref(), Some("and"), "{:?}: {}", strategy, xml);
+            assert_eq!(relation("-enc").as_deref(), Some("and"), "{:?}: {}", strategy, xml);
+            assert_eq!(relation("cmd.exe").as_deref(), Some("or"), "{:?}: {}", strategy, xml);
+            assert!(xml.contains(r#"<Rule groupRelation="and" name="encoded">"#), "{}", xml);
+        }
+    }
+
+    #[test]
+    fn test_merge_compound_rules() {
+        let config = |rules: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="processes" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                            {rules}
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let input_dir = tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("a.xml"),
+            config(
+                r#"<Rule name="encoded" groupRelation="and">
+                    <Image condition="end with">powershell.exe</Image>
+                    <CommandLine condition="contains">-enc</CommandLine>
+                </Rule>"#,
+            ),
+        )
+        .unwrap();
+        // The same compound rule under another name and in another order,
+        // and a rule of its own
+        fs::write(
+            input_dir.path().join("b.xml"),
+            config(
+                r#"<Rule name="encoded again" groupRelation="and">
+                    <CommandLine condition="contains">-ENC</CommandLine>
+                    <Image condition="end with">PowerShell.exe</Image>
+                </Rule>
+                <Rule name="scripts" groupRelation="and">
+                    <Image condition="end with">wscript.exe</Image>
+                    <CommandLine condition="contains">.vbs</CommandLine>
+                </Rule>"#,
+            ),
+        )
+        .unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let xml_output = output_dir.path().join("merged.xml");
+        let report = merge_configs(input_dir.path(), &xml_output, false).unwrap();
+        assert_eq!(report.stats.duplicates_removed, 2);
+        assert!(report.conflicts.is_empty());
+
+        let xml = fs::read_to_string(&xml_output).unwrap();
+        #[cfg(feature = "validation")]
+        validate_xml(&xml, "merged.xml").unwrap();
+        assert!(xml.contains(r#"<Rule groupRelation="and" name="encoded">"#), "{}", xml);
+        assert!(xml.contains(r#"<Rule groupRelation="and" name="scripts">"#), "{}", xml);
+        assert!(!xml.contains("encoded again"), "{}", xml);
+        let rules = crate::document::SysmonDocument::load(&xml_output).unwrap().rules();
+        for value in ["powershell.exe", "-enc", "wscript.exe", ".vbs"] {
+            let rule = rules.iter().find(|r| r.value == value).unwrap();
+            assert_eq!(rule.group_relation.as_deref(), Some("and"), "{}: {}", value, xml);
+        }
+
+        let json_output = output_dir.path().join("merged.json");
+        merge_configs(input_dir.path(), &json_output, false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_output).unwrap()).unwrap();
+        let process_create = &json["EventFiltering"]["RuleGroup"][0]["ProcessCreate"];
+        let compound = process_create["Rule"].as_array().unwrap();
+        assert_eq!(compound.len(), 2, "{}", json);
+        assert_eq!(compound[0]["@name"], "encoded");
+        assert_eq!(compound[0]["@groupRelation"], "and");
+        assert_eq!(compound[0]["Image"]["$text"], "powershell.exe");
+        assert_eq!(compound[0]["CommandLine"]["$text"], "-enc");
+        assert_eq!(compound[1]["@name"], "scripts");
+        assert_eq!(process_create["Image"]["$text"], "cmd.exe");
+
+        // A compound rule both included and excluded is a conflict
+        fs::write(
+            input_dir.path().join("c.xml"),
+            config(
+                r#"<Rule name="encoded" groupRelation="and">
+                    <Image condition="end with">powershell.exe</Image>
+                    <CommandLine condition="contains">-enc</CommandLine>
+                </Rule>"#,
+            )
+            .replace(r#"onmatch="include""#, r#"onmatch="exclude""#),
+        )
+        .unwrap();
+        let report = merge_configs_report(input_dir.path(), false, &MergeOptions::default()).unwrap();
+        let conflicts: Vec<_> = report.conflicts.iter().filter(|c| c.field == "Rule").collect();
+        assert_eq!(conflicts.len(), 1, "{:?}", report.conflicts);
+        assert_eq!(conflicts[0].condition, "and");
+        assert_eq!(conflicts[0].value, r#"CommandLine contains "-enc"; Image end with "powershell.exe""#);
+    }
+
+    #[test]
+    fn test_duplicate_filter_policies() {
+        let group = |name: &str, relation: &str, onmatch: &str, images: &[&str]| serde_json::json!({
+            "@name": name,
+            "@groupRelation": relation,
+            "ProcessCreate": {
+                "@onmatch": onmatch,
+                "Image": images
+                    .iter()
+                    .map(|image| serde_json::json!({ "@condition": "end with", "$text": image }))
+                    .collect::<Vec<_>>()
+            }
+        });
+        let mut tools = group("tools", "or", "include", &["psexec.exe"]);
+        tools["NetworkConnect"] = serde_json::json!({
+            "@onmatch": "include",
+            "DestinationPort": { "@condition": "is", "$text": "4444" }
+        });
+        let config = serde_json::json!({
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": [
+                    group("shells", "or", "include", &["cmd.exe"]),
+                    tools,
+                    group("pairs", "and", "include", &["a.exe", "b.exe"]),
+                    group("noise", "or", "exclude", &["noisy.exe"]),
+                ]
+            }
+        });
+        let merge = |policy: DuplicateFilterPolicy| {
+            let options = MergeOptionsBuilder::new()
+                .strategy(MergeStrategy::PreserveGroups)
+                .duplicate_filter_policy(policy)
+                .build();
+            let mut merger = ConfigMerger::with_options(options);
+            merger.add_json_value(config.clone(), "config.json").unwrap();
+            merger.finish()
+        };
+
+        let merged = merge(DuplicateFilterPolicy::Consolidate).unwrap();
+        let doc = crate::document::SysmonDocument::from_value(merged);
+        let mut filters: Vec<_> = doc
+            .rules()
+            .into_iter()
+            .map(|r| (r.event_type, r.onmatch.unwrap_or_default(), r.rule_group.unwrap_or_default()))
+            .collect();
+        filters.sort();
+        filters.dedup();
+        // One group per event type and onmatch, keeping the `and` pair together
+        assert_eq!(
+            filters,
+            [
+                ("NetworkConnect".to_string(), "include".to_string(), "tools".to_string()),
+                ("ProcessCreate".to_string(), "exclude".to_string(), "noise".to_string()),
+                ("ProcessCreate".to_string(), "include".to_string(), "MergedRules ProcessCreate include".to_string()),
+            ]
+        );
+        let pair: Vec<_> = doc.rules().into_iter().filter(|r| r.value == "a.exe" || r.value == "b.exe").collect();
+        assert_eq!(pair.len(), 2);
+        assert!(pair.iter().all(|r| r.group_relation.as_deref() == Some("and")));
+
+        let error = merge(DuplicateFilterPolicy::Error).unwrap_err();
+        assert!(matches!(error, ConversionError::ValidationError(_)));
+        let message = error.to_string();
+        assert!(message.contains("ProcessCreate onmatch=include is filtered by shells, tools, pairs"), "{}", message);
+        assert!(!message.contains("NetworkConnect"), "{}", message);
+    }
+
+    #[test]
+    fn test_merge_observer() {
+        use std::cell::RefCell;
+
+        #[derive(Default)]
+        struct Recorder(RefCell<Vec<String>>);
+
+        impl MergeObserver for Recorder {
+            fn on_phase(&self, phase: MergePhase) {
+                self.0.borrow_mut().push(format!("{:?}", phase));
+            }
+
+            fn on_file_start(&self, path: &Path) {
+                let name = path.file_name().unwrap().to_string_lossy();
+                self.0.borrow_mut().push(format!("start {}", name));
+            }
+
+            fn on_file_done(&self, path: &Path, rules_added: usize) {
+                let name = path.file_name().unwrap().to_string_lossy();
+                self.0.borrow_mut().push(format!("done {} {}", name, rules_added));
+            }
+
+            fn on_warning(&self, warning: MergeWarning) {
+                self.0.borrow_mut().push(format!("warning {}", warning));
+            }
+        }
+
+        let input_dir = tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="a" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="b" groupRelation="or">
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">443</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+        fs::write(
+            input_dir.path().join("b.json"),
+            r#"{"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": [{"@name": "c",
+                "@groupRelation": "or", "ProcessCreate": {"@onmatch": "exclude",
+                    "Image": {"@condition": "end with", "$text": "cmd.exe"}}}]}}"#,
+        )
+        .unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let output = output_dir.path().join("merged.xml");
+        let recorder = Recorder::default();
+        merge_configs_with_options(input_dir.path(), &output, false, &MergeOptions::default(), Some(&recorder))
+            .unwrap();
+
+        let mut expected = vec![
+                "Scan".to_string(),
+                "Parse".to_string(),
+                "start a.xml".to_string(),
+                "done a.xml 2".to_string(),
+                "start b.json".to_string(),
+                "done b.json 1".to_string(),
+                "Combine".to_string(),
+                format!(
+                    "warning ProcessCreate Image end with \"cmd.exe\" is included by {} and excluded by {}",
+                    input_dir.path().join("a.xml").display(),
+                    input_dir.path().join("b.json").display()
+                ),
+                "Write".to_string(),
+        ];
+        if cfg!(feature = "validation") {
+            expected.push("Validate".to_string());
+        }
+        assert_eq!(recorder.0.into_inner(), expected);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn test_merge_validates_once() {
+        use std::cell::RefCell;
+
+        #[derive(Default)]
+        struct Phases(RefCell<Vec<MergePhase>>);
+
+        impl MergeObserver for Phases {
+            fn on_phase(&self, phase: MergePhase) {
+                self.0.borrow_mut().push(phase);
+            }
+        }
+
+        let input_dir = tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="a" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+
+        let output_dir = tempdir().unwrap();
+        let options = MergeOptionsBuilder::new().annotate_sources(true).build();
+        for name in ["merged.xml", "merged.json"] {
+            let output = output_dir.path().join(name);
+            let phases = Phases::default();
+            merge_configs_with_options(input_dir.path(), &output, false, &options, Some(&phases)).unwrap();
+            assert_eq!(
+                phases.0.into_inner(),
+                vec![MergePhase::Scan, MergePhase::Parse, MergePhase::Combine, MergePhase::Write, MergePhase::Validate],
+                "{}",
+                name
+            );
+            assert!(fs::read_to_string(&output).unwrap().contains("cmd.exe"));
+        }
+    }
+
+    #[test]
+    fn test_merge_globs() {
+        let config = |image: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{image}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">{image}</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        let input_dir = tempdir().unwrap();
+        for (path, image) in [
+            ("top.xml", "top.exe"),
+            ("top.draft.xml", "topdraft.exe"),
+            ("windows/base.xml", "windows.exe"),
+            ("windows/new.draft.xml", "draft.exe"),
+            ("archive/old.xml", "old.exe"),
+        ] {
+            let path = input_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, config(image)).unwrap();
+        }
+        let merged_images = |options: &MergeOptions| {
+            let output_dir = tempdir().unwrap();
+            let output = output_dir.path().join("merged.xml");
+            let progress = ProgressReporter::new(5);
+            merge_configs_with_options(input_dir.path(), &output, true, options, Some(&progress)).unwrap();
+            let mut images: Vec<_> = crate::document::SysmonDocument::load(&output)
+                .unwrap()
+                .rules()
+                .into_iter()
+                .map(|rule| rule.value)
+                .collect();
+            images.sort();
+            (images, progress.processed())
+        };
+
+        let drafts = MergeOptionsBuilder::new()
+            .exclude_globs(vec!["**/*.draft.xml".to_string()])
+            .build();
+        let report = merge_configs_report(input_dir.path(), true, &drafts).unwrap();
+        assert_eq!((report.stats.files, report.stats.excluded_files), (3, 2));
+        assert_eq!(merged_images(&drafts), (vec!["old.exe".to_string(), "top.exe".into(), "windows.exe".into()], 3));
+
+        let windows = MergeOptionsBuilder::new()
+            .include_globs(vec!["windows/**".to_string()])
+            .exclude_globs(vec!["**/*.draft.xml".to_string()])
+            .build();
+        let report = merge_configs_report(input_dir.path(), true, &windows).unwrap();
+        assert_eq!((report.stats.files, report.stats.excluded_files), (1, 4));
+        assert_eq!(merged_images(&windows), (vec!["windows.exe".to_string()], 1));
+
+        let invalid = MergeOptionsBuilder::new()
+            .exclude_globs(vec!["[".to_string()])
+            .build();
+        let error = merge_configs_report(input_dir.path(), true, &invalid).unwrap_err();
+        assert!(matches!(error, ConversionError::ValidationError(_)));
+    }
+
+    #[test]
+    fn test_merge_directory_hidden_entries() {
+        let input_dir = tempdir().unwrap();
+        let config = |image: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{image}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">{image}</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        fs::create_dir(input_dir.path().join(".git")).unwrap();
+        fs::write(input_dir.path().join("a.xml"), config("a.exe")).unwrap();
+        fs::write(input_dir.path().join(".git").join("b.xml"), config("b.exe")).unwrap();
+
+        let report = merge_configs_report(input_dir.path(), true, &MergeOptions::default()).unwrap();
+        assert_eq!(report.stats.files, 1);
+
+        let hidden = MergeOptionsBuilder::new().include_hidden(true).build();
+        let report = merge_configs_report(input_dir.path(), true, &hidden).unwrap();
+        assert_eq!(report.stats.files, 2);
+    }
+
+    #[test]
+    fn test_merge_keeps_onmatch() {
+        let input_dir = tempdir().unwrap();
+        let output_dir = tempdir().unwrap();
+
+        let config = |onmatch: &str, image: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{onmatch}" groupRelation="or">
+                        <ProcessCreate onmatch="{onmatch}">
+                            <Image condition="image">{image}</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#
+        );
+        fs::write(input_dir.path().join("a.xml"), config("include", "cmd.exe")).unwrap();
+        fs::write(input_dir.path().join("b.xml"), config("exclude", "noisy.exe")).unwrap();
+
+        // Writing XML validates the merged config
+        let output = output_dir.path().join("merged.xml");
+        merge_configs(input_dir.path(), &output, false).unwrap();
+
+        let doc = crate::document::SysmonDocument::load(&output).unwrap();
+        let mut rules: Vec<_> = doc
+            .rules()
+            .into_iter()
+            .map(|r| (r.event_type, r.onmatch, r.value))
+            .collect();
+        rules.sort();
+        assert_eq!(
+            rules,
+            vec![
+                ("ProcessCreate".to_string(), Some("exclude".to_string()), "noisy.exe".to_string()),
+                ("ProcessCreate".to_string(), Some("include".to_string()), "cmd.exe".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_strategies() {
+        let input_dir = tempdir().unwrap();
+        let output_dir = tempdir().unwrap();
+
+        let xml = r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="shells" groupRelation="and">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="image">cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                    <RuleGroup name="noise" groupRelation="or">
+                        <ProcessCreate onmatch="exclude">
+                            <Image condition="image">noisy.exe</Image>
+                        </ProcessCreate>
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">4444</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#;
+        let json = r#"{
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "more shells",
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "image", "$text": "powershell.exe" }
+                    }
+                }
+            }
+        }"#;
+        fs::write(input_dir.path().join("a.xml"), xml).unwrap();
+        fs::write(input_dir.path().join("b.json"), json).unwrap();
+
+        let merge = |strategy: MergeStrategy| {
+            let options = MergeOptions {
+                strategy,
+                ..Default::default()
+            };
+            let output = output_dir.path().join("merged.xml");
+            merge_configs_with_options(input_dir.path(), &output, false, &options, None).unwrap();
+            let doc = crate::document::SysmonDocument::load(&output).unwrap();
+            let mut rules: Vec<_> = doc
+                .rules()
+                .into_iter()
+                .map(|r| (r.rule_group.unwrap_or_default(), r.onmatch.unwrap_or_default(), r.value))
+                .collect();
+            rules.sort();
+            (rules, fs::read_to_string(&output).unwrap())
+        };
+        let rule = |group: &str, onmatch: &str, value: &str| {
+            (group.to_string(), onmatch.to_string(), value.to_string())
+        };
+
+        let (rules, _) = merge(MergeStrategy::Flatten);
+        assert_eq!(
+            rules,
+            vec![
+                rule("MergedRules NetworkConnect include", "include", "4444"),
+                rule("MergedRules ProcessCreate exclude", "exclude", "noisy.exe"),
+                rule("MergedRules ProcessCreate include", "include", "cmd.exe"),
+                rule("MergedRules ProcessCreate include", "include", "powershell.exe"),
+            ]
+        );
+
+        // Both inputs include ProcessCreate events, which Sysmon only
+        // honors in one group
+        let (rules, xml) = merge(MergeStrategy::PreserveGroups);
+        assert_eq!(
+            rules,
+            vec![
+                rule("MergedRules ProcessCreate include", "include", "cmd.exe"),
+                rule("MergedRules ProcessCreate include", "include", "powershell.exe"),
+                rule("noise", "exclude", "noisy.exe"),
+                rule("noise", "include", "4444"),
+            ]
+        );
+        assert!(xml.contains(r#"groupRelation="or" name="noise""#), "{}", xml);
+
+        let (rules, _) = merge(MergeStrategy::GroupPerFile);
+        assert_eq!(
+            rules,
+            vec![
+                rule("MergedRules ProcessCreate include", "include", "cmd.exe"),
+                rule("MergedRules ProcessCreate include", "include", "powershell.exe"),
+                rule("a.xml", "exclude", "noisy.exe"),
+                rule("a.xml", "include", "4444"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let config = |image: &str, other: &str| serde_json::json!({
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": other,
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": [
+                            { "@condition": "is", "$text": image },
+                            { "@condition": "is", "$text": other }
+                        ]
+                    }
+                }
+            }
+        });
+        let inputs = [
+            config(r"C:\Windows\System32\cmd.exe", "a.exe"),
+            config(r"c:/windows/system32/CMD.EXE", "b.exe"),
+            config(r"C:\Windows\System32\cmd.exe", "c.exe"),
+        ];
+        let merge = |options: MergeOptions| {
+            let mut merger = ConfigMerger::with_options(options);
+            for (i, input) in inputs.iter().enumerate() {
+                merger.add_json_value(input.clone(), &format!("{}.json", i)).unwrap();
+            }
+            let merged = merger.finish().unwrap();
+            let rules = crate::document::SysmonDocument::from_value(merged).rules();
+            (merger.stats(), rules)
+        };
+
+        let (stats, rules) = merge(MergeOptions::default());
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.event_types, vec![EventType::ProcessCreate]);
+        assert!(stats.coverage_percentage() > 0.0);
+        assert_eq!(stats.duplicates_removed, 2);
+        let cmd: Vec<_> = rules.iter().filter(|r| r.value.to_lowercase().contains("cmd.exe")).collect();
+        assert_eq!(cmd.len(), 1);
+        assert_eq!(cmd[0].value, r"C:\Windows\System32\cmd.exe");
+        assert_eq!(rules.len(), 4);
+        assert_eq!(stats.rule_stats.total, 4);
+        assert_eq!(stats.rule_stats.by_condition.get("is"), Some(&4));
+
+        let (stats, rules) = merge(MergeOptions {
+            dedupe: false,
+            ..Default::default()
+        });
+        assert_eq!(stats.duplicates_removed, 0);
+        assert_eq!(rules.len(), 6);
+
+        // Exclude filters and filters combined with `and` are not duplicates
+        let mut merger = ConfigMerger::new();
+        let mut exclude = config("x.exe", "y.exe");
+        exclude["EventFiltering"]["RuleGroup"]["ProcessCreate"]["@onmatch"] = "exclude".into();
+        let mut and_group = config("x.exe", "z.exe");
+        and_group["EventFiltering"]["RuleGroup"]["@groupRelation"] = "and".into();
+        merger.add_json_value(config("x.exe", "y.exe"), "a.json").unwrap();
+        merger.add_json_value(exclude, "b.json").unwrap();
+        merger.add_json_value(and_group, "c.json").unwrap();
+        assert_eq!(merger.stats().duplicates_removed, 0);
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let config = |onmatch: &str, image: &str| serde_json::json!({
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": onmatch,
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": onmatch,
+                        "Image": [
+                            { "@condition": "is", "$text": image },
+                            { "@condition": "is", "$text": format!("{}.exe", onmatch) }
+                        ]
+                    }
+                }
+            }
+        });
+        let merger = |policy: ConflictPolicy| {
+            let options = MergeOptions {
+                conflict_policy: policy,
+                ..Default::default()
+            };
+            let mut merger = ConfigMerger::with_options(options);
+            merger
+                .add_json_value(config("include", r"C:\Windows\System32\wscript.exe"), "a.json")
+                .unwrap();
+            merger
+                .add_json_value(config("exclude", r"c:\windows\system32\WScript.exe"), "b.json")
+                .unwrap();
+            merger
+        };
+        let wscript = |merger: &ConfigMerger| {
+            let merged = merger.finish().unwrap();
+            let mut onmatch: Vec<_> = crate::document::SysmonDocument::from_value(merged)
+                .rules()
+                .into_iter()
+                .filter(|r| r.value.to_lowercase().contains("wscript"))
+                .map(|r| r.onmatch.unwrap())
+                .collect();
+            onmatch.sort();
+            onmatch
+        };
+
+        let warn = merger(ConflictPolicy::Warn);
+        let report = warn.report();
+        assert_eq!(
+            report.conflicts,
+            vec![MergeConflict {
+                event_type: "ProcessCreate".to_string(),
+                field: "Image".to_string(),
+                condition: "is".to_string(),
+                value: r"C:\Windows\System32\wscript.exe".to_string(),
+                include_source: "a.json".to_string(),
+                exclude_source: "b.json".to_string(),
+            }]
+        );
+        assert_eq!(report.stats.files, 2);
+        assert_eq!(wscript(&warn), vec!["exclude", "include"]);
+
+        let error = merger(ConflictPolicy::Error).finish().unwrap_err();
+        assert!(error.to_string().contains("wscript.exe"), "{}", error);
+
+        assert_eq!(wscript(&merger(ConflictPolicy::IncludeWins)), vec!["include"]);
+        assert_eq!(wscript(&merger(ConflictPolicy::ExcludeWins)), vec!["exclude"]);
+
+        // The other filters of each event are kept
+        let merged = merger(ConflictPolicy::IncludeWins).finish().unwrap();
+        let rules = crate::document::SysmonDocument::from_value(merged).rules();
+        assert_eq!(rules.len(), 3);
+    }
+
+    #[test]
+    fn test_schema_version_policies() {
+        let config = |version: &str, image: &str| serde_json::json!({
+            "@schemaversion": version,
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": image,
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "image", "$text": image }
+                    }
+                }
+            }
+        });
+        let merge = |policy: SchemaVersionPolicy, versions: [&str; 2]| {
+            let options = MergeOptions {
+                schema_version_policy: policy,
+                ..Default::default()
+            };
+            let mut merger = ConfigMerger::with_options(options);
+            merger.add_json_value(config(versions[0], "a.exe"), "a.json").unwrap();
+            merger.add_json_value(config(versions[1], "b.exe"), "b.json").unwrap();
+            let version = merger.finish().map(|merged| match merged {
+                Value::Object(root) => match root.get("@schemaversion") {
+                    Some(Value::String(version)) => version.clone(),
+                    _ => panic!("no schemaversion"),
+                },
+                _ => panic!("not an object"),
+            });
+            (merger, version)
+        };
+
+        let (merger, version) = merge(SchemaVersionPolicy::Highest, ["4.22", "4.50"]);
+        assert_eq!(version.unwrap(), "4.50");
+        assert_eq!(
+            merger.report().schema_versions,
+            vec![
+                ("a.json".to_string(), "4.22".to_string()),
+                ("b.json".to_string(), "4.50".to_string()),
+            ]
+        );
+        let (_, version) = merge(SchemaVersionPolicy::default(), ["4.50", "4.22"]);
+        assert_eq!(version.unwrap(), "4.50");
+
+        let (_, version) = merge(SchemaVersionPolicy::First, ["4.22", "4.50"]);
+        assert_eq!(version.unwrap(), "4.22");
+
+        let (_, version) = merge(SchemaVersionPolicy::Explicit("4.90".to_string()), ["4.22", "4.50"]);
+        assert_eq!(version.unwrap(), "4.90");
+
+        let (_, version) = merge(SchemaVersionPolicy::ErrorOnMismatch, ["4.22", "4.50"]);
+        let error = version.unwrap_err().to_string();
+        assert!(error.contains("a.json (4.22)"), "{}", error);
+        assert!(error.contains("b.json (4.50)"), "{}", error);
+
+        // 4.5 and 4.50 are the same version
+        let (_, version) = merge(SchemaVersionPolicy::ErrorOnMismatch, ["4.50", "4.5"]);
+        assert_eq!(version.unwrap(), "4.50");
+    }
+
+    #[test]
+    fn test_default_schema_version() {
+        let fragment = serde_json::json!({
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "fragment",
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "image", "$text": "cmd.exe" }
+                    }
+                }
+            }
+        });
+        let merge = |default_schema_version: Option<&str>| {
+            let options = MergeOptions {
+                default_schema_version: default_schema_version.map(str::to_string),
+                ..Default::default()
+            };
+            let mut merger = ConfigMerger::with_options(options);
+            merger.add_json_value(fragment.clone(), "a.json").unwrap();
+            merger.add_json_value(fragment.clone(), "b.json").unwrap();
+            let version = match merger.finish().unwrap() {
+                Value::Object(root) => match root.get("@schemaversion") {
+                    Some(Value::String(version)) => version.clone(),
+                    _ => panic!("no schemaversion"),
+                },
+                _ => panic!("not an object"),
+            };
+            (merger.report(), version)
+        };
+
+        let (report, version) = merge(Some("4.50"));
+        assert_eq!(version, "4.50");
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("4.50"), "{:?}", report.warnings);
+
+        let (report, version) = merge(None);
+        assert_eq!(version, DEFAULT_SCHEMA_VERSION);
+        assert!(report.warnings[0].contains(DEFAULT_SCHEMA_VERSION));
+
+        // A declared version never warns
+        let mut merger = ConfigMerger::new();
+        let mut declared = fragment.clone();
+        declared["@schemaversion"] = serde_json::json!("4.22");
+        merger.add_json_value(declared, "a.json").unwrap();
+        assert!(merger.report().warnings.is_empty());
+
+        // Rendering a root without a version uses the default
+        let mut root = HashMap::new();
+        root.insert("EventFiltering".to_string(), Value::Object(HashMap::new()));
+        let xml = merged_config_to_xml(&Value::Object(root)).unwrap();
+        assert!(xml.contains(&format!(r#"schemaversion="{}""#, DEFAULT_SCHEMA_VERSION)), "{}", xml);
+    }
+
+    #[test]
+    fn test_settings_policies() {
+        let xml = r#"<Sysmon schemaversion="4.50">
+            <HashAlgorithms>md5</HashAlgorithms>
+            <CheckRevocation/>
+            <EventFiltering>
+                <RuleGroup name="a" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="image">a.exe</Image>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#;
+        let json = serde_json::json!({
+            "@schemaversion": "4.50",
+            "HashAlgorithms": "sha256",
+            "DnsLookup": "False",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "b",
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "image", "$text": "b.exe" }
+                    }
+                }
+            }
+        });
+        let merge = |policy: SettingsPolicy| {
+            let options = MergeOptions {
+                settings_policy: policy,
+                ..Default::default()
+            };
+            let mut merger = ConfigMerger::with_options(options);
+            merger.add_xml_str(xml, "a.xml").unwrap();
+            merger.add_json_value(json.clone(), "b.json").unwrap();
+            merger.finish()
+        };
+        let setting = |merged: &Value, name: &str| match merged {
+            Value::Object(root) => match root.get(name) {
+                Some(Value::String(text)) => Some(text.clone()),
+                _ => None,
+            },
+            _ => panic!("not an object"),
+        };
+
+        let merged = merge(SettingsPolicy::FirstWins).unwrap();
+        assert_eq!(setting(&merged, "HashAlgorithms").as_deref(), Some("md5"));
+        assert_eq!(setting(&merged, "DnsLookup").as_deref(), Some("False"));
+        assert_eq!(setting(&merged, "CheckRevocation").as_deref(), Some(""));
+        assert_eq!(setting(&merged, "ArchiveDirectory"), None);
+
+        let xml = merged_config_to_xml(&merged).unwrap();
+        let position = |text: &str| xml.find(text).unwrap();
+        assert!(position("<HashAlgorithms>md5</HashAlgorithms>") < position("<EventFiltering>"));
+        assert!(position("<DnsLookup>") < position("<EventFiltering>"));
+
+        let merged = merge(SettingsPolicy::LastWins).unwrap();
+        assert_eq!(setting(&merged, "HashAlgorithms").as_deref(), Some("sha256"));
+        assert_eq!(setting(&merged, "DnsLookup").as_deref(), Some("False"));
+
+        let error = merge(SettingsPolicy::ErrorOnConflict).unwrap_err().to_string();
+        assert!(error.contains("HashAlgorithms"), "{}", error);
+        assert!(error.contains("a.xml (md5)"), "{}", error);
+        assert!(error.contains("b.json (sha256)"), "{}", error);
+    }
+
+    #[test]
+    fn test_annotate_sources() {
+        let temp_dir = tempdir().unwrap();
+        let config = |name: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="image">{}.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+            name, name
+        );
+        let office = temp_dir.path().join("office.xml");
+        let servers = temp_dir.path().join("servers.xml");
+        fs::write(&office, config("office")).unwrap();
+        fs::write(&servers, config("servers")).unwrap();
+        let paths = vec![office.clone(), servers.clone()];
+
+        let options = MergeOptionsBuilder::new().annotate_sources(true).build();
+        let output = temp_dir.path().join("merged.xml");
+        merge_config_files(&paths, &output, &options).unwrap();
+        let xml = fs::read_to_string(&output).unwrap();
+        for (path, image) in [(&office, "office.exe"), (&servers, "servers.exe")] {
+            let comment = format!("<!-- source: {} --><Image condition=\"image\">{}</Image>", path.display(), image);
+            assert!(xml.contains(&comment), "{}", xml);
+        }
+        #[cfg(feature = "validation")]
+        validate_xml(&xml, "merged.xml").unwrap();
+
+        // JSON output drops the marks unless asked to keep them
+        let output = temp_dir.path().join("merged.json");
+        merge_config_files(&paths, &output, &options).unwrap();
+        assert!(!fs::read_to_string(&output).unwrap().contains("$source"));
+
+        let options = MergeOptionsBuilder::new()
+            .annotate_sources(true)
+            .sources_in_json(true)
+            .build();
+        merge_config_files(&paths, &output, &options).unwrap();
+        let json = fs::read_to_string(&output).unwrap();
+        assert!(json.contains(&serde_json::to_string(&office.display().to_string()).unwrap()), "{}", json);
+
+        // Without the option nothing is marked
+        let output = temp_dir.path().join("plain.xml");
+        merge_config_files(&paths, &output, &MergeOptions::default()).unwrap();
+        assert!(!fs::read_to_string(&output).unwrap().contains("<!--"));
+    }
+
+    #[test]
+    fn test_merge_files_order() {
+        let temp_dir = tempdir().unwrap();
+        let config = |hash: &str| format!(
+            r#"<Sysmon schemaversion="4.50">
+                <HashAlgorithms>{}</HashAlgorithms>
+                <EventFiltering>
+                    <RuleGroup name="{}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="image">{}.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+            hash, hash, hash
+        );
+        let base = temp_dir.path().join("z_base.xml");
+        let overlay = temp_dir.path().join("a_overlay.xml");
+        fs::write(&base, config("md5")).unwrap();
+        fs::write(&overlay, config("sha256")).unwrap();
+
+        let options = MergeOptionsBuilder::new()
+            .settings_policy(SettingsPolicy::LastWins)
+            .build();
+        let hash_algorithms = |paths: &[PathBuf]| {
+            let mut merger = ConfigMerger::with_options(options.clone());
+            let xml = merged_config_to_xml(&merger.merge_files(paths).unwrap()).unwrap();
+            let start = xml.find("<HashAlgorithms>").unwrap() + "<HashAlgorithms>".len();
+            let end = xml.find("</HashAlgorithms>").unwrap();
+            xml[start..end].to_string()
+        };
+        assert_eq!(hash_algorithms(&[base.clone(), overlay.clone()]), "sha256");
+        assert_eq!(hash_algorithms(&[overlay.clone(), base.clone()]), "md5");
+
+        let output = temp_dir.path().join("merged.json");
+        let paths = vec![base.clone(), overlay.clone()];
+        let progress = ProgressReporter::new(paths.len());
+        merge_config_files_with_progress(&paths, &output, &options, &progress).unwrap();
+        assert_eq!(progress.processed(), 2);
+        let merged: serde_json::Value = serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(merged["HashAlgorithms"], "sha256");
+
+        // Unsupported inputs are an error rather than skipped
+        let notes = temp_dir.path().join("notes.txt");
+        fs::write(&notes, "not a config").unwrap();
+        let error = merge_config_files(&[base, notes], &output, &options).unwrap_err();
+        assert!(error.to_string().contains("notes.txt"), "{}", error);
+    }
+
+    #[test]
+    fn test_template_variables() {
+        let config = serde_json::json!({
+            "@schemaversion": "4.30",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "{{GROUP}}",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": { "@condition": "is", "$text": "{{TOOLS}}" }
+                    }
+                }
+            }
+        });
+        let mut variables = HashMap::new();
+        variables.insert("TOOLS".to_string(), "tools.exe".to_string());
+
+        let options = MergeOptions {
+            variables: Some(variables.clone()),
+            ..Default::default()
+        };
+        let mut merger = ConfigMerger::with_options(options);
+        merger.add_json_value(config.clone(), "a.json").unwrap();
+        let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+        assert!(xml.contains("tools.exe"));
+        assert!(xml.contains("{{GROUP}}"));
+
+        let options = MergeOptions {
+            variables: Some(variables),
+            strict_variables: true,
+            ..Default::default()
+        };
+        let mut merger = ConfigMerger::with_options(options);
+        assert!(merger.add_json_value(config, "a.json").is_err());
+    }
+
+    #[test]
+    fn test_rule_inspection() {
+        let mut merger = ConfigMerger::new();
+        assert_eq!(merger.schema_version(), None);
+
+        let config = serde_json::json!({
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": [
+                    {
+                        "@name": "processes",
+                        "@groupRelation": "or",
+                        "ProcessCreate": { "@onmatch": "include", "Image": "a.exe" },
+                        "NetworkConnect": { "@onmatch": "include", "DestinationPort": "4444" }
+                    },
+                    {
+                        "@name": "more processes",
+                        "@groupRelation": "or",
+                        "ProcessCreate": { "@onmatch": "include", "Image": "b.exe" }
+                    }
+                ]
+            }
+        });
+        merger.add_json_value(config, "inline").unwrap();
+
+        assert_eq!(merger.schema_version(), Some("4.50"));
+        let stats = merger.stats();
+        assert_eq!(stats.rules_by_event_type.get("ProcessCreate"), Some(&2));
+        assert_eq!(stats.rules_by_event_type.get("NetworkConnect"), Some(&1));
+        assert_eq!(stats.schema_versions, vec!["4.50"]);
+        assert_eq!(stats.last_processed_file, None);
+        assert_eq!(merger.unique_event_types(), vec!["NetworkConnect", "ProcessCreate"]);
+    }
+
+    #[test]
+    fn test_stats_count_filters() {
+        let config = serde_json::json!({
+            "@schemaversion": "4.50",
+            "EventFiltering": {
+                "RuleGroup": {
+                    "@name": "tools",
+                    "@groupRelation": "or",
+                    "ProcessCreate": {
+                        "@onmatch": "include",
+                        "Image": [
+                            { "@condition": "image", "$text": "a.exe" },
+                            { "@condition": "image", "$text": "b.exe" }
+                        ],
+                        "CommandLine": { "@condition": "contains", "$text": "-enc" }
+                    },
+                    "NetworkConnect": {
+                        "@onmatch": "include",
+                        "DestinationPort": { "@condition": "is", "$text": "4444" }
+                    }
+                }
+            }
+        });
+        let mut merger = ConfigMerger::new();
+        merger.add_json_value(config.clone(), "a.json").unwrap();
+        merger.add_json_value(config, "b.json").unwrap();
+
+        let stats = merger.stats();
+        assert_eq!(stats.rules, 8);
+        assert_eq!(
+            stats.rules_by_event_type,
+            BTreeMap::from([("NetworkConnect".to_string(), 2), ("ProcessCreate".to_string(), 6)])
+        );
+        assert_eq!(stats.duplicates_removed, 4);
+        assert_eq!(stats.rule_stats.total, 4);
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    fn xml_config(name: &str, image: &str) -> String {
+        format!(
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="{}" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="image">{}</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+            name, image
+        )
+    }
+
+    #[test]
+    fn test_merge_in_memory() {
+        let mut merger = ConfigMerger::new();
+        merger.add_xml_str(&xml_config("first", "a.exe"), "upload-1").unwrap();
+        merger.add_xml_str(&xml_config("second", "b.exe"), "upload-2").unwrap();
+        merger
+            .add_json_value(
+                serde_json::json!({
+                    "@schemaversion": "4.50",
+                    "EventFiltering": {
+                        "RuleGroup": {
+                            "@name": "third",
+                            "@groupRelation": "or",
+                            "NetworkConnect": {
+                                "@onmatch": "include",
+                                "DestinationPort": { "@condition": "is", "$text": "4444" }
+                            }
+                        }
+                    }
+                }),
+                "upload-3",
+            )
+            .unwrap();
+        assert_eq!(merger.stats().files, 3);
+
+        let merged = merger.finish().unwrap();
+        let xml = merged_config_to_xml(&merged).unwrap();
+        for text in ["a.exe", "b.exe", "4444"] {
+            assert!(xml.contains(text), "{}", xml);
+        }
+        #[cfg(feature = "validation")]
+        {
+            let report = crate::validation::validate_str(&xml, crate::converter::FileFormat::Xml).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+
+            // Labels stand in for file paths in errors
+            let error = merger.add_xml_str("<Sysmon><Broken></Sysmon>", "upload-4").unwrap_err();
+            assert!(error.to_string().contains("upload-4"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(feature = "fs")]
+use crate::batch::ProgressReporter;
+use std::path::{Path, PathBuf};
+
+use super::MergeConflict;
+
+/// The stages of a directory merge, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePhase {
+    /// Listing the input files
+    Scan,
+    /// Reading the rules of each input
+    Parse,
+    /// Building the merged config from the collected rules
+    Combine,
+    /// Writing the output file
+    Write,
+    /// Validating the written output, before it replaces `output_file`
+    Validate,
+}
+
+/// A problem that does not stop the merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeWarning {
+    /// A file left out under `InvalidFilePolicy::Skip`
+    SkippedFile { path: PathBuf, error: String },
+    /// A config whose rules were skipped by `ConfigMerger::with_skip_invalid_rules`
+    SkippedRules(String),
+    /// A conflict kept under `ConflictPolicy::Warn`
+    Conflict(MergeConflict),
+    /// No input declares a schema version, so the default is used
+    SchemaVersion(String),
+}
+
+impl std::fmt::Display for MergeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeWarning::SkippedFile { path, error } => {
+                write!(f, "Skipped {}: {}", path.display(), error)
+            }
+            MergeWarning::SkippedRules(message) | MergeWarning::SchemaVersion(message) => {
+                write!(f, "{}", message)
+            }
+            MergeWarning::Conflict(conflict) => write!(f, "{}", conflict),
+        }
+    }
+}
+
+/// What happened to one input file, as passed to the sink given to
+/// `ConfigMerger::with_log_sink`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeEvent {
+    /// The file is about to be read
+    FileStarted(PathBuf),
+    /// The file was read, adding `rules_added` rule groups
+    FileCompleted { path: PathBuf, rules_added: usize },
+    /// The file was left out, under `InvalidFilePolicy::Skip` or
+    /// `ConfigMerger::with_skip_invalid_rules`
+    FileSkipped { path: PathBuf, reason: String },
+    /// The file could not be read, which stops the merge
+    FileFailed { path: PathBuf, error: String },
+}
+
+/// Receives the progress of a merge. Every method does nothing by default,
+/// so an observer only implements the callbacks it needs.
+pub trait MergeObserver {
+    /// Called as each phase starts
+    fn on_phase(&self, _phase: MergePhase) {}
+
+    /// Called before an input file is read
+    fn on_file_start(&self, _path: &Path) {}
+
+    /// Called after an input file is read, with the number of rule groups
+    /// it added. A file skipped under `InvalidFilePolicy::Skip` adds none.
+    fn on_file_done(&self, _path: &Path, _rules_added: usize) {}
+
+    /// Called for each problem that does not stop the merge
+    fn on_warning(&self, _warning: MergeWarning) {}
+}
+
+/// Observes nothing; stands in when no observer is given
+impl MergeObserver for () {}
+
+/// Counts each input file read, in a `merge` stage followed by a `validate`
+/// stage
+#[cfg(feature = "fs")]
+impl MergeObserver for ProgressReporter {
+    fn on_phase(&self, phase: MergePhase) {
+        match phase {
+            MergePhase::Parse => self.begin_stage("merge", self.total() as usize),
+            MergePhase::Validate => self.begin_stage("validate", 1),
+            MergePhase::Scan | MergePhase::Combine | MergePhase::Write => {}
+        }
+    }
+
+    fn on_file_done(&self, _path: &Path, _rules_added: usize) {
+        self.increment();
+    }
+}
@@ -0,0 +1,167 @@
+use crate::converter::FileFormat;
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use crate::model::Value;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::extract::event_type_groups;
+use super::{rule_groups, rule_identities, write_derived_config};
+
+/// A file written by `split`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SplitFile {
+    /// Event type of every rule in the file, such as `ProcessCreate`
+    pub event_type: String,
+    pub path: PathBuf,
+    /// Filters in the file, counting a `groupRelation="and"` group as one
+    pub rules: usize,
+}
+
+/// Outcome of `split`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SplitReport {
+    /// The files written, sorted by event type
+    pub files: Vec<SplitFile>,
+}
+
+/// Writes one config per event type of `input` into `output_dir`, such as
+/// `ProcessCreate.xml`, creating the directory as needed.
+///
+/// Each file carries the schema version of `input` and its `RuleGroup`s with
+/// only that event type; other settings are left out, as in sysmon-modular
+/// rule files. Event types without filters are skipped. Every file is
+/// validated against the Sysmon schema with the `validation` feature.
+/// Merging the directory back gives the rules of `input`.
+pub fn split(input: &Path, output_dir: &Path, format: FileFormat) -> Result<SplitReport, ConversionError> {
+    if !matches!(format, FileFormat::Xml | FileFormat::Json) {
+        return Err(ConversionError::InvalidFile(format!(
+            "Unsupported split format: {}",
+            format.extension()
+        )));
+    }
+    let doc = SysmonDocument::load(input)?;
+    std::fs::create_dir_all(output_dir).map_err(|e| ConversionError::io_error(output_dir, e))?;
+
+    let event_types: BTreeSet<&str> = rule_groups(doc.value())
+        .into_iter()
+        .filter_map(|group| match group {
+            Value::Object(group) => Some(group.keys()),
+            _ => None,
+        })
+        .flatten()
+        .filter(|name| !name.starts_with('@') && !name.starts_with('$'))
+        .map(String::as_str)
+        .collect();
+
+    let mut report = SplitReport::default();
+    for event_type in event_types {
+        let groups = event_type_groups(doc.value(), &[event_type]);
+        let rules: usize = groups.iter().map(|group| rule_identities(group).len()).sum();
+        if rules == 0 {
+            continue;
+        }
+
+        let mut root = HashMap::new();
+        if let Some(version) = doc.schema_version() {
+            root.insert("@schemaversion".to_string(), Value::String(version.to_string()));
+        }
+        let filtering = HashMap::from([("RuleGroup".to_string(), Value::Array(groups))]);
+        root.insert("EventFiltering".to_string(), Value::Object(filtering));
+
+        let path = output_dir.join(format!("{}.{}", event_type, format.extension()));
+        write_derived_config(&Value::Object(root), &path, true)?;
+        report.files.push(SplitFile {
+            event_type: event_type.to_string(),
+            path,
+            rules,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merger::{diff, merge_configs};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_and_merge_back() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("full.xml");
+        fs::write(
+            &input,
+            r#"<Sysmon schemaversion="4.50">
+                <EventFiltering>
+                    <RuleGroup name="mixed" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="end with">powershell.exe</Image>
+                            <CommandLine condition="contains">-enc</CommandLine>
+                        </ProcessCreate>
+                        <NetworkConnect onmatch="include">
+                            <DestinationPort condition="is">4444</DestinationPort>
+                        </NetworkConnect>
+                    </RuleGroup>
+                    <RuleGroup name="exclusions" groupRelation="or">
+                        <ProcessCreate onmatch="exclude">
+                            <Image condition="is">C:\Windows\System32\svchost.exe</Image>
+                        </ProcessCreate>
+                        <DnsQuery onmatch="exclude">
+                            <QueryName condition="end with">.microsoft.com</QueryName>
+                        </DnsQuery>
+                    </RuleGroup>
+                    <RuleGroup name="empty" groupRelation="or">
+                        <FileCreate onmatch="include"/>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+
+        let output_dir = temp_dir.path().join("rules");
+        let report = split(&input, &output_dir, FileFormat::Xml).unwrap();
+        let written: Vec<_> = report
+            .files
+            .iter()
+            .map(|file| (file.event_type.as_str(), file.rules))
+            .collect();
+        assert_eq!(written, vec![("DnsQuery", 1), ("NetworkConnect", 1), ("ProcessCreate", 3)]);
+        for file in &report.files {
+            assert_eq!(file.path, output_dir.join(format!("{}.xml", file.event_type)));
+            let doc = SysmonDocument::load(&file.path).unwrap();
+            assert_eq!(doc.schema_version(), Some("4.50"));
+            assert!(doc.rules().iter().all(|rule| rule.event_type == file.event_type));
+        }
+        assert!(!output_dir.join("FileCreate.xml").exists());
+
+        let merged = temp_dir.path().join("merged.xml");
+        merge_configs(&output_dir, &merged, false).unwrap();
+        let changes = diff(&input, &merged).unwrap();
+        assert!(changes.events.is_empty(), "{}", changes);
+    }
+
+    #[test]
+    fn test_split_json() {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("full.json");
+        fs::write(
+            &input,
+            r#"{"Sysmon": {"@schemaversion": "4.50", "EventFiltering": {"RuleGroup": {
+                "@name": "dns", "@groupRelation": "or",
+                "DnsQuery": {"@onmatch": "exclude",
+                    "QueryName": {"@condition": "end with", "$text": ".microsoft.com"}}}}}}"#,
+        )
+        .unwrap();
+
+        let output_dir = temp_dir.path().join("rules");
+        let report = split(&input, &output_dir, FileFormat::Json).unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].path, output_dir.join("DnsQuery.json"));
+        assert_eq!(SysmonDocument::load(&report.files[0].path).unwrap().rules().len(), 1);
+
+        assert!(split(&input, &output_dir, FileFormat::Yaml).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use crate::model::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{retain_filters, rule_groups, set_rule_groups, write_derived_config, FilterKey};
+
+/// What `subtract` does when no rules are left
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptySubtractionPolicy {
+    /// Write the config without `EventFiltering`, keeping its settings
+    #[default]
+    Minimal,
+    /// Fail without writing the output
+    Error,
+}
+
+/// Options for `subtract`
+#[derive(Debug, Clone)]
+pub struct SubtractOptions {
+    pub on_empty: EmptySubtractionPolicy,
+    /// Validate the result against the Sysmon schema before writing it
+    pub validate: bool,
+}
+
+impl Default for SubtractOptions {
+    fn default() -> Self {
+        Self {
+            on_empty: EmptySubtractionPolicy::default(),
+            validate: true,
+        }
+    }
+}
+
+/// Writes `config` without the rules also in `baseline`, as XML or JSON
+/// by the extension of `output`.
+///
+/// Rules match the way `ConfigMerger` dedupes them, so a rule differing
+/// only in case or path separators is removed, while one with another
+/// condition or `onmatch` is kept. `RuleGroup`s left without rules are
+/// dropped, and groups with `groupRelation="and"` are kept whole. Settings
+/// come from `config`. Returns the number of filters removed.
+pub fn subtract(
+    config: &Path,
+    baseline: &Path,
+    output: &Path,
+    options: &SubtractOptions,
+) -> Result<usize, ConversionError> {
+    let mut root = SysmonDocument::load(config)?.value().clone();
+    let baseline_doc = SysmonDocument::load(baseline)?;
+
+    let mut baseline_keys = HashSet::new();
+    for group in rule_groups(baseline_doc.value()) {
+        retain_filters(group, &mut |key: &FilterKey, _: &str, _: &str| {
+            baseline_keys.insert(key.clone());
+            true
+        });
+    }
+
+    let mut removed = 0;
+    let kept: Vec<Value> = rule_groups(&root)
+        .into_iter()
+        .filter_map(|group| {
+            retain_filters(group, &mut |key: &FilterKey, _: &str, _: &str| {
+                let keep = !baseline_keys.contains(key);
+                if !keep {
+                    removed += 1;
+                }
+                keep
+            })
+            .map(Cow::into_owned)
+        })
+        .collect();
+
+    if kept.is_empty() && options.on_empty == EmptySubtractionPolicy::Error {
+        return Err(ConversionError::ValidationError(format!(
+            "No rules left in {} after subtracting {}",
+            config.display(),
+            baseline.display()
+        )));
+    }
+    set_rule_groups(&mut root, kept);
+
+    write_derived_config(&root, output, options.validate)?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const BASELINE: &str = r#"<Sysmon schemaversion="4.50">
+        <EventFiltering>
+            <RuleGroup name="upstream" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="end with">C:\Windows\System32\cmd.exe</Image>
+                    <Image condition="is">a.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+            <RuleGroup name="network" groupRelation="or">
+                <NetworkConnect onmatch="include">
+                    <DestinationPort condition="is">4444</DestinationPort>
+                </NetworkConnect>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_subtract() {
+        let temp_dir = tempdir().unwrap();
+        let baseline = temp_dir.path().join("baseline.xml");
+        fs::write(&baseline, BASELINE).unwrap();
+        let config = temp_dir.path().join("site.json");
+        fs::write(
+            &config,
+            r#"{"Sysmon": {
+                "@schemaversion": "4.50",
+                "HashAlgorithms": "sha256",
+                "EventFiltering": {"RuleGroup": [
+                    {"@name": "site", "@groupRelation": "or",
+                     "ProcessCreate": {"@onmatch": "include",
+                        "Image": [
+                            {"@condition": "end with", "$text": "c:/windows/system32/CMD.EXE"},
+                            {"@condition": "contains", "$text": "a.exe"},
+                            {"@condition": "is", "$text": "site.exe"}
+                        ]}},
+                    {"@name": "site network", "@groupRelation": "or",
+                     "NetworkConnect": {"@onmatch": "include",
+                        "DestinationPort": {"@condition": "is", "$text": "4444"}}}
+                ]}
+            }}"#,
+        )
+        .unwrap();
+
+        for output in ["overlay.xml", "overlay.json"] {
+            let output = temp_dir.path().join(output);
+            let removed = subtract(&config, &baseline, &output, &SubtractOptions::default()).unwrap();
+            assert_eq!(removed, 2);
+
+            let overlay = SysmonDocument::load(&output).unwrap();
+            let rules: Vec<_> = overlay
+                .rules()
+                .into_iter()
+                .map(|rule| (rule.rule_group, rule.condition, rule.value))
+                .collect();
+            let site = Some("site".to_string());
+            assert_eq!(
+                rules,
+                vec![
+                    (site.clone(), "contains".to_string(), "a.exe".to_string()),
+                    (site, "is".to_string(), "site.exe".to_string()),
+                ]
+            );
+            let Value::Object(root) = overlay.value() else {
+                panic!("root is not an object");
+            };
+            assert!(matches!(root.get("HashAlgorithms"), Some(Value::String(v)) if v == "sha256"));
+        }
+    }
+
+    #[test]
+    fn test_subtract_everything() {
+        let temp_dir = tempdir().unwrap();
+        let baseline = temp_dir.path().join("baseline.xml");
+        fs::write(&baseline, BASELINE).unwrap();
+
+        let output = temp_dir.path().join("empty.xml");
+        assert_eq!(subtract(&baseline, &baseline, &output, &SubtractOptions::default()).unwrap(), 3);
+        let empty = SysmonDocument::load(&output).unwrap();
+        assert!(empty.rules().is_empty());
+        assert_eq!(empty.schema_version(), Some("4.50"));
+
+        let options = SubtractOptions {
+            on_empty: EmptySubtractionPolicy::Error,
+            ..SubtractOptions::default()
+        };
+        let output = temp_dir.path().join("error.xml");
+        let error = subtract(&baseline, &baseline, &output, &options).unwrap_err();
+        assert!(error.to_string().contains("No rules left"), "{}", error);
+        assert!(!output.exists());
+    }
+}
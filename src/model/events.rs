@@ -0,0 +1,412 @@
+use crate::error::ConversionError;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// A Sysmon configuration schema version, such as `4.30`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parses a version as written in `schemaversion`. A single minor digit
+    /// is read as tenths, so `4.3` equals `4.30`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let (major, minor) = version.trim().split_once('.')?;
+        let major = major.parse().ok()?;
+        let minor = match minor.len() {
+            1 => minor.parse::<u32>().ok()? * 10,
+            2 => minor.parse().ok()?,
+            _ => return None,
+        };
+        Some(Self { major, minor })
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)
+    }
+}
+
+/// Static description of a Sysmon event filter element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventTypeInfo {
+    /// Element name used under `RuleGroup`, such as `ProcessCreate`
+    pub name: &'static str,
+    /// Windows event ID (the first one, for elements covering several)
+    pub event_id: u32,
+    /// First schema version that accepts the element
+    pub min_schema: SchemaVersion,
+    /// Fields that can be used as filters
+    pub fields: &'static [&'static str],
+}
+
+/// Schema version given to configs that do not declare one, such as a merge
+/// of JSON fragments without `@schemaversion`
+pub const DEFAULT_SCHEMA_VERSION: &str = "4.90";
+
+/// Schema versions accepted by the bundled validator, oldest first
+pub static SCHEMA_VERSIONS: &[SchemaVersion] = &[
+    SchemaVersion::new(3, 10),
+    SchemaVersion::new(3, 20),
+    SchemaVersion::new(3, 30),
+    SchemaVersion::new(3, 40),
+    SchemaVersion::new(3, 50),
+    SchemaVersion::new(4, 0),
+    SchemaVersion::new(4, 10),
+    SchemaVersion::new(4, 20),
+    SchemaVersion::new(4, 21),
+    SchemaVersion::new(4, 22),
+    SchemaVersion::new(4, 30),
+    SchemaVersion::new(4, 40),
+    SchemaVersion::new(4, 50),
+    SchemaVersion::new(4, 60),
+    SchemaVersion::new(4, 70),
+    SchemaVersion::new(4, 80),
+    SchemaVersion::new(4, 81),
+    SchemaVersion::new(4, 82),
+    SchemaVersion::new(4, 83),
+    SchemaVersion::new(4, 90),
+];
+
+/// Every known event filter element, in event ID order
+pub static EVENT_TYPES: &[EventTypeInfo] = &[
+    EventTypeInfo {
+        name: "ProcessCreate",
+        event_id: 1,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "FileVersion",
+            "Description", "Product", "Company", "OriginalFileName", "CommandLine",
+            "CurrentDirectory", "User", "LogonGuid", "LogonId", "TerminalSessionId",
+            "IntegrityLevel", "Hashes", "ParentProcessGuid", "ParentProcessId", "ParentImage",
+            "ParentCommandLine", "ParentUser",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileCreateTime",
+        event_id: 2,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "TargetFilename",
+            "CreationUtcTime", "PreviousCreationUtcTime", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "NetworkConnect",
+        event_id: 3,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "User", "Protocol",
+            "Initiated", "SourceIsIpv6", "SourceIp", "SourceHostname", "SourcePort",
+            "SourcePortName", "DestinationIsIpv6", "DestinationIp", "DestinationHostname",
+            "DestinationPort", "DestinationPortName",
+        ],
+    },
+    EventTypeInfo {
+        name: "ProcessTerminate",
+        event_id: 5,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &["RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "User"],
+    },
+    EventTypeInfo {
+        name: "DriverLoad",
+        event_id: 6,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "ImageLoaded", "Hashes", "Signed", "Signature",
+            "SignatureStatus",
+        ],
+    },
+    EventTypeInfo {
+        name: "ImageLoad",
+        event_id: 7,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "ImageLoaded",
+            "FileVersion", "Description", "Product", "Company", "OriginalFileName", "Hashes",
+            "Signed", "Signature", "SignatureStatus", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "CreateRemoteThread",
+        event_id: 8,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &[
+            "RuleName", "UtcTime", "SourceProcessGuid", "SourceProcessId", "SourceImage",
+            "TargetProcessGuid", "TargetProcessId", "TargetImage", "NewThreadId",
+            "StartAddress", "StartModule", "StartFunction", "SourceUser", "TargetUser",
+        ],
+    },
+    EventTypeInfo {
+        name: "RawAccessRead",
+        event_id: 9,
+        min_schema: SchemaVersion::new(3, 10),
+        fields: &["RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "Device", "User"],
+    },
+    EventTypeInfo {
+        name: "ProcessAccess",
+        event_id: 10,
+        min_schema: SchemaVersion::new(3, 20),
+        fields: &[
+            "RuleName", "UtcTime", "SourceProcessGUID", "SourceProcessId", "SourceThreadId",
+            "SourceImage", "TargetProcessGUID", "TargetProcessId", "TargetImage",
+            "GrantedAccess", "CallTrace", "SourceUser", "TargetUser",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileCreate",
+        event_id: 11,
+        min_schema: SchemaVersion::new(3, 20),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "TargetFilename",
+            "CreationUtcTime", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "RegistryEvent",
+        event_id: 12,
+        min_schema: SchemaVersion::new(3, 20),
+        fields: &[
+            "RuleName", "EventType", "UtcTime", "ProcessGuid", "ProcessId", "Image",
+            "TargetObject", "Details", "NewName", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileCreateStreamHash",
+        event_id: 15,
+        min_schema: SchemaVersion::new(3, 20),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "TargetFilename",
+            "CreationUtcTime", "Hash", "Contents", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "PipeEvent",
+        event_id: 17,
+        min_schema: SchemaVersion::new(3, 30),
+        fields: &[
+            "RuleName", "EventType", "UtcTime", "ProcessGuid", "ProcessId", "PipeName",
+            "Image", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "WmiEvent",
+        event_id: 19,
+        min_schema: SchemaVersion::new(3, 40),
+        fields: &[
+            "RuleName", "EventType", "UtcTime", "Operation", "User", "EventNamespace", "Name",
+            "Query", "Type", "Destination", "Consumer", "Filter",
+        ],
+    },
+    EventTypeInfo {
+        name: "DnsQuery",
+        event_id: 22,
+        min_schema: SchemaVersion::new(4, 21),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "QueryName", "QueryStatus",
+            "QueryResults", "Image", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileDelete",
+        event_id: 23,
+        min_schema: SchemaVersion::new(4, 30),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "User", "Image",
+            "TargetFilename", "Hashes", "IsExecutable", "Archived",
+        ],
+    },
+    EventTypeInfo {
+        name: "ClipboardChange",
+        event_id: 24,
+        min_schema: SchemaVersion::new(4, 40),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "Session",
+            "ClientInfo", "Hashes", "Archived", "User",
+        ],
+    },
+    EventTypeInfo {
+        name: "ProcessTampering",
+        event_id: 25,
+        min_schema: SchemaVersion::new(4, 50),
+        fields: &["RuleName", "UtcTime", "ProcessGuid", "ProcessId", "Image", "Type", "User"],
+    },
+    EventTypeInfo {
+        name: "FileDeleteDetected",
+        event_id: 26,
+        min_schema: SchemaVersion::new(4, 60),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "User", "Image",
+            "TargetFilename", "Hashes", "IsExecutable",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileBlockExecutable",
+        event_id: 27,
+        min_schema: SchemaVersion::new(4, 82),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "User", "Image",
+            "TargetFilename", "Hashes",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileBlockShredding",
+        event_id: 28,
+        min_schema: SchemaVersion::new(4, 83),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "User", "Image",
+            "TargetFilename", "Hashes", "IsExecutable",
+        ],
+    },
+    EventTypeInfo {
+        name: "FileExecutableDetected",
+        event_id: 29,
+        min_schema: SchemaVersion::new(4, 90),
+        fields: &[
+            "RuleName", "UtcTime", "ProcessGuid", "ProcessId", "User", "Image",
+            "TargetFilename", "Hashes",
+        ],
+    },
+];
+
+/// A Sysmon event filter element, in event ID order. Each variant has an
+/// entry at the same position in `EVENT_TYPES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(into = "String")]
+pub enum EventType {
+    ProcessCreate,
+    FileCreateTime,
+    NetworkConnect,
+    ProcessTerminate,
+    DriverLoad,
+    ImageLoad,
+    CreateRemoteThread,
+    RawAccessRead,
+    ProcessAccess,
+    FileCreate,
+    RegistryEvent,
+    FileCreateStreamHash,
+    PipeEvent,
+    WmiEvent,
+    DnsQuery,
+    FileDelete,
+    ClipboardChange,
+    ProcessTampering,
+    FileDeleteDetected,
+    FileBlockExecutable,
+    FileBlockShredding,
+    FileExecutableDetected,
+}
+
+impl EventType {
+    /// Every event type, in event ID order
+    pub fn all() -> &'static [EventType] {
+        &[
+            EventType::ProcessCreate,
+            EventType::FileCreateTime,
+            EventType::NetworkConnect,
+            EventType::ProcessTerminate,
+            EventType::DriverLoad,
+            EventType::ImageLoad,
+            EventType::CreateRemoteThread,
+            EventType::RawAccessRead,
+            EventType::ProcessAccess,
+            EventType::FileCreate,
+            EventType::RegistryEvent,
+            EventType::FileCreateStreamHash,
+            EventType::PipeEvent,
+            EventType::WmiEvent,
+            EventType::DnsQuery,
+            EventType::FileDelete,
+            EventType::ClipboardChange,
+            EventType::ProcessTampering,
+            EventType::FileDeleteDetected,
+            EventType::FileBlockExecutable,
+            EventType::FileBlockShredding,
+            EventType::FileExecutableDetected,
+        ]
+    }
+
+    /// Gets the description of the element in `EVENT_TYPES`
+    pub fn info(&self) -> &'static EventTypeInfo {
+        &EVENT_TYPES[*self as usize]
+    }
+
+    /// Windows event ID (the first one, for elements covering several)
+    pub fn event_id(&self) -> u32 {
+        self.info().event_id
+    }
+}
+
+impl AsRef<str> for EventType {
+    fn as_ref(&self) -> &str {
+        self.info().name
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl From<EventType> for String {
+    fn from(event_type: EventType) -> Self {
+        event_type.as_ref().to_string()
+    }
+}
+
+impl FromStr for EventType {
+    type Err = ConversionError;
+
+    /// Parses an element name, such as `ProcessCreate`. Names are case
+    /// sensitive, as in the XML.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        EventType::all()
+            .iter()
+            .find(|event_type| event_type.as_ref() == name)
+            .copied()
+            .ok_or_else(|| ConversionError::ValidationError(format!("Unknown event type: {}", name)))
+    }
+}
+
+impl TryFrom<&str> for EventType {
+    type Error = ConversionError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_types_match_table() {
+        assert_eq!(EventType::all().len(), EVENT_TYPES.len());
+        for (event_type, info) in EventType::all().iter().zip(EVENT_TYPES) {
+            assert_eq!(format!("{:?}", event_type), info.name);
+            assert_eq!(event_type.as_ref(), info.name);
+            assert_eq!(EventType::try_from(info.name).unwrap(), *event_type);
+        }
+    }
+
+    #[test]
+    fn test_event_type_parsing() {
+        assert_eq!("DnsQuery".parse::<EventType>().unwrap(), EventType::DnsQuery);
+        assert_eq!(EventType::DnsQuery.event_id(), 22);
+        assert_eq!(EventType::ProcessCreate.event_id(), 1);
+        assert_eq!(EventType::RegistryEvent.to_string(), "RegistryEvent");
+        assert!("processcreate".parse::<EventType>().is_err());
+        assert!(EventType::try_from("Bogus").is_err());
+    }
+}
@@ -1,2 +1,4 @@
+mod events;
 mod types;
-pub use types::*;
\ No newline at end of file
+pub use events::*;
+pub use types::*;
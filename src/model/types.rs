@@ -16,6 +16,8 @@ pub enum Value {
 // Sysmon-specific types module
 pub mod sysmon {
     use super::*;
+    use crate::error::ConversionError;
+    use crate::model::EventType;
 
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct SysmonConfig {
@@ -28,6 +30,43 @@ pub mod sysmon {
         pub dns_lookup: Option<bool>,
     }
 
+    impl SysmonConfig {
+        /// Lists the known event types with filters in `EventFiltering`, in
+        /// event ID order. Unknown element names are skipped.
+        pub fn event_types(&self) -> Vec<EventType> {
+            let mut event_types: Vec<EventType> = self
+                .event_filtering
+                .iter()
+                .flat_map(|filtering| &filtering.rule_groups)
+                .flat_map(|group| group.events.keys())
+                .filter_map(|name| name.parse().ok())
+                .collect();
+            event_types.sort();
+            event_types.dedup();
+            event_types
+        }
+
+        /// Whether `EventFiltering` has filters for the event type
+        pub fn covers(&self, event_type: EventType) -> bool {
+            self.event_types().contains(&event_type)
+        }
+
+        /// Lists the known event types without filters, in event ID order
+        pub fn uncovered_event_types(&self) -> Vec<EventType> {
+            let covered = self.event_types();
+            EventType::all()
+                .iter()
+                .filter(|event_type| !covered.contains(event_type))
+                .copied()
+                .collect()
+        }
+
+        /// Percentage of the known event types with filters
+        pub fn coverage_percentage(&self) -> f64 {
+            self.event_types().len() as f64 / EventType::all().len() as f64 * 100.0
+        }
+    }
+
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct EventFiltering {
         #[serde(rename = "RuleGroup")]
@@ -52,14 +91,301 @@ pub mod sysmon {
         pub rules: HashMap<String, Vec<Rule>>,
     }
 
+    /// How a field filter compares its value, as written in `condition`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[serde(try_from = "String", into = "String")]
+    pub enum Condition {
+        Is,
+        IsNot,
+        Contains,
+        ContainsAll,
+        ContainsAny,
+        BeginsWith,
+        EndsWith,
+        Image,
+        LessThan,
+        MoreThan,
+        Excludes,
+        ExcludesAll,
+        ExcludesAny,
+    }
+
+    impl Condition {
+        /// Whether the condition compares numbers rather than strings
+        pub fn is_numeric(&self) -> bool {
+            matches!(self, Condition::LessThan | Condition::MoreThan)
+        }
+    }
+
+    impl AsRef<str> for Condition {
+        /// The spelling Sysmon's schema uses
+        fn as_ref(&self) -> &str {
+            match self {
+                Condition::Is => "is",
+                Condition::IsNot => "is not",
+                Condition::Contains => "contains",
+                Condition::ContainsAll => "contains all",
+                Condition::ContainsAny => "contains any",
+                Condition::BeginsWith => "begin with",
+                Condition::EndsWith => "end with",
+                Condition::Image => "image",
+                Condition::LessThan => "less than",
+                Condition::MoreThan => "more than",
+                Condition::Excludes => "excludes",
+                Condition::ExcludesAll => "excludes all",
+                Condition::ExcludesAny => "excludes any",
+            }
+        }
+    }
+
+    impl std::fmt::Display for Condition {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.as_ref())
+        }
+    }
+
+    impl TryFrom<&str> for Condition {
+        type Error = ConversionError;
+
+        /// Parses a condition ignoring case and surrounding whitespace.
+        /// `begins with` and `ends with` are accepted alongside Sysmon's
+        /// `begin with` and `end with`.
+        fn try_from(value: &str) -> Result<Self, Self::Error> {
+            match value.trim().to_lowercase().as_str() {
+                "is" => Ok(Condition::Is),
+                "is not" => Ok(Condition::IsNot),
+                "contains" => Ok(Condition::Contains),
+                "contains all" => Ok(Condition::ContainsAll),
+                "contains any" => Ok(Condition::ContainsAny),
+                "begin with" | "begins with" => Ok(Condition::BeginsWith),
+                "end with" | "ends with" => Ok(Condition::EndsWith),
+                "image" => Ok(Condition::Image),
+                "less than" => Ok(Condition::LessThan),
+                "more than" => Ok(Condition::MoreThan),
+                "excludes" => Ok(Condition::Excludes),
+                "excludes all" => Ok(Condition::ExcludesAll),
+                "excludes any" => Ok(Condition::ExcludesAny),
+                _ => Err(ConversionError::ValidationError(format!("Unknown condition: {}", value))),
+            }
+        }
+    }
+
+    impl TryFrom<String> for Condition {
+        type Error = ConversionError;
+
+        fn try_from(value: String) -> Result<Self, Self::Error> {
+            Condition::try_from(value.as_str())
+        }
+    }
+
+    impl From<Condition> for String {
+        fn from(condition: Condition) -> Self {
+            condition.as_ref().to_string()
+        }
+    }
+
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct Rule {
         #[serde(rename = "@condition")]
-        pub condition: String,
+        pub condition: Condition,
         #[serde(rename = "$text")]
         pub value: String,
     }
+
+    impl Rule {
+        /// Evaluates the rule against a field value the way Sysmon does:
+        /// string comparisons ignore case, and the multi-value conditions
+        /// (`contains any`, `excludes all`, ...) split the rule value on `;`,
+        /// or on whitespace when it has no `;`.
+        pub fn matches(&self, text: &str) -> bool {
+            let text = text.to_lowercase();
+            let value = self.value.to_lowercase();
+            let terms = || -> Vec<&str> {
+                if value.contains(';') {
+                    value.split(';').map(str::trim).filter(|t| !t.is_empty()).collect()
+                } else {
+                    value.split_whitespace().collect()
+                }
+            };
+
+            match self.condition {
+                Condition::Is => text == value,
+                Condition::IsNot => text != value,
+                Condition::Contains => text.contains(&value),
+                Condition::ContainsAll => terms().iter().all(|term| text.contains(term)),
+                Condition::ContainsAny => terms().iter().any(|term| text.contains(term)),
+                Condition::Excludes => !text.contains(&value),
+                Condition::ExcludesAll => !terms().iter().any(|term| text.contains(term)),
+                Condition::ExcludesAny => terms().iter().any(|term| !text.contains(term)),
+                Condition::BeginsWith => text.starts_with(&value),
+                Condition::EndsWith => text.ends_with(&value),
+                // A bare image name matches that name in any directory; a
+                // full path must match exactly
+                Condition::Image => {
+                    if value.contains('\\') {
+                        text == value
+                    } else {
+                        image_name(&text) == value
+                    }
+                }
+                Condition::LessThan => compare_numbers(&text, &value).is_some_and(|o| o.is_lt()),
+                Condition::MoreThan => compare_numbers(&text, &value).is_some_and(|o| o.is_gt()),
+            }
+        }
+    }
+
+    /// Gets the file name part of a Windows path
+    fn image_name(path: &str) -> &str {
+        path.rsplit(['\\', '/']).next().unwrap_or(path)
+    }
+
+    fn compare_numbers(text: &str, value: &str) -> Option<std::cmp::Ordering> {
+        let text: f64 = text.trim().parse().ok()?;
+        let value: f64 = value.trim().parse().ok()?;
+        text.partial_cmp(&value)
+    }
 }
 
 // Re-export SysmonConfig for convenience
-pub use self::sysmon::SysmonConfig;
\ No newline at end of file
+pub use self::sysmon::SysmonConfig;
+#[cfg(test)]
+mod tests {
+    use super::sysmon::{Condition, EventFiltering, EventRules, Rule, RuleGroup, SysmonConfig};
+    use crate::model::EventType;
+    use std::collections::HashMap;
+
+    fn rule(condition: &str, value: &str) -> Rule {
+        Rule {
+            condition: Condition::try_from(condition).unwrap(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_and_is_not() {
+        assert!(rule("is", r"C:\Windows\System32\cmd.exe").matches(r"c:\windows\system32\CMD.EXE"));
+        assert!(!rule("is", "cmd.exe").matches(r"C:\Windows\System32\cmd.exe"));
+        assert!(rule("is not", "cmd.exe").matches("powershell.exe"));
+        assert!(!rule("is not", "cmd.exe").matches("CMD.exe"));
+    }
+
+    #[test]
+    fn test_contains_and_excludes() {
+        assert!(rule("contains", "PowerShell").matches(r"C:\Tools\powershell.exe"));
+        assert!(!rule("contains", "pwsh").matches(r"C:\Tools\powershell.exe"));
+        assert!(rule("excludes", "pwsh").matches(r"C:\Tools\powershell.exe"));
+        assert!(!rule("excludes", "POWERSHELL").matches(r"C:\Tools\powershell.exe"));
+    }
+
+    #[test]
+    fn test_multi_value_conditions() {
+        let command = "powershell.exe -NoProfile -EncodedCommand AAAA";
+        assert!(rule("contains all", "-noprofile -encodedcommand").matches(command));
+        assert!(!rule("contains all", "-noprofile -windowstyle").matches(command));
+        assert!(rule("contains any", "-windowstyle -encodedcommand").matches(command));
+        assert!(!rule("contains any", "-windowstyle -exec").matches(command));
+
+        // Sysmon's own delimiter keeps terms with spaces together
+        assert!(rule("contains any", "Program Files;-exec").matches(r"C:\Program Files\a.exe"));
+        assert!(!rule("contains any", "Program Data;-exec").matches(r"C:\Program Files\a.exe"));
+
+        assert!(rule("excludes all", "-windowstyle -exec").matches(command));
+        assert!(!rule("excludes all", "-windowstyle -noprofile").matches(command));
+        assert!(rule("excludes any", "-windowstyle -noprofile").matches(command));
+        assert!(!rule("excludes any", "-noprofile -encodedcommand").matches(command));
+    }
+
+    #[test]
+    fn test_begins_and_ends_with() {
+        assert!(rule("begins with", r"C:\Users\").matches(r"c:\users\bob\a.exe"));
+        assert!(!rule("begins with", r"C:\Windows\").matches(r"c:\users\bob\a.exe"));
+        assert!(rule("ends with", ".EXE").matches(r"c:\users\bob\a.exe"));
+        assert!(!rule("ends with", ".dll").matches(r"c:\users\bob\a.exe"));
+    }
+
+    #[test]
+    fn test_image() {
+        assert!(rule("image", "LSASS.exe").matches(r"C:\Windows\System32\lsass.exe"));
+        assert!(rule("image", "lsass.exe").matches("lsass.exe"));
+        assert!(!rule("image", "lsass.exe").matches(r"C:\Windows\System32\notlsass.exe"));
+        assert!(rule("image", r"C:\Windows\System32\lsass.exe").matches(r"c:\windows\system32\lsass.exe"));
+        assert!(!rule("image", r"C:\Windows\System32\lsass.exe").matches(r"C:\Temp\lsass.exe"));
+    }
+
+    #[test]
+    fn test_numeric_conditions() {
+        assert!(rule("less than", "1024").matches("443"));
+        assert!(!rule("less than", "1024").matches("1024"));
+        assert!(rule("more than", "1024").matches(" 4444 "));
+        assert!(!rule("more than", "1024").matches("80"));
+        assert!(!rule("more than", "1024").matches("http"));
+        assert!(!rule("less than", "many").matches("1"));
+    }
+
+    #[test]
+    fn test_event_type_coverage() {
+        let group = |events: &[&str]| RuleGroup {
+            name: String::new(),
+            group_relation: "or".to_string(),
+            events: events
+                .iter()
+                .map(|name| {
+                    let rules = EventRules {
+                        onmatch: "include".to_string(),
+                        rules: HashMap::from([("Image".to_string(), vec![rule("image", "cmd.exe")])]),
+                    };
+                    (name.to_string(), rules)
+                })
+                .collect(),
+        };
+        let mut config = SysmonConfig {
+            schema_version: "4.90".to_string(),
+            event_filtering: Some(EventFiltering {
+                rule_groups: vec![
+                    group(&["NetworkConnect", "ProcessCreate"]),
+                    group(&["ProcessCreate", "NotAnEvent"]),
+                ],
+            }),
+            hash_algorithms: None,
+            check_revocation: None,
+            dns_lookup: None,
+        };
+
+        assert_eq!(config.event_types(), vec![EventType::ProcessCreate, EventType::NetworkConnect]);
+        assert!(config.covers(EventType::NetworkConnect));
+        assert!(!config.covers(EventType::DnsQuery));
+        let uncovered = config.uncovered_event_types();
+        assert_eq!(uncovered.len(), EventType::all().len() - 2);
+        assert_eq!(uncovered[0], EventType::FileCreateTime);
+        let expected = 2.0 / EventType::all().len() as f64 * 100.0;
+        assert!((config.coverage_percentage() - expected).abs() < 1e-9);
+
+        config.event_filtering = None;
+        assert!(config.event_types().is_empty());
+        assert_eq!(config.coverage_percentage(), 0.0);
+    }
+
+    #[test]
+    fn test_condition_parsing() {
+        assert_eq!(Condition::try_from("IS").unwrap(), Condition::Is);
+        assert_eq!(Condition::try_from(" contains any ").unwrap(), Condition::ContainsAny);
+        assert_eq!(Condition::try_from("begins with").unwrap(), Condition::BeginsWith);
+        assert_eq!(Condition::try_from("end with").unwrap(), Condition::EndsWith);
+        assert_eq!(Condition::EndsWith.as_ref(), "end with");
+        assert!(Condition::try_from("resembles").is_err());
+
+        assert!(Condition::LessThan.is_numeric());
+        assert!(Condition::MoreThan.is_numeric());
+        assert!(!Condition::Contains.is_numeric());
+    }
+
+    #[test]
+    fn test_rule_serde() {
+        let rule: Rule = serde_json::from_str(r#"{"@condition": "Begins With", "$text": "C:\\Temp"}"#).unwrap();
+        assert_eq!(rule.condition, Condition::BeginsWith);
+        let json = serde_json::to_value(&rule).unwrap();
+        assert_eq!(json["@condition"], "begin with");
+
+        assert!(serde_json::from_str::<Rule>(r#"{"@condition": "resembles", "$text": "a"}"#).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use crate::error::PreprocessError;
+
+/// A rule repeating an earlier rule of the same event type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateRuleWarning {
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+    /// Line of the first occurrence
+    pub first_line: u64,
+    /// Line of the repeat
+    pub second_line: u64,
+}
+
+/// An include rule and an exclude rule matching the same values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContradictionWarning {
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+    /// Name of the `RuleGroup` holding the include rule
+    pub include_group: Option<String>,
+    /// Name of the `RuleGroup` holding the exclude rule
+    pub exclude_group: Option<String>,
+    pub message: String,
+}
+
+/// A field filter found by `collect_rules`
+struct LintRule {
+    event_type: String,
+    onmatch: Option<String>,
+    group: Option<String>,
+    field: String,
+    condition: String,
+    value: String,
+    line: u64,
+}
+
+impl LintRule {
+    fn key(&self) -> (&str, &str, &str, &str) {
+        (&self.event_type, &self.field, &self.condition, &self.value)
+    }
+}
+
+/// Finds rules that repeat an earlier `(event_type, field, condition, value)`
+/// in XML content, without changing it.
+///
+/// Every repeat is reported against the first occurrence, so a rule written
+/// three times gives two warnings. Lines are 1-based and point at the start
+/// tags.
+pub fn detect_duplicates_in_config(content: &str) -> Result<Vec<DuplicateRuleWarning>, PreprocessError> {
+    let rules = collect_rules(content)?;
+    let mut first_lines: HashMap<(&str, &str, &str, &str), u64> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for rule in &rules {
+        match first_lines.get(&rule.key()) {
+            Some(&first_line) => warnings.push(DuplicateRuleWarning {
+                event_type: rule.event_type.clone(),
+                field: rule.field.clone(),
+                condition: rule.condition.clone(),
+                value: rule.value.clone(),
+                first_line,
+                second_line: rule.line,
+            }),
+            None => {
+                first_lines.insert(rule.key(), rule.line);
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Finds rules included by one event filter and excluded by another, across
+/// the whole `EventFiltering` section. Each distinct
+/// `(event_type, field, condition, value)` is reported once, against the
+/// first include and the first exclude.
+pub fn detect_contradictions(content: &str) -> Result<Vec<ContradictionWarning>, PreprocessError> {
+    let rules = collect_rules(content)?;
+    let mut index: HashMap<(&str, &str, &str, &str), (Option<&LintRule>, Option<&LintRule>)> = HashMap::new();
+    let mut order = Vec::new();
+
+    for rule in &rules {
+        let entry = index.entry(rule.key()).or_insert_with(|| {
+            order.push(rule.key());
+            (None, None)
+        });
+        match rule.onmatch.as_deref() {
+            Some("include") => {
+                entry.0.get_or_insert(rule);
+            }
+            Some("exclude") => {
+                entry.1.get_or_insert(rule);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(order
+        .into_iter()
+        .filter_map(|key| match index[&key] {
+            (Some(include), Some(exclude)) => Some(ContradictionWarning {
+                event_type: include.event_type.clone(),
+                field: include.field.clone(),
+                condition: include.condition.clone(),
+                value: include.value.clone(),
+                include_group: include.group.clone(),
+                exclude_group: exclude.group.clone(),
+                message: format!(
+                    "{} {} {} {:?} is included on line {} and excluded on line {}",
+                    include.event_type, include.field, include.condition, include.value,
+                    include.line, exclude.line
+                ),
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// A field filter whose text is being read
+struct OpenRule {
+    field: String,
+    condition: String,
+    line: u64,
+    value: String,
+}
+
+/// An event filter, such as `ProcessCreate`, being read
+struct OpenEvent {
+    name: String,
+    onmatch: Option<String>,
+    depth: usize,
+}
+
+/// Reads every field filter under `EventFiltering`, in document order
+fn collect_rules(content: &str) -> Result<Vec<LintRule>, PreprocessError> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut rules = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut group: Option<String> = None;
+    let mut event: Option<OpenEvent> = None;
+    let mut rule: Option<OpenRule> = None;
+    let mut lines = LineCounter::new();
+
+    loop {
+        let start = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = element_name(&e)?;
+                let line = lines.line_at(content, start, reader.buffer_position() as usize);
+                match &event {
+                    None if name == "RuleGroup" => group = attribute(&e, b"name")?,
+                    None if is_event_position(&open, &name) => {
+                        event = Some(OpenEvent {
+                            name: name.clone(),
+                            onmatch: attribute(&e, b"onmatch")?,
+                            depth: open.len(),
+                        });
+                    }
+                    Some(_) if name != "Rule" => {
+                        rule = Some(OpenRule {
+                            field: name.clone(),
+                            condition: attribute(&e, b"condition")?.unwrap_or_else(|| "is".to_string()),
+                            line,
+                            value: String::new(),
+                        });
+                    }
+                    _ => {}
+                }
+                open.push(name);
+            }
+            Event::Text(e) => {
+                if let Some(rule) = rule.as_mut() {
+                    rule.value.push_str(&e.unescape()?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(rule) = rule.as_mut() {
+                    rule.value.push_str(&String::from_utf8_lossy(&e));
+                }
+            }
+            Event::End(e) => {
+                open.pop();
+                if let (Some(done), Some(event)) = (rule.take(), &event) {
+                    rules.push(LintRule {
+                        event_type: event.name.clone(),
+                        onmatch: event.onmatch.clone(),
+                        group: group.clone(),
+                        field: done.field,
+                        condition: done.condition,
+                        value: done.value.trim().to_string(),
+                        line: done.line,
+                    });
+                }
+                if event.as_ref().is_some_and(|event| event.depth == open.len()) {
+                    event = None;
+                } else if event.is_none() && e.name().as_ref() == b"RuleGroup" {
+                    group = None;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Event filters sit directly in a `RuleGroup`, or in `EventFiltering` in the
+/// older layout
+fn is_event_position(open: &[String], name: &str) -> bool {
+    match open {
+        [.., filtering, group] if filtering == "EventFiltering" && group == "RuleGroup" => true,
+        [.., filtering] if filtering == "EventFiltering" => name != "RuleGroup",
+        _ => false,
+    }
+}
+
+fn element_name(e: &BytesStart) -> Result<String, PreprocessError> {
+    Ok(std::str::from_utf8(e.name().as_ref())?.to_string())
+}
+
+fn attribute(e: &BytesStart, key: &[u8]) -> Result<Option<String>, PreprocessError> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(PreprocessError::from)?;
+        if attr.key.as_ref() == key {
+            return Ok(Some(attr.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Converts byte offsets to line numbers, scanning the content once
+struct LineCounter {
+    offset: usize,
+    line: u64,
+}
+
+impl LineCounter {
+    fn new() -> Self {
+        Self { offset: 0, line: 1 }
+    }
+
+    /// Line of the first non-whitespace byte in `start..end`
+    fn line_at(&mut self, content: &str, start: usize, end: usize) -> u64 {
+        let bytes = content.as_bytes();
+        let end = end.min(bytes.len());
+        let tag = (start..end)
+            .find(|&i| bytes[i] == b'<')
+            .unwrap_or(end);
+        self.line += bytes[self.offset..tag].iter().filter(|&&b| b == b'\n').count() as u64;
+        self.offset = tag;
+        self.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="first" groupRelation="or">
+            <ProcessCreate onmatch="include">
+                <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+                <Image condition="contains">cmd.exe</Image>
+                <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+            </ProcessCreate>
+        </RuleGroup>
+        <RuleGroup name="second" groupRelation="or">
+            <ProcessCreate onmatch="include">
+                <Rule groupRelation="and">
+                    <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+                </Rule>
+            </ProcessCreate>
+            <NetworkConnect onmatch="include">
+                <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+            </NetworkConnect>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+
+    #[test]
+    fn test_detect_duplicates() {
+        let warnings = detect_duplicates_in_config(XML).unwrap();
+        assert_eq!(warnings.len(), 2);
+
+        for warning in &warnings {
+            assert_eq!(warning.event_type, "ProcessCreate");
+            assert_eq!(warning.field, "Image");
+            assert_eq!(warning.condition, "is");
+            assert_eq!(warning.value, r"C:\Windows\System32\cmd.exe");
+            assert_eq!(warning.first_line, 5);
+        }
+        assert_eq!(warnings[0].second_line, 7);
+        assert_eq!(warnings[1].second_line, 13);
+    }
+
+    #[test]
+    fn test_first_occurrence_moves() {
+        let xml = XML.replacen(r"C:\Windows\System32\cmd.exe", "a.exe", 1);
+        let warnings = detect_duplicates_in_config(&xml).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].first_line, warnings[0].second_line), (7, 13));
+        assert!(detect_duplicates_in_config("<Sysmon/>").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_detect_contradictions() {
+        let xml = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="shells" groupRelation="or">
+            <ProcessCreate onmatch="include">
+                <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+                <Image condition="end with">powershell.exe</Image>
+            </ProcessCreate>
+        </RuleGroup>
+        <RuleGroup name="noise" groupRelation="or">
+            <ProcessCreate onmatch="exclude">
+                <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+                <Image condition="is">powershell.exe</Image>
+            </ProcessCreate>
+            <NetworkConnect onmatch="exclude">
+                <Image condition="end with">powershell.exe</Image>
+            </NetworkConnect>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+        let warnings = detect_contradictions(xml).unwrap();
+        assert_eq!(warnings.len(), 1);
+        let warning = &warnings[0];
+        assert_eq!(warning.event_type, "ProcessCreate");
+        assert_eq!(warning.value, r"C:\Windows\System32\cmd.exe");
+        assert_eq!(warning.include_group.as_deref(), Some("shells"));
+        assert_eq!(warning.exclude_group.as_deref(), Some("noise"));
+        assert!(warning.message.contains("line 5"), "{}", warning.message);
+        assert!(warning.message.contains("line 11"), "{}", warning.message);
+
+        assert!(detect_contradictions(XML).unwrap().is_empty());
+    }
+}
@@ -1,3 +1,21 @@
+mod lint;
+mod options;
 mod path;
+mod pipeline;
+mod template;
 
-pub use path::preprocess_config;
\ No newline at end of file
+pub use lint::{
+    detect_contradictions, detect_duplicates_in_config, ContradictionWarning, DuplicateRuleWarning,
+};
+pub use options::{PathStyle, PreprocessorOptions, PreprocessorOptionsBuilder};
+pub use pipeline::{
+    default_windows_env_map, CaseNormalizeStep, CommentStrippingStep, ExpandEnvVarsStep,
+    PathNormalizationStep, PreprocessPipeline, PreprocessStep, SortRulesStep,
+    WhitespaceNormalizationStep,
+};
+pub use template::TemplateSubstitutionStep;
+pub(crate) use template::substitute_json;
+#[cfg(feature = "fs")]
+pub use path::{preprocess_config, preprocess_config_with_options};
+pub use path::{preprocess_str, preprocess_str_with_options};
+pub(crate) use path::normalize_path;
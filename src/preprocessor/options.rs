@@ -0,0 +1,103 @@
+/// Separator style applied to path values during preprocessing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathStyle {
+    /// Drive-letter and UNC paths get Windows form; other values are kept
+    #[default]
+    Auto,
+    /// Every path value uses backslashes
+    Windows,
+    /// Every path value uses forward slashes
+    Linux,
+}
+
+/// Options controlling what the preprocessor changes in XML content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessorOptions {
+    /// Drop XML comments
+    pub strip_comments: bool,
+    /// Trim whitespace around text and drop whitespace-only text
+    pub normalize_whitespace: bool,
+    /// Sort the rules inside each event filter by element name, condition,
+    /// and value
+    pub sort_rules: bool,
+    /// Normalize path values (see `path_style`)
+    pub normalize_paths: bool,
+    /// How paths are normalized when `normalize_paths` is set
+    pub path_style: PathStyle,
+    /// Lowercase path-like element text, after path normalization
+    pub normalize_case: bool,
+}
+
+impl Default for PreprocessorOptions {
+    fn default() -> Self {
+        Self {
+            strip_comments: true,
+            normalize_whitespace: true,
+            sort_rules: false,
+            normalize_paths: true,
+            path_style: PathStyle::Auto,
+            normalize_case: true,
+        }
+    }
+}
+
+/// Builder pattern for PreprocessorOptions
+pub struct PreprocessorOptionsBuilder {
+    options: PreprocessorOptions,
+}
+
+impl PreprocessorOptionsBuilder {
+    /// Creates a new PreprocessorOptionsBuilder with default values
+    pub fn new() -> Self {
+        Self {
+            options: PreprocessorOptions::default(),
+        }
+    }
+
+    /// Sets whether to drop comments
+    pub fn strip_comments(mut self, strip: bool) -> Self {
+        self.options.strip_comments = strip;
+        self
+    }
+
+    /// Sets whether to trim whitespace
+    pub fn normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.options.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Sets whether to sort rules within event filters
+    pub fn sort_rules(mut self, sort: bool) -> Self {
+        self.options.sort_rules = sort;
+        self
+    }
+
+    /// Sets whether to normalize path values
+    pub fn normalize_paths(mut self, normalize: bool) -> Self {
+        self.options.normalize_paths = normalize;
+        self
+    }
+
+    /// Sets the separator style for normalized paths
+    pub fn path_style(mut self, style: PathStyle) -> Self {
+        self.options.path_style = style;
+        self
+    }
+
+    /// Sets whether to lowercase path values
+    pub fn normalize_case(mut self, normalize: bool) -> Self {
+        self.options.normalize_case = normalize;
+        self
+    }
+
+    /// Builds the PreprocessorOptions
+    pub fn build(self) -> PreprocessorOptions {
+        self.options
+    }
+}
+
+impl Default for PreprocessorOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,100 +1,104 @@
+#[cfg(feature = "fs")]
+use log::warn;
+#[cfg(feature = "fs")]
 use std::path::Path;
-use quick_xml::Reader;
-use quick_xml::Writer;
-use quick_xml::events::{BytesStart, BytesText, Event};
+#[cfg(feature = "validation")]
 use sysmon_validator::{
     parse_sysmon_config_from_str,
     validate_sysmon_config,
 };
 use crate::error::PreprocessError;
+#[cfg(feature = "fs")]
+use super::lint::detect_contradictions;
+use super::options::{PathStyle, PreprocessorOptions};
+use super::pipeline::PreprocessPipeline;
 
+/// Validates and preprocesses a config file. Errors carry the file path
+/// (see `PreprocessError::path`).
+#[cfg(feature = "fs")]
 pub fn preprocess_config(input_path: &Path) -> Result<String, PreprocessError> {
+    preprocess_config_with_options(input_path, &PreprocessorOptions::default())
+}
+
+/// Validates and preprocesses a config file, as controlled by `options`
+#[cfg(feature = "fs")]
+pub fn preprocess_config_with_options(
+    input_path: &Path,
+    options: &PreprocessorOptions,
+) -> Result<String, PreprocessError> {
     // Check file extension
     let _ext = input_path.extension()
         .and_then(|e| e.to_str())
-        .ok_or_else(|| PreprocessError::PathError("Missing file extension".to_string()))?
+        .ok_or_else(|| PreprocessError::PathError("Missing file extension".to_string()).in_file(input_path))?
         .to_lowercase();
 
     // Read and validate the input file
-    let content = std::fs::read_to_string(input_path)?;
-    let config = parse_sysmon_config_from_str(&content)
-        .map_err(PreprocessError::ParserError)?;
-    validate_sysmon_config(&config)
-        .map_err(PreprocessError::ValidationError)?;
-
-    // Process the content with path normalization
-    let preprocessed = process_xml_content(&content)?;
-    Ok(preprocessed)
-}
+    let content = std::fs::read_to_string(input_path)
+        .map_err(|e| PreprocessError::from(e).in_file(input_path))?;
+    let processed = preprocess_str_with_options(&content, options).map_err(|e| e.in_file(input_path))?;
 
-fn process_xml_content(content: &str) -> Result<String, PreprocessError> {
-    let mut reader = Reader::from_str(content);
-    let mut writer = Writer::new(Vec::new());
-    let mut buf = Vec::new();
-    reader.config_mut().trim_text(true);
-
-    while let Ok(event) = reader.read_event_into(&mut buf) {
-        match event {
-            Event::Start(ref e) => {
-                let name_bytes = e.name();
-                let name = name_bytes.as_ref();
-                let name_str = std::str::from_utf8(name)?;
-                let mut elem = BytesStart::new(name_str);
-                
-                // Process attributes with path normalization if needed
-                for attr_result in e.attributes() {
-                    let attr = attr_result.map_err(PreprocessError::from)?;
-                    if is_path_attribute(attr.key.as_ref()) {
-                        let value = attr.unescape_value()?;
-                        if let Ok(normalized) = normalize_path(&value) {
-                            elem.push_attribute((
-                                std::str::from_utf8(attr.key.as_ref())?,
-                                normalized.as_str()
-                            ));
-                        } else {
-                            elem.push_attribute(attr);
-                        }
-                    } else {
-                        elem.push_attribute(attr);
-                    }
-                }
-                
-                writer.write_event(Event::Start(elem))?;
-            }
-            Event::Text(e) => {
-                let text = e.unescape()?;
-                if is_path_element(&text) {
-                    if let Ok(normalized) = normalize_path(&text) {
-                        writer.write_event(Event::Text(BytesText::new(&normalized)))?;
-                    } else {
-                        writer.write_event(Event::Text(e))?;
-                    }
-                } else {
-                    writer.write_event(Event::Text(e))?;
-                }
-            }
-            Event::End(e) => writer.write_event(Event::End(e))?,
-            Event::Comment(_) => continue,
-            Event::Eof => break,
-            _ => writer.write_event(event)?,
+    // Rules that are both included and excluded are legal but almost always
+    // a mistake
+    if let Ok(contradictions) = detect_contradictions(&content) {
+        for contradiction in contradictions {
+            warn!("{}: {}", input_path.display(), contradiction.message);
         }
-        buf.clear();
+    }
+    Ok(processed)
+}
+
+/// Validates (with the `validation` feature) and preprocesses XML content
+/// already held in memory
+pub fn preprocess_str(content: &str) -> Result<String, PreprocessError> {
+    preprocess_str_with_options(content, &PreprocessorOptions::default())
+}
+
+/// Validates and preprocesses XML content held in memory, as controlled by
+/// `options`
+pub fn preprocess_str_with_options(
+    content: &str,
+    options: &PreprocessorOptions,
+) -> Result<String, PreprocessError> {
+    #[cfg(feature = "validation")]
+    {
+        let config = parse_sysmon_config_from_str(content)
+            .map_err(PreprocessError::ParserError)?;
+        validate_sysmon_config(&config)
+            .map_err(PreprocessError::ValidationError)?;
     }
 
-    String::from_utf8(writer.into_inner())
-        .map_err(|e| PreprocessError::PathError(e.to_string()))
+    // Run the preprocessing steps selected by the options
+    PreprocessPipeline::from_options(options).run(content)
 }
 
-fn is_path_attribute(key: &[u8]) -> bool {
+pub(super) fn is_path_attribute(key: &[u8]) -> bool {
     matches!(key, b"Image" | b"ImageLoaded" | b"TargetFilename")
 }
 
-fn is_path_element(text: &str) -> bool {
+pub(super) fn is_path_element(text: &str) -> bool {
     text.contains('\\') || text.contains(':')
 }
 
-fn normalize_path(path: &str) -> Result<String, PreprocessError> {
+pub(crate) fn normalize_path(path: &str) -> Result<String, PreprocessError> {
+    normalize_path_with_style(path, PathStyle::Auto)
+}
+
+pub(super) fn normalize_path_with_style(path: &str, style: PathStyle) -> Result<String, PreprocessError> {
     let path = path.trim();
+
+    // URLs and other values with a scheme are not paths
+    match style {
+        PathStyle::Auto => {}
+        _ if path.contains("://") => return Ok(path.to_string()),
+        PathStyle::Windows => {
+            let normalized = normalize_path_with_style(path, PathStyle::Auto)?;
+            return Ok(normalized.replace('/', "\\"));
+        }
+        PathStyle::Linux => {
+            let normalized = normalize_path_with_style(path, PathStyle::Auto)?;
+            return Ok(normalized.replace('\\', "/"));
+        }
+    }
     
     // Handle UNC paths
     if path.starts_with("\\\\") {
@@ -123,7 +127,9 @@ fn normalize_path(path: &str) -> Result<String, PreprocessError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "fs")]
     use std::fs;
+    #[cfg(feature = "fs")]
     use tempfile::tempdir;
 
     #[test]
@@ -148,6 +154,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "fs")]
     fn test_config_preprocessing() {
         let temp_dir = tempdir().unwrap();
         let input_path = temp_dir.path().join("test.xml");
@@ -172,4 +179,53 @@ mod tests {
         let processed = result.unwrap();
         assert!(processed.contains(r"C:\windows\system32\cmd.exe"));
     }
-}
\ No newline at end of file
+
+    const RULES_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <!-- shells -->
+                    <Image condition="is">c:/windows/system32/cmd.exe</Image>
+                    <CommandLine condition="contains">bypass</CommandLine>
+                    <Image condition="is">c:/windows/system32/at.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_default_options_match_preprocess_str() {
+        let default = preprocess_str_with_options(RULES_XML, &PreprocessorOptions::default()).unwrap();
+        assert_eq!(default, preprocess_str(RULES_XML).unwrap());
+        assert!(!default.contains("shells"));
+        assert!(default.contains(r"C:\windows\system32\cmd.exe"));
+    }
+
+    #[test]
+    fn test_options_disable_normalization() {
+        let options = PreprocessorOptionsBuilder::new()
+            .strip_comments(false)
+            .normalize_paths(false)
+            .build();
+        let processed = preprocess_str_with_options(RULES_XML, &options).unwrap();
+        assert!(processed.contains("<!-- shells -->"));
+        assert!(processed.contains("c:/windows/system32/cmd.exe"));
+
+        let options = PreprocessorOptionsBuilder::new()
+            .path_style(PathStyle::Linux)
+            .build();
+        let processed = preprocess_str_with_options(RULES_XML, &options).unwrap();
+        assert!(processed.contains("C:/windows/system32/cmd.exe"));
+    }
+
+    #[test]
+    fn test_sort_rules() {
+        let options = PreprocessorOptionsBuilder::new().sort_rules(true).build();
+        let processed = preprocess_str_with_options(RULES_XML, &options).unwrap();
+
+        let position = |text: &str| processed.find(text).unwrap();
+        assert!(position("bypass") < position("at.exe"));
+        assert!(position("at.exe") < position("cmd.exe"));
+        assert!(processed.contains(r#"<RuleGroup name="test" groupRelation="or"><ProcessCreate onmatch="include">"#));
+    }
+}
@@ -0,0 +1,583 @@
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::Reader;
+use quick_xml::Writer;
+use log::warn;
+use std::collections::HashMap;
+use crate::error::PreprocessError;
+use super::options::{PathStyle, PreprocessorOptions};
+use super::path::{is_path_attribute, is_path_element, normalize_path_with_style};
+
+/// A single preprocessing stage, rewriting XML content
+pub trait PreprocessStep: Send + Sync {
+    fn process(&self, content: &str) -> Result<String, PreprocessError>;
+
+    /// Name used to identify the step in a pipeline
+    fn name(&self) -> &str;
+}
+
+/// An ordered list of preprocessing steps, each run on the output of the
+/// previous one
+#[derive(Default)]
+pub struct PreprocessPipeline {
+    steps: Vec<Box<dyn PreprocessStep>>,
+}
+
+impl PreprocessPipeline {
+    /// Creates a pipeline with no steps
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the pipeline `preprocess_str_with_options` runs for `options`
+    pub fn from_options(options: &PreprocessorOptions) -> Self {
+        let mut pipeline = Self::new();
+        if options.normalize_whitespace {
+            pipeline.add_step(Box::new(WhitespaceNormalizationStep));
+        }
+        if options.strip_comments {
+            pipeline.add_step(Box::new(CommentStrippingStep));
+        }
+        if options.normalize_paths {
+            pipeline.add_step(Box::new(PathNormalizationStep::new(options.path_style)));
+        }
+        if options.normalize_case {
+            pipeline.add_step(Box::new(CaseNormalizeStep::new()));
+        }
+        if options.sort_rules {
+            pipeline.add_step(Box::new(SortRulesStep));
+        }
+        pipeline
+    }
+
+    /// Appends a step
+    pub fn add_step(&mut self, step: Box<dyn PreprocessStep>) -> &mut Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Inserts a step at `index`, shifting later steps back
+    pub fn insert_step(&mut self, index: usize, step: Box<dyn PreprocessStep>) -> &mut Self {
+        self.steps.insert(index, step);
+        self
+    }
+
+    /// Removes every step with the given name
+    pub fn remove_step(&mut self, name: &str) -> &mut Self {
+        self.steps.retain(|step| step.name() != name);
+        self
+    }
+
+    /// Gets the names of the steps, in order
+    pub fn step_names(&self) -> Vec<&str> {
+        self.steps.iter().map(|step| step.name()).collect()
+    }
+
+    /// Runs every step in order
+    pub fn run(&self, content: &str) -> Result<String, PreprocessError> {
+        let mut content = content.to_string();
+        for step in &self.steps {
+            content = step.process(&content)?;
+        }
+        Ok(content)
+    }
+}
+
+/// Trims whitespace around text and drops whitespace-only text
+pub struct WhitespaceNormalizationStep;
+
+impl PreprocessStep for WhitespaceNormalizationStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        rewrite(content, true, |event, writer| Ok(writer.write_event(event)?))
+    }
+
+    fn name(&self) -> &str {
+        "whitespace"
+    }
+}
+
+/// Drops XML comments
+pub struct CommentStrippingStep;
+
+impl PreprocessStep for CommentStrippingStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        rewrite(content, false, |event, writer| match event {
+            Event::Comment(_) => Ok(()),
+            other => Ok(writer.write_event(other)?),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "comments"
+    }
+}
+
+/// Normalizes path attributes and path-like text (see `PathStyle`)
+pub struct PathNormalizationStep {
+    style: PathStyle,
+}
+
+impl PathNormalizationStep {
+    pub fn new(style: PathStyle) -> Self {
+        Self { style }
+    }
+}
+
+impl PreprocessStep for PathNormalizationStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        rewrite(content, false, |event, writer| {
+            match event {
+                Event::Start(ref e) => {
+                    let name_bytes = e.name();
+                    let name_str = std::str::from_utf8(name_bytes.as_ref())?;
+                    let mut elem = BytesStart::new(name_str);
+
+                    // Process attributes with path normalization if needed
+                    for attr_result in e.attributes() {
+                        let attr = attr_result.map_err(PreprocessError::from)?;
+                        if is_path_attribute(attr.key.as_ref()) {
+                            let value = attr.unescape_value()?;
+                            if let Ok(normalized) = normalize_path_with_style(&value, self.style) {
+                                elem.push_attribute((
+                                    std::str::from_utf8(attr.key.as_ref())?,
+                                    normalized.as_str()
+                                ));
+                            } else {
+                                elem.push_attribute(attr);
+                            }
+                        } else {
+                            elem.push_attribute(attr);
+                        }
+                    }
+
+                    writer.write_event(Event::Start(elem))?;
+                }
+                Event::Text(e) => {
+                    let text = e.unescape()?;
+                    if is_path_element(&text) {
+                        if let Ok(normalized) = normalize_path_with_style(&text, self.style) {
+                            writer.write_event(Event::Text(BytesText::new(&normalized)))?;
+                            return Ok(());
+                        }
+                    }
+                    writer.write_event(Event::Text(e))?;
+                }
+                other => writer.write_event(other)?,
+            }
+            Ok(())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "paths"
+    }
+}
+
+/// Lowercases path-like element text, so the same path written in different
+/// cases compares equal across configs. A leading drive letter stays
+/// uppercase, as written by `PathNormalizationStep`.
+#[derive(Default)]
+pub struct CaseNormalizeStep {
+    excluded: Vec<String>,
+}
+
+impl CaseNormalizeStep {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves the text of the named elements, such as `CommandLine`, as written
+    pub fn with_excluded_elements(elements: &[&str]) -> Self {
+        Self {
+            excluded: elements.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn lowercase(value: &str) -> String {
+        let mut lowered = value.to_lowercase();
+        if lowered.as_bytes().get(1) == Some(&b':') && lowered.as_bytes()[0].is_ascii_alphabetic() {
+            lowered[..1].make_ascii_uppercase();
+        }
+        lowered
+    }
+}
+
+impl PreprocessStep for CaseNormalizeStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        let mut open: Vec<String> = Vec::new();
+        rewrite(content, false, |event, writer| {
+            match event {
+                Event::Start(ref e) => {
+                    open.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                    writer.write_event(event)?;
+                }
+                Event::End(_) => {
+                    open.pop();
+                    writer.write_event(event)?;
+                }
+                Event::Text(e) => {
+                    let text = e.unescape()?;
+                    let excluded = open
+                        .last()
+                        .is_some_and(|name| self.excluded.iter().any(|x| x == name));
+                    if !excluded && is_path_element(&text) {
+                        writer.write_event(Event::Text(BytesText::new(&Self::lowercase(&text))))?;
+                    } else {
+                        writer.write_event(Event::Text(e))?;
+                    }
+                }
+                other => writer.write_event(other)?,
+            }
+            Ok(())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "case"
+    }
+}
+
+/// Expands `%NAME%` references in text and attribute values from a variable
+/// map. Names are matched case-insensitively, as on Windows; unknown names are
+/// left as written and logged.
+pub struct ExpandEnvVarsStep {
+    env_map: HashMap<String, String>,
+}
+
+/// Common Windows environment variables with their stock values, as seen by
+/// the Sysmon service
+pub fn default_windows_env_map() -> HashMap<String, String> {
+    [
+        ("SystemRoot", r"C:\Windows"),
+        ("WINDIR", r"C:\Windows"),
+        ("SystemDrive", "C:"),
+        ("ProgramFiles", r"C:\Program Files"),
+        ("ProgramFiles(x86)", r"C:\Program Files (x86)"),
+        ("CommonProgramFiles", r"C:\Program Files\Common Files"),
+        ("TEMP", r"C:\Windows\Temp"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect()
+}
+
+impl ExpandEnvVarsStep {
+    /// Expands from `default_windows_env_map`
+    pub fn new() -> Self {
+        Self::with_custom_map(default_windows_env_map())
+    }
+
+    /// Expands from the given variables instead of the default map
+    pub fn with_custom_map(map: HashMap<String, String>) -> Self {
+        Self { env_map: map }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&str> {
+        self.env_map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn expand(&self, value: &str) -> String {
+        let mut expanded = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find('%') {
+            let after = &rest[start + 1..];
+            let Some(end) = after.find('%') else {
+                break;
+            };
+            expanded.push_str(&rest[..start]);
+            let name = &after[..end];
+            match self.lookup(name).filter(|_| !name.is_empty()) {
+                Some(replacement) => {
+                    expanded.push_str(replacement);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    if !name.is_empty() && !name.contains(char::is_whitespace) {
+                        warn!("Unresolved environment variable %{}%", name);
+                    }
+                    // Keep the opening `%` and retry from the closing one,
+                    // which may start a reference of its own
+                    expanded.push('%');
+                    expanded.push_str(name);
+                    rest = &after[end..];
+                }
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+
+    fn element(&self, e: &BytesStart) -> Result<BytesStart<'static>, PreprocessError> {
+        let name_bytes = e.name();
+        let mut elem = BytesStart::new(std::str::from_utf8(name_bytes.as_ref())?.to_string());
+        for attr_result in e.attributes() {
+            let attr = attr_result.map_err(PreprocessError::from)?;
+            let value = self.expand(&attr.unescape_value()?);
+            elem.push_attribute((std::str::from_utf8(attr.key.as_ref())?, value.as_str()));
+        }
+        Ok(elem)
+    }
+}
+
+impl Default for ExpandEnvVarsStep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreprocessStep for ExpandEnvVarsStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        rewrite(content, false, |event, writer| {
+            match event {
+                Event::Start(ref e) => writer.write_event(Event::Start(self.element(e)?))?,
+                Event::Empty(ref e) => writer.write_event(Event::Empty(self.element(e)?))?,
+                Event::Text(e) => {
+                    let text = e.unescape()?;
+                    if text.contains('%') {
+                        writer.write_event(Event::Text(BytesText::new(&self.expand(&text))))?;
+                    } else {
+                        writer.write_event(Event::Text(e))?;
+                    }
+                }
+                other => writer.write_event(other)?,
+            }
+            Ok(())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "env_vars"
+    }
+}
+
+/// Sorts the rules inside each event filter by element name, condition, and
+/// value
+pub struct SortRulesStep;
+
+impl PreprocessStep for SortRulesStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        // Names of the open elements, and the event filter being sorted
+        let mut open: Vec<String> = Vec::new();
+        let mut sorter: Option<RuleSorter> = None;
+
+        rewrite(content, false, |event, writer| {
+            match event {
+                Event::Start(e) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                    // Rules sit in event filters, which are the children of a
+                    // RuleGroup (or of EventFiltering, in the older layout)
+                    let is_event_filter = match open.last().map(String::as_str) {
+                        Some("RuleGroup") => true,
+                        Some("EventFiltering") => name != "RuleGroup",
+                        _ => false,
+                    };
+                    open.push(name);
+
+                    match &mut sorter {
+                        Some(sorter) => {
+                            if open.len() == sorter.depth + 1 {
+                                sorter.start_rule(&e);
+                            }
+                            sorter.push(Event::Start(e.into_owned()));
+                        }
+                        None => {
+                            writer.write_event(Event::Start(e))?;
+                            if is_event_filter {
+                                sorter = Some(RuleSorter::new(open.len()));
+                            }
+                        }
+                    }
+                }
+                Event::Text(e) => match &mut sorter {
+                    Some(sorter) => {
+                        if open.len() == sorter.depth + 1 {
+                            sorter.set_text(&e.unescape()?);
+                        }
+                        sorter.push(Event::Text(e.into_owned()));
+                    }
+                    None => writer.write_event(Event::Text(e))?,
+                },
+                Event::End(e) => {
+                    let end = Event::End(e.into_owned());
+                    match sorter.take() {
+                        Some(finished) if open.len() == finished.depth => {
+                            for event in finished.into_sorted_events() {
+                                writer.write_event(event)?;
+                            }
+                            writer.write_event(end)?;
+                        }
+                        Some(mut active) => {
+                            active.push(end);
+                            sorter = Some(active);
+                        }
+                        None => writer.write_event(end)?,
+                    }
+                    open.pop();
+                }
+                other => match &mut sorter {
+                    Some(sorter) => {
+                        if let Event::Empty(ref e) = other {
+                            if open.len() == sorter.depth {
+                                sorter.start_rule(e);
+                            }
+                        }
+                        sorter.push(other.into_owned());
+                    }
+                    None => writer.write_event(other)?,
+                },
+            }
+            Ok(())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "sort_rules"
+    }
+}
+
+/// Buffers the rules of one event filter so they can be written sorted
+struct RuleSorter {
+    /// Depth of the event filter element
+    depth: usize,
+    /// Events before the first rule
+    leading: Vec<Event<'static>>,
+    /// Rules with their sort key (element name, condition, text)
+    rules: Vec<((String, String, String), Vec<Event<'static>>)>,
+}
+
+impl RuleSorter {
+    fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            leading: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    fn start_rule(&mut self, elem: &BytesStart) {
+        let name = String::from_utf8_lossy(elem.name().as_ref()).into_owned();
+        let condition = elem
+            .try_get_attribute("condition")
+            .ok()
+            .flatten()
+            .map(|attr| String::from_utf8_lossy(&attr.value).into_owned())
+            .unwrap_or_default();
+        self.rules.push(((name, condition, String::new()), Vec::new()));
+    }
+
+    fn set_text(&mut self, text: &str) {
+        if let Some(((_, _, key), _)) = self.rules.last_mut() {
+            key.push_str(text);
+        }
+    }
+
+    fn push(&mut self, event: Event<'static>) {
+        match self.rules.last_mut() {
+            Some((_, events)) => events.push(event),
+            None => self.leading.push(event),
+        }
+    }
+
+    fn into_sorted_events(mut self) -> Vec<Event<'static>> {
+        self.rules.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut events = self.leading;
+        events.extend(self.rules.into_iter().flat_map(|(_, events)| events));
+        events
+    }
+}
+
+/// Runs one streaming pass over `content`, handing each event to `handle`
+/// along with the output writer
+pub(super) fn rewrite<F>(content: &str, trim_text: bool, mut handle: F) -> Result<String, PreprocessError>
+where
+    F: FnMut(Event<'_>, &mut Writer<Vec<u8>>) -> Result<(), PreprocessError>,
+{
+    let mut reader = Reader::from_str(content);
+    let mut writer = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+    reader.config_mut().trim_text(trim_text);
+
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        if let Event::Eof = event {
+            break;
+        }
+        handle(event, &mut writer)?;
+        buf.clear();
+    }
+
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| PreprocessError::PathError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <!-- tools -->
+                    <Image condition="is">%SystemRoot%\System32\cmd.exe</Image>
+                    <CommandLine condition="contains">%Missing%</CommandLine>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_default_pipeline_steps() {
+        let pipeline = PreprocessPipeline::from_options(&PreprocessorOptions::default());
+        assert_eq!(pipeline.step_names(), vec!["whitespace", "comments", "paths", "case"]);
+    }
+
+    #[test]
+    fn test_custom_pipeline() {
+        let vars = HashMap::from([("SystemRoot".to_string(), r"c:\windows".to_string())]);
+        let mut pipeline = PreprocessPipeline::from_options(&PreprocessorOptions::default());
+        pipeline
+            .remove_step("comments")
+            .insert_step(1, Box::new(ExpandEnvVarsStep::with_custom_map(vars)));
+        assert_eq!(pipeline.step_names(), vec!["whitespace", "env_vars", "paths", "case"]);
+
+        let processed = pipeline.run(XML).unwrap();
+        assert!(processed.contains("<!-- tools -->"));
+        assert!(processed.contains(r"C:\windows\system32\cmd.exe"));
+        assert!(processed.contains("%Missing%"));
+    }
+
+    #[test]
+    fn test_env_var_expansion() {
+        let vars = HashMap::from([("A".to_string(), "1".to_string())]);
+        let step = ExpandEnvVarsStep::with_custom_map(vars);
+        assert_eq!(step.expand("%A%/%B%/%A%"), "1/%B%/1");
+        assert_eq!(step.expand("100% %a%"), "100% 1");
+        assert_eq!(step.expand("50%"), "50%");
+    }
+
+    #[test]
+    fn test_case_normalization() {
+        let xml = r#"<ProcessCreate><Image condition="is">c:\Windows\System32\CMD.EXE</Image><CommandLine condition="contains">C:\Tools\Run.EXE</CommandLine><Description condition="is">Windows Host</Description></ProcessCreate>"#;
+        let processed = CaseNormalizeStep::new().process(xml).unwrap();
+        assert!(processed.contains(r"C:\windows\system32\cmd.exe"));
+        assert!(processed.contains(r"C:\tools\run.exe"));
+        assert!(processed.contains("Windows Host"));
+
+        let step = CaseNormalizeStep::with_excluded_elements(&["CommandLine"]);
+        let processed = step.process(xml).unwrap();
+        assert!(processed.contains(r"C:\windows\system32\cmd.exe"));
+        assert!(processed.contains(r"C:\Tools\Run.EXE"));
+    }
+
+    #[test]
+    fn test_default_windows_env_map() {
+        let step = ExpandEnvVarsStep::new();
+        let xml = r#"<ProcessCreate><Image condition="begin with">%ProgramFiles(x86)%\Tool</Image><Rule path="%windir%\Temp"/></ProcessCreate>"#;
+        let processed = step.process(xml).unwrap();
+        assert!(processed.contains(r"C:\Program Files (x86)\Tool"));
+        assert!(processed.contains(r#"path="C:\Windows\Temp""#));
+        assert_eq!(default_windows_env_map().len(), 7);
+    }
+}
@@ -0,0 +1,171 @@
+use quick_xml::events::{BytesStart, BytesText, Event};
+use std::collections::HashMap;
+use crate::error::PreprocessError;
+use super::pipeline::{rewrite, PreprocessStep};
+
+/// Replaces `{{NAME}}` placeholders in element text and attribute values.
+///
+/// In strict mode an unknown name is an error; otherwise the placeholder is
+/// left as written.
+pub struct TemplateSubstitutionStep {
+    variables: HashMap<String, String>,
+    strict: bool,
+}
+
+impl TemplateSubstitutionStep {
+    pub fn new(variables: HashMap<String, String>, strict: bool) -> Self {
+        Self { variables, strict }
+    }
+
+    /// Substitutes the placeholders in a single value
+    pub fn substitute(&self, value: &str) -> Result<String, PreprocessError> {
+        substitute(value, &self.variables, self.strict)
+    }
+
+    fn element(&self, e: &BytesStart) -> Result<BytesStart<'static>, PreprocessError> {
+        let name_bytes = e.name();
+        let mut elem = BytesStart::new(std::str::from_utf8(name_bytes.as_ref())?.to_string());
+        for attr_result in e.attributes() {
+            let attr = attr_result.map_err(PreprocessError::from)?;
+            let value = attr.unescape_value()?;
+            if value.contains("{{") {
+                let value = self.substitute(&value)?;
+                elem.push_attribute((std::str::from_utf8(attr.key.as_ref())?, value.as_str()));
+            } else {
+                elem.push_attribute(attr);
+            }
+        }
+        Ok(elem)
+    }
+}
+
+impl PreprocessStep for TemplateSubstitutionStep {
+    fn process(&self, content: &str) -> Result<String, PreprocessError> {
+        rewrite(content, false, |event, writer| {
+            match event {
+                Event::Start(ref e) => writer.write_event(Event::Start(self.element(e)?))?,
+                Event::Empty(ref e) => writer.write_event(Event::Empty(self.element(e)?))?,
+                Event::Text(e) => {
+                    let text = e.unescape()?;
+                    if text.contains("{{") {
+                        let substituted = self.substitute(&text)?;
+                        writer.write_event(Event::Text(BytesText::new(&substituted)))?;
+                    } else {
+                        writer.write_event(Event::Text(e))?;
+                    }
+                }
+                other => writer.write_event(other)?,
+            }
+            Ok(())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "template"
+    }
+}
+
+/// Substitutes the placeholders in every string of a JSON document
+pub(crate) fn substitute_json(
+    value: &mut serde_json::Value,
+    variables: &HashMap<String, String>,
+    strict: bool,
+) -> Result<(), PreprocessError> {
+    match value {
+        serde_json::Value::String(s) if s.contains("{{") => {
+            *s = substitute(s, variables, strict)?;
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                substitute_json(value, variables, strict)?;
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for value in map.values_mut() {
+                substitute_json(value, variables, strict)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn substitute(
+    value: &str,
+    variables: &HashMap<String, String>,
+    strict: bool,
+) -> Result<String, PreprocessError> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + 2 + len + 2];
+        let name = placeholder[2..placeholder.len() - 2].trim();
+
+        result.push_str(&rest[..start]);
+        match variables.get(name) {
+            Some(replacement) => result.push_str(replacement),
+            None if strict => return Err(PreprocessError::UnresolvedVariable(name.to_string())),
+            None => result.push_str(placeholder),
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("CORP_TOOLS_DIR".to_string(), r"C:\Tools".to_string()),
+            ("AGENT".to_string(), "agent.exe".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_substitute() {
+        let vars = variables();
+        assert_eq!(
+            substitute(r"{{CORP_TOOLS_DIR}}\{{ AGENT }}", &vars, true).unwrap(),
+            r"C:\Tools\agent.exe"
+        );
+        assert_eq!(substitute("{{OTHER}} {{", &vars, false).unwrap(), "{{OTHER}} {{");
+        assert!(matches!(
+            substitute("{{OTHER}}", &vars, true),
+            Err(PreprocessError::UnresolvedVariable(name)) if name == "OTHER"
+        ));
+    }
+
+    #[test]
+    fn test_step_substitutes_text_and_attributes() {
+        let step = TemplateSubstitutionStep::new(variables(), true);
+        let xml = r#"<Rule name="{{AGENT}}"><Image condition="is">{{CORP_TOOLS_DIR}}\{{AGENT}}</Image><Empty value="{{AGENT}}"/></Rule>"#;
+        let processed = step.process(xml).unwrap();
+        assert_eq!(
+            processed,
+            r#"<Rule name="agent.exe"><Image condition="is">C:\Tools\agent.exe</Image><Empty value="agent.exe"/></Rule>"#
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_keeps_placeholders() {
+        let xml = r#"<Sysmon schemaversion="4.30">
+            <EventFiltering>
+                <RuleGroup name="test" groupRelation="or">
+                    <ProcessCreate onmatch="include">
+                        <Image condition="is">{{CORP_TOOLS_DIR}}\agent.exe</Image>
+                        <ParentImage condition="is">{{PARENT}}</ParentImage>
+                    </ProcessCreate>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#;
+        let processed = super::super::preprocess_str(xml).unwrap();
+        assert!(processed.contains(r"{{CORP_TOOLS_DIR}}\agent.exe"));
+        assert!(processed.contains("{{PARENT}}"));
+    }
+}
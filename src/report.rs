@@ -0,0 +1,231 @@
+//! MITRE ATT&CK coverage from technique annotations in rule names
+//!
+//! Configs such as sysmon-modular name their rules
+//! `technique_id=T1059,technique_name=Command-Line Interface`. The report
+//! counts the rules annotated with each technique and lists the rest.
+
+use crate::document::{DocumentRule, SysmonDocument};
+use crate::error::ConversionError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "fs")]
+use crate::config::ProcessingOptions;
+
+/// Rules annotated with one technique
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TechniqueCoverage {
+    /// Name from the first annotation that gave one
+    pub technique_name: Option<String>,
+    pub rule_count: usize,
+}
+
+/// A rule whose name, and whose `RuleGroup` name, carry no technique
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnannotatedRule {
+    /// File the rule came from, when the report was built from files
+    pub path: Option<PathBuf>,
+    pub rule_group: Option<String>,
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    pub value: String,
+}
+
+/// Outcome of `attack_coverage`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AttackCoverage {
+    pub total_rules: usize,
+    /// Rule counts keyed by technique ID, such as `T1059`
+    pub techniques: BTreeMap<String, TechniqueCoverage>,
+    pub unannotated: Vec<UnannotatedRule>,
+}
+
+impl AttackCoverage {
+    /// Builds the report for a document already in memory
+    pub fn from_document(doc: &SysmonDocument) -> Self {
+        let mut coverage = Self::default();
+        coverage.add_document(doc, None);
+        coverage
+    }
+
+    /// Serializes the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, ConversionError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn add_document(&mut self, doc: &SysmonDocument, path: Option<&Path>) {
+        for rule in doc.rules() {
+            self.add_rule(rule, path);
+        }
+    }
+
+    fn add_rule(&mut self, rule: DocumentRule, path: Option<&Path>) {
+        self.total_rules += 1;
+
+        // The rule's own name takes precedence over its group's
+        let technique = [&rule.rule_name, &rule.rule_group]
+            .into_iter()
+            .flatten()
+            .find_map(|name| parse_technique(name));
+
+        match technique {
+            Some((id, name)) => {
+                let entry = self.techniques.entry(id).or_default();
+                entry.rule_count += 1;
+                if entry.technique_name.is_none() {
+                    entry.technique_name = name;
+                }
+            }
+            None => self.unannotated.push(UnannotatedRule {
+                path: path.map(Path::to_path_buf),
+                rule_group: rule.rule_group,
+                event_type: rule.event_type,
+                field: rule.field,
+                condition: rule.condition,
+                value: rule.value,
+            }),
+        }
+    }
+}
+
+/// Builds the ATT&CK coverage report for an XML or JSON config, or for every
+/// config in a directory (recursively, walked as with the default
+/// `ProcessingOptions`), as if they were merged
+#[cfg(feature = "fs")]
+pub fn attack_coverage(input: &Path) -> Result<AttackCoverage, ConversionError> {
+    let mut coverage = AttackCoverage::default();
+    if !input.is_dir() {
+        let doc = SysmonDocument::load(input)?;
+        coverage.add_document(&doc, Some(input));
+        return Ok(coverage);
+    }
+
+    std::fs::read_dir(input).map_err(|e| ConversionError::io_error(input, e))?;
+    for entry in crate::walk_files(input, true, &ProcessingOptions::default()) {
+        let path = entry.path();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase());
+        if !matches!(ext.as_deref(), Some("xml") | Some("json")) {
+            continue;
+        }
+
+        let doc = SysmonDocument::load(path)?;
+        coverage.add_document(&doc, Some(path));
+    }
+
+    Ok(coverage)
+}
+
+/// Extracts the `technique_id` and `technique_name` from a rule name made of
+/// comma-separated `key=value` pairs. Commas inside a value are kept.
+fn parse_technique(name: &str) -> Option<(String, Option<String>)> {
+    let mut pairs: Vec<(&str, String)> = Vec::new();
+    for part in name.split(',') {
+        match part.split_once('=') {
+            Some((key, value)) => pairs.push((key.trim(), value.trim().to_string())),
+            None => {
+                if let Some((_, value)) = pairs.last_mut() {
+                    value.push(',');
+                    value.push_str(part);
+                }
+            }
+        }
+    }
+
+    let get = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    Some((get("technique_id")?, get("technique_name")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::FileFormat;
+
+    const CONFIG: &str = r#"<Sysmon schemaversion="4.50">
+        <EventFiltering>
+            <RuleGroup name="processes" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image name="technique_id=T1059,technique_name=Command-Line Interface" condition="image">cmd.exe</Image>
+                    <Image name="technique_id=T1059,technique_name=Command-Line Interface" condition="image">powershell.exe</Image>
+                    <Rule name="technique_id=T1218,technique_name=Signed Binary Proxy Execution" groupRelation="and">
+                        <Image condition="image">rundll32.exe</Image>
+                        <CommandLine condition="contains">javascript:</CommandLine>
+                    </Rule>
+                    <ParentImage condition="image">winword.exe</ParentImage>
+                </ProcessCreate>
+            </RuleGroup>
+            <RuleGroup name="technique_id=T1071,technique_name=Standard Application Layer Protocol" groupRelation="or">
+                <NetworkConnect onmatch="include">
+                    <DestinationPort condition="is">4444</DestinationPort>
+                </NetworkConnect>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_parse_technique() {
+        assert_eq!(
+            parse_technique("technique_id=T1003.001,technique_name=LSASS Memory"),
+            Some(("T1003.001".to_string(), Some("LSASS Memory".to_string())))
+        );
+        assert_eq!(
+            parse_technique("technique_id=T1105,technique_name=Ingress Tool Transfer, Downloads"),
+            Some(("T1105".to_string(), Some("Ingress Tool Transfer, Downloads".to_string())))
+        );
+        assert_eq!(parse_technique("technique_id=T1047"), Some(("T1047".to_string(), None)));
+        assert_eq!(parse_technique("Suspicious parents"), None);
+        assert_eq!(parse_technique("technique_id="), None);
+    }
+
+    #[test]
+    fn test_attack_coverage_counts() {
+        let doc = SysmonDocument::load_str(CONFIG, FileFormat::Xml).unwrap();
+        let coverage = AttackCoverage::from_document(&doc);
+
+        assert_eq!(coverage.total_rules, 6);
+        assert_eq!(coverage.techniques.len(), 3);
+        assert_eq!(coverage.techniques["T1059"].rule_count, 2);
+        assert_eq!(
+            coverage.techniques["T1059"].technique_name.as_deref(),
+            Some("Command-Line Interface")
+        );
+        assert_eq!(coverage.techniques["T1218"].rule_count, 2);
+        assert_eq!(coverage.techniques["T1071"].rule_count, 1);
+
+        assert_eq!(coverage.unannotated.len(), 1);
+        assert_eq!(coverage.unannotated[0].field, "ParentImage");
+        assert_eq!(coverage.unannotated[0].path, None);
+
+        let json: serde_json::Value = serde_json::from_str(&coverage.to_json().unwrap()).unwrap();
+        assert_eq!(json["techniques"]["T1218"]["rule_count"], 2);
+        assert_eq!(json["unannotated"][0]["value"], "winword.exe");
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_attack_coverage_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let xml_path = temp_dir.path().join("a.xml");
+        std::fs::write(&xml_path, CONFIG).unwrap();
+        let doc = SysmonDocument::load(&xml_path).unwrap();
+        doc.save(&temp_dir.path().join("b.json")).unwrap();
+
+        let coverage = attack_coverage(temp_dir.path()).unwrap();
+        assert_eq!(coverage.total_rules, 12);
+        assert_eq!(coverage.techniques["T1059"].rule_count, 4);
+        assert_eq!(coverage.unannotated.len(), 2);
+        assert_eq!(coverage.unannotated[0].path.as_ref(), Some(&xml_path));
+
+        let single = attack_coverage(&xml_path).unwrap();
+        assert_eq!(single.total_rules, 6);
+    }
+}
@@ -0,0 +1,147 @@
+//! Sysmon schema versions and the event types each of them supports
+
+#[cfg(any(test, feature = "validation"))]
+use crate::converter::xml::xml_to_value;
+#[cfg(feature = "validation")]
+use crate::error::ConversionError;
+use crate::model::{EventTypeInfo, SchemaVersion, EVENT_TYPES, SCHEMA_VERSIONS};
+use serde_json::Value;
+
+/// Lists the schema versions accepted by the bundled validator, oldest first
+pub fn supported_versions() -> Vec<SchemaVersion> {
+    SCHEMA_VERSIONS.to_vec()
+}
+
+/// Lists the event types available at a schema version, in event ID order
+pub fn event_types(version: SchemaVersion) -> Vec<EventTypeInfo> {
+    EVENT_TYPES
+        .iter()
+        .filter(|info| info.min_schema <= version)
+        .copied()
+        .collect()
+}
+
+/// Looks up an event type by its element name
+pub fn event_type(name: &str) -> Option<&'static EventTypeInfo> {
+    EVENT_TYPES.iter().find(|info| info.name == name)
+}
+
+/// Explains event filters a configuration cannot use: unknown element names,
+/// and event types newer than the declared `schemaversion`
+#[cfg(any(test, feature = "validation"))]
+pub(crate) fn event_type_hints(xml: &str) -> Vec<String> {
+    let Ok(value) = xml_to_value(xml) else {
+        return Vec::new();
+    };
+    let Some(sysmon) = value.get("Sysmon").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let version = sysmon
+        .get("@schemaversion")
+        .and_then(Value::as_str)
+        .and_then(SchemaVersion::parse);
+    let Some(filtering) = sysmon.get("EventFiltering").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+
+    let mut names = Vec::new();
+    for (key, value) in filtering {
+        if key == "RuleGroup" {
+            let groups = match value {
+                Value::Array(groups) => groups.iter().collect(),
+                other => vec![other],
+            };
+            for group in groups.into_iter().filter_map(Value::as_object) {
+                names.extend(group.keys().filter(|k| !k.starts_with(['@', '$'])));
+            }
+        } else if !key.starts_with(['@', '$']) {
+            names.push(key);
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| match (event_type(name), version) {
+            (None, _) => Some(format!("unknown event type '{}'", name)),
+            (Some(info), Some(version)) if info.min_schema > version => Some(format!(
+                "{} requires schema version {} or later (config declares {})",
+                name, info.min_schema, version
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Appends event type hints to a validation or parse error about `xml`
+#[cfg(feature = "validation")]
+pub(crate) fn annotate_validation_error(error: ConversionError, xml: &str) -> ConversionError {
+    let hints = event_type_hints(xml);
+    if hints.is_empty() {
+        return error;
+    }
+    let hints = hints.join("; ");
+    match error {
+        ConversionError::ValidationError(msg) => {
+            ConversionError::ValidationError(format!("{} ({})", msg, hints))
+        }
+        ConversionError::ParserError(msg) => {
+            ConversionError::ParserError(format!("{} ({})", msg, hints))
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names_at(version: &str) -> Vec<&'static str> {
+        event_types(SchemaVersion::parse(version).unwrap())
+            .into_iter()
+            .map(|info| info.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_version_parsing() {
+        assert_eq!(SchemaVersion::parse("4.3"), Some(SchemaVersion::new(4, 30)));
+        assert_eq!(SchemaVersion::parse("4.22"), Some(SchemaVersion::new(4, 22)));
+        assert_eq!(SchemaVersion::parse("4"), None);
+        assert_eq!(SchemaVersion::new(4, 0).to_string(), "4.00");
+        assert!(supported_versions().windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_event_types_by_version() {
+        assert!(!names_at("4.20").contains(&"DnsQuery"));
+        assert!(names_at("4.21").contains(&"DnsQuery"));
+        assert!(!names_at("4.50").contains(&"FileDeleteDetected"));
+        assert!(names_at("4.60").contains(&"FileDeleteDetected"));
+        assert!(!names_at("4.30").contains(&"ClipboardChange"));
+        assert!(names_at("3.10").contains(&"ProcessCreate"));
+        assert_eq!(names_at("4.90").len(), EVENT_TYPES.len());
+
+        let dns = event_type("DnsQuery").unwrap();
+        assert_eq!(dns.event_id, 22);
+        assert!(dns.fields.contains(&"QueryName"));
+    }
+
+    #[test]
+    fn test_event_type_hints() {
+        let hints = event_type_hints(r#"<Sysmon schemaversion="4.30">
+            <EventFiltering>
+                <RuleGroup name="test" groupRelation="or">
+                    <ProcessCreate onmatch="include"/>
+                    <ClipboardChange onmatch="include"/>
+                    <ProcesCreate onmatch="include"/>
+                </RuleGroup>
+            </EventFiltering>
+        </Sysmon>"#);
+
+        assert_eq!(hints.len(), 2, "{:?}", hints);
+        assert!(hints[0].contains("ClipboardChange requires schema version 4.40"));
+        assert_eq!(hints[1], "unknown event type 'ProcesCreate'");
+    }
+}
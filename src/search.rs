@@ -0,0 +1,223 @@
+//! Finding rules across a directory of XML and JSON configs
+
+use crate::config::ProcessingOptions;
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use crate::preprocessor::normalize_path;
+use log::warn;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// How a rule value is matched
+#[derive(Debug, Clone)]
+enum ValueMatch {
+    /// Case-insensitive substring, compared after path normalization
+    Contains(String),
+    /// Regular expression, applied to the normalized value
+    Regex(Regex),
+}
+
+/// Criteria for `find_rules`. Every criterion that is set must match; names
+/// and conditions are compared case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct RuleQuery {
+    event_type: Option<String>,
+    field: Option<String>,
+    condition: Option<String>,
+    value: Option<ValueMatch>,
+}
+
+impl RuleQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches the event filter element, such as `ProcessCreate`
+    pub fn event_type(mut self, event_type: &str) -> Self {
+        self.event_type = Some(event_type.to_string());
+        self
+    }
+
+    /// Matches the field, such as `Image`
+    pub fn field(mut self, field: &str) -> Self {
+        self.field = Some(field.to_string());
+        self
+    }
+
+    /// Matches the condition, such as `contains`
+    pub fn condition(mut self, condition: &str) -> Self {
+        self.condition = Some(condition.to_string());
+        self
+    }
+
+    /// Matches values containing `text`. Windows paths are normalized on
+    /// both sides, so `c:/windows` finds `C:\Windows`.
+    pub fn value_contains(mut self, text: &str) -> Self {
+        self.value = Some(ValueMatch::Contains(normalize(text).to_lowercase()));
+        self
+    }
+
+    /// Matches values (after path normalization) against a regular expression
+    pub fn value_regex(mut self, pattern: &str) -> Result<Self, ConversionError> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            ConversionError::InvalidFile(format!("Invalid value pattern {:?}: {}", pattern, e))
+        })?;
+        self.value = Some(ValueMatch::Regex(regex));
+        Ok(self)
+    }
+
+    fn matches(&self, event_type: &str, field: &str, condition: &str, value: &str) -> bool {
+        let same = |expected: &Option<String>, actual: &str| match expected {
+            Some(expected) => expected.eq_ignore_ascii_case(actual),
+            None => true,
+        };
+        if !same(&self.event_type, event_type)
+            || !same(&self.field, field)
+            || !same(&self.condition, condition)
+        {
+            return false;
+        }
+
+        match &self.value {
+            None => true,
+            Some(ValueMatch::Contains(text)) => normalize(value).to_lowercase().contains(text),
+            Some(ValueMatch::Regex(regex)) => regex.is_match(&normalize(value)),
+        }
+    }
+}
+
+/// A rule found by `find_rules`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    pub path: PathBuf,
+    pub rule_group: Option<String>,
+    pub event_type: String,
+    pub field: String,
+    pub condition: String,
+    /// Value as written in the file
+    pub value: String,
+}
+
+/// Finds the rules matching `query` in the XML and JSON configs of a
+/// directory, walked as with the default `ProcessingOptions`. Files that
+/// cannot be loaded are skipped with a warning.
+pub fn find_rules(dir: &Path, recursive: bool, query: &RuleQuery) -> Vec<RuleMatch> {
+    let mut matches = Vec::new();
+    for entry in crate::walk_files(dir, recursive, &ProcessingOptions::default()) {
+        let path = entry.path();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase());
+        if !matches!(ext.as_deref(), Some("xml") | Some("json")) {
+            continue;
+        }
+
+        let doc = match SysmonDocument::load(path) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        matches.extend(
+            doc.rules()
+                .into_iter()
+                .filter(|r| query.matches(&r.event_type, &r.field, &r.condition, &r.value))
+                .map(|r| RuleMatch {
+                    path: path.to_path_buf(),
+                    rule_group: r.rule_group,
+                    event_type: r.event_type,
+                    field: r.field,
+                    condition: r.condition,
+                    value: r.value,
+                }),
+        );
+    }
+
+    matches
+}
+
+fn normalize(value: &str) -> String {
+    normalize_path(value).unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn setup() -> tempfile::TempDir {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("a.xml"),
+            r#"<Sysmon schemaversion="4.30">
+                <EventFiltering>
+                    <RuleGroup name="shells" groupRelation="or">
+                        <ProcessCreate onmatch="include">
+                            <Image condition="is">C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe</Image>
+                            <Image condition="is">C:\Windows\System32\cmd.exe</Image>
+                        </ProcessCreate>
+                    </RuleGroup>
+                </EventFiltering>
+            </Sysmon>"#,
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("b.json"),
+            r#"{
+                "@schemaversion": "4.30",
+                "EventFiltering": {
+                    "RuleGroup": {
+                        "@name": "net",
+                        "@groupRelation": "or",
+                        "NetworkConnect": {
+                            "@onmatch": "include",
+                            "Image": {
+                                "@condition": "end with",
+                                "$text": "c:/windows/system32/windowspowershell/v1.0/powershell.exe"
+                            }
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_find_by_value_across_formats() {
+        let temp_dir = setup();
+        let query = RuleQuery::new().value_contains("powershell.exe");
+        let matches = find_rules(temp_dir.path(), false, &query);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].path, temp_dir.path().join("a.xml"));
+        assert_eq!(matches[0].rule_group.as_deref(), Some("shells"));
+        assert_eq!(matches[1].path, temp_dir.path().join("b.json"));
+        assert_eq!(matches[1].event_type, "NetworkConnect");
+        assert_eq!(matches[1].condition, "end with");
+    }
+
+    #[test]
+    fn test_path_normalized_matching() {
+        let temp_dir = setup();
+        let query = RuleQuery::new()
+            .field("image")
+            .value_contains(r"C:\Windows\System32\WindowsPowerShell");
+        assert_eq!(find_rules(temp_dir.path(), false, &query).len(), 2);
+
+        let query = RuleQuery::new()
+            .event_type("ProcessCreate")
+            .value_regex(r"(?i)^C:\\Windows\\System32\\cmd\.exe$")
+            .unwrap();
+        let matches = find_rules(temp_dir.path(), false, &query);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].value.ends_with("cmd.exe"));
+
+        assert!(RuleQuery::new().value_regex("(").is_err());
+    }
+}
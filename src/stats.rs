@@ -0,0 +1,201 @@
+//! Rule counts for a config, by event type, condition, `onmatch`, and
+//! `groupRelation`
+//!
+//! The counts cover the same filters as `SysmonDocument::rules`, so a config
+//! and its conversion give the same numbers.
+
+use crate::document::{collect_group_rules, collect_rules, DocumentRule};
+#[cfg(feature = "fs")]
+use crate::document::SysmonDocument;
+#[cfg(feature = "fs")]
+use crate::error::ConversionError;
+use crate::model::sysmon::Condition;
+use crate::model::Value;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Outcome of `compute_rule_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RuleStats {
+    pub total: usize,
+    /// Rule counts keyed by event type, such as `ProcessCreate`
+    pub by_event_type: HashMap<String, usize>,
+    /// Rule counts keyed by condition, in the Sysmon spelling (`begin with`)
+    pub by_condition: HashMap<String, usize>,
+    /// Rule counts for `include` and `exclude`; rules whose event sets no
+    /// `onmatch` are left out
+    pub by_onmatch: HashMap<String, usize>,
+    /// Rule counts keyed by the `groupRelation` of the enclosing `Rule` or
+    /// `RuleGroup`; rules outside both are left out
+    pub by_group_relation: HashMap<String, usize>,
+}
+
+impl RuleStats {
+    /// Adds the counts of `other`, for totals across several configs
+    pub fn add(&mut self, other: &RuleStats) {
+        self.total += other.total;
+        let maps = [
+            (&mut self.by_event_type, &other.by_event_type),
+            (&mut self.by_condition, &other.by_condition),
+            (&mut self.by_onmatch, &other.by_onmatch),
+            (&mut self.by_group_relation, &other.by_group_relation),
+        ];
+        for (counts, other) in maps {
+            for (key, count) in other {
+                *counts.entry(key.clone()).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Renders the counts as aligned text tables, largest count first
+    pub fn display_table(&self) -> String {
+        let sections = [
+            ("Event type", &self.by_event_type),
+            ("Condition", &self.by_condition),
+            ("Onmatch", &self.by_onmatch),
+            ("Group relation", &self.by_group_relation),
+        ];
+
+        let mut table = format!("Total rules: {}\n", self.total);
+        for (heading, counts) in sections {
+            if counts.is_empty() {
+                continue;
+            }
+            let mut rows: Vec<(&String, &usize)> = counts.iter().collect();
+            rows.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            let width = rows
+                .iter()
+                .map(|(key, _)| key.len())
+                .chain([heading.len()])
+                .max()
+                .unwrap_or_default();
+
+            let _ = writeln!(table, "\n{:<width$}  {:>6}", heading, "Rules");
+            for (key, count) in rows {
+                let _ = writeln!(table, "{:<width$}  {:>6}", key, count);
+            }
+        }
+        table
+    }
+
+    fn count(&mut self, rule: &DocumentRule) {
+        self.total += 1;
+        *self.by_event_type.entry(rule.event_type.clone()).or_insert(0) += 1;
+
+        let condition = Condition::try_from(rule.condition.as_str())
+            .map(String::from)
+            .unwrap_or_else(|_| rule.condition.to_lowercase());
+        *self.by_condition.entry(condition).or_insert(0) += 1;
+
+        if let Some(onmatch) = &rule.onmatch {
+            *self.by_onmatch.entry(onmatch.to_lowercase()).or_insert(0) += 1;
+        }
+        if let Some(relation) = &rule.group_relation {
+            *self.by_group_relation.entry(relation.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Counts the filter rules of a config, given as the content of its
+/// `Sysmon` root element (as produced by the converters and `ConfigMerger`)
+pub fn compute_rule_stats(config: &Value) -> RuleStats {
+    let mut stats = RuleStats::default();
+    for rule in collect_rules(config) {
+        stats.count(&rule);
+    }
+    stats
+}
+
+/// Counts the filter rules of the given `RuleGroup`s
+pub(crate) fn compute_group_stats<'a>(groups: impl IntoIterator<Item = &'a Value>) -> RuleStats {
+    let mut rules = Vec::new();
+    for group in groups {
+        collect_group_rules(group, &mut rules);
+    }
+    let mut stats = RuleStats::default();
+    for rule in rules {
+        stats.count(&rule);
+    }
+    stats
+}
+
+/// Counts the filter rules of a config file, using the extension to pick
+/// XML or JSON
+#[cfg(feature = "fs")]
+pub fn compute_rule_stats_from_file(path: &Path) -> Result<RuleStats, ConversionError> {
+    let doc = SysmonDocument::load(path)?;
+    Ok(compute_rule_stats(doc.value()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::FileFormat;
+    use crate::document::SysmonDocument;
+
+    const CONFIG_XML: &str = r#"<Sysmon schemaversion="4.50">
+        <EventFiltering>
+            <RuleGroup name="processes" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="end with">\cmd.exe</Image>
+                    <Image condition="image">powershell.exe</Image>
+                    <Rule name="encoded" groupRelation="and">
+                        <Image condition="image">powershell.exe</Image>
+                        <CommandLine condition="contains">-enc</CommandLine>
+                    </Rule>
+                </ProcessCreate>
+            </RuleGroup>
+            <RuleGroup name="network" groupRelation="or">
+                <NetworkConnect onmatch="exclude">
+                    <DestinationPort condition="is">443</DestinationPort>
+                </NetworkConnect>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    fn config_stats() -> RuleStats {
+        let doc = SysmonDocument::load_str(CONFIG_XML, FileFormat::Xml).unwrap();
+        compute_rule_stats(doc.value())
+    }
+
+    #[test]
+    fn test_compute_rule_stats() {
+        let stats = config_stats();
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.by_event_type["ProcessCreate"], 4);
+        assert_eq!(stats.by_event_type["NetworkConnect"], 1);
+        assert_eq!(stats.by_condition["image"], 2);
+        assert_eq!(stats.by_condition["end with"], 1);
+        assert_eq!(stats.by_onmatch["include"], 4);
+        assert_eq!(stats.by_onmatch["exclude"], 1);
+        assert_eq!(stats.by_group_relation["or"], 3);
+        assert_eq!(stats.by_group_relation["and"], 2);
+    }
+
+    #[test]
+    fn test_add_and_display_table() {
+        let mut stats = config_stats();
+        stats.add(&config_stats());
+        assert_eq!(stats.total, 10);
+        assert_eq!(stats.by_event_type["ProcessCreate"], 8);
+
+        let table = stats.display_table();
+        assert!(table.starts_with("Total rules: 10\n"), "{}", table);
+        let process = table.find("ProcessCreate        8").unwrap();
+        let network = table.find("NetworkConnect       2").unwrap();
+        assert!(process < network, "{}", table);
+        assert!(table.contains("Group relation"), "{}", table);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_compute_rule_stats_from_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.xml");
+        std::fs::write(&path, CONFIG_XML).unwrap();
+        assert_eq!(compute_rule_stats_from_file(&path).unwrap(), config_stats());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::converter::warnings::{collect_warnings, WarningSeverity};
+use crate::converter::xml::xml_to_value;
+use crate::converter::FileFormat;
+use crate::document::SysmonDocument;
+use crate::error::ConversionError;
+use std::borrow::Cow;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
+use sysmon_validator::{parse_sysmon_config_from_str, validate_sysmon_config};
+
+/// A single problem found while validating a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// File the issue was found in, for `validate_file`
+    pub file: Option<PathBuf>,
+    /// Element the issue refers to, as a `/`-separated path from the root,
+    /// when it is known
+    pub element: Option<String>,
+    /// Description of the issue
+    pub message: String,
+    /// Severity, for warnings
+    pub severity: Option<WarningSeverity>,
+}
+
+/// Outcome of validating a configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// File that was validated, for `validate_file`
+    pub file: Option<PathBuf>,
+    /// Problems that make the configuration unusable by Sysmon
+    pub errors: Vec<ValidationIssue>,
+    /// Non-fatal issues (see `converter::warnings::collect_warnings`)
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true when no errors were found; warnings are allowed
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, error: ConversionError) {
+        self.errors.push(ValidationIssue {
+            file: None,
+            element: None,
+            message: error.to_string(),
+            severity: None,
+        });
+    }
+}
+
+/// Validates a configuration file without writing any output.
+///
+/// The format is picked from the extension. Problems with the configuration
+/// are listed in the report; `Err` is only returned when the file cannot be
+/// read or its format is not supported.
+#[cfg(feature = "fs")]
+pub fn validate_file(path: &Path) -> Result<ValidationReport, ConversionError> {
+    let format = FileFormat::from_path(path).ok_or_else(|| {
+        ConversionError::InvalidFile(format!("Unknown format: {}", path.display()))
+    })?;
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConversionError::io_error(path, e))?;
+
+    let mut report = validate_str(&content, format)?;
+    report.file = Some(path.to_path_buf());
+    for issue in report.errors.iter_mut().chain(report.warnings.iter_mut()) {
+        issue.file = Some(path.to_path_buf());
+    }
+    Ok(report)
+}
+
+/// Validates configuration content held in memory.
+///
+/// JSON content is converted to XML in memory before it is handed to the
+/// Sysmon schema validator.
+pub fn validate_str(content: &str, format: FileFormat) -> Result<ValidationReport, ConversionError> {
+    let mut report = ValidationReport::default();
+
+    let xml = match format {
+        FileFormat::Xml => Cow::Borrowed(content),
+        FileFormat::Json => match SysmonDocument::load_str(content, FileFormat::Json) {
+            Ok(doc) => Cow::Owned(doc.to_xml_string()?),
+            Err(e) => {
+                report.error(e);
+                return Ok(report);
+            }
+        },
+        other => {
+            return Err(ConversionError::InvalidFile(format!(
+                "Unsupported format for validation: {}",
+                other.extension()
+            )))
+        }
+    };
+
+    match parse_sysmon_config_from_str(&xml) {
+        Ok(config) => {
+            if let Err(e) = validate_sysmon_config(&config) {
+                report.error(e.into());
+            }
+        }
+        Err(e) => report.error(e.into()),
+    }
+
+    if report.is_valid() {
+        let value = xml_to_value(&xml)?;
+        report.warnings = collect_warnings(&value)
+            .into_iter()
+            .map(|warning| ValidationIssue {
+                file: None,
+                element: warning.path,
+                message: warning.message,
+                severity: Some(warning.severity),
+            })
+            .collect();
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_valid_config() {
+        let report = validate_str(VALID_XML, FileFormat::Xml).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let report = validate_str("<Sysmon><Broken></Sysmon>", FileFormat::Xml).unwrap();
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_json_config() {
+        let json = crate::convert_str(VALID_XML, FileFormat::Xml, FileFormat::Json).unwrap();
+        let report = validate_str(&json, FileFormat::Json).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        let report = validate_str("{ not json", FileFormat::Json).unwrap();
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_warnings_carry_element() {
+        let xml = VALID_XML.replace(
+            "</ProcessCreate>",
+            r#"<CommandLine condition="contains">-e</CommandLine></ProcessCreate>"#,
+        );
+        let report = validate_str(&xml, FileFormat::Xml).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(
+            report.warnings[0].element.as_deref(),
+            Some("Sysmon/EventFiltering/RuleGroup/ProcessCreate/CommandLine")
+        );
+        assert_eq!(report.warnings[0].severity, Some(WarningSeverity::High));
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_validate_file_sets_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("bad.xml");
+        std::fs::write(&path, "<Sysmon><Broken></Sysmon>").unwrap();
+
+        let report = validate_file(&path).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.file.as_deref(), Some(path.as_path()));
+        assert_eq!(report.errors[0].file.as_deref(), Some(path.as_path()));
+    }
+}
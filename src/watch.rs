@@ -0,0 +1,353 @@
+use crate::config::ProcessingOptions;
+use crate::converter::FileFormat;
+use crate::error::ConversionError;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Callback receiving per-file errors while watching
+pub type WatchErrorCallback = Arc<dyn Fn(&Path, &ConversionError) + Send + Sync>;
+
+/// How often the watcher checks the cancel flag and pending changes
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options for `watch_directory`
+#[derive(Clone)]
+pub struct WatchOptions {
+    /// Options applied to each conversion
+    pub processing: ProcessingOptions,
+    /// Watch subdirectories as well
+    pub recursive: bool,
+    /// How long a file must be quiet before it is converted
+    pub debounce: Duration,
+    /// Delete the converted output when its input is removed
+    pub remove_deleted_outputs: bool,
+    on_error: Option<WatchErrorCallback>,
+}
+
+impl WatchOptions {
+    /// Creates options with a 250ms debounce, watching recursively
+    pub fn new(processing: ProcessingOptions) -> Self {
+        Self {
+            processing,
+            recursive: true,
+            debounce: Duration::from_millis(250),
+            remove_deleted_outputs: false,
+            on_error: None,
+        }
+    }
+
+    /// Sets the callback receiving per-file errors. Without one, errors are
+    /// logged and the watcher carries on.
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path, &ConversionError) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self::new(ProcessingOptions::default())
+    }
+}
+
+impl fmt::Debug for WatchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchOptions")
+            .field("processing", &self.processing)
+            .field("recursive", &self.recursive)
+            .field("debounce", &self.debounce)
+            .field("remove_deleted_outputs", &self.remove_deleted_outputs)
+            .field("on_error", &self.on_error.is_some())
+            .finish()
+    }
+}
+
+/// A filesystem change relevant to the watcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WatchEvent {
+    Changed(PathBuf),
+    Removed(PathBuf),
+}
+
+impl WatchEvent {
+    fn path(&self) -> &Path {
+        match self {
+            WatchEvent::Changed(path) | WatchEvent::Removed(path) => path,
+        }
+    }
+
+    /// Translates a notify event into watcher events
+    fn from_notify(event: Event) -> Vec<WatchEvent> {
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Create(_) => paths.map(WatchEvent::Changed).collect(),
+            EventKind::Remove(_) => paths.map(WatchEvent::Removed).collect(),
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                paths.map(WatchEvent::Removed).collect()
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                let mut events = Vec::new();
+                events.extend(paths.next().map(WatchEvent::Removed));
+                events.extend(paths.map(WatchEvent::Changed));
+                events
+            }
+            EventKind::Modify(_) => paths.map(WatchEvent::Changed).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Applies watcher events to the output directory
+struct WatchHandler<'a> {
+    input_dir: &'a Path,
+    output_dir: &'a Path,
+    options: &'a WatchOptions,
+}
+
+impl WatchHandler<'_> {
+    /// Gets the output path mirroring `input`, or `None` for files the
+    /// watcher does not convert
+    fn output_path(&self, input: &Path) -> Option<PathBuf> {
+        let target = match FileFormat::from_path(input)? {
+            FileFormat::Xml => FileFormat::Json,
+            FileFormat::Json => FileFormat::Xml,
+            _ => return None,
+        };
+        let relative = input.strip_prefix(self.input_dir).ok()?;
+        Some(self.output_dir.join(relative).with_extension(target.extension()))
+    }
+
+    fn handle(&self, event: &WatchEvent) {
+        let input = event.path();
+        let Some(output) = self.output_path(input) else {
+            return;
+        };
+
+        let result = match event {
+            WatchEvent::Changed(_) if input.is_file() => self.convert(input, &output),
+            // A change to a path that no longer exists is a removal
+            _ => self.remove(&output),
+        };
+
+        if let Err(e) = result {
+            self.report(input, &e);
+        }
+    }
+
+    fn convert(&self, input: &Path, output: &Path) -> Result<(), ConversionError> {
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| ConversionError::io_error(parent, e))?;
+        }
+        crate::convert_file_with_options(input, output, &self.options.processing)?;
+        log::info!("Converted {} -> {}", input.display(), output.display());
+        Ok(())
+    }
+
+    fn remove(&self, output: &Path) -> Result<(), ConversionError> {
+        if !self.options.remove_deleted_outputs || !output.exists() {
+            return Ok(());
+        }
+        std::fs::remove_file(output).map_err(|e| ConversionError::io_error(output, e))?;
+        log::info!("Removed {}", output.display());
+        Ok(())
+    }
+
+    fn report(&self, input: &Path, error: &ConversionError) {
+        match &self.options.on_error {
+            Some(callback) => callback(input, error),
+            None => log::error!("Error processing {}: {}", input.display(), error),
+        }
+    }
+
+    /// Converts every file currently in the input directory
+    fn convert_all(&self) {
+        for entry in crate::walk_files(self.input_dir, self.options.recursive, &self.options.processing) {
+            self.handle(&WatchEvent::Changed(entry.into_path()));
+        }
+    }
+}
+
+/// Watches `input_dir` and mirrors converted files into `output_dir`.
+///
+/// Every file is converted once up front; afterwards added or changed files
+/// are re-converted once they have been quiet for `options.debounce`. Errors
+/// on individual files are passed to the `on_error` callback and do not stop
+/// the watcher. Returns when `cancel` is set.
+pub fn watch_directory(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &WatchOptions,
+    cancel: &AtomicBool,
+) -> Result<(), ConversionError> {
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| ConversionError::io_error(output_dir, e))?;
+
+    // notify reports absolute paths, so the prefix must match them
+    let input_dir = input_dir
+        .canonicalize()
+        .map_err(|e| ConversionError::io_error(input_dir, e))?;
+    let handler = WatchHandler {
+        input_dir: &input_dir,
+        output_dir,
+        options,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| ConversionError::BatchError(format!("Failed to start watcher: {}", e)))?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(&input_dir, mode)
+        .map_err(|e| ConversionError::BatchError(format!("Failed to watch {}: {}", input_dir.display(), e)))?;
+
+    handler.convert_all();
+
+    let mut pending: HashMap<PathBuf, (WatchEvent, Instant)> = HashMap::new();
+    while !cancel.load(Ordering::SeqCst) {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                for event in WatchEvent::from_notify(event) {
+                    pending.insert(event.path().to_path_buf(), (event, Instant::now()));
+                }
+            }
+            Ok(Err(e)) => log::warn!("Watch error: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, (_, seen))| seen.elapsed() >= options.debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in ready {
+            if let Some((event, _)) = pending.remove(&path) {
+                handler.handle(&event);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+    use std::fs;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+        <EventFiltering>
+            <RuleGroup name="test" groupRelation="or">
+                <ProcessCreate onmatch="include">
+                    <Image condition="is">C:\Windows\System32\test.exe</Image>
+                </ProcessCreate>
+            </RuleGroup>
+        </EventFiltering>
+    </Sysmon>"#;
+
+    #[test]
+    fn test_handler_applies_event_sequence() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(input_dir.join("nested")).unwrap();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&errors);
+        let mut options = WatchOptions::default().on_error(move |path, _| {
+            recorded.lock().unwrap().push(path.to_path_buf());
+        });
+        options.remove_deleted_outputs = true;
+
+        let handler = WatchHandler {
+            input_dir: &input_dir,
+            output_dir: &output_dir,
+            options: &options,
+        };
+        let input = input_dir.join("nested").join("config.xml");
+        let output = output_dir.join("nested").join("config.json");
+
+        // Create
+        fs::write(&input, VALID_XML).unwrap();
+        handler.handle(&WatchEvent::Changed(input.clone()));
+        assert!(fs::read_to_string(&output).unwrap().contains("test.exe"));
+
+        // Modify
+        fs::write(&input, VALID_XML.replace("test.exe", "other.exe")).unwrap();
+        handler.handle(&WatchEvent::Changed(input.clone()));
+        assert!(fs::read_to_string(&output).unwrap().contains("other.exe"));
+
+        // An invalid edit is reported and does not touch the output
+        fs::write(&input, "<Sysmon><Broken></Sysmon>").unwrap();
+        handler.handle(&WatchEvent::Changed(input.clone()));
+        assert_eq!(*errors.lock().unwrap(), vec![input.clone()]);
+        assert!(fs::read_to_string(&output).unwrap().contains("other.exe"));
+
+        // Delete
+        fs::remove_file(&input).unwrap();
+        handler.handle(&WatchEvent::Removed(input.clone()));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_removed_outputs_kept_by_default() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::create_dir_all(&output_dir).unwrap();
+        fs::write(output_dir.join("config.json"), "{}").unwrap();
+
+        let options = WatchOptions::default();
+        let handler = WatchHandler {
+            input_dir: &input_dir,
+            output_dir: &output_dir,
+            options: &options,
+        };
+        handler.handle(&WatchEvent::Removed(input_dir.join("config.xml")));
+
+        assert!(output_dir.join("config.json").exists());
+    }
+
+    #[test]
+    fn test_events_from_notify() {
+        let path = PathBuf::from("/configs/a.xml");
+        let renamed = PathBuf::from("/configs/b.xml");
+
+        let create = Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone());
+        assert_eq!(WatchEvent::from_notify(create), vec![WatchEvent::Changed(path.clone())]);
+
+        let modify = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(path.clone());
+        assert_eq!(WatchEvent::from_notify(modify), vec![WatchEvent::Changed(path.clone())]);
+
+        let remove = Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.clone());
+        assert_eq!(WatchEvent::from_notify(remove), vec![WatchEvent::Removed(path.clone())]);
+
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(path.clone())
+            .add_path(renamed.clone());
+        assert_eq!(
+            WatchEvent::from_notify(rename),
+            vec![WatchEvent::Removed(path), WatchEvent::Changed(renamed)]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "cli")]
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+const VALID_XML: &str = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="test" groupRelation="or">
+            <ProcessCreate onmatch="include">
+                <Image condition="is">C:\Windows\System32\test.exe</Image>
+            </ProcessCreate>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+
+fn sysmon_json() -> Command {
+    Command::cargo_bin("sysmon-json").unwrap()
+}
+
+#[test]
+fn test_convert() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("config.xml");
+    let output = temp_dir.path().join("config.json");
+    fs::write(&input, VALID_XML).unwrap();
+
+    sysmon_json()
+        .arg("convert")
+        .arg(&input)
+        .arg(&output)
+        .assert()
+        .success();
+
+    assert!(fs::read_to_string(&output).unwrap().contains("ProcessCreate"));
+}
+
+#[test]
+fn test_stdio() {
+    sysmon_json()
+        .args(["stdio", "xml", "json"])
+        .write_stdin(format!("\u{feff}{}", VALID_XML))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ProcessCreate"));
+
+    sysmon_json()
+        .args(["stdio", "xml", "json"])
+        .write_stdin("<Sysmon><Broken></Sysmon>")
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_convert_invalid_input_exit_code() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("config.xml");
+    fs::write(&input, "<Sysmon><Broken></Sysmon>").unwrap();
+
+    sysmon_json()
+        .arg("convert")
+        .arg(&input)
+        .arg(temp_dir.path().join("config.json"))
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Error:"));
+}
+
+#[test]
+fn test_convert_missing_input_exit_code() {
+    let temp_dir = tempdir().unwrap();
+
+    sysmon_json()
+        .arg("convert")
+        .arg(temp_dir.path().join("missing.json"))
+        .arg(temp_dir.path().join("missing.xml"))
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn test_merge() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("configs");
+    let nested = input_dir.join("nested");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(input_dir.join("a.xml"), VALID_XML).unwrap();
+    fs::write(nested.join("b.xml"), VALID_XML).unwrap();
+    let output = temp_dir.path().join("merged.xml");
+
+    sysmon_json()
+        .args(["merge", "--recursive"])
+        .arg(&input_dir)
+        .arg(&output)
+        .assert()
+        .success();
+
+    assert!(fs::read_to_string(&output).unwrap().contains("<Sysmon"));
+}
+
+#[test]
+fn test_batch() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("keep.xml"), VALID_XML).unwrap();
+    fs::write(input_dir.join("temp_skip.xml"), VALID_XML).unwrap();
+
+    sysmon_json()
+        .args(["batch", "--workers", "2", "--ignore", "temp"])
+        .arg(&input_dir)
+        .arg(&output_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Processed: 1, Skipped: 1, Errors: 0"));
+
+    assert!(output_dir.join("keep.json").exists());
+    assert!(!output_dir.join("temp_skip.json").exists());
+}
+
+#[test]
+fn test_batch_quiet_reports_failures_by_exit_code() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("broken.xml"), "<Sysmon><Broken></Sysmon>").unwrap();
+
+    sysmon_json()
+        .args(["--quiet", "batch"])
+        .arg(&input_dir)
+        .arg(&output_dir)
+        .assert()
+        .code(3)
+        .stdout(predicate::str::is_empty());
+}
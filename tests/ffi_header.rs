@@ -0,0 +1,74 @@
+//! Checks that the committed C header matches what cbindgen generates.
+//!
+//! Regenerate with `cbindgen --config cbindgen.toml --output include/sysmon_json.h`.
+#![cfg(feature = "ffi")]
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Strips `/* */` and `//` comments
+fn strip_comments(source: &str) -> String {
+    let mut out = String::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+        } else if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else {
+            let mut chars = rest.chars();
+            out.push(chars.next().unwrap());
+            rest = chars.as_str();
+        }
+    }
+    out
+}
+
+/// Collects the exported constants and prototypes with whitespace removed
+fn declarations(header: &str) -> BTreeSet<String> {
+    let source = strip_comments(header);
+    let mut decls = BTreeSet::new();
+    let mut code = String::new();
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.starts_with("#define SYSMON_JSON_") && line != "#define SYSMON_JSON_H" {
+            decls.insert(line.split_whitespace().collect::<String>());
+        } else if !line.starts_with('#') {
+            code.push_str(line);
+            code.push(' ');
+        }
+    }
+
+    for statement in code.split(';') {
+        let statement = statement.rsplit(['{', '}']).next().unwrap_or_default();
+        if statement.contains("sysmon_json_") {
+            decls.insert(statement.split_whitespace().collect::<String>());
+        }
+    }
+
+    decls
+}
+
+#[test]
+fn test_header_matches_cbindgen_output() {
+    let crate_dir = env!("CARGO_MANIFEST_DIR");
+    let config = cbindgen::Config::from_file(Path::new(crate_dir).join("cbindgen.toml"))
+        .expect("cbindgen.toml should parse");
+    let bindings = cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("cbindgen should generate bindings");
+
+    let mut generated = Vec::new();
+    bindings.write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let committed = std::fs::read_to_string(Path::new(crate_dir).join("include/sysmon_json.h"))
+        .expect("include/sysmon_json.h should exist");
+
+    let expected = declarations(&generated);
+    assert!(expected.iter().any(|d| d.contains("sysmon_json_convert_str")));
+    assert_eq!(declarations(&committed), expected, "include/sysmon_json.h is out of date");
+}
@@ -0,0 +1,71 @@
+//! The string pipeline without the `fs` feature, as built for
+//! `wasm32-unknown-unknown` and embedded targets.
+//!
+//! Run with `cargo test --no-default-features --test no_fs`, adding
+//! `--features validation` to cover validation as well.
+#![cfg(not(feature = "fs"))]
+
+use sysmon_json::converter::json::value_to_xml;
+use sysmon_json::converter::xml::xml_to_value;
+use sysmon_json::merger::{merged_config_to_xml, ConfigMerger};
+use sysmon_json::{convert_str, generate_skeleton, preprocess_str, FileFormat, SysmonDocument};
+
+const CONFIG_XML: &str = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="test" groupRelation="or">
+            <ProcessCreate onmatch="include">
+                <Image condition="is">c:/windows/system32/test.exe</Image>
+            </ProcessCreate>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+
+const OTHER_XML: &str = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="network" groupRelation="or">
+            <NetworkConnect onmatch="include">
+                <DestinationPort condition="is">4444</DestinationPort>
+            </NetworkConnect>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+
+#[test]
+fn test_convert_str_round_trip() {
+    let json = convert_str(CONFIG_XML, FileFormat::Xml, FileFormat::Json).unwrap();
+    assert!(json.contains(r#"C:\\windows\\system32\\test.exe"#), "{}", json);
+
+    let xml = convert_str(&json, FileFormat::Json, FileFormat::Xml).unwrap();
+    assert!(xml.contains(r"C:\windows\system32\test.exe"), "{}", xml);
+}
+
+#[test]
+fn test_values_round_trip() {
+    let preprocessed = preprocess_str(CONFIG_XML).unwrap();
+    let value = xml_to_value(&preprocessed).unwrap();
+    assert_eq!(value["Sysmon"]["@schemaversion"], "4.30");
+
+    let xml = value_to_xml(&value).unwrap();
+    assert!(xml.contains(r#"<Image condition="is">"#), "{}", xml);
+}
+
+#[test]
+fn test_merge_strings() {
+    let mut merger = ConfigMerger::new();
+    merger.add_xml_str(CONFIG_XML, "processes.xml").unwrap();
+    merger.add_xml_str(OTHER_XML, "network.xml").unwrap();
+    assert_eq!(merger.stats().files, 2);
+
+    let xml = merged_config_to_xml(&merger.finish().unwrap()).unwrap();
+    assert!(xml.contains("test.exe") && xml.contains("4444"), "{}", xml);
+}
+
+#[test]
+fn test_document_and_skeleton() {
+    let doc = SysmonDocument::load_str(CONFIG_XML, FileFormat::Xml).unwrap();
+    assert_eq!(doc.rules().len(), 1);
+    assert!(doc.to_json_string().unwrap().contains("ProcessCreate"));
+
+    let skeleton = generate_skeleton("4.50", FileFormat::Xml).unwrap();
+    assert!(skeleton.contains(r#"schemaversion="4.50""#), "{}", skeleton);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "fs")]
+
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use sysmon_json::batch::{BatchProcessor, FileStatus, RetryPolicy};
+use sysmon_json::converter::{Converter, ConverterRegistry};
+use sysmon_json::error::ConversionError;
+use sysmon_json::ProcessingOptions;
+use tempfile::tempdir;
+
+static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+/// Fails with a transient IO error on the first two attempts
+struct FlakyConverter;
+
+impl Converter for FlakyConverter {
+    fn convert_str(&self, input: &str) -> Result<String, ConversionError> {
+        if ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+            return Err(ConversionError::io_error(
+                "flaky.flaky",
+                io::Error::from(io::ErrorKind::WouldBlock),
+            ));
+        }
+        Ok(input.to_string())
+    }
+}
+
+#[test]
+fn test_retry_policy_recovers_transient_failures() {
+    ConverterRegistry::global()
+        .lock()
+        .unwrap()
+        .register("flaky", "xml", || Box::new(FlakyConverter));
+
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("config.flaky"), "<Sysmon/>").unwrap();
+
+    let stats = BatchProcessor::new()
+        .with_retry_policy(RetryPolicy::new(3, Duration::from_millis(1)))
+        .process_directory(&input_dir, &output_dir, false, &ProcessingOptions::default())
+        .unwrap();
+
+    assert_eq!(stats.processed, 1);
+    assert_eq!(stats.errors, 0);
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 3);
+
+    let result = &stats.results[0];
+    assert_eq!(result.status, FileStatus::Converted);
+    assert_eq!(result.retry_count, 2);
+    assert_eq!(fs::read_to_string(output_dir.join("config.xml")).unwrap(), "<Sysmon/>");
+}
@@ -0,0 +1,52 @@
+//! Conversion paths without the `validation` feature.
+//!
+//! Run with `cargo test --no-default-features --test structural`.
+#![cfg(not(feature = "validation"))]
+
+use sysmon_json::merger::ConfigMerger;
+use sysmon_json::{convert_str, preprocess_str, FileFormat, SysmonDocument};
+
+/// Well-formed, but `onmatch="sometimes"` fails Sysmon schema validation
+const UNVALIDATED_XML: &str = r#"<Sysmon schemaversion="4.30">
+    <EventFiltering>
+        <RuleGroup name="test" groupRelation="or">
+            <ProcessCreate onmatch="sometimes">
+                <Image condition="is">c:\windows\system32\test.exe</Image>
+            </ProcessCreate>
+        </RuleGroup>
+    </EventFiltering>
+</Sysmon>"#;
+
+#[test]
+fn test_convert_str_is_structural() {
+    let json = convert_str(UNVALIDATED_XML, FileFormat::Xml, FileFormat::Json).unwrap();
+    assert!(json.contains("sometimes"));
+
+    let xml = convert_str(&json, FileFormat::Json, FileFormat::Xml).unwrap();
+    assert!(xml.contains("ProcessCreate"));
+}
+
+#[test]
+fn test_preprocess_str_is_structural() {
+    let processed = preprocess_str(UNVALIDATED_XML).unwrap();
+    assert!(processed.contains("ProcessCreate"));
+}
+
+#[test]
+fn test_malformed_xml_still_fails() {
+    assert!(convert_str("<Sysmon><Broken></Sysmon>", FileFormat::Xml, FileFormat::Json).is_err());
+}
+
+#[test]
+fn test_merger_is_structural() {
+    let mut merger = ConfigMerger::new();
+    merger.add_xml_str(UNVALIDATED_XML, "unvalidated.xml").unwrap();
+    assert!(merger.finish().is_ok());
+}
+
+#[test]
+fn test_document_is_structural() {
+    let doc = SysmonDocument::load_str(UNVALIDATED_XML, FileFormat::Xml).unwrap();
+    assert_eq!(doc.rules().len(), 1);
+    assert!(doc.to_xml_string().unwrap().contains("sometimes"));
+}